@@ -314,8 +314,8 @@ impl Account {
             hex::encode(&self.cloud_key),
             self.pub_height,
             self.own_height,
-            self.datetime,
             self.event.to_hex(),
+            self.datetime,
             self.id,
         );
         db.update(&sql)
@@ -334,12 +334,12 @@ impl Account {
         db.update(&sql)
     }
 
-    pub fn _delete(&self, db: &DStorage) -> Result<usize> {
+    pub fn delete(&self, db: &DStorage) -> Result<usize> {
         let sql = format!("DELETE FROM accounts WHERE id = {}", self.id);
         db.delete(&sql)
     }
 
-    pub fn _update_consensus(&mut self, db: &DStorage, height: u64, eid: EventId) -> Result<usize> {
+    pub fn update_consensus(&mut self, db: &DStorage, height: u64, eid: EventId) -> Result<usize> {
         self.own_height = height;
         self.event = eid;
         let sql = format!(
@@ -1,14 +1,22 @@
-use cloud_types::LayerServerEvent;
+use cloud_types::{LayerPeerEvent, LayerServerEvent, CLOUD_ID};
 use std::sync::Arc;
 use tdn::types::{
-    message::RecvType,
-    primitives::{HandleResult, Result},
+    message::{RecvType, SendType},
+    primitives::{HandleResult, PeerId, Result},
 };
 
 use crate::global::Global;
+use crate::group::{rpc as chat_rpc, Friend, Message};
+use crate::rpc::{account_update, session_create, session_delete};
+use crate::session::{Session, SessionType};
+use crate::storage::{chat_db, cloud_db, release_content, session_db, write_file};
+use crate::utils::crypto::{cloud_data_key, cloud_decrypt, cloud_encrypt};
 
-pub(crate) async fn handle(msg: RecvType, _global: &Arc<Global>) -> Result<HandleResult> {
-    let results = HandleResult::new();
+use super::models::{CloudEvent, Provider};
+use super::rpc;
+
+pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<HandleResult> {
+    let mut results = HandleResult::new();
 
     match msg {
         RecvType::Connect(..)
@@ -18,8 +26,87 @@ pub(crate) async fn handle(msg: RecvType, _global: &Arc<Global>) -> Result<Handl
         | RecvType::Stream(..) => {
             info!("cloud message nerver to here.")
         }
-        RecvType::Event(_addr, bytes) => {
-            let _event: LayerServerEvent = bincode::deserialize(&bytes)?;
+        RecvType::Event(addr, bytes) => {
+            let event: LayerServerEvent = bincode::deserialize(&bytes)?;
+
+            let pid = global.pid().await;
+            let (db_key, cloud) = {
+                let own_lock = global.own.read().await;
+                (own_lock.db_key(&pid)?, own_lock.account(&pid)?.cloud)
+            };
+            let db = cloud_db(&global.base, &pid, &db_key)?;
+            let mut provider = Provider::get_by_addr(&db, &addr)?;
+
+            // only handle the synced data from the main cloud.
+            if addr != cloud && !matches!(event, LayerServerEvent::Status(..)) {
+                warn!("cloud got event from other provider {}", addr.to_hex());
+                return Ok(results);
+            }
+
+            match event {
+                LayerServerEvent::Status(name, free, vips) => {
+                    let vips = vips
+                        .into_iter()
+                        .map(|(space, price)| (space as i64, price as i64))
+                        .collect();
+                    provider.ok(&db, name, free as i64, vips)?;
+                    results.rpcs.push(rpc::cloud_status(&provider));
+                }
+                LayerServerEvent::PeerStatus(peer, is_ok) => {
+                    results.rpcs.push(rpc::peer_status(&peer, is_ok));
+                }
+                LayerServerEvent::SyncEvent(events, more) => {
+                    let key = data_key(global).await?;
+                    let mut height = provider.height;
+                    for (h, data) in events {
+                        height = std::cmp::max(height, h as i64);
+                        match cloud_decrypt(&key, &data)
+                            .ok()
+                            .and_then(|bytes| bincode::deserialize(&bytes).ok())
+                        {
+                            Some(event) => apply(global, event, &mut results).await?,
+                            None => warn!("cloud event {} decrypt failure.", h),
+                        }
+                    }
+                    provider.synced(&db, height)?;
+                    results.rpcs.push(rpc::cloud_status(&provider));
+
+                    if more {
+                        let data = bincode::serialize(&LayerPeerEvent::Pull(height as u64))?;
+                        results
+                            .layers
+                            .push((CLOUD_ID, SendType::Event(0, addr, data)));
+                    }
+                }
+                LayerServerEvent::SyncFile(name, data) => {
+                    // file name must not jump out of the files directory.
+                    if data.is_empty() || name.contains('/') || name.contains('\\') {
+                        results.rpcs.push(rpc::file_pull(&name, false));
+                        return Ok(results);
+                    }
+                    let key = data_key(global).await?;
+                    if let Ok(bytes) = cloud_decrypt(&key, &data) {
+                        write_file(&global.base, &pid, &db_key, &name, &bytes).await?;
+                        results.rpcs.push(rpc::file_pull(&name, true));
+                    } else {
+                        results.rpcs.push(rpc::file_pull(&name, false));
+                    }
+                }
+                LayerServerEvent::Files(files) => {
+                    results.rpcs.push(rpc::file_list(files));
+                }
+                LayerServerEvent::Stored(used, total) => {
+                    provider.used(&db, used as i64, total as i64)?;
+                    results.rpcs.push(rpc::cloud_status(&provider));
+                    if provider.is_warn() {
+                        results.rpcs.push(rpc::cloud_warn(&provider));
+                    }
+                }
+                LayerServerEvent::Full(used, total) => {
+                    provider.used(&db, used as i64, total as i64)?;
+                    results.rpcs.push(rpc::cloud_full(&provider));
+                }
+            }
         }
         RecvType::Delivery(_t, _tid, _is_ok) => {
             // MAYBE
@@ -28,3 +115,88 @@ pub(crate) async fn handle(msg: RecvType, _global: &Arc<Global>) -> Result<Handl
 
     Ok(results)
 }
+
+/// the key which encrypt all data to the cloud.
+pub(crate) async fn data_key(global: &Arc<Global>) -> Result<[u8; 32]> {
+    let pid = global.pid().await;
+    let own_lock = global.own.read().await;
+    let secret = own_lock.keypair().to_db_bytes();
+    let account = own_lock.account(&pid)?;
+    Ok(cloud_data_key(&secret, &account.cloud_key))
+}
+
+/// encrypt and push the event to main cloud, if account not set cloud, ignore it.
+pub(crate) async fn cloud_push(
+    global: &Arc<Global>,
+    event: CloudEvent,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let (db_key, cloud) = {
+        let own_lock = global.own.read().await;
+        (own_lock.db_key(&pid)?, own_lock.account(&pid)?.cloud)
+    };
+    if cloud == PeerId::default() {
+        return Ok(());
+    }
+
+    let key = data_key(global).await?;
+    let bytes = cloud_encrypt(&key, &bincode::serialize(&event)?)?;
+
+    let db = cloud_db(&global.base, &pid, &db_key)?;
+    let provider = Provider::get_by_addr(&db, &cloud)?;
+    if provider.is_full(bytes.len()) {
+        results.rpcs.push(rpc::cloud_full(&provider));
+        return Ok(());
+    }
+
+    let data = bincode::serialize(&LayerPeerEvent::Event(bytes))?;
+    results
+        .layers
+        .push((CLOUD_ID, SendType::Event(0, cloud, data)));
+    Ok(())
+}
+
+/// apply the synced event from other devices.
+async fn apply(global: &Arc<Global>, event: CloudEvent, results: &mut HandleResult) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+
+    match event {
+        CloudEvent::Info(name, avatar) => {
+            let mut own_lock = global.own.write().await;
+            own_lock.update_account(pid, &name, avatar.clone(), &global.base, &global.secret)?;
+            drop(own_lock);
+            results
+                .rpcs
+                .push(account_update(&pid, &name, base64::encode(avatar)));
+        }
+        CloudEvent::Friend(fpid, name) => {
+            let db = chat_db(&global.base, &pid, &db_key)?;
+            let is_new = Friend::get_id(&db, &fpid).is_err();
+            let friend = Friend::from_remote(&db, fpid, name, PeerId::default(), [0u8; 32])?;
+            if is_new {
+                let s_db = session_db(&global.base, &pid, &db_key)?;
+                let mut session = friend.to_session();
+                session.insert(&s_db)?;
+                results.rpcs.push(session_create(&session));
+            }
+            results.rpcs.push(chat_rpc::friend_info(&friend));
+        }
+        CloudEvent::FriendDelete(fpid) => {
+            let db = chat_db(&global.base, &pid, &db_key)?;
+            if let Ok(friend) = Friend::get_id(&db, &fpid) {
+                for msg in Message::get_by_fid(&db, &friend.id)? {
+                    release_content(&global.base, &pid, &db_key, &msg.m_type, &msg.content)?;
+                }
+                Friend::delete(&db, &friend.id)?;
+                let s_db = session_db(&global.base, &pid, &db_key)?;
+                let sid = Session::delete(&s_db, &friend.id, &SessionType::Chat)?;
+                results.rpcs.push(chat_rpc::friend_delete(friend.id));
+                results.rpcs.push(session_delete(&sid));
+            }
+        }
+    }
+
+    Ok(())
+}
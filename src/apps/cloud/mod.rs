@@ -2,5 +2,6 @@ mod layer;
 mod models;
 
 pub(crate) mod rpc;
-pub(crate) use layer::handle;
+pub(crate) use layer::{cloud_push, handle};
+pub(crate) use models::CloudEvent;
 pub(crate) use rpc::new_rpc_handler;
@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use tdn::types::{
+    primitives::{PeerId, Result},
+    rpc::{json, RpcParam},
+};
+use tdn_storage::local::{DStorage, DsValue};
+
+/// Account events which sync by cloud, it is encrypted before push.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum CloudEvent {
+    /// account info updated.
+    /// params: name, avatar.
+    Info(String, Vec<u8>),
+    /// friend added.
+    /// params: friend id, friend name.
+    Friend(PeerId, String),
+    /// friend deleted.
+    /// params: friend id.
+    FriendDelete(PeerId),
+}
+
+/// warning when used space over the percent of total space.
+const QUOTA_WARN: i64 = 90;
+
+/// Cloud Provider Model.
+pub(crate) struct Provider {
+    /// db auto-increment id.
+    pub id: i64,
+    /// name.
+    pub name: String,
+    /// address.
+    pub addr: PeerId,
+    /// synced events height.
+    pub height: i64,
+    /// used space.
+    pub used: i64,
+    /// total space of this account.
+    pub space: i64,
+    /// free space of the provider.
+    pub free: i64,
+    /// VIP space & monthly price.
+    pub vips: Vec<(i64, i64)>,
+    /// is add ok.
+    pub is_ok: bool,
+    /// is default provider, it is the account main cloud.
+    pub is_default: bool,
+}
+
+impl Provider {
+    pub fn prepare(addr: PeerId) -> Self {
+        Self {
+            id: 0,
+            name: addr.to_hex(),
+            addr: addr,
+            height: 0,
+            used: 0,
+            space: 0,
+            free: 0,
+            vips: vec![],
+            is_ok: false,
+            is_default: false,
+        }
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([
+            self.id,
+            self.name,
+            self.addr.to_hex(),
+            self.height,
+            self.used,
+            self.space,
+            self.remain(),
+            self.free,
+            self.vips,
+            self.is_ok,
+            self.is_default,
+        ])
+    }
+
+    fn vips_to_str(vips: &[(i64, i64)]) -> String {
+        let vips: Vec<String> = vips.iter().map(|(s, p)| format!("{}:{}", s, p)).collect();
+        vips.join(";")
+    }
+
+    fn vips_from_str(s: &str) -> Vec<(i64, i64)> {
+        s.split(';')
+            .filter_map(|v| {
+                let mut i = v.split(':');
+                let space = i.next()?.parse().ok()?;
+                let price = i.next()?.parse().ok()?;
+                Some((space, price))
+            })
+            .collect()
+    }
+
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            is_default: v.pop().unwrap().as_bool(),
+            is_ok: v.pop().unwrap().as_bool(),
+            vips: Self::vips_from_str(v.pop().unwrap().as_str()),
+            free: v.pop().unwrap().as_i64(),
+            space: v.pop().unwrap().as_i64(),
+            used: v.pop().unwrap().as_i64(),
+            height: v.pop().unwrap().as_i64(),
+            addr: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            name: v.pop().unwrap().as_string(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    /// use in rpc when load providers.
+    pub fn list(db: &DStorage) -> Result<Vec<Self>> {
+        let matrix = db.query(
+            "SELECT id, name, addr, height, used, space, free, vips, is_ok, is_default FROM providers",
+        )?;
+        let mut providers = vec![];
+        for values in matrix {
+            providers.push(Self::from_values(values));
+        }
+        Ok(providers)
+    }
+
+    /// use in rpc when load provider by id.
+    pub fn get(db: &DStorage, id: &i64) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, name, addr, height, used, space, free, vips, is_ok, is_default FROM providers WHERE id = {}",
+            id
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("provider is missing"))
+    }
+
+    /// get the default provider.
+    pub fn get_default(db: &DStorage) -> Result<Self> {
+        let mut matrix = db.query("SELECT id, name, addr, height, used, space, free, vips, is_ok, is_default FROM providers WHERE is_default = true")?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("provider is missing"))
+    }
+
+    /// get the provider by address.
+    pub fn get_by_addr(db: &DStorage, addr: &PeerId) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, name, addr, height, used, space, free, vips, is_ok, is_default FROM providers WHERE addr = '{}'",
+            addr.to_hex()
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("provider is missing"))
+    }
+
+    /// insert a new provider, if the address exists, load it.
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        if let Ok(provider) = Self::get_by_addr(db, &self.addr) {
+            *self = provider;
+            return Ok(());
+        }
+
+        let sql = format!(
+            "INSERT INTO providers (name, addr, height, used, space, free, vips, is_ok, is_default) VALUES ('{}', '{}', {}, {}, {}, {}, '{}', {}, {})",
+            self.name,
+            self.addr.to_hex(),
+            self.height,
+            self.used,
+            self.space,
+            self.free,
+            Self::vips_to_str(&self.vips),
+            self.is_ok,
+            self.is_default,
+        );
+        self.id = db.insert(&sql)?;
+        Ok(())
+    }
+
+    /// update the provider status when check ok.
+    pub fn ok(
+        &mut self,
+        db: &DStorage,
+        name: String,
+        free: i64,
+        vips: Vec<(i64, i64)>,
+    ) -> Result<usize> {
+        self.name = name;
+        self.free = free;
+        self.vips = vips;
+        self.is_ok = true;
+        if self.space == 0 {
+            self.space = free;
+        }
+        let sql = format!(
+            "UPDATE providers SET name = '{}', space = {}, free = {}, vips = '{}', is_ok = true WHERE id = {}",
+            self.name,
+            self.space,
+            self.free,
+            Self::vips_to_str(&self.vips),
+            self.id
+        );
+        db.update(&sql)
+    }
+
+    /// set or unset the default provider.
+    pub fn default(&mut self, db: &DStorage, is_default: bool) -> Result<usize> {
+        self.is_default = is_default;
+        let sql = format!(
+            "UPDATE providers SET is_default = {} WHERE id = {}",
+            self.is_default, self.id
+        );
+        db.update(&sql)
+    }
+
+    /// update the used space.
+    pub fn used(&mut self, db: &DStorage, used: i64, space: i64) -> Result<usize> {
+        self.used = used;
+        self.space = space;
+        let sql = format!(
+            "UPDATE providers SET used = {}, space = {} WHERE id = {}",
+            self.used, self.space, self.id
+        );
+        db.update(&sql)
+    }
+
+    /// update the synced events height.
+    pub fn synced(&mut self, db: &DStorage, height: i64) -> Result<usize> {
+        self.height = height;
+        let sql = format!(
+            "UPDATE providers SET height = {} WHERE id = {}",
+            self.height, self.id
+        );
+        db.update(&sql)
+    }
+
+    /// delete provider.
+    pub fn delete(db: &DStorage, id: &i64) -> Result<()> {
+        let sql = format!("DELETE FROM providers WHERE id = {}", id);
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    /// remaining space.
+    pub fn remain(&self) -> i64 {
+        std::cmp::max(self.space - self.used, 0)
+    }
+
+    /// check the space is enough for new data.
+    pub fn is_full(&self, size: usize) -> bool {
+        self.space > 0 && self.used + size as i64 > self.space
+    }
+
+    /// check the used space is near to the quota.
+    pub fn is_warn(&self) -> bool {
+        self.space > 0 && self.used * 100 >= self.space * QUOTA_WARN
+    }
+}
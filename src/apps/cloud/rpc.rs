@@ -1,10 +1,47 @@
+use cloud_types::{LayerPeerEvent, CLOUD_ID};
 use std::sync::Arc;
 use tdn::types::{
-    primitives::HandleResult,
-    rpc::{json, RpcHandler, RpcParam},
+    message::SendType,
+    primitives::{HandleResult, PeerId},
+    rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
 
 use crate::global::Global;
+use crate::storage::{account_db, cloud_db, read_db_file};
+use crate::utils::crypto::{cloud_encrypt, cloud_session_key};
+
+use super::layer::data_key;
+use super::models::Provider;
+
+#[inline]
+pub(crate) fn cloud_status(provider: &Provider) -> RpcParam {
+    rpc_response(0, "cloud-status", provider.to_rpc())
+}
+
+#[inline]
+pub(crate) fn cloud_full(provider: &Provider) -> RpcParam {
+    rpc_response(0, "cloud-full", provider.to_rpc())
+}
+
+#[inline]
+pub(crate) fn cloud_warn(provider: &Provider) -> RpcParam {
+    rpc_response(0, "cloud-quota-warn", provider.to_rpc())
+}
+
+#[inline]
+pub(crate) fn peer_status(pid: &PeerId, is_ok: bool) -> RpcParam {
+    rpc_response(0, "cloud-peer-status", json!([pid.to_hex(), is_ok]))
+}
+
+#[inline]
+pub(crate) fn file_pull(name: &str, is_ok: bool) -> RpcParam {
+    rpc_response(0, "cloud-file-pull", json!([name, is_ok]))
+}
+
+#[inline]
+pub(crate) fn file_list(files: Vec<(String, u64)>) -> RpcParam {
+    rpc_response(0, "cloud-file-list", json!(files))
+}
 
 pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
     handler.add_method(
@@ -13,4 +50,212 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             Ok(HandleResult::rpc(json!(params)))
         },
     );
+
+    handler.add_method(
+        "cloud-provider-list",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+
+            let providers = Provider::list(&db)?;
+            let providers: Vec<RpcParam> = providers.iter().map(|p| p.to_rpc()).collect();
+            Ok(HandleResult::rpc(json!(providers)))
+        },
+    );
+
+    handler.add_method(
+        "cloud-provider-add",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let addr = PeerId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+
+            let mut provider = Provider::prepare(addr);
+            provider.insert(&db)?;
+
+            let mut results = HandleResult::rpc(provider.to_rpc());
+            let data = bincode::serialize(&LayerPeerEvent::Check)?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, addr, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-provider-check",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+            let provider = Provider::get(&db, &id)?;
+
+            let mut results = HandleResult::new();
+            let data = bincode::serialize(&LayerPeerEvent::Check)?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, provider.addr, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-provider-default",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+
+            let mut provider = Provider::get(&db, &id)?;
+            if !provider.is_ok {
+                return Err(RpcError::Custom("cloud provider is not ok".to_owned()));
+            }
+            if let Ok(mut default) = Provider::get_default(&db) {
+                if default.id == provider.id {
+                    return Ok(HandleResult::new());
+                }
+                default.default(&db, false)?;
+            }
+            provider.default(&db, true)?;
+
+            // the default provider is the account main cloud.
+            let mut own_lock = state.own.write().await;
+            let secret = own_lock.keypair().to_db_bytes();
+            let account = own_lock.account_mut(&pid)?;
+            account.cloud = provider.addr;
+            account.cloud_key = cloud_session_key(&secret, provider.addr.to_hex().as_bytes());
+            let a_db = account_db(&state.base, &state.secret)?;
+            account.update_info(&a_db)?;
+            drop(own_lock);
+
+            // start sync from the new cloud.
+            let mut results = HandleResult::new();
+            let data = bincode::serialize(&LayerPeerEvent::Pull(provider.height as u64))?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, provider.addr, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-provider-remove",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+
+            let provider = Provider::get(&db, &id)?;
+            if !provider.is_default {
+                Provider::delete(&db, &id)?;
+            }
+
+            Ok(HandleResult::new())
+        },
+    );
+
+    handler.add_method(
+        "cloud-sync",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let (db_key, cloud) = {
+                let own_lock = state.own.read().await;
+                (own_lock.db_key(&pid)?, own_lock.account(&pid)?.cloud)
+            };
+            if cloud == PeerId::default() {
+                return Err(RpcError::Custom("cloud is not set".to_owned()));
+            }
+
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+            let provider = Provider::get_by_addr(&db, &cloud)?;
+
+            let mut results = HandleResult::new();
+            let data = bincode::serialize(&LayerPeerEvent::Pull(provider.height as u64))?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, cloud, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-file-push",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let name = params[0].as_str().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let (db_key, cloud) = {
+                let own_lock = state.own.read().await;
+                (own_lock.db_key(&pid)?, own_lock.account(&pid)?.cloud)
+            };
+            if cloud == PeerId::default() {
+                return Err(RpcError::Custom("cloud is not set".to_owned()));
+            }
+
+            let bytes = read_db_file(&state.base, &pid, &db_key, name).await?;
+            let key = data_key(&state).await?;
+            let data = cloud_encrypt(&key, &bytes)?;
+
+            let db = cloud_db(&state.base, &pid, &db_key)?;
+            let provider = Provider::get_by_addr(&db, &cloud)?;
+            if provider.is_full(data.len()) {
+                return Ok(HandleResult::rpc(cloud_full(&provider)));
+            }
+
+            let mut results = HandleResult::new();
+            let event = LayerPeerEvent::File(name.to_owned(), data);
+            let data = bincode::serialize(&event)?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, cloud, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-file-pull",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let name = params[0].as_str().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let cloud = state.own.read().await.account(&pid)?.cloud;
+            if cloud == PeerId::default() {
+                return Err(RpcError::Custom("cloud is not set".to_owned()));
+            }
+
+            let mut results = HandleResult::new();
+            let data = bincode::serialize(&LayerPeerEvent::PullFile(name.to_owned()))?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, cloud, data)));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "cloud-file-list",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let cloud = state.own.read().await.account(&pid)?.cloud;
+            if cloud == PeerId::default() {
+                return Err(RpcError::Custom("cloud is not set".to_owned()));
+            }
+
+            let mut results = HandleResult::new();
+            let data = bincode::serialize(&LayerPeerEvent::FileList)?;
+            results
+                .layers
+                .push((CLOUD_ID, SendType::Event(0, cloud, data)));
+            Ok(results)
+        },
+    );
 }
@@ -1,816 +1,1564 @@
-use std::path::PathBuf;
+use dao_types::{
+    key_hash, CheckType, ConnectProof, DaoId, DaoType, Event, JoinProof, LayerConnect, LayerEvent,
+    LayerResult, PackedEvent, ProposalType, ZkpProof, DAO_ID,
+};
+use esse_primitives::{id_to_str, MessageType};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    group::GroupId,
     message::{RecvType, SendType},
-    primitive::{HandleResult, Peer, PeerId, Result},
-};
-use tokio::sync::RwLock;
-
-use group_types::{
-    CheckType, ConnectProof, Event, GroupType, JoinProof, LayerConnect, LayerEvent, LayerResult,
-    PackedEvent,
+    primitives::{HandleResult, Peer, PeerId, Result},
 };
-use tdn_did::Proof;
 use tdn_storage::local::DStorage;
 
+use crate::global::Global;
 use crate::group::Friend;
-use crate::layer::{Layer, Online};
-use crate::rpc::{session_connect, session_create, session_last, session_lost, session_suspend};
+use crate::rpc::{
+    session_close, session_connect, session_create, session_last, session_lost, session_suspend,
+    session_update_name,
+};
 use crate::session::{connect_session, Session, SessionType};
 use crate::storage::{
-    chat_db, delete_avatar, group_db, read_avatar, session_db, write_avatar, write_avatar_sync,
+    chat_db, dao_db, delete_avatar, read_avatar, release_content, session_db, write_avatar_sync,
+    write_image_sync,
 };
 
 use super::models::{
-    from_network_message, Consensus, ConsensusType, GroupChat, Member, Provider, Request,
+    handle_network_message, Consensus, ConsensusType, Dao, Member, Message, Proposal, Provider,
+    Request, Vote,
 };
-use super::{add_layer, add_server_layer, rpc};
+use super::rpc;
 
 // variable statement:
-// gcd: Group Chat ID.
-// fgid: where is event come from.
-// ogid: my account ID. if server is group owner. if client is my.
-// mgid: member account ID.
-// id: Group Chat database Id.
+// did: DAO ID.
+// pid: my account ID.
+// mpid: member account ID.
+// id: DAO database Id.
+// sid: DAO Session Id.
 // mid: member database Id.
-pub(crate) async fn handle(
-    layer: &Arc<RwLock<Layer>>,
-    fgid: GroupId, // when as client, `fgid` is GROUP_ID
-    tgid: GroupId, // when as server, `tgid` is GROUP_ID
-    is_server: bool,
-    msg: RecvType,
-) -> Result<HandleResult> {
+// rid: request database Id.
+pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<HandleResult> {
     let mut results = HandleResult::new();
 
     match msg {
-        RecvType::Connect(addr, data) => {
-            // only server handle it. IMPORTANT !!! fgid IS mgid.
-            if !is_server {
-                let s = SendType::Result(0, addr, false, false, vec![]);
-                add_server_layer(&mut results, fgid, s);
-                return Ok(results);
-            }
-
-            let LayerConnect(gcd, connect) = bincode::deserialize(&data)?;
-            let (ogid, height, id) = layer.read().await.running(&gcd)?.owner_height_id();
-
-            match connect {
-                ConnectProof::Common(_proof) => {
-                    // check is member.
-                    let db = group_db(&layer.read().await.base, &ogid)?;
-
-                    if let Ok((mid, _)) = Member::get_id(&db, &id, &fgid) {
-                        let res = LayerResult(gcd, height);
-                        let data = bincode::serialize(&res).unwrap_or(vec![]);
-                        let s = SendType::Result(0, addr.clone(), true, false, data);
-                        add_server_layer(&mut results, fgid, s);
-
-                        layer.write().await.running_mut(&gcd)?.check_add_online(
-                            fgid,
-                            Online::Direct(addr.id),
-                            id,
-                            mid,
-                        )?;
-
-                        let _ = Member::addr_update(&db, &id, &fgid, &addr.id);
-                        results
-                            .rpcs
-                            .push(rpc::member_online(ogid, id, fgid, addr.id));
-
-                        let new_data =
-                            bincode::serialize(&LayerEvent::MemberOnline(gcd, fgid, addr.id))?;
-
-                        for (mid, maddr) in layer.read().await.running(&gcd)?.onlines() {
-                            let s = SendType::Event(0, *maddr, new_data.clone());
-                            add_server_layer(&mut results, *mid, s);
-                        }
-                    } else {
-                        let s = SendType::Result(0, addr, false, false, vec![]);
-                        add_server_layer(&mut results, fgid, s);
-                    }
-                }
-                ConnectProof::Zkp(_proof) => {
-                    //
-                }
+        RecvType::Connect(peer, data) => {
+            // SERVER
+            let LayerConnect(did, proof) = bincode::deserialize(&data)?;
+            if handle_connect(global, &peer, did, proof, &mut results)
+                .await
+                .is_err()
+            {
+                let data = bincode::serialize(&did)?;
+                let msg = SendType::Result(0, peer, false, false, data);
+                results.layers.push((DAO_ID, msg));
             }
         }
-        RecvType::Leave(_addr) => {
-            // only server handle it. IMPORTANT !!! fgid IS mgid.
-            // TODO
-        }
-        RecvType::Result(addr, is_ok, data) => {
-            // only cleint handle it. IMPORTANT !!! tgid IS ogid.
-            if !is_server && is_ok {
-                let mut layer_lock = layer.write().await;
-                handle_connect(tgid, &addr, data, &mut layer_lock, &mut results)?;
+        RecvType::Result(peer, is_ok, data) => {
+            // PEER
+            if is_ok {
+                handle_result(global, &peer, data, &mut results).await?;
             } else {
-                let msg = SendType::Result(0, addr, false, false, vec![]);
-                add_layer(&mut results, tgid, msg);
+                // close the dao.
+                let did: DaoId = bincode::deserialize(&data)?;
+
+                let pid = global.pid().await;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = dao_db(&global.base, &pid, &db_key)?;
+                let s_db = session_db(&global.base, &pid, &db_key)?;
+
+                let dao = Dao::close_id(&db, &did, &peer.id)?;
+                let sid = Session::close(&s_db, &dao.id, &SessionType::Dao)?;
+                results.rpcs.push(session_close(&sid));
             }
         }
-        RecvType::ResultConnect(addr, data) => {
-            // only cleint handle it. IMPORTANT !!! tgid IS ogid.
-            if is_server {
-                let msg = SendType::Result(0, addr.clone(), false, false, vec![]);
-                add_layer(&mut results, tgid, msg);
-            }
-
-            let mut layer_lock = layer.write().await;
-            if handle_connect(tgid, &addr, data, &mut layer_lock, &mut results)? {
-                let msg = SendType::Result(0, addr, true, false, vec![]);
-                add_layer(&mut results, tgid, msg);
+        RecvType::ResultConnect(peer, data) => {
+            // PEER
+            if handle_result(global, &peer, data, &mut results)
+                .await
+                .is_err()
+            {
+                let msg = SendType::Result(0, peer, true, false, vec![]);
+                results.layers.push((DAO_ID, msg));
             }
         }
         RecvType::Event(addr, bytes) => {
-            println!("----------- DEBUG GROUP CHAT: GOT LAYER EVENT");
-            // server & client handle it.
+            // PEER & SERVER
             let event: LayerEvent = bincode::deserialize(&bytes)?;
-            handle_event(fgid, tgid, is_server, addr, event, layer, &mut results).await?;
-            println!("----------- DEBUG GROUP CHAT: OVER LAYER EVENT");
+            handle_event(addr, event, global, &mut results).await?;
         }
+        RecvType::Delivery(..) => {}
         RecvType::Stream(_uid, _stream, _bytes) => {
             // TODO stream
         }
-        RecvType::Delivery(_t, _tid, _is_ok) => {
-            //
-        }
+        RecvType::Leave(..) => {} // nerver here.
     }
 
     Ok(results)
 }
 
-fn handle_connect(
-    ogid: GroupId,
-    addr: &Peer,
+async fn handle_connect(
+    global: &Arc<Global>,
+    peer: &Peer,
+    did: DaoId,
+    proof: ConnectProof,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let (height, _, id, _) = global.layer.read().await.dao(&did)?.info();
+
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = dao_db(&global.base, &pid, &db_key)?;
+
+    // check is member.
+    let mid = match proof {
+        ConnectProof::Common => Member::get_ok(&db, &id, &peer.id)?,
+        ConnectProof::Zkp(proof) => {
+            let dao = Dao::get(&db, &id)?;
+            if !verify_proof(&dao, &proof, &peer.id) {
+                return Err(anyhow!("zkp proof is invalid"));
+            }
+            // anonymous member maybe not in the members list.
+            Member::get_ok(&db, &id, &peer.id).unwrap_or(0)
+        }
+    };
+
+    let data = bincode::serialize(&LayerResult(did, height)).unwrap_or(vec![]);
+    let s = SendType::Result(0, peer.clone(), true, false, data);
+    results.layers.push((DAO_ID, s));
+
+    global.layer.write().await.dao_add_member(&did, peer.id);
+    if mid > 0 {
+        results.rpcs.push(rpc::member_online(id, mid));
+    }
+
+    // anonymous member's address never leaks to other members.
+    if mid > 0 {
+        let data = LayerEvent::MemberOnline(did, peer.id);
+        broadcast(&did, global, &data, results).await?;
+    }
+    Ok(())
+}
+
+async fn handle_result(
+    global: &Arc<Global>,
+    peer: &Peer,
     data: Vec<u8>,
-    layer: &mut Layer,
     results: &mut HandleResult,
-) -> Result<bool> {
+) -> Result<()> {
     // 0. deserialize result.
-    let LayerResult(gcd, height) = bincode::deserialize(&data)?;
-
-    // 1. check group.
-    let db = group_db(layer.base(), &ogid)?;
-    if let Some(group) = GroupChat::get(&db, &gcd)? {
-        // 1.0 check address.
-        if group.g_addr != addr.id {
-            return Ok(false);
-        }
-
-        // 1.1 get session.
-        let session_some = connect_session(
-            layer.base(),
-            &ogid,
-            &SessionType::Group,
-            &group.id,
-            &addr.id,
-        )?;
-        if session_some.is_none() {
-            return Ok(false);
-        }
-        let sid = session_some.unwrap().id;
-
-        // 1.2 online this group.
-        layer
-            .running_mut(&ogid)?
-            .check_add_online(gcd, Online::Direct(addr.id), sid, group.id)?;
-
-        // 1.3 online to UI.
-        results.rpcs.push(session_connect(ogid, &sid, &addr.id));
-
-        println!("will sync remote: {}, my: {}", height, group.height);
-        // 1.4 sync group height.
-        if group.height < height {
-            add_layer(results, ogid, sync(gcd, addr.id, group.height));
-        } else {
-            // sync online members.
-            add_layer(results, ogid, sync_online(gcd, addr.id));
-        }
-        Ok(true)
+    let LayerResult(did, height) = bincode::deserialize(&data)?;
+
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = dao_db(&global.base, &pid, &db_key)?;
+    let s_db = session_db(&global.base, &pid, &db_key)?;
+
+    // 1. check dao.
+    let dao = Dao::get_id(&db, &did, &peer.id)?;
+
+    // 1.1 get session.
+    let session_some = connect_session(&s_db, &SessionType::Dao, &dao.id, &peer.id)?;
+    if session_some.is_none() {
+        return Err(anyhow!("invalid dao address."));
+    }
+    let sid = session_some.unwrap().id;
+
+    // 1.2 online this dao.
+    global
+        .layer
+        .write()
+        .await
+        .dao_add(did, peer.id, sid, dao.id, height);
+
+    // 1.3 online to UI.
+    results.rpcs.push(session_connect(&sid, &peer.id));
+
+    debug!("will sync remote: {}, my: {}", height, dao.height);
+    // 1.4 sync dao height.
+    if dao.height < height {
+        results
+            .layers
+            .push((DAO_ID, sync(did, peer.id, dao.height)));
     } else {
-        Ok(false)
+        // sync online members.
+        results.layers.push((DAO_ID, sync_online(did, peer.id)));
     }
+
+    Ok(())
 }
 
-// variable statement:
-// gcd: Group Chat ID.
-// fgid: where is event come from.
-// ogid: my account ID. if server is group owner. if client is my.
-// mgid: member account ID.
-// id: Group Chat database Id.
-// mid: member database Id.
-// sid: session Id.
 async fn handle_event(
-    fgid: GroupId, // server use fgid is remote account.
-    tgid: GroupId, // client user tgid is my account.
-    is_server: bool,
     addr: PeerId,
     event: LayerEvent,
-    layer: &Arc<RwLock<Layer>>,
+    global: &Arc<Global>,
     results: &mut HandleResult,
 ) -> Result<()> {
-    println!("Got event.......is server: {:?}", is_server);
-    let base = layer.read().await.base().clone();
-    let (sid, db, id, height, ogid, fgid) = if let Some(gcd) = event.gcd() {
-        if is_server {
-            let (ogid, height, id) = layer.read().await.running(gcd)?.owner_height_id();
-            println!("--- DEBUG server:--- online info ok");
-            let db = group_db(&base, &ogid)?;
-            println!("--- DEBUG server:--- db ok");
-            (0, db, id, height, ogid, fgid)
-        } else {
-            let (sid, id) = if event.need_online() {
-                println!("--- DEBUG client: --- need online info start");
-                let (sid, id) = layer.read().await.get_running_remote_id(&tgid, gcd)?;
-                (sid, id)
-            } else {
-                println!("--- DEBUG client: --- not need online info");
-                (0, 0)
-            };
-            println!("--- DEBUG client:--- online info ok");
+    if !event.need_online() {
+        return handle_request(addr, event, global, results).await;
+    }
 
-            let db = group_db(&base, &tgid)?;
-            println!("--- DEBUG client:--- db ok");
-            (sid, db, id, 0, tgid, *gcd)
-        }
-    } else {
-        println!("--- DEBUG --- no group id");
-        let db = group_db(&base, &tgid)?;
-        (0, db, 0, 0, tgid, fgid)
-    };
-    println!("Handle variable statement ok.");
+    let did = *event.dao_id().ok_or(anyhow!("missing dao id"))?;
+    let (height, sid, id, daddr) = global.layer.read().await.dao(&did)?.info();
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = dao_db(&global.base, &pid, &db_key)?;
+    let is_server = daddr == pid;
+    if !is_server && daddr != addr {
+        warn!("INVALID EVENT NOT FROM THE SERVER.");
+        return Err(anyhow!("NOT THE SERVER EVENT"));
+    }
 
     match event {
-        LayerEvent::Offline(gcd) => {
+        LayerEvent::Offline(did) => {
+            // SERVER & PEER
             if is_server {
                 // 1. check member online.
-                if layer.write().await.remove_online(&gcd, &fgid).is_none() {
+                if !global.layer.write().await.dao_del_online(&did, &addr) {
                     return Ok(());
                 }
 
-                // 2. UI: offline the member.
-                results.rpcs.push(rpc::member_offline(ogid, id, fgid));
+                // 2. UI: offline the member, anonymous member not broadcast.
+                if let Ok((mid, _)) = Member::get_id(&db, &id, &addr) {
+                    results.rpcs.push(rpc::member_offline(id, mid));
 
-                // 3. broadcast offline event.
-                broadcast(&LayerEvent::MemberOffline(gcd, fgid), layer, &gcd, results).await?;
+                    // 3. broadcast offline event.
+                    let event = LayerEvent::MemberOffline(did, addr);
+                    broadcast(&did, global, &event, results).await?;
+                }
             } else {
-                // 1. offline group chat.
-                layer
-                    .write()
-                    .await
-                    .running_mut(&ogid)?
-                    .check_offline(&gcd, &addr);
+                // 1. offline dao.
+                global.layer.write().await.dao_del(&did);
 
                 // 2. UI: offline the session.
-                results.rpcs.push(session_lost(ogid, &sid));
+                results.rpcs.push(session_lost(&sid));
             }
         }
-        LayerEvent::Suspend(gcd) => {
-            // TODO server & client handle it.
-            if is_server {
-                //
-            } else {
-                if layer
-                    .write()
-                    .await
-                    .running_mut(&ogid)?
-                    .suspend(&gcd, false, true)?
-                {
-                    results.rpcs.push(session_suspend(ogid, &sid));
-                }
+        LayerEvent::Suspend(did) => {
+            // PEER
+            if global
+                .layer
+                .write()
+                .await
+                .dao_mut(&did)?
+                .suspend(false, true)
+                .is_some()
+            {
+                results.rpcs.push(session_suspend(&sid));
             }
         }
-        LayerEvent::Actived(gcd) => {
-            // TODO if only client handle it ???
-            let _ = layer.write().await.running_mut(&ogid)?.active(&gcd, false);
-            results.rpcs.push(session_connect(ogid, &sid, &addr));
+        LayerEvent::Actived(did) => {
+            // PEER
+            let _ = global.layer.write().await.dao_mut(&did)?.active(false);
+            results.rpcs.push(session_connect(&sid, &addr));
         }
-        LayerEvent::CheckResult(ct, name, remain, supported) => {
-            // only client handle it.
-            println!("check: {:?}, supported: {:?}", ct, supported);
-            let mut provider = Provider::get_by_addr(&db, &addr)?;
-            let rpc_ui = match ct {
-                CheckType::Allow => {
-                    provider.update(&db, name, supported, remain)?;
-                    rpc::provider_check(ogid, &provider)
-                }
-                CheckType::None => {
-                    provider.update(&db, name, supported, 0)?;
-                    rpc::provider_check(ogid, &provider)
-                }
-                CheckType::Suspend => {
-                    provider.suspend(&db)?;
-                    rpc::provider_check(ogid, &provider)
-                }
-                CheckType::Deny => {
-                    Provider::delete(&db, &provider.id)?;
-                    rpc::provider_delete(ogid, provider.id)
-                }
-            };
-            results.rpcs.push(rpc_ui)
+        LayerEvent::MemberOnline(_did, mpid) => {
+            // PEER
+            if let Ok((mid, _)) = Member::get_id(&db, &id, &mpid) {
+                results.rpcs.push(rpc::member_online(id, mid));
+            }
         }
-        LayerEvent::CreateResult(gcd, ok) => {
-            // only client handle it.
-            println!("Create result: {}", ok);
-            if ok {
-                // get gc by gcd.
-                if let Some(mut gc) = GroupChat::get(&db, &gcd)? {
-                    gc.ok(&db)?;
-                    results.rpcs.push(rpc::create_result(ogid, gc.id, ok));
-
-                    // ADD NEW SESSION.
-                    let s_db = session_db(&base, &ogid)?;
-                    let mut session = gc.to_session();
-                    session.insert(&s_db)?;
-                    results.rpcs.push(session_create(ogid, &session));
-                }
+        LayerEvent::MemberOffline(_did, mpid) => {
+            // PEER
+            if let Ok((mid, _)) = Member::get_id(&db, &id, &mpid) {
+                results.rpcs.push(rpc::member_offline(id, mid));
             }
         }
-        LayerEvent::Agree(gcd, info) => {
-            // only client handle it.
-            println!("Agree..........");
-            let (rid, key) = Request::over(&db, &gcd, true)?;
-
-            // 1. add group chat.
-            let mut group = GroupChat::from_info(key, info, 0, addr, &base, &ogid, true)?;
-            group.insert(&db)?;
-
-            // 2. ADD NEW SESSION.
-            let s_db = session_db(&base, &ogid)?;
-            let mut session = group.to_session();
-            session.insert(&s_db)?;
-            results.rpcs.push(session_create(ogid, &session));
-
-            // 3. update UI.
-            results
-                .rpcs
-                .push(rpc::request_handle(ogid, id, rid, true, false));
-            results.rpcs.push(rpc::group_create(ogid, group));
-
-            // 4. try connect.
-            let proof = layer
-                .read()
-                .await
-                .group
+        LayerEvent::MemberOnlineSync(did) => {
+            // SERVER
+            let onlines = global
+                .layer
                 .read()
                 .await
-                .prove_addr(&ogid, &addr)?;
-            add_layer(results, ogid, group_conn(proof, Peer::peer(addr), gcd));
-        }
-        LayerEvent::Reject(gcd, efficacy) => {
-            // only client handle it.
-            println!("Reject..........");
-            let (rid, _key) = Request::over(&db, &gcd, true)?;
-            results
-                .rpcs
-                .push(rpc::request_handle(ogid, id, rid, false, efficacy));
+                .dao(&did)?
+                .addrs
+                .iter()
+                .filter(|a| Member::get_id(&db, &id, a).is_ok())
+                .cloned()
+                .collect();
+            let event = LayerEvent::MemberOnlineSyncResult(did, onlines);
+            let data = bincode::serialize(&event).unwrap_or(vec![]);
+            let msg = SendType::Event(0, addr, data);
+            results.layers.push((DAO_ID, msg));
         }
-        LayerEvent::MemberOnline(_gcd, mgid, maddr) => {
-            // only client handle it.
-            let _ = Member::addr_update(&db, &id, &mgid, &maddr);
-            results.rpcs.push(rpc::member_online(ogid, id, mgid, maddr));
+        LayerEvent::MemberOnlineSyncResult(_did, onlines) => {
+            // PEER
+            for mpid in onlines {
+                if let Ok((mid, _)) = Member::get_id(&db, &id, &mpid) {
+                    results.rpcs.push(rpc::member_online(id, mid));
+                }
+            }
         }
-        LayerEvent::MemberOffline(_gcd, mgid) => {
-            // only client handle it.
-            results.rpcs.push(rpc::member_offline(ogid, id, mgid));
+        LayerEvent::RequestHandle(did, rpid, join_proof, rid, time) => {
+            // PEER (manager)
+            match join_proof {
+                JoinProof::Invite(inviter, mname, mavatar) => {
+                    let remark = id_to_str(&inviter);
+                    let mut req = Request::new_by_remote(id, rid, did, rpid, mname, remark, time);
+                    req.insert(&db)?;
+                    if mavatar.len() > 0 {
+                        write_avatar_sync(&global.base, &pid, &db_key, &rpid, mavatar)?;
+                    }
+                    results.rpcs.push(rpc::request_create(&req));
+                }
+                JoinProof::Open(..) | JoinProof::Zkp(..) => {} // nerver here.
+            }
         }
-        LayerEvent::MemberOnlineSyncResult(_gcd, onlines) => {
-            // only client handle it.
-            for (mgid, maddr) in onlines {
-                results.rpcs.push(rpc::member_online(ogid, id, mgid, maddr));
+        LayerEvent::RequestResult(did, rid, ok) => {
+            if is_server {
+                // SERVER: manager handled the request.
+                let (_, is_manager) = Member::get_id(&db, &id, &addr)?;
+                if !is_manager {
+                    return Err(anyhow!("request handle permission denied"));
+                }
+                request_handle(global, &db, did, id, rid, ok, results).await?;
+            } else {
+                // PEER: the request is handled by others.
+                if let Ok(rid) = Request::over_rid(&db, &id, &rid, ok) {
+                    results.rpcs.push(rpc::request_handle(id, rid, ok, false));
+                }
             }
         }
-        LayerEvent::Sync(gcd, height, event) => {
-            // all server & client handle it.
-            println!("Sync: handle height: {}", height);
-
+        LayerEvent::Sync(did, height, event) => {
+            // SERVER & PEER
+            debug!("Sync: handle is_server: {} height: {} ", is_server, height);
             match event {
-                Event::GroupInfo => {}
-                Event::GroupTransfer => {}
-                Event::GroupManagerAdd => {}
-                Event::GroupManagerDel => {}
-                Event::GroupClose => {}
-                Event::MemberJoin(mgid, maddr, mname, mavatar, mtime) => {
-                    // only client handle it.
-                    if Member::get_id(&db, &id, &mgid).is_err() {
-                        let mut member = Member::new(id, mgid, maddr, mname, false, mtime);
-                        member.insert(&db)?;
-                        if mavatar.len() > 0 {
-                            write_avatar_sync(&base, &ogid, &mgid, mavatar)?;
-                        }
-                        results.rpcs.push(rpc::member_join(ogid, &member));
+                Event::MemberJoin(mpid, mname, mavatar, mtime) => {
+                    // PEER
+                    if is_server {
+                        return Err(anyhow!("member join is invalid"));
                     }
-
-                    // save consensus.
-                    GroupChat::add_height(&db, id, height)?;
+                    let mut member = Member::new(id, mpid, mname, false, mtime);
+                    member.insert(&db)?;
+                    if mavatar.len() > 0 {
+                        write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar)?;
+                    }
+                    results.rpcs.push(rpc::member_join(&member));
+                    Dao::add_height(&db, id, height)?;
                 }
-                Event::MemberInfo(mgid, maddr, mname, mavatar) => {
-                    // TOOD server & client all handlt it.
-
-                    let (mid, _) = Member::get_id(&db, &id, &mgid)?;
-                    Member::update(&db, &mid, &maddr, &mname)?;
+                Event::MemberInfo(mpid, mname, mavatar) => {
+                    if is_server && mpid != addr {
+                        return Err(anyhow!("member info is invalid"));
+                    }
+                    let (mid, _) = Member::get_id(&db, &id, &mpid)?;
+                    Member::update(&db, &mid, &mname)?;
                     if mavatar.len() > 0 {
-                        write_avatar_sync(&base, &ogid, &mgid, mavatar)?;
+                        write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar.clone())?;
                     }
-                    results
-                        .rpcs
-                        .push(rpc::member_info(ogid, id, mid, maddr, mname));
+                    results.rpcs.push(rpc::member_info(id, mid, &mname));
 
-                    // save consensus.
-                    GroupChat::add_height(&db, id, height)?;
-                }
-                Event::MemberLeave(mgid) => {
-                    // TODO server & client all handle it.
+                    let h = if is_server {
+                        let h = global.layer.write().await.dao_mut(&did)?.increased();
+                        Consensus::insert(&db, &id, &h, &mid, &ConsensusType::MemberInfo)?;
+                        h
+                    } else {
+                        height
+                    };
+                    Dao::add_height(&db, id, h)?;
 
-                    let (mid, _) = Member::get_id(&db, &id, &mgid)?;
+                    if is_server {
+                        let new_e = Event::MemberInfo(mpid, mname, mavatar);
+                        broadcast(&did, global, &LayerEvent::Sync(did, h, new_e), results).await?;
+                    }
+                }
+                Event::MemberLeave(mpid) => {
+                    if is_server && mpid != addr {
+                        return Err(anyhow!("member leave is invalid"));
+                    }
+                    let (mid, _) = Member::get_id(&db, &id, &mpid)?;
                     Member::leave(&db, &mid)?;
+
                     // check mid is my chat friend. if not, delete avatar.
-                    let s_db = chat_db(&base, &mgid)?;
-                    if Friend::get(&s_db, &mgid)?.is_none() {
-                        let _ = delete_avatar(&base, &ogid, &mgid).await;
+                    let c_db = chat_db(&global.base, &pid, &db_key)?;
+                    if Friend::get_id(&c_db, &mpid).is_err() {
+                        let _ = delete_avatar(&global.base, &pid, &db_key, &mpid).await;
                     }
-                    results.rpcs.push(rpc::member_leave(ogid, id, mid));
+                    results.rpcs.push(rpc::member_leave(id, mid));
+
+                    let h = if is_server {
+                        let mut layer_lock = global.layer.write().await;
+                        layer_lock.dao_del_online(&did, &mpid);
+                        let h = layer_lock.dao_mut(&did)?.increased();
+                        drop(layer_lock);
+                        Consensus::insert(&db, &id, &h, &mid, &ConsensusType::MemberLeave)?;
+                        h
+                    } else {
+                        height
+                    };
+                    Dao::add_height(&db, id, h)?;
 
-                    // save consensus.
-                    GroupChat::add_height(&db, id, height)?;
+                    if is_server {
+                        let new_e = Event::MemberLeave(mpid);
+                        broadcast(&did, global, &LayerEvent::Sync(did, h, new_e), results).await?;
+                    }
                 }
-                Event::MessageCreate(mgid, nmsg, mtime) => {
-                    // server & client all handle it.
-                    println!("Sync: create message start");
-                    let (mid, _) = Member::get_id(&db, &id, &mgid)?;
-                    let new_height = if is_server {
-                        let new_e = Event::MessageCreate(mgid, nmsg.clone(), mtime);
-                        let height = layer.write().await.running_mut(&gcd)?.increased();
-                        Consensus::insert(&db, &id, &height, &mid, &ConsensusType::MessageCreate)?;
-                        broadcast(&LayerEvent::Sync(gcd, height, new_e), layer, &gcd, results)
-                            .await?;
-                        height
+                Event::MessageCreate(mpid, nmsg, mtime) => {
+                    debug!("Sync: create message start");
+                    if is_server && mpid != addr {
+                        return Err(anyhow!("message create is invalid"));
+                    }
+                    let h = if is_server {
+                        global.layer.write().await.dao_mut(&did)?.increased()
                     } else {
                         height
                     };
-                    GroupChat::add_height(&db, id, new_height)?;
 
-                    let (msg, scontent) =
-                        from_network_message(new_height, id, mgid, &ogid, nmsg, mtime, &base)?;
-                    results.rpcs.push(rpc::message_create(ogid, &msg));
-                    println!("Sync: create message ok");
+                    let msg = handle_network_message(
+                        &pid,
+                        &global.base,
+                        &db_key,
+                        h,
+                        id,
+                        mpid,
+                        nmsg.clone(),
+                        mtime,
+                        results,
+                    )
+                    .await?;
+                    results.rpcs.push(rpc::message_create(&msg));
+                    debug!("Sync: create message ok");
+
+                    if is_server {
+                        Consensus::insert(&db, &id, &h, &msg.id, &ConsensusType::MessageCreate)?;
+                    }
 
                     // UPDATE SESSION.
-                    let s_db = session_db(&base, &ogid)?;
-                    if let Ok(sid) = Session::last(
-                        &s_db,
-                        &id,
-                        &SessionType::Group,
-                        &msg.datetime,
-                        &scontent,
-                        true,
-                    ) {
-                        results.rpcs.push(session_last(
-                            ogid,
-                            &sid,
-                            &msg.datetime,
-                            &scontent,
-                            false,
-                        ));
+                    let s_db = session_db(&global.base, &pid, &db_key)?;
+                    update_session(&s_db, &id, &msg, results);
+
+                    Dao::add_height(&db, id, h)?;
+                    if is_server {
+                        let new_e = Event::MessageCreate(mpid, nmsg, mtime);
+                        broadcast(&did, global, &LayerEvent::Sync(did, h, new_e), results).await?;
                     }
                 }
+                Event::ProposalCreate(mpid, p_type, start, end) => {
+                    if is_server {
+                        if mpid != addr {
+                            return Err(anyhow!("proposal create is invalid"));
+                        }
+                        proposal_create(global, &db, did, id, mpid, p_type, end, results).await?;
+                    } else {
+                        let mut p = Proposal::new(id, height, mpid, p_type, start, end);
+                        p.insert(&db)?;
+                        results.rpcs.push(rpc::proposal_create(&p));
+                        Dao::add_height(&db, id, height)?;
+                    }
+                }
+                Event::ProposalVote(mpid, pheight, agree, time) => {
+                    if is_server {
+                        if mpid != addr {
+                            return Err(anyhow!("proposal vote is invalid"));
+                        }
+                        proposal_vote(global, &db, did, id, mpid, pheight, agree, results).await?;
+                    } else {
+                        proposal_voted(&db, id, mpid, pheight, agree, time, results)?;
+                        Dao::add_height(&db, id, height)?;
+                    }
+                }
+                Event::ProposalClose(pheight, agree, disagree, passed) => {
+                    // PEER, only server can tally.
+                    if is_server {
+                        return Err(anyhow!("proposal close is invalid"));
+                    }
+                    let mut p = Proposal::get_by_height(&db, &id, &pheight)?;
+                    p.over(&db, agree, disagree, passed)?;
+                    results.rpcs.push(rpc::proposal_update(&p));
+                    Dao::add_height(&db, id, height)?;
+                }
+                Event::Info(_, name, bio, avatar) => {
+                    // PEER, only server can execute.
+                    if is_server {
+                        return Err(anyhow!("proposal execute is invalid"));
+                    }
+                    let p_type = ProposalType::Info(name, bio, avatar);
+                    proposal_execute(global, &db, id, p_type, results).await?;
+                    Dao::add_height(&db, id, height)?;
+                }
+                Event::Transfer(_, mpid) => {
+                    if is_server {
+                        return Err(anyhow!("proposal execute is invalid"));
+                    }
+                    let p_type = ProposalType::Transfer(mpid);
+                    proposal_execute(global, &db, id, p_type, results).await?;
+                    Dao::add_height(&db, id, height)?;
+                }
+                Event::ManagerAdd(_, mpid) => {
+                    if is_server {
+                        return Err(anyhow!("proposal execute is invalid"));
+                    }
+                    let p_type = ProposalType::ManagerAdd(mpid);
+                    proposal_execute(global, &db, id, p_type, results).await?;
+                    Dao::add_height(&db, id, height)?;
+                }
+                Event::ManagerDel(_, mpid) => {
+                    if is_server {
+                        return Err(anyhow!("proposal execute is invalid"));
+                    }
+                    let p_type = ProposalType::ManagerDel(mpid);
+                    proposal_execute(global, &db, id, p_type, results).await?;
+                    Dao::add_height(&db, id, height)?;
+                }
+                Event::Close => {
+                    // TODO dao close.
+                }
+            }
+        }
+        LayerEvent::SyncReq(did, from) => {
+            // SERVER
+            debug!("Got sync request. height: {} from: {}", height, from);
+            // member or anonymous member had connected with zkp proof.
+            if !global.layer.read().await.dao(&did)?.addrs.contains(&addr) {
+                let _mid = Member::get_ok(&db, &id, &addr)?;
+            }
+
+            if height >= from {
+                let to = if height - from > 100 {
+                    from + 100
+                } else {
+                    height
+                };
+                let packed =
+                    Consensus::pack(&db, &global.base, &pid, &db_key, &id, &from, &to).await?;
+                let event = LayerEvent::Packed(did, height, from, to, packed);
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                let s = SendType::Event(0, addr, data);
+                results.layers.push((DAO_ID, s));
+                debug!("Sended sync request results. from: {}, to: {}", from, to);
             }
         }
-        LayerEvent::Packed(gcd, height, from, to, events) => {
-            // only client handle it.
+        LayerEvent::Packed(did, height, from, to, events) => {
+            // PEER
             if to >= height {
                 // when last packed sync, start sync online members.
-                add_layer(results, ogid, sync_online(gcd, addr));
+                results.layers.push((DAO_ID, sync_online(did, addr)));
+            }
+
+            debug!("Start handle sync packed... {}, {}, {}", height, from, to);
+            handle_packed(global, &pid, &db_key, &db, id, from, events, results).await?;
+
+            if to < height {
+                results.layers.push((DAO_ID, sync(did, addr, to)));
             }
 
-            println!("Start handle sync packed... {}, {}, {}", height, from, to);
-            handle_sync(
-                &db, ogid, id, gcd, addr, height, from, to, events, base, results,
-            )?;
+            // update dao height.
+            Dao::add_height(&db, id, to)?;
+            debug!("Over handle sync packed... {}, {}, {}", height, from, to);
         }
-        LayerEvent::RequestHandle(_gcd, rgid, raddr, join_proof, rid, time) => {
-            // only client handle it.
-            match join_proof {
-                JoinProof::Invite(i, _proof, mname, mavatar) => {
-                    let mut req =
-                        Request::new_by_remote(id, rid, rgid, raddr, mname, i.to_hex(), time);
-                    req.insert(&db)?;
-                    if mavatar.len() > 0 {
-                        write_avatar_sync(&base, &ogid, &rgid, mavatar)?;
-                    }
-                    results.rpcs.push(rpc::request_create(ogid, &req));
+        _ => {} // nerver here.
+    }
+
+    Ok(())
+}
+
+/// handle the events which not need connected: provider check & create, join request.
+async fn handle_request(
+    addr: PeerId,
+    event: LayerEvent,
+    global: &Arc<Global>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = dao_db(&global.base, &pid, &db_key)?;
+
+    match event {
+        LayerEvent::CheckResult(ct, name, remain, supported) => {
+            // PEER
+            debug!("check: {:?}, supported: {:?}", ct, supported);
+            let mut provider = Provider::get_by_addr(&db, &addr)?;
+            let rpc_ui = match ct {
+                CheckType::Allow => {
+                    provider.update(&db, name, supported, remain)?;
+                    rpc::provider_check(&provider)
                 }
-                JoinProof::Zkp(_proof) => {
-                    //
+                CheckType::None => {
+                    provider.update(&db, name, supported, 0)?;
+                    rpc::provider_check(&provider)
                 }
-                JoinProof::Open(..) => {} // nerver here.
-            }
-        }
-        LayerEvent::RequestResult(_gcd, rrid, ok) => {
-            // only client handle it.
-            let rid = Request::over_rid(&db, &id, &rrid, ok)?;
-            results
-                .rpcs
-                .push(rpc::request_handle(ogid, id, rid, ok, false));
+                CheckType::Suspend => {
+                    provider.suspend(&db)?;
+                    rpc::provider_check(&provider)
+                }
+                CheckType::Deny => {
+                    Provider::delete(&db, &provider.id)?;
+                    rpc::provider_delete(provider.id)
+                }
+            };
+            results.rpcs.push(rpc_ui)
         }
-        LayerEvent::MemberOnlineSync(gcd) => {
-            // only server handle it.
-            let onlines = layer
-                .read()
-                .await
-                .running(&gcd)?
-                .onlines()
-                .iter()
-                .map(|(g, a)| (**g, **a))
-                .collect();
-            let event = LayerEvent::MemberOnlineSyncResult(gcd, onlines);
-            let data = bincode::serialize(&event).unwrap_or(vec![]);
-            let s = SendType::Event(0, addr, data);
-            add_server_layer(results, fgid, s);
+        LayerEvent::CreateResult(did, ok) => {
+            // PEER
+            let mut dao = Dao::get_id(&db, &did, &addr)?;
+            results.rpcs.push(rpc::create_result(dao.id, ok));
+
+            if ok {
+                dao.ok(&db)?;
+
+                // ADD NEW SESSION.
+                let s_db = session_db(&global.base, &pid, &db_key)?;
+                let mut session = dao.to_session();
+                session.insert(&s_db)?;
+                results.rpcs.push(session_create(&session));
+
+                let proof = dao.connect_proof(&pid);
+                dao_conn(addr, did, proof, results);
+            }
         }
-        LayerEvent::Request(gcd, join_proof) => {
-            // only server handle it.
-            println!("----------- PRINTLN GROUP CHAT: GOT REQUEST: {}", id);
-            let group = GroupChat::get_id(&db, &id)?.ok_or(anyhow!("missing group"))?;
+        LayerEvent::Request(did, join_proof) => {
+            // SERVER
+            let (_, _, id, daddr) = global.layer.read().await.dao(&did)?.info();
+            if daddr != pid {
+                return Err(anyhow!("NOT THE SERVER EVENT"));
+            }
+            let dao = Dao::get(&db, &id)?;
+
+            // had joined.
+            if Member::get_ok(&db, &id, &addr).is_ok() {
+                let s = agree(global, &db, &id, addr).await?;
+                results.layers.push((DAO_ID, s));
+                return Ok(());
+            }
 
-            // 1. check account is online, if not online, nothing.
             match join_proof {
                 JoinProof::Open(mname, mavatar) => {
-                    // check is member.
-                    if Member::get_id(&db, &id, &fgid).is_ok() {
-                        let s = agree(&base, &ogid, &gcd, group, addr).await;
-                        add_server_layer(results, fgid, s);
+                    if dao.d_type != DaoType::Open {
+                        results.layers.push((DAO_ID, reject(did, addr, false)));
                         return Ok(());
                     }
 
-                    if group.g_type == GroupType::Open {
-                        let mut m = Member::new_notime(id, fgid, addr, mname, false);
-                        m.insert(&db)?;
-                        // save avatar.
-                        let _ = write_avatar(&base, &ogid, &m.m_id, &mavatar).await;
+                    member_join(global, &db, did, id, addr, mname, mavatar, results).await?;
+                    let s = agree(global, &db, &id, addr).await?;
+                    results.layers.push((DAO_ID, s));
+                }
+                JoinProof::Invite(inviter, mname, mavatar) => {
+                    // check if inviter is member.
+                    let inv_is_manager = match Member::get_id(&db, &id, &inviter) {
+                        Ok((_, is_manager)) => is_manager,
+                        Err(_) => {
+                            results.layers.push((DAO_ID, reject(did, addr, false)));
+                            return Ok(());
+                        }
+                    };
 
-                        // add consensuse and storage.
-                        let height = layer.write().await.running_mut(&gcd)?.increased();
-                        Consensus::insert(&db, &id, &height, &m.id, &ConsensusType::MemberJoin)?;
-                        GroupChat::add_height(&db, id, height)?;
+                    if dao.is_need_agree && !inv_is_manager {
+                        // waiting managers to handle it.
+                        if Request::exist_remote(&db, &id, &addr)? {
+                            return Ok(());
+                        }
 
-                        // UI: update.
-                        results.rpcs.push(rpc::member_join(ogid, &m));
+                        let remark = id_to_str(&inviter);
+                        let mut req = Request::new_by_server(id, did, addr, mname.clone(), remark);
+                        req.insert(&db)?;
+                        req.rid = req.id; // not need save. beacuse UI rpc will sended.
+                        if mavatar.len() > 0 {
+                            write_avatar_sync(&global.base, &pid, &db_key, &addr, mavatar.clone())?;
+                        }
+                        results.rpcs.push(rpc::request_create(&req));
 
-                        // broadcast join event.
+                        // send to online managers.
+                        let proof = JoinProof::Invite(inviter, mname, mavatar);
                         let event =
-                            Event::MemberJoin(m.m_id, m.m_addr, m.m_name, mavatar, m.datetime);
-                        broadcast(&LayerEvent::Sync(gcd, height, event), layer, &gcd, results)
-                            .await?;
-
-                        // return join result.
-                        let s = agree(&base, &ogid, &gcd, group, addr).await;
-                        add_server_layer(results, fgid, s);
-                    } else {
-                        add_server_layer(results, fgid, reject(gcd, addr, false));
-                    }
-                }
-                JoinProof::Invite(invite_gid, _proof, mname, mavatar) => {
-                    println!("----------- PRINTLN GROUP CHAT: GOT REQUEST INVITE: {}", id);
-                    // check is member.
-                    if Member::get_id(&db, &id, &fgid).is_ok() {
-                        let s = agree(&base, &ogid, &gcd, group, addr).await;
-                        add_server_layer(results, fgid, s);
-                        println!("----------- PRINTLN GROUP CHAT: GOT REQUEST HAD MEMBER");
+                            LayerEvent::RequestHandle(did, addr, proof, req.id, req.datetime);
+                        let data = bincode::serialize(&event)?;
+                        let managers = Member::managers(&db, &id)?;
+                        let layer_lock = global.layer.read().await;
+                        for maddr in layer_lock.dao(&did)?.addrs.iter().skip(1) {
+                            if managers.contains(maddr) {
+                                let s = SendType::Event(0, *maddr, data.clone());
+                                results.layers.push((DAO_ID, s));
+                            }
+                        }
                         return Ok(());
                     }
 
-                    // TODO check if request had or is blocked by manager.
-
-                    // check if inviter is member.
-                    let inv_mid = Member::get_id(&db, &id, &invite_gid);
-                    if inv_mid.is_err() {
-                        add_server_layer(results, fgid, reject(gcd, addr, false));
-                        println!("----------- PRINTLN GROUP CHAT: Inviter not exists");
+                    member_join(global, &db, did, id, addr, mname, mavatar, results).await?;
+                    let s = agree(global, &db, &id, addr).await?;
+                    results.layers.push((DAO_ID, s));
+                }
+                JoinProof::Zkp(proof, _mname, _mavatar) => {
+                    // had the dao key, not need managers agree.
+                    if !verify_proof(&dao, &proof, &addr) {
+                        results.layers.push((DAO_ID, reject(did, addr, false)));
                         return Ok(());
                     }
 
-                    // TODO check proof.
-                    //proof.verify(&invite_gid, &addr, &layer.addr)?;
-
-                    if group.is_need_agree {
-                        let (_inv_id, inv_is_manager) = inv_mid.unwrap();
-                        // only server holder to handle this request.
-                        if !inv_is_manager {
-                            let mut req =
-                                Request::new_by_server(id, fgid, addr, mname, invite_gid.to_hex());
-                            req.insert(&db)?;
-                            req.rid = req.id; // not need save. beacuse UI rpc will sended.
-                            if mavatar.len() > 0 {
-                                write_avatar_sync(&base, &ogid, &fgid, mavatar)?;
-                            }
-                            results.rpcs.push(rpc::request_create(ogid, &req));
-                            return Ok(());
-                        }
-                    }
+                    // anonymous member, not saved to members and not broadcast,
+                    // so the other members never know the joiner's address.
+                    let s = agree(global, &db, &id, addr).await?;
+                    results.layers.push((DAO_ID, s));
+                }
+            }
+        }
+        LayerEvent::Agree(did, info) => {
+            // PEER
+            let (rid, key) = Request::over(&db, &did, &addr, true).unwrap_or((0, vec![]));
+
+            // 1. add dao.
+            let mut dao = Dao::from_info(key, info, addr, &global.base, &pid, &db_key)?;
+            if let Err(e) = dao.insert(&db) {
+                let _ = release_content(
+                    &global.base,
+                    &pid,
+                    &db_key,
+                    &MessageType::Image,
+                    &dao.avatar,
+                );
+                return Err(e);
+            }
 
-                    let mut m = Member::new_notime(id, fgid, addr, mname, false);
-                    m.insert(&db)?;
-                    // save avatar.
-                    let _ = write_avatar(&base, &ogid, &m.m_id, &mavatar).await;
+            // 2. ADD NEW SESSION.
+            let s_db = session_db(&global.base, &pid, &db_key)?;
+            let mut session = dao.to_session();
+            session.insert(&s_db)?;
+            results.rpcs.push(session_create(&session));
+
+            // 3. update UI.
+            results.rpcs.push(rpc::request_handle(0, rid, true, false));
+            results.rpcs.push(rpc::dao_create(&dao));
 
-                    // add consensuse and storage.
-                    let height = layer.write().await.running_mut(&gcd)?.increased();
-                    Consensus::insert(&db, &id, &height, &m.id, &ConsensusType::MemberJoin)?;
-                    GroupChat::add_height(&db, id, height)?;
+            // 4. try connect.
+            dao_conn(addr, did, dao.connect_proof(&pid), results);
+        }
+        LayerEvent::Reject(did, efficacy) => {
+            // PEER
+            let (rid, _key) = Request::over(&db, &did, &addr, false)?;
+            results
+                .rpcs
+                .push(rpc::request_handle(0, rid, false, efficacy));
+        }
+        _ => {} // nerver here.
+    }
 
-                    // UI: update.
-                    results.rpcs.push(rpc::member_join(ogid, &m));
+    Ok(())
+}
 
-                    // broadcast join event.
-                    let event = Event::MemberJoin(m.m_id, m.m_addr, m.m_name, mavatar, m.datetime);
-                    broadcast(&LayerEvent::Sync(gcd, height, event), layer, &gcd, results).await?;
+async fn handle_packed(
+    global: &Arc<Global>,
+    pid: &PeerId,
+    db_key: &str,
+    db: &DStorage,
+    id: i64,
+    mut height: i64,
+    events: Vec<PackedEvent>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let mut last_message = None;
 
-                    // return join result.
-                    let s = agree(&base, &ogid, &gcd, group, addr).await;
-                    add_server_layer(results, fgid, s);
-                    println!("----------- PRINTLN GROUP CHAT: GOT REQUEST INVITE OVER");
+    for event in events {
+        match event {
+            PackedEvent::MemberJoin(mpid, mname, mavatar, mtime) => {
+                let mut member = Member::new(id, mpid, mname, false, mtime);
+                member.insert(db)?;
+                if mavatar.len() > 0 {
+                    write_avatar_sync(&global.base, pid, db_key, &mpid, mavatar)?;
                 }
-                JoinProof::Zkp(_proof) => {
-                    // TOOD zkp join.
+                results.rpcs.push(rpc::member_join(&member));
+            }
+            PackedEvent::MemberInfo(mpid, mname, mavatar) => {
+                if let Ok((mid, _)) = Member::get_id(db, &id, &mpid) {
+                    Member::update(db, &mid, &mname)?;
+                    if mavatar.len() > 0 {
+                        write_avatar_sync(&global.base, pid, db_key, &mpid, mavatar)?;
+                    }
+                    results.rpcs.push(rpc::member_info(id, mid, &mname));
                 }
             }
-        }
-        LayerEvent::SyncReq(gcd, from) => {
-            // only server handle it.
-            println!("Got sync request. height: {} from: {}", height, from);
-
-            if height >= from {
-                let to = if height - from > 100 {
-                    from + 100
-                } else {
-                    height
-                };
-                let packed = Consensus::pack(&db, &base, &gcd, &id, &from, &to).await?;
-                let event = LayerEvent::Packed(gcd, height, from, to, packed);
-                let data = bincode::serialize(&event).unwrap_or(vec![]);
-                let s = SendType::Event(0, addr, data);
-                add_server_layer(results, fgid, s);
-                println!("Sended sync request results. from: {}, to: {}", from, to);
+            PackedEvent::MemberLeave(mpid) => {
+                if let Ok((mid, _)) = Member::get_id(db, &id, &mpid) {
+                    Member::leave(db, &mid)?;
+                    results.rpcs.push(rpc::member_leave(id, mid));
+                }
+            }
+            PackedEvent::MessageCreate(mpid, nmsg, mtime) => {
+                if let Ok(msg) = handle_network_message(
+                    pid,
+                    &global.base,
+                    db_key,
+                    height,
+                    id,
+                    mpid,
+                    nmsg,
+                    mtime,
+                    results,
+                )
+                .await
+                {
+                    results.rpcs.push(rpc::message_create(&msg));
+                    last_message = Some(msg);
+                }
+            }
+            PackedEvent::ProposalCreate(mpid, p_type, start, end) => {
+                let mut p = Proposal::new(id, height, mpid, p_type, start, end);
+                p.insert(db)?;
+                results.rpcs.push(rpc::proposal_create(&p));
+            }
+            PackedEvent::ProposalVote(mpid, pheight, agree, time) => {
+                proposal_voted(db, id, mpid, pheight, agree, time, results)?;
+            }
+            PackedEvent::ProposalClose(pheight, agree, disagree, passed) => {
+                if let Ok(mut p) = Proposal::get_by_height(db, &id, &pheight) {
+                    p.over(db, agree, disagree, passed)?;
+                    results.rpcs.push(rpc::proposal_update(&p));
+                }
+            }
+            PackedEvent::Info(_, name, bio, avatar) => {
+                let p_type = ProposalType::Info(name, bio, avatar);
+                proposal_execute(global, db, id, p_type, results).await?;
+            }
+            PackedEvent::Transfer(_, mpid) => {
+                let p_type = ProposalType::Transfer(mpid);
+                proposal_execute(global, db, id, p_type, results).await?;
             }
+            PackedEvent::ManagerAdd(_, mpid) => {
+                let p_type = ProposalType::ManagerAdd(mpid);
+                proposal_execute(global, db, id, p_type, results).await?;
+            }
+            PackedEvent::ManagerDel(_, mpid) => {
+                let p_type = ProposalType::ManagerDel(mpid);
+                proposal_execute(global, db, id, p_type, results).await?;
+            }
+            PackedEvent::Close => {
+                // TODO dao close.
+            }
+            PackedEvent::None => {}
         }
-        LayerEvent::Check => {}      // nerver here.
-        LayerEvent::Create(..) => {} // nerver here.
+        height += 1;
+    }
+
+    // UPDATE SESSION.
+    if let Some(msg) = last_message {
+        let s_db = session_db(&global.base, pid, db_key)?;
+        update_session(&s_db, &id, &msg, results);
     }
 
     Ok(())
 }
 
-async fn broadcast(
-    event: &LayerEvent,
-    layer: &Arc<RwLock<Layer>>,
-    gcd: &GroupId,
+/// SERVER: handle the join request, when is agree, add member and send dao info.
+pub(crate) async fn request_handle(
+    global: &Arc<Global>,
+    db: &DStorage,
+    did: DaoId,
+    id: i64,
+    rid: i64,
+    ok: bool,
     results: &mut HandleResult,
 ) -> Result<()> {
-    let new_data = bincode::serialize(&event)?;
-
-    for (mgid, maddr) in layer.read().await.running(&gcd)?.onlines() {
-        let s = SendType::Event(0, *maddr, new_data.clone());
-        add_server_layer(results, *mgid, s);
-        println!("--- DEBUG broadcast to: {:?}", mgid);
+    let req = Request::get(db, &rid)?;
+    if req.fid != id || req.is_over {
+        return Err(anyhow!("request is invalid"));
+    }
+    Request::over_id(db, &rid, ok)?;
+    results.rpcs.push(rpc::request_handle(id, rid, ok, false));
+
+    if ok {
+        let pid = global.pid().await;
+        let db_key = global.own.read().await.db_key(&pid)?;
+        let mavatar = read_avatar(&global.base, &pid, &db_key, &req.pid)
+            .await
+            .unwrap_or(vec![]);
+        member_join(global, db, did, id, req.pid, req.name, mavatar, results).await?;
+        let s = agree(global, db, &id, req.pid).await?;
+        results.layers.push((DAO_ID, s));
+    } else {
+        results.layers.push((DAO_ID, reject(did, req.pid, false)));
     }
 
-    Ok(())
+    // sync to other managers.
+    broadcast(
+        &did,
+        global,
+        &LayerEvent::RequestResult(did, rid, ok),
+        results,
+    )
+    .await
 }
 
-pub(crate) fn group_conn(proof: Proof, addr: Peer, gid: GroupId) -> SendType {
-    let data =
-        bincode::serialize(&LayerConnect(gid, ConnectProof::Common(proof))).unwrap_or(vec![]);
-    SendType::Connect(0, addr, data)
-}
+/// SERVER: add new member, and broadcast it.
+async fn member_join(
+    global: &Arc<Global>,
+    db: &DStorage,
+    did: DaoId,
+    id: i64,
+    mpid: PeerId,
+    mname: String,
+    mavatar: Vec<u8>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let mut m = Member::new_notime(id, mpid, mname, false);
+    m.insert(db)?;
+    if mavatar.len() > 0 {
+        let db_key = global.own.read().await.db_key(&pid)?;
+        write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar.clone())?;
+    }
 
-fn sync(gcd: GroupId, addr: PeerId, height: i64) -> SendType {
-    println!("Send sync request...");
-    let data = bincode::serialize(&LayerEvent::SyncReq(gcd, height + 1)).unwrap_or(vec![]);
-    SendType::Event(0, addr, data)
-}
+    // add consensus and storage.
+    let h = global.layer.write().await.dao_mut(&did)?.increased();
+    Consensus::insert(db, &id, &h, &m.id, &ConsensusType::MemberJoin)?;
+    Dao::add_height(db, id, h)?;
 
-fn sync_online(gcd: GroupId, addr: PeerId) -> SendType {
-    let data = bincode::serialize(&LayerEvent::MemberOnlineSync(gcd)).unwrap_or(vec![]);
-    SendType::Event(0, addr, data)
+    // UI: update.
+    results.rpcs.push(rpc::member_join(&m));
+
+    // broadcast join event.
+    let event = Event::MemberJoin(m.pid, m.name, mavatar, m.datetime);
+    broadcast(&did, global, &LayerEvent::Sync(did, h, event), results).await
 }
 
-async fn agree(
-    base: &PathBuf,
-    ogid: &GroupId,
-    gcd: &GroupId,
-    group: GroupChat,
-    addr: PeerId,
-) -> SendType {
-    let gavatar = read_avatar(base, ogid, gcd).await.unwrap_or(vec![]);
-    let group_info = group.to_group_info("".to_owned(), gavatar, vec![]);
-    let res = LayerEvent::Agree(*gcd, group_info);
-    let d = bincode::serialize(&res).unwrap_or(vec![]);
-    SendType::Event(0, addr, d)
+/// SERVER: create new proposal, the voting window is fixed by the dao type's rule.
+pub(crate) async fn proposal_create(
+    global: &Arc<Global>,
+    db: &DStorage,
+    did: DaoId,
+    id: i64,
+    mpid: PeerId,
+    p_type: ProposalType,
+    end: i64,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let _mid = Member::get_ok(db, &id, &mpid)?;
+    let dao = Dao::get(db, &id)?;
+    proposal_check(db, &dao, &p_type)?;
+
+    let start = now();
+    let end = dao.d_type.vote_rule().window(start, end);
+
+    let h = global.layer.write().await.dao_mut(&did)?.increased();
+    let mut p = Proposal::new(id, h, mpid, p_type.clone(), start, end);
+    p.insert(db)?;
+    Consensus::insert(db, &id, &h, &p.id, &ConsensusType::ProposalCreate)?;
+    Dao::add_height(db, id, h)?;
+    results.rpcs.push(rpc::proposal_create(&p));
+
+    let event = Event::ProposalCreate(mpid, p_type, start, end);
+    broadcast(&did, global, &LayerEvent::Sync(did, h, event), results).await
 }
 
-fn reject(gcd: GroupId, addr: PeerId, lost: bool) -> SendType {
-    let d = bincode::serialize(&LayerEvent::Reject(gcd, lost)).unwrap_or(vec![]);
-    SendType::Event(0, addr, d)
+/// SERVER: member vote the proposal, one member one vote.
+pub(crate) async fn proposal_vote(
+    global: &Arc<Global>,
+    db: &DStorage,
+    did: DaoId,
+    id: i64,
+    mpid: PeerId,
+    pheight: i64,
+    agree: bool,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let _mid = Member::get_ok(db, &id, &mpid)?;
+    let mut p = Proposal::get_by_height(db, &id, &pheight)?;
+    if p.is_over {
+        return Err(anyhow!("proposal voting is over"));
+    }
+
+    let time = now();
+    if time >= p.end {
+        // voting is end, tally it.
+        return proposal_close(global, db, did, id, p, results).await;
+    }
+    if Vote::exist(db, &p.id, &mpid)? {
+        return Err(anyhow!("proposal had voted"));
+    }
+
+    let mut vote = Vote::new(p.id, mpid, agree, time);
+    vote.insert(db)?;
+    let (agrees, disagrees) = Vote::count(db, &p.id)?;
+    p.tally(db, agrees, disagrees)?;
+
+    let h = global.layer.write().await.dao_mut(&did)?.increased();
+    Consensus::insert(db, &id, &h, &vote.id, &ConsensusType::ProposalVote)?;
+    Dao::add_height(db, id, h)?;
+    results.rpcs.push(rpc::proposal_update(&p));
+
+    let event = Event::ProposalVote(mpid, pheight, agree, time);
+    broadcast(&did, global, &LayerEvent::Sync(did, h, event), results).await?;
+
+    // all members had voted, not need waiting.
+    if agrees + disagrees >= Member::count(db, &id)? {
+        proposal_close(global, db, did, id, p, results).await?;
+    }
+    Ok(())
 }
 
-fn handle_sync(
+/// SERVER: tally the proposal, and execute it when passed.
+async fn proposal_close(
+    global: &Arc<Global>,
     db: &DStorage,
-    ogid: GroupId,
+    did: DaoId,
     id: i64,
-    gcd: GroupId,
-    addr: PeerId,
-    height: i64,
-    mut from: i64,
-    to: i64,
-    events: Vec<PackedEvent>,
-    base: PathBuf,
+    mut p: Proposal,
     results: &mut HandleResult,
 ) -> Result<()> {
-    let mut last_scontent: Option<(String, i64)> = None;
+    let dao = Dao::get(db, &id)?;
+    let (agree, disagree) = Vote::count(db, &p.id)?;
+    let members = Member::count(db, &id)?;
+    let passed = dao.d_type.vote_rule().passed(members, agree, disagree);
+
+    let h = global.layer.write().await.dao_mut(&did)?.increased();
+    p.over(db, agree, disagree, passed)?;
+    Consensus::insert(db, &id, &h, &p.id, &ConsensusType::ProposalClose)?;
+    Dao::add_height(db, id, h)?;
+    results.rpcs.push(rpc::proposal_update(&p));
+
+    let event = Event::ProposalClose(p.height, agree, disagree, passed);
+    broadcast(&did, global, &LayerEvent::Sync(did, h, event), results).await?;
+
+    if !passed {
+        return Ok(());
+    }
 
-    for event in events {
-        if let Ok(scontent) = handle_sync_event(&ogid, &id, from, event, &base, db, results) {
-            last_scontent = scontent;
+    // the members maybe changed when voting, check again.
+    if let Err(e) = proposal_check(db, &dao, &p.p_type) {
+        warn!("proposal cannot execute: {}", e);
+        return Ok(());
+    }
+
+    let (event, ctype) = match p.p_type.clone() {
+        ProposalType::Text(..) => return Ok(()),
+        ProposalType::Info(name, bio, avatar) => (
+            Event::Info(p.height, name, bio, avatar),
+            ConsensusType::DaoInfo,
+        ),
+        ProposalType::Transfer(mpid) => {
+            (Event::Transfer(p.height, mpid), ConsensusType::DaoTransfer)
         }
-        from += 1;
+        ProposalType::ManagerAdd(mpid) => (
+            Event::ManagerAdd(p.height, mpid),
+            ConsensusType::DaoManagerAdd,
+        ),
+        ProposalType::ManagerDel(mpid) => (
+            Event::ManagerDel(p.height, mpid),
+            ConsensusType::DaoManagerDel,
+        ),
+    };
+
+    let h = global.layer.write().await.dao_mut(&did)?.increased();
+    proposal_execute(global, db, id, p.p_type, results).await?;
+    Consensus::insert(db, &id, &h, &p.id, &ctype)?;
+    Dao::add_height(db, id, h)?;
+    broadcast(&did, global, &LayerEvent::Sync(did, h, event), results).await
+}
+
+/// SERVER: tally all proposals which voting time is end.
+pub(crate) async fn proposal_tally(global: &Arc<Global>) -> Result<HandleResult> {
+    let mut results = HandleResult::new();
+
+    let pid = global.pid().await;
+    let daos: Vec<(DaoId, i64)> = global
+        .layer
+        .read()
+        .await
+        .daos
+        .iter()
+        .filter(|(_, session)| session.addrs[0] == pid)
+        .map(|(did, session)| (*did, session.db_id))
+        .collect();
+    if daos.is_empty() {
+        return Ok(results);
     }
 
-    if to < height {
-        add_layer(results, ogid, sync(gcd, addr, to + 1));
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = dao_db(&global.base, &pid, &db_key)?;
+    for (did, id) in daos {
+        for p in Proposal::expired(&db, &id)? {
+            proposal_close(global, &db, did, id, p, &mut results).await?;
+        }
     }
 
-    // update group chat height.
-    GroupChat::add_height(db, id, to)?;
+    Ok(results)
+}
 
-    // UPDATE SESSION.
-    if let Some((sc, t)) = last_scontent {
-        let s_db = session_db(&base, &ogid)?;
-        if let Ok(sid) = Session::last(&s_db, &id, &SessionType::Group, &t, &sc, true) {
-            results.rpcs.push(session_last(ogid, &sid, &t, &sc, false));
+/// check the proposal can be executed.
+fn proposal_check(db: &DStorage, dao: &Dao, p_type: &ProposalType) -> Result<()> {
+    match p_type {
+        ProposalType::Text(title, _) => {
+            if title.is_empty() {
+                return Err(anyhow!("proposal title is empty"));
+            }
+        }
+        ProposalType::Info(name, _, _) => {
+            if name.is_empty() {
+                return Err(anyhow!("dao name is empty"));
+            }
+        }
+        ProposalType::ManagerAdd(mpid) => {
+            let (_, is_manager) = Member::get_id(db, &dao.id, mpid)?;
+            if is_manager {
+                return Err(anyhow!("member is manager"));
+            }
+        }
+        ProposalType::ManagerDel(mpid) => {
+            let (_, is_manager) = Member::get_id(db, &dao.id, mpid)?;
+            if !is_manager || mpid == &dao.owner {
+                return Err(anyhow!("manager cannot be removed"));
+            }
+        }
+        ProposalType::Transfer(mpid) => {
+            let _mid = Member::get_ok(db, &dao.id, mpid)?;
+            if mpid == &dao.owner {
+                return Err(anyhow!("member is owner"));
+            }
         }
     }
-
     Ok(())
 }
 
-fn handle_sync_event(
-    ogid: &GroupId,
-    fid: &i64,
-    height: i64,
-    event: PackedEvent,
-    base: &PathBuf,
+/// SERVER & PEER: execute the passed proposal.
+async fn proposal_execute(
+    global: &Arc<Global>,
     db: &DStorage,
+    id: i64,
+    p_type: ProposalType,
     results: &mut HandleResult,
-) -> Result<Option<(String, i64)>> {
-    let scontent = match event {
-        PackedEvent::GroupInfo => {
-            // TODO
-            None
-        }
-        PackedEvent::GroupTransfer => {
-            // TODO
-            None
-        }
-        PackedEvent::GroupManagerAdd => {
-            // TODO
-            None
-        }
-        PackedEvent::GroupManagerDel => {
-            // TODO
-            None
-        }
-        PackedEvent::GroupClose => {
-            // TOOD
-            None
-        }
-        PackedEvent::MemberInfo(_mid, _maddr, _mname, _mavatar) => {
-            // TODO
-            None
-        }
-        PackedEvent::MemberJoin(mid, maddr, mname, mavatar, mtime) => {
-            if Member::get_id(db, fid, &mid).is_err() {
-                if mavatar.len() > 0 {
-                    write_avatar_sync(&base, &ogid, &mid, mavatar)?;
-                }
-                let mut member = Member::new(*fid, mid, maddr, mname, false, mtime);
-                member.insert(&db)?;
-                results.rpcs.push(rpc::member_join(*ogid, &member));
+) -> Result<()> {
+    match p_type {
+        ProposalType::Text(..) => {}
+        ProposalType::Info(name, bio, avatar) => {
+            let pid = global.pid().await;
+            let db_key = global.own.read().await.db_key(&pid)?;
+            let mut dao = Dao::get(db, &id)?;
+            if avatar.len() > 0 {
+                // the dao row keeps the avatar image reference.
+                let old = std::mem::take(&mut dao.avatar);
+                dao.avatar = write_image_sync(&global.base, &pid, &db_key, avatar)?;
+                release_content(&global.base, &pid, &db_key, &MessageType::Image, &old)?;
+            }
+            Dao::update_info(db, &id, &name, &bio, &dao.avatar)?;
+            dao.name = name;
+            dao.bio = bio;
+            results.rpcs.push(rpc::dao_info(&dao));
+
+            let s_db = session_db(&global.base, &pid, &db_key)?;
+            if let Ok(sid) = Session::update_name_by_id(&s_db, &id, &SessionType::Dao, &dao.name) {
+                results.rpcs.push(session_update_name(&sid, &dao.name));
             }
-            None
         }
-        PackedEvent::MemberLeave(_mid) => {
-            // TODO
-            None
+        ProposalType::Transfer(mpid) => {
+            Dao::transfer(db, &id, &mpid)?;
+            if let Ok((mid, _)) = Member::get_id(db, &id, &mpid) {
+                Member::manager(db, &mid, true)?;
+                results.rpcs.push(rpc::member_manager(id, mid, true));
+            }
+            results.rpcs.push(rpc::dao_info(&Dao::get(db, &id)?));
+        }
+        ProposalType::ManagerAdd(mpid) => {
+            let (mid, _) = Member::get_id(db, &id, &mpid)?;
+            Member::manager(db, &mid, true)?;
+            results.rpcs.push(rpc::member_manager(id, mid, true));
+        }
+        ProposalType::ManagerDel(mpid) => {
+            let (mid, _) = Member::get_id(db, &id, &mpid)?;
+            Member::manager(db, &mid, false)?;
+            results.rpcs.push(rpc::member_manager(id, mid, false));
         }
-        PackedEvent::MessageCreate(mid, nmsg, time) => {
-            let (msg, scontent) = from_network_message(height, *fid, mid, ogid, nmsg, time, base)?;
-            results.rpcs.push(rpc::message_create(*ogid, &msg));
-            Some((scontent, time))
+    }
+    Ok(())
+}
+
+/// PEER: save the vote from server, and update the proposal's tally.
+fn proposal_voted(
+    db: &DStorage,
+    id: i64,
+    mpid: PeerId,
+    pheight: i64,
+    agree: bool,
+    time: i64,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let mut p = Proposal::get_by_height(db, &id, &pheight)?;
+    Vote::new(p.id, mpid, agree, time).insert(db)?;
+    let (agrees, disagrees) = Vote::count(db, &p.id)?;
+    p.tally(db, agrees, disagrees)?;
+    results.rpcs.push(rpc::proposal_update(&p));
+    Ok(())
+}
+
+#[inline]
+fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
+
+pub(crate) async fn broadcast(
+    did: &DaoId,
+    global: &Arc<Global>,
+    event: &LayerEvent,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let new_data = bincode::serialize(event)?;
+
+    for mpid in global.layer.read().await.dao(did)?.addrs.iter().skip(1) {
+        let s = SendType::Event(0, *mpid, new_data.clone());
+        results.layers.push((DAO_ID, s));
+        debug!("--- DEBUG broadcast to: {:?}", mpid);
+    }
+
+    Ok(())
+}
+
+// UPDATE SESSION. follow the session's notification rules.
+pub(crate) fn update_session(s_db: &DStorage, id: &i64, msg: &Message, results: &mut HandleResult) {
+    let scontent = match msg.m_type {
+        MessageType::String => {
+            format!("{}:{}", msg.m_type.to_int(), msg.content)
         }
-        PackedEvent::None => None,
+        _ => format!("{}:", msg.m_type.to_int()),
     };
 
-    Ok(scontent)
+    let session = match Session::get_by_fid(s_db, id, &SessionType::Dao) {
+        Ok(session) => session,
+        Err(_) => return,
+    };
+    let notice = !msg.is_me && session.is_notice(false);
+
+    if let Ok(sid) = Session::last(
+        &s_db,
+        id,
+        &SessionType::Dao,
+        &msg.datetime,
+        &scontent,
+        !notice,
+    ) {
+        results
+            .rpcs
+            .push(session_last(&sid, &msg.datetime, &scontent, !notice));
+    }
+}
+
+pub(crate) fn dao_conn(addr: PeerId, did: DaoId, proof: ConnectProof, results: &mut HandleResult) {
+    let data = bincode::serialize(&LayerConnect(did, proof)).unwrap_or(vec![]);
+    let msg = SendType::Connect(0, Peer::peer(addr), data);
+    results.layers.push((DAO_ID, msg));
+}
+
+/// verify the zero-knowledge-proof with the dao key, only encrypted dao supported.
+fn verify_proof(dao: &Dao, proof: &ZkpProof, addr: &PeerId) -> bool {
+    dao.d_type == DaoType::Encrypted
+        && dao.key.len() > 0
+        && proof.verify(&key_hash(&dao.key), &dao.did, addr)
+}
+
+async fn agree(global: &Arc<Global>, db: &DStorage, id: &i64, addr: PeerId) -> Result<SendType> {
+    let pid = global.pid().await;
+    let (me, db_key) = {
+        let own_lock = global.own.read().await;
+        (own_lock.clone_user(&pid)?, own_lock.db_key(&pid)?)
+    };
+    let dao = Dao::get(db, id)?;
+    let info = dao
+        .to_info(&global.base, &pid, &db_key, me.name, me.avatar)
+        .await;
+    let data = bincode::serialize(&LayerEvent::Agree(dao.did, info))?;
+    Ok(SendType::Event(0, addr, data))
+}
+
+fn reject(did: DaoId, addr: PeerId, lost: bool) -> SendType {
+    let data = bincode::serialize(&LayerEvent::Reject(did, lost)).unwrap_or(vec![]);
+    SendType::Event(0, addr, data)
+}
+
+fn sync(did: DaoId, addr: PeerId, height: i64) -> SendType {
+    let data = bincode::serialize(&LayerEvent::SyncReq(did, height + 1)).unwrap_or(vec![]);
+    SendType::Event(0, addr, data)
+}
+
+fn sync_online(did: DaoId, addr: PeerId) -> SendType {
+    let data = bincode::serialize(&LayerEvent::MemberOnlineSync(did)).unwrap_or(vec![]);
+    SendType::Event(0, addr, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::Node;
+
+    /// the events sent to the peers in the results.
+    fn events(results: &HandleResult) -> Vec<(PeerId, LayerEvent)> {
+        let mut events = vec![];
+        for (_, msg) in results.layers.iter() {
+            if let SendType::Event(_, addr, data) = msg {
+                events.push((*addr, bincode::deserialize(data).unwrap()));
+            }
+        }
+        events
+    }
+
+    /// create a local dao in the node, the same as rpc `dao-create`.
+    async fn create(node: &Node, d_type: DaoType, need_agree: bool) -> (i64, DaoId) {
+        let db = node.db(dao_db).await;
+        let s_db = node.db(session_db).await;
+        let mut dao = Dao::new(
+            node.pid,
+            d_type,
+            node.pid,
+            "dao".to_owned(),
+            "".to_owned(),
+            need_agree,
+            true,
+        );
+        dao.insert(&db).unwrap();
+        let mut m = Member::new_notime(dao.id, node.pid, "host".to_owned(), true);
+        m.insert(&db).unwrap();
+        Consensus::insert(&db, &dao.id, &1, &m.id, &ConsensusType::MemberJoin).unwrap();
+        Dao::add_height(&db, dao.id, 1).unwrap();
+        let mut session = dao.to_session();
+        session.insert(&s_db).unwrap();
+        node.global
+            .layer
+            .write()
+            .await
+            .dao_add(dao.did, node.pid, session.id, dao.id, 1);
+        (dao.id, dao.did)
+    }
+
+    #[tokio::test]
+    async fn create_local_dao() {
+        let host = Node::new("host").await;
+        let (id, did) = create(&host, DaoType::Open, false).await;
+
+        let db = host.db(dao_db).await;
+        let dao = Dao::get(&db, &id).unwrap();
+        assert_eq!(dao.did, did);
+        assert_eq!(dao.height, 1);
+        assert!(Member::get_ok(&db, &id, &host.pid).is_ok());
+        assert_eq!(Member::managers(&db, &id).unwrap(), vec![host.pid]);
+        assert_eq!(
+            host.global.layer.read().await.dao(&did).unwrap().info().0,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn join_open_dao_and_agree() {
+        let host = Node::new("host").await;
+        let peer = Node::new("peer").await;
+        let (id, did) = create(&host, DaoType::Open, false).await;
+
+        // peer request to join.
+        let proof = JoinProof::Open("peer".to_owned(), vec![]);
+        let mut results = HandleResult::new();
+        handle_event(
+            peer.pid,
+            LayerEvent::Request(did, proof),
+            &host.global,
+            &mut results,
+        )
+        .await
+        .unwrap();
+
+        let db = host.db(dao_db).await;
+        assert!(Member::get_ok(&db, &id, &peer.pid).is_ok());
+        assert_eq!(Dao::get(&db, &id).unwrap().height, 2);
+
+        // host agree, peer add the dao and connect to host.
+        let (to, agree) = events(&results).pop().unwrap();
+        assert_eq!(to, peer.pid);
+        assert!(matches!(agree, LayerEvent::Agree(..)));
+        let mut results = HandleResult::new();
+        handle_event(host.pid, agree, &peer.global, &mut results)
+            .await
+            .unwrap();
+
+        let p_db = peer.db(dao_db).await;
+        let dao = Dao::get_id(&p_db, &did, &host.pid).unwrap();
+        assert_eq!(dao.d_type, DaoType::Open);
+        assert!(results
+            .layers
+            .iter()
+            .any(|(_, msg)| matches!(msg, SendType::Connect(..))));
+    }
+
+    #[tokio::test]
+    async fn join_encrypted_dao_anonymous() {
+        let host = Node::new("host").await;
+        let member = Node::new("member").await;
+        let peer = Node::new("peer").await;
+        let (id, did) = create(&host, DaoType::Encrypted, false).await;
+        let db = host.db(dao_db).await;
+        let key = Dao::get(&db, &id).unwrap().key;
+        host.global
+            .layer
+            .write()
+            .await
+            .dao_add_member(&did, member.pid);
+
+        // wrong key is rejected.
+        let proof = ZkpProof::prove(&[0u8; 32], &did, &peer.pid);
+        let event = LayerEvent::Request(did, JoinProof::Zkp(proof, "peer".to_owned(), vec![]));
+        let mut results = HandleResult::new();
+        handle_event(peer.pid, event, &host.global, &mut results)
+            .await
+            .unwrap();
+        assert!(matches!(
+            events(&results).pop(),
+            Some((_, LayerEvent::Reject(..)))
+        ));
+
+        // agree only to the joiner, not saved and not broadcast to members.
+        let proof = ZkpProof::prove(&key, &did, &peer.pid);
+        let event = LayerEvent::Request(did, JoinProof::Zkp(proof, "peer".to_owned(), vec![]));
+        let mut results = HandleResult::new();
+        handle_event(peer.pid, event, &host.global, &mut results)
+            .await
+            .unwrap();
+        let events = events(&results);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, peer.pid);
+        assert!(matches!(events[0].1, LayerEvent::Agree(..)));
+        assert!(Member::get_ok(&db, &id, &peer.pid).is_err());
+        assert_eq!(Dao::get(&db, &id).unwrap().height, 1);
+    }
+
+    #[tokio::test]
+    async fn join_private_dao_need_manager() {
+        let host = Node::new("host").await;
+        let member = Node::new("member").await;
+        let peer = Node::new("peer").await;
+        let (id, did) = create(&host, DaoType::Private, true).await;
+        let db = host.db(dao_db).await;
+        Member::new_notime(id, member.pid, "member".to_owned(), false)
+            .insert(&db)
+            .unwrap();
+
+        // open join is rejected by private dao.
+        let proof = JoinProof::Open("peer".to_owned(), vec![]);
+        let mut results = HandleResult::new();
+        handle_event(
+            peer.pid,
+            LayerEvent::Request(did, proof),
+            &host.global,
+            &mut results,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            events(&results).pop(),
+            Some((_, LayerEvent::Reject(..)))
+        ));
+
+        // invited by member, waiting the managers.
+        let proof = JoinProof::Invite(member.pid, "peer".to_owned(), vec![]);
+        let mut results = HandleResult::new();
+        handle_event(
+            peer.pid,
+            LayerEvent::Request(did, proof),
+            &host.global,
+            &mut results,
+        )
+        .await
+        .unwrap();
+        assert!(Member::get_ok(&db, &id, &peer.pid).is_err());
+        let requests = Request::list(&db, true).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].pid, peer.pid);
+
+        // manager agree the request.
+        let mut results = HandleResult::new();
+        request_handle(
+            &host.global,
+            &db,
+            did,
+            id,
+            requests[0].id,
+            true,
+            &mut results,
+        )
+        .await
+        .unwrap();
+        assert!(Member::get_ok(&db, &id, &peer.pid).is_ok());
+        assert!(Request::get(&db, &requests[0].id).unwrap().is_over);
+        assert!(events(&results)
+            .iter()
+            .any(|(to, e)| to == &peer.pid && matches!(e, LayerEvent::Agree(..))));
+
+        // the handled request cannot handle again.
+        let mut results = HandleResult::new();
+        assert!(request_handle(
+            &host.global,
+            &db,
+            did,
+            id,
+            requests[0].id,
+            false,
+            &mut results
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn sync_packed_events() {
+        let host = Node::new("host").await;
+        let peer = Node::new("peer").await;
+        let (id, did) = create(&host, DaoType::Open, false).await;
+
+        let proof = JoinProof::Open("peer".to_owned(), vec![]);
+        let mut results = HandleResult::new();
+        handle_event(
+            peer.pid,
+            LayerEvent::Request(did, proof),
+            &host.global,
+            &mut results,
+        )
+        .await
+        .unwrap();
+        let (_, agree) = events(&results).pop().unwrap();
+        let mut results = HandleResult::new();
+        handle_event(host.pid, agree, &peer.global, &mut results)
+            .await
+            .unwrap();
+
+        // pack all the events.
+        let db = host.db(dao_db).await;
+        let key = host.db_key().await;
+        let packed = Consensus::pack(&db, &host.global.base, &host.pid, &key, &id, &1, &2)
+            .await
+            .unwrap();
+        assert_eq!(packed.len(), 2);
+        assert!(matches!(&packed[0], PackedEvent::MemberJoin(p, ..) if p == &host.pid));
+        assert!(matches!(&packed[1], PackedEvent::MemberJoin(p, ..) if p == &peer.pid));
+
+        // peer connected and sync from the host.
+        let p_db = peer.db(dao_db).await;
+        let p_id = Dao::get_id(&p_db, &did, &host.pid).unwrap().id;
+        peer.global
+            .layer
+            .write()
+            .await
+            .dao_add(did, host.pid, 0, p_id, 0);
+        host.global
+            .layer
+            .write()
+            .await
+            .dao_add_member(&did, peer.pid);
+
+        let mut results = HandleResult::new();
+        handle_event(
+            peer.pid,
+            LayerEvent::SyncReq(did, 1),
+            &host.global,
+            &mut results,
+        )
+        .await
+        .unwrap();
+        let (to, packed) = events(&results).pop().unwrap();
+        assert_eq!(to, peer.pid);
+        assert!(matches!(packed, LayerEvent::Packed(_, 2, 1, 2, _)));
+
+        let mut results = HandleResult::new();
+        handle_event(host.pid, packed, &peer.global, &mut results)
+            .await
+            .unwrap();
+        let members: Vec<PeerId> = Member::list(&p_db, &p_id)
+            .unwrap()
+            .iter()
+            .map(|m| m.pid)
+            .collect();
+        assert_eq!(members, vec![host.pid, peer.pid]);
+        assert_eq!(Dao::get(&p_db, &p_id).unwrap().height, 2);
+    }
 }
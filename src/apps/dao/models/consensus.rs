@@ -1,21 +1,26 @@
+use dao_types::{PackedEvent, ProposalType};
 use std::path::PathBuf;
-use tdn::types::{group::GroupId, primitive::Result};
+use tdn::types::primitives::{PeerId, Result};
 use tdn_storage::local::{DStorage, DsValue};
 
-use group_types::PackedEvent;
+use crate::group::to_network_message;
+use crate::storage::read_avatar;
 
-use super::{to_network_message, Member, Message};
+use super::{Member, Message, Proposal, Vote};
 
 pub(crate) enum ConsensusType {
-    GroupInfo,
-    GroupTransfer,
-    GroupManagerAdd,
-    GroupManagerDel,
-    GroupClose,
+    DaoInfo,
+    DaoTransfer,
+    DaoManagerAdd,
+    DaoManagerDel,
+    DaoClose,
     MemberInfo,
     MemberJoin,
     MemberLeave,
     MessageCreate,
+    ProposalCreate,
+    ProposalVote,
+    ProposalClose,
     None,
 }
 
@@ -23,42 +28,48 @@ impl ConsensusType {
     fn to_i64(&self) -> i64 {
         match self {
             ConsensusType::None => 0,
-            ConsensusType::GroupInfo => 1,
-            ConsensusType::GroupTransfer => 2,
-            ConsensusType::GroupManagerAdd => 3,
-            ConsensusType::GroupManagerDel => 4,
-            ConsensusType::GroupClose => 5,
+            ConsensusType::DaoInfo => 1,
+            ConsensusType::DaoTransfer => 2,
+            ConsensusType::DaoManagerAdd => 3,
+            ConsensusType::DaoManagerDel => 4,
+            ConsensusType::DaoClose => 5,
             ConsensusType::MemberInfo => 6,
             ConsensusType::MemberJoin => 7,
             ConsensusType::MemberLeave => 8,
             ConsensusType::MessageCreate => 9,
+            ConsensusType::ProposalCreate => 10,
+            ConsensusType::ProposalVote => 11,
+            ConsensusType::ProposalClose => 12,
         }
     }
 
     fn from_i64(a: i64) -> Self {
         match a {
-            1 => ConsensusType::GroupInfo,
-            2 => ConsensusType::GroupTransfer,
-            3 => ConsensusType::GroupManagerAdd,
-            4 => ConsensusType::GroupManagerDel,
-            5 => ConsensusType::GroupClose,
+            1 => ConsensusType::DaoInfo,
+            2 => ConsensusType::DaoTransfer,
+            3 => ConsensusType::DaoManagerAdd,
+            4 => ConsensusType::DaoManagerDel,
+            5 => ConsensusType::DaoClose,
             6 => ConsensusType::MemberInfo,
             7 => ConsensusType::MemberJoin,
             8 => ConsensusType::MemberLeave,
             9 => ConsensusType::MessageCreate,
+            10 => ConsensusType::ProposalCreate,
+            11 => ConsensusType::ProposalVote,
+            12 => ConsensusType::ProposalClose,
             _ => ConsensusType::None,
         }
     }
 }
 
-/// Group Chat Consensus.
+/// DAO Consensus.
 pub(crate) struct Consensus {
     /// db auto-increment id.
     _id: i64,
-    /// group's db id.
+    /// dao's db id.
     _fid: i64,
-    /// group's height.
-    _height: i64,
+    /// dao's height.
+    height: i64,
     /// consensus type.
     ctype: ConsensusType,
     /// consensus point value db id.
@@ -66,75 +77,102 @@ pub(crate) struct Consensus {
 }
 
 impl Consensus {
+    /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Consensus {
         Consensus {
             cid: v.pop().unwrap().as_i64(),
             ctype: ConsensusType::from_i64(v.pop().unwrap().as_i64()),
-            _height: v.pop().unwrap().as_i64(),
+            height: v.pop().unwrap().as_i64(),
             _fid: v.pop().unwrap().as_i64(),
             _id: v.pop().unwrap().as_i64(),
         }
     }
 
+    /// pack the events between heights, missing height will be `PackedEvent::None`.
     pub async fn pack(
         db: &DStorage,
         base: &PathBuf,
-        gcd: &GroupId,
+        own: &PeerId,
+        db_key: &str,
         fid: &i64,
         from: &i64,
         to: &i64,
     ) -> Result<Vec<PackedEvent>> {
         let matrix = db.query(&format!(
-            "SELECT id, fid, height, ctype, cid FROM consensus WHERE fid = {} AND height BETWEEN {} AND {}", fid, from, to))?;
+            "SELECT id, fid, height, ctype, cid FROM consensus WHERE fid = {} AND height BETWEEN {} AND {} ORDER BY height", fid, from, to))?;
 
-        let mut packed = vec![];
         let mut consensuses = vec![];
         for res in matrix {
             consensuses.push(Consensus::from_values(res));
         }
 
+        let mut packed = vec![];
+        let mut height = *from;
         for consensus in consensuses {
-            match consensus.ctype {
-                ConsensusType::GroupInfo => {
-                    //
-                }
-                ConsensusType::GroupTransfer => {
-                    //
-                }
-                ConsensusType::GroupManagerAdd => {
-                    //
-                }
-                ConsensusType::GroupManagerDel => {
-                    //
-                }
-                ConsensusType::GroupClose => {
-                    //
+            while height < consensus.height {
+                packed.push(PackedEvent::None);
+                height += 1;
+            }
+            height += 1;
+
+            let event = match consensus.ctype {
+                ConsensusType::DaoInfo
+                | ConsensusType::DaoTransfer
+                | ConsensusType::DaoManagerAdd
+                | ConsensusType::DaoManagerDel => {
+                    // the executed proposal.
+                    let p = Proposal::get(db, &consensus.cid)?;
+                    match p.p_type {
+                        ProposalType::Info(name, bio, avatar) => {
+                            PackedEvent::Info(p.height, name, bio, avatar)
+                        }
+                        ProposalType::Transfer(pid) => PackedEvent::Transfer(p.height, pid),
+                        ProposalType::ManagerAdd(pid) => PackedEvent::ManagerAdd(p.height, pid),
+                        ProposalType::ManagerDel(pid) => PackedEvent::ManagerDel(p.height, pid),
+                        ProposalType::Text(..) => PackedEvent::None,
+                    }
                 }
+                ConsensusType::DaoClose => PackedEvent::Close,
                 ConsensusType::MemberInfo => {
-                    //
+                    let m = Member::get(db, &consensus.cid)?;
+                    let mavatar = read_avatar(base, own, db_key, &m.pid)
+                        .await
+                        .unwrap_or(vec![]);
+                    PackedEvent::MemberInfo(m.pid, m.name, mavatar)
                 }
                 ConsensusType::MemberJoin => {
                     let m = Member::get(db, &consensus.cid)?;
-                    // TODO load member avatar.
-                    let mavatar = vec![];
-                    packed.push(PackedEvent::MemberJoin(
-                        m.m_id, m.m_addr, m.m_name, mavatar, m.datetime,
-                    ))
+                    let mavatar = read_avatar(base, own, db_key, &m.pid)
+                        .await
+                        .unwrap_or(vec![]);
+                    PackedEvent::MemberJoin(m.pid, m.name, mavatar, m.datetime)
                 }
                 ConsensusType::MemberLeave => {
-                    //
+                    let m = Member::get(db, &consensus.cid)?;
+                    PackedEvent::MemberLeave(m.pid)
                 }
                 ConsensusType::MessageCreate => {
                     let m = Message::get(db, &consensus.cid)?;
-                    let datetime = m.datetime;
                     let mem = Member::get(db, &m.mid)?;
-                    let (nmsg, _) = to_network_message(base, gcd, m.m_type, &m.content).await?;
-                    packed.push(PackedEvent::MessageCreate(mem.m_id, nmsg, datetime))
+                    let nmsg = to_network_message(own, base, db_key, m.m_type, m.content).await?;
+                    PackedEvent::MessageCreate(mem.pid, nmsg, m.datetime)
                 }
-                ConsensusType::None => {
-                    // None
+                ConsensusType::ProposalCreate => {
+                    let p = Proposal::get(db, &consensus.cid)?;
+                    PackedEvent::ProposalCreate(p.pid, p.p_type, p.start, p.end)
                 }
-            }
+                ConsensusType::ProposalVote => {
+                    let v = Vote::get(db, &consensus.cid)?;
+                    let p = Proposal::get(db, &v.fid)?;
+                    PackedEvent::ProposalVote(v.pid, p.height, v.is_agree, v.datetime)
+                }
+                ConsensusType::ProposalClose => {
+                    let p = Proposal::get(db, &consensus.cid)?;
+                    PackedEvent::ProposalClose(p.height, p.agree, p.disagree, p.is_passed)
+                }
+                ConsensusType::None => PackedEvent::None,
+            };
+            packed.push(event);
         }
 
         Ok(packed)
@@ -154,15 +192,15 @@ impl Consensus {
 
         if unique_check.len() > 0 {
             let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
-            let _ = db.query(&format!(
+            db.update(&format!(
                 "UPDATE consensus SET ctype = {}, cid = {} WHERE id = {}",
                 ctype.to_i64(),
                 cid,
                 id
             ))?;
         } else {
-            let _ = db.query(&format!(
-                "INSERT INTO consensus ( fid, height, ctype, cid ) VALUES ( {}, {}, {}, {} )",
+            db.insert(&format!(
+                "INSERT INTO consensus (fid, height, ctype, cid) VALUES ({}, {}, {}, {})",
                 fid,
                 height,
                 ctype.to_i64(),
@@ -172,4 +210,9 @@ impl Consensus {
 
         Ok(())
     }
+
+    pub fn delete(db: &DStorage, fid: &i64) -> Result<usize> {
+        let sql = format!("DELETE FROM consensus WHERE fid = {}", fid);
+        db.delete(&sql)
+    }
 }
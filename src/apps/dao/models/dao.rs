@@ -0,0 +1,394 @@
+use dao_types::{ConnectProof, DaoId, DaoInfo, DaoType, ZkpProof};
+use esse_primitives::{id_from_str, id_to_str};
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaChaRng,
+};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::{
+    primitives::{PeerId, Result},
+    rpc::{json, RpcParam},
+};
+use tdn_storage::local::{DStorage, DsValue};
+
+use crate::session::{Session, SessionType};
+use crate::storage::{read_image, write_image_sync};
+
+/// DAO Model.
+pub(crate) struct Dao {
+    /// db auto-increment id.
+    pub id: i64,
+    /// consensus height.
+    pub height: i64,
+    /// dao owner.
+    pub owner: PeerId,
+    /// dao id.
+    pub did: DaoId,
+    /// dao type.
+    pub d_type: DaoType,
+    /// dao server addresse.
+    pub addr: PeerId,
+    /// dao name.
+    pub name: String,
+    /// dao simple intro.
+    pub bio: String,
+    /// dao avatar image name.
+    pub avatar: String,
+    /// dao is created ok.
+    is_ok: bool,
+    /// dao need manager agree.
+    pub is_need_agree: bool,
+    /// dao is closed.
+    pub is_closed: bool,
+    /// dao encrypted-key.
+    pub key: Vec<u8>,
+    /// dao created time.
+    pub datetime: i64,
+    /// dao is in my device.
+    pub local: bool,
+}
+
+impl Dao {
+    pub fn new(
+        owner: PeerId,
+        d_type: DaoType,
+        addr: PeerId,
+        name: String,
+        bio: String,
+        is_need_agree: bool,
+        local: bool,
+    ) -> Self {
+        let mut rng = ChaChaRng::from_entropy();
+        let did = rng.next_u64() >> 1; // fixed i64
+
+        // encrypted dao need a key, members use it to make zero-knowledge-proof.
+        let key = if d_type == DaoType::Encrypted {
+            let mut key = vec![0u8; 32];
+            rng.fill_bytes(&mut key);
+            key
+        } else {
+            vec![]
+        };
+
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        Self {
+            owner,
+            did,
+            d_type,
+            addr,
+            name,
+            bio,
+            is_need_agree,
+            datetime,
+            local,
+            id: 0,
+            height: 0,
+            avatar: String::new(),
+            key,
+            is_ok: local,
+            is_closed: false,
+        }
+    }
+
+    pub fn from_info(
+        key: Vec<u8>,
+        info: DaoInfo,
+        addr: PeerId,
+        base: &PathBuf,
+        pid: &PeerId,
+        db_key: &str,
+    ) -> Result<Self> {
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        let (owner, did, d_type, is_need_agree, name, bio, avatar) = match info {
+            DaoInfo::Common(owner, _, _, did, d_type, agree, name, bio, avatar) => {
+                (owner, did, d_type, agree, name, bio, avatar)
+            }
+            DaoInfo::Encrypted(owner, _, _, did, agree, _hash, _name, _bio, avatar) => {
+                // TODO decrypted.
+                let d_type = DaoType::Encrypted;
+                (
+                    owner,
+                    did,
+                    d_type,
+                    agree,
+                    String::new(),
+                    String::new(),
+                    avatar,
+                )
+            }
+        };
+
+        let avatar = if avatar.len() > 0 {
+            write_image_sync(base, pid, db_key, avatar)?
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            owner,
+            did,
+            d_type,
+            addr,
+            name,
+            bio,
+            avatar,
+            is_need_agree,
+            key,
+            datetime,
+            id: 0,
+            height: 0,
+            is_ok: true,
+            is_closed: false,
+            local: false,
+        })
+    }
+
+    pub fn to_session(&self) -> Session {
+        Session::new(
+            self.id,
+            self.did.to_string(),
+            self.addr,
+            SessionType::Dao,
+            self.name.clone(),
+            self.datetime,
+        )
+    }
+
+    /// the proof when connect to the dao server.
+    pub fn connect_proof(&self, pid: &PeerId) -> ConnectProof {
+        if self.d_type == DaoType::Encrypted && self.key.len() > 0 {
+            ConnectProof::Zkp(ZkpProof::prove(&self.key, &self.did, pid))
+        } else {
+            ConnectProof::Common
+        }
+    }
+
+    pub async fn to_info(
+        &self,
+        base: &PathBuf,
+        pid: &PeerId,
+        db_key: &str,
+        owner_name: String,
+        owner_avatar: Vec<u8>,
+    ) -> DaoInfo {
+        let avatar = if self.avatar.len() > 0 {
+            read_image(base, pid, db_key, &self.avatar)
+                .await
+                .unwrap_or(vec![])
+        } else {
+            vec![]
+        };
+
+        // TODO encrypted
+        DaoInfo::Common(
+            self.owner,
+            owner_name,
+            owner_avatar,
+            self.did,
+            self.d_type,
+            self.is_need_agree,
+            self.name.clone(),
+            self.bio.clone(),
+            avatar,
+        )
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([
+            self.id,
+            id_to_str(&self.owner),
+            self.did,
+            self.d_type.to_i64(),
+            id_to_str(&self.addr),
+            self.name,
+            self.bio,
+            self.avatar,
+            self.is_ok,
+            self.is_closed,
+            self.is_need_agree,
+            self.local,
+            hex::encode(&self.key),
+        ])
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            local: v.pop().unwrap().as_bool(),
+            datetime: v.pop().unwrap().as_i64(),
+            key: hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]),
+            is_closed: v.pop().unwrap().as_bool(),
+            is_need_agree: v.pop().unwrap().as_bool(),
+            is_ok: v.pop().unwrap().as_bool(),
+            avatar: v.pop().unwrap().as_string(),
+            bio: v.pop().unwrap().as_string(),
+            name: v.pop().unwrap().as_string(),
+            addr: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            d_type: DaoType::from_i64(v.pop().unwrap().as_i64()),
+            did: v.pop().unwrap().as_i64() as DaoId,
+            owner: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            height: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn all(db: &DStorage) -> Result<Vec<Dao>> {
+        let matrix = db.query("SELECT id, height, owner, did, dtype, addr, name, bio, avatar, is_ok, is_need_agree, is_closed, key, datetime, is_local FROM daos WHERE is_deleted = false")?;
+        let mut daos = vec![];
+        for values in matrix {
+            daos.push(Self::from_values(values));
+        }
+        Ok(daos)
+    }
+
+    /// list all local daos as running layer.
+    pub fn local(db: &DStorage) -> Result<Vec<Dao>> {
+        let matrix = db.query("SELECT id, height, owner, did, dtype, addr, name, bio, avatar, is_ok, is_need_agree, is_closed, key, datetime, is_local FROM daos WHERE is_local = true AND is_closed = false AND is_deleted = false")?;
+        let mut daos = vec![];
+        for values in matrix {
+            daos.push(Self::from_values(values));
+        }
+        Ok(daos)
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<Dao> {
+        let sql = format!("SELECT id, height, owner, did, dtype, addr, name, bio, avatar, is_ok, is_need_agree, is_closed, key, datetime, is_local FROM daos WHERE id = {} AND is_deleted = false", id);
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            Ok(Self::from_values(values))
+        } else {
+            Err(anyhow!("missing dao"))
+        }
+    }
+
+    pub fn get_id(db: &DStorage, did: &DaoId, addr: &PeerId) -> Result<Dao> {
+        let sql = format!("SELECT id, height, owner, did, dtype, addr, name, bio, avatar, is_ok, is_need_agree, is_closed, key, datetime, is_local FROM daos WHERE did = {} AND addr = '{}' AND is_deleted = false", did, id_to_str(addr));
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            Ok(Self::from_values(values))
+        } else {
+            Err(anyhow!("missing dao"))
+        }
+    }
+
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let mut unique_check = db.query(&format!(
+            "SELECT id from daos WHERE did = {} AND addr = '{}'",
+            self.did,
+            id_to_str(&self.addr)
+        ))?;
+        if unique_check.len() > 0 {
+            if self.local {
+                self.did += 1;
+                return self.insert(db);
+            }
+
+            let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
+            self.id = id;
+            let sql = format!("UPDATE daos SET height = {}, owner = '{}', dtype = {}, name = '{}', bio = '{}', avatar = '{}', is_ok = {}, is_need_agree = {}, is_closed = {}, key = '{}', datetime = {}, is_local = {}, is_deleted = false WHERE id = {}",
+                self.height,
+                id_to_str(&self.owner),
+                self.d_type.to_i64(),
+                self.name,
+                self.bio,
+                self.avatar,
+                self.is_ok,
+                self.is_need_agree,
+                self.is_closed,
+                hex::encode(&self.key),
+                self.datetime,
+                self.local,
+                self.id
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!("INSERT INTO daos (height, owner, did, dtype, addr, name, bio, avatar, is_ok, is_need_agree, is_closed, key, datetime, is_local, is_deleted) VALUES ({}, '{}', {}, {}, '{}', '{}', '{}', '{}', {}, {}, {}, '{}', {}, {}, false)",
+                self.height,
+                id_to_str(&self.owner),
+                self.did,
+                self.d_type.to_i64(),
+                id_to_str(&self.addr),
+                self.name,
+                self.bio,
+                self.avatar,
+                self.is_ok,
+                self.is_need_agree,
+                self.is_closed,
+                hex::encode(&self.key),
+                self.datetime,
+                self.local,
+            );
+            let id = db.insert(&sql)?;
+            self.id = id;
+        }
+        Ok(())
+    }
+
+    pub fn ok(&mut self, db: &DStorage) -> Result<usize> {
+        self.is_ok = true;
+        let sql = format!("UPDATE daos SET is_ok = true WHERE id = {}", self.id);
+        db.update(&sql)
+    }
+
+    pub fn update_info(
+        db: &DStorage,
+        id: &i64,
+        name: &str,
+        bio: &str,
+        avatar: &str,
+    ) -> Result<usize> {
+        let sql = format!(
+            "UPDATE daos SET name = '{}', bio = '{}', avatar = '{}' WHERE id = {}",
+            name, bio, avatar, id
+        );
+        db.update(&sql)
+    }
+
+    pub fn transfer(db: &DStorage, id: &i64, owner: &PeerId) -> Result<usize> {
+        let sql = format!(
+            "UPDATE daos SET owner = '{}' WHERE id = {}",
+            id_to_str(owner),
+            id
+        );
+        db.update(&sql)
+    }
+
+    pub fn add_height(db: &DStorage, id: i64, height: i64) -> Result<usize> {
+        let sql = format!("UPDATE daos SET height = {} WHERE id = {}", height, id);
+        db.update(&sql)
+    }
+
+    pub fn close(db: &DStorage, id: &i64) -> Result<usize> {
+        let sql = format!("UPDATE daos SET is_closed = true WHERE id = {}", id);
+        db.update(&sql)
+    }
+
+    pub fn close_id(db: &DStorage, did: &DaoId, addr: &PeerId) -> Result<Dao> {
+        let dao = Self::get_id(db, did, addr)?;
+        Self::close(db, &dao.id)?;
+        Ok(dao)
+    }
+
+    pub fn delete(db: &DStorage, id: &i64) -> Result<Dao> {
+        let dao = Self::get(db, id)?;
+        let sql = format!(
+            "UPDATE daos SET is_closed = true, is_deleted = true WHERE id = {}",
+            id
+        );
+        db.update(&sql)?;
+        Ok(dao)
+    }
+}
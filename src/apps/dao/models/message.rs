@@ -1,34 +1,29 @@
+use esse_primitives::{MessageType, NetworkMessage};
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    group::GroupId,
-    primitive::Result,
+    primitives::{HandleResult, PeerId, Result},
     rpc::{json, RpcParam},
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-use group_types::NetworkMessage;
-
-use crate::group::{Friend, MessageType};
-use crate::storage::{
-    chat_db, group_db, read_avatar, read_file, read_record, write_avatar_sync, write_file_sync,
-    write_image_sync, write_record_sync,
-};
+use crate::group::{from_network_message, raw_to_network_message};
+use crate::storage::{dao_db, release_content};
 
 use super::Member;
 
-/// Group Chat Message Model.
+/// DAO Message Model.
 pub(crate) struct Message {
     /// db auto-increment id.
     pub id: i64,
-    /// group message consensus height.
-    height: i64,
-    /// group's db id.
+    /// dao message consensus height.
+    pub height: i64,
+    /// dao's db id.
     fid: i64,
     /// member's db id.
     pub mid: i64,
     /// message is mine.
-    is_me: bool,
+    pub is_me: bool,
     /// message type.
     pub m_type: MessageType,
     /// message content.
@@ -61,22 +56,6 @@ impl Message {
             id: 0,
         }
     }
-    pub(crate) fn new(
-        height: i64,
-        fid: i64,
-        mid: i64,
-        is_me: bool,
-        m_type: MessageType,
-        content: String,
-    ) -> Message {
-        let start = SystemTime::now();
-        let datetime = start
-            .duration_since(UNIX_EPOCH)
-            .map(|s| s.as_secs())
-            .unwrap_or(0) as i64; // safe for all life.
-
-        Self::new_with_time(height, fid, mid, is_me, m_type, content, datetime)
-    }
 
     /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Message {
@@ -112,17 +91,17 @@ impl Message {
         if matrix.len() > 0 {
             Ok(Message::from_values(matrix.pop().unwrap())) // safe unwrap.
         } else {
-            Err(anyhow!("missing member"))
+            Err(anyhow!("missing message"))
         }
     }
 
-    pub fn all(db: &DStorage, fid: &i64) -> Result<Vec<Message>> {
-        let matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE is_deleted = false AND fid = {}", fid))?;
-        let mut groups = vec![];
+    pub fn list(db: &DStorage, fid: &i64) -> Result<Vec<Message>> {
+        let matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE fid = {}", fid))?;
+        let mut messages = vec![];
         for values in matrix {
-            groups.push(Message::from_values(values));
+            messages.push(Message::from_values(values));
         }
-        Ok(groups)
+        Ok(messages)
     }
 
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
@@ -134,7 +113,7 @@ impl Message {
             let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
             self.id = id;
         } else {
-            let sql = format!("INSERT INTO messages (height, fid, mid, is_me, m_type, content, is_delivery, datetime, is_deleted) VALUES ({}, {}, {}, {}, {}, '{}', {}, {}, false)",
+            let sql = format!("INSERT INTO messages (height, fid, mid, is_me, m_type, content, is_delivery, datetime) VALUES ({}, {}, {}, {}, {}, '{}', {}, {})",
                 self.height,
                 self.fid,
                 self.mid,
@@ -149,143 +128,50 @@ impl Message {
         }
         Ok(())
     }
+
+    pub fn delete(db: &DStorage, fid: &i64) -> Result<usize> {
+        let sql = format!("DELETE FROM messages WHERE fid = {}", fid);
+        db.delete(&sql)
+    }
 }
 
 pub(crate) async fn to_network_message(
+    own: &PeerId,
     base: &PathBuf,
-    gid: &GroupId,
+    db_key: &str,
     mtype: MessageType,
     content: &str,
-) -> Result<(NetworkMessage, i64)> {
+) -> Result<(NetworkMessage, i64, String)> {
     let start = SystemTime::now();
     let datetime = start
         .duration_since(UNIX_EPOCH)
         .map(|s| s.as_secs())
         .unwrap_or(0) as i64; // safe for all life.
 
-    let nmsg = match mtype {
-        MessageType::String => NetworkMessage::String(content.to_owned()),
-        MessageType::Image => {
-            let bytes = read_file(&PathBuf::from(content)).await?;
-            NetworkMessage::Image(bytes)
-        }
-        MessageType::File => {
-            let file_path = PathBuf::from(content);
-            let bytes = read_file(&file_path).await?;
-            let old_name = file_path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_owned();
-            NetworkMessage::File(old_name, bytes)
-        }
-        MessageType::Contact => {
-            let cid: i64 = content.parse()?;
-            let db = chat_db(base, gid)?;
-            let contact = Friend::get_id(&db, cid)?.ok_or(anyhow!("contact missind"))?;
-            drop(db);
-            let avatar_bytes = read_avatar(base, &gid, &contact.gid).await?;
-            NetworkMessage::Contact(contact.name, contact.gid, contact.addr, avatar_bytes)
-        }
-        MessageType::Record => {
-            let (bytes, time) = if let Some(i) = content.find('-') {
-                let time = content[0..i].parse().unwrap_or(0);
-                let bytes = read_record(base, &gid, &content[i + 1..]).await?;
-                (bytes, time)
-            } else {
-                (vec![], 0)
-            };
-            NetworkMessage::Record(bytes, time)
-        }
-        MessageType::Emoji => {
-            // TODO
-            NetworkMessage::Emoji
-        }
-        MessageType::Phone => {
-            // TODO
-            NetworkMessage::Phone
-        }
-        MessageType::Video => {
-            // TODO
-            NetworkMessage::Video
-        }
-        MessageType::Invite => NetworkMessage::Invite(content.to_owned()),
-    };
-
-    Ok((nmsg, datetime))
+    let (nmsg, raw) = raw_to_network_message(own, base, db_key, &mtype, content).await?;
+    Ok((nmsg, datetime, raw))
 }
 
-pub(crate) fn from_network_message(
+pub(crate) async fn handle_network_message(
+    own: &PeerId,
+    base: &PathBuf,
+    db_key: &str,
     height: i64,
-    gdid: i64,
-    mid: GroupId,
-    mgid: &GroupId,
+    id: i64,
+    mid: PeerId,
     msg: NetworkMessage,
     datetime: i64,
-    base: &PathBuf,
-) -> Result<(Message, String)> {
-    let db = group_db(base, mgid)?;
-    let mdid = Member::get_ok(&db, &gdid, &mid)?;
-    let is_me = &mid == mgid;
-
-    // handle event.
-    let (m_type, raw) = match msg {
-        NetworkMessage::String(content) => (MessageType::String, content),
-        NetworkMessage::Image(bytes) => {
-            let image_name = write_image_sync(base, mgid, bytes)?;
-            (MessageType::Image, image_name)
-        }
-        NetworkMessage::File(old_name, bytes) => {
-            let filename = write_file_sync(base, mgid, &old_name, bytes)?;
-            (MessageType::File, filename)
-        }
-        NetworkMessage::Contact(name, rgid, addr, avatar_bytes) => {
-            write_avatar_sync(base, mgid, &rgid, avatar_bytes)?;
-            let tmp_name = name.replace(";", "-;");
-            let contact_values = format!("{};;{};;{}", tmp_name, rgid.to_hex(), addr.to_hex());
-            (MessageType::Contact, contact_values)
-        }
-        NetworkMessage::Emoji => {
-            // TODO
-            (MessageType::Emoji, "".to_owned())
-        }
-        NetworkMessage::Record(bytes, time) => {
-            let record_name = write_record_sync(base, mgid, gdid, time, bytes)?;
-            (MessageType::Record, record_name)
-        }
-        NetworkMessage::Phone => {
-            // TODO
-            (MessageType::Phone, "".to_owned())
-        }
-        NetworkMessage::Video => {
-            // TODO
-            (MessageType::Video, "".to_owned())
-        }
-        NetworkMessage::Invite(content) => (MessageType::Invite, content),
-        NetworkMessage::None => {
-            return Ok((
-                Message::new(
-                    height,
-                    gdid,
-                    mdid,
-                    is_me,
-                    MessageType::String,
-                    "".to_owned(),
-                ),
-                "".to_owned(),
-            ));
-        }
-    };
-
-    let scontent = match m_type {
-        MessageType::String => {
-            format!("{}:{}", m_type.to_int(), raw)
-        }
-        _ => format!("{}:", m_type.to_int()),
-    };
-
-    let mut msg = Message::new_with_time(height, gdid, mdid, is_me, m_type, raw, datetime);
-    msg.insert(&db)?;
-
-    Ok((msg, scontent))
+    results: &mut HandleResult,
+) -> Result<Message> {
+    let db = dao_db(base, own, db_key)?;
+    let mdid = Member::get_ok(&db, &id, &mid)?;
+    let is_me = &mid == own;
+    let (m_type, raw) = from_network_message(own, base, db_key, msg, results).await?;
+    let mut msg = Message::new_with_time(height, id, mdid, is_me, m_type, raw, datetime);
+    if let Err(e) = msg.insert(&db) {
+        // the blob reference only kept by the saved message.
+        let _ = release_content(base, own, db_key, &msg.m_type, &msg.content);
+        return Err(e);
+    }
+    Ok(msg)
 }
@@ -0,0 +1,320 @@
+use dao_types::ProposalType;
+use esse_primitives::{id_from_str, id_to_str};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::{
+    primitives::{PeerId, Result},
+    rpc::{json, RpcParam},
+};
+use tdn_storage::local::{DStorage, DsValue};
+
+/// DAO Proposal Model.
+pub(crate) struct Proposal {
+    /// db auto-increment id.
+    pub id: i64,
+    /// dao's db id.
+    fid: i64,
+    /// consensus height when proposal created.
+    pub height: i64,
+    /// proposer's account.
+    pub pid: PeerId,
+    /// proposal type and content.
+    pub p_type: ProposalType,
+    /// voting start time.
+    pub start: i64,
+    /// voting end time.
+    pub end: i64,
+    /// agree votes count.
+    pub agree: i64,
+    /// disagree votes count.
+    pub disagree: i64,
+    /// voting is over.
+    pub is_over: bool,
+    /// proposal is passed.
+    pub is_passed: bool,
+}
+
+impl Proposal {
+    pub fn new(
+        fid: i64,
+        height: i64,
+        pid: PeerId,
+        p_type: ProposalType,
+        start: i64,
+        end: i64,
+    ) -> Self {
+        Self {
+            fid,
+            height,
+            pid,
+            p_type,
+            start,
+            end,
+            id: 0,
+            agree: 0,
+            disagree: 0,
+            is_over: false,
+            is_passed: false,
+        }
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        let content = match &self.p_type {
+            ProposalType::Text(title, content) => json!([title, content]),
+            ProposalType::ManagerAdd(pid)
+            | ProposalType::ManagerDel(pid)
+            | ProposalType::Transfer(pid) => json!([id_to_str(pid)]),
+            ProposalType::Info(name, bio, avatar) => json!([name, bio, base64::encode(avatar)]),
+        };
+
+        json!([
+            self.id,
+            self.fid,
+            self.height,
+            id_to_str(&self.pid),
+            self.p_type.to_i64(),
+            content,
+            self.start,
+            self.end,
+            self.agree,
+            self.disagree,
+            self.is_over,
+            self.is_passed,
+        ])
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Result<Self> {
+        let is_passed = v.pop().unwrap().as_bool();
+        let is_over = v.pop().unwrap().as_bool();
+        let disagree = v.pop().unwrap().as_i64();
+        let agree = v.pop().unwrap().as_i64();
+        let end = v.pop().unwrap().as_i64();
+        let start = v.pop().unwrap().as_i64();
+        let content = hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]);
+        let p_type = bincode::deserialize(&content)?;
+        Ok(Self {
+            is_passed,
+            is_over,
+            disagree,
+            agree,
+            end,
+            start,
+            p_type,
+            pid: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            height: v.pop().unwrap().as_i64(),
+            fid: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        })
+    }
+
+    pub fn list(db: &DStorage, fid: &i64) -> Result<Vec<Proposal>> {
+        let matrix = db.query(&format!("SELECT id, fid, height, pid, content, start_at, end_at, agree, disagree, is_over, is_passed FROM proposals WHERE fid = {} ORDER BY height", fid))?;
+        let mut proposals = vec![];
+        for values in matrix {
+            proposals.push(Self::from_values(values)?);
+        }
+        Ok(proposals)
+    }
+
+    /// list the proposals which voting time is end, but not tally.
+    pub fn expired(db: &DStorage, fid: &i64) -> Result<Vec<Proposal>> {
+        let start = SystemTime::now();
+        let now = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        let matrix = db.query(&format!("SELECT id, fid, height, pid, content, start_at, end_at, agree, disagree, is_over, is_passed FROM proposals WHERE fid = {} AND is_over = false AND end_at <= {}", fid, now))?;
+        let mut proposals = vec![];
+        for values in matrix {
+            proposals.push(Self::from_values(values)?);
+        }
+        Ok(proposals)
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<Proposal> {
+        let mut matrix = db.query(&format!("SELECT id, fid, height, pid, content, start_at, end_at, agree, disagree, is_over, is_passed FROM proposals WHERE id = {}", id))?;
+        if matrix.len() > 0 {
+            Self::from_values(matrix.pop().unwrap()) // safe unwrap.
+        } else {
+            Err(anyhow!("missing proposal"))
+        }
+    }
+
+    pub fn get_by_height(db: &DStorage, fid: &i64, height: &i64) -> Result<Proposal> {
+        let mut matrix = db.query(&format!("SELECT id, fid, height, pid, content, start_at, end_at, agree, disagree, is_over, is_passed FROM proposals WHERE fid = {} AND height = {}", fid, height))?;
+        if matrix.len() > 0 {
+            Self::from_values(matrix.pop().unwrap()) // safe unwrap.
+        } else {
+            Err(anyhow!("missing proposal"))
+        }
+    }
+
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let content = hex::encode(bincode::serialize(&self.p_type)?);
+        let mut unique_check = db.query(&format!(
+            "SELECT id from proposals WHERE fid = {} AND height = {}",
+            self.fid, self.height
+        ))?;
+        if unique_check.len() > 0 {
+            let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
+            self.id = id;
+            let sql = format!("UPDATE proposals SET pid = '{}', p_type = {}, content = '{}', start_at = {}, end_at = {} WHERE id = {}",
+                id_to_str(&self.pid),
+                self.p_type.to_i64(),
+                content,
+                self.start,
+                self.end,
+                self.id,
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!("INSERT INTO proposals (fid, height, pid, p_type, content, start_at, end_at, agree, disagree, is_over, is_passed) VALUES ({}, {}, '{}', {}, '{}', {}, {}, {}, {}, {}, {})",
+                self.fid,
+                self.height,
+                id_to_str(&self.pid),
+                self.p_type.to_i64(),
+                content,
+                self.start,
+                self.end,
+                self.agree,
+                self.disagree,
+                self.is_over,
+                self.is_passed,
+            );
+            let id = db.insert(&sql)?;
+            self.id = id;
+        }
+        Ok(())
+    }
+
+    pub fn tally(&mut self, db: &DStorage, agree: i64, disagree: i64) -> Result<usize> {
+        self.agree = agree;
+        self.disagree = disagree;
+        let sql = format!(
+            "UPDATE proposals SET agree = {}, disagree = {} WHERE id = {}",
+            agree, disagree, self.id
+        );
+        db.update(&sql)
+    }
+
+    pub fn over(
+        &mut self,
+        db: &DStorage,
+        agree: i64,
+        disagree: i64,
+        passed: bool,
+    ) -> Result<usize> {
+        self.agree = agree;
+        self.disagree = disagree;
+        self.is_over = true;
+        self.is_passed = passed;
+        let sql = format!(
+            "UPDATE proposals SET agree = {}, disagree = {}, is_over = true, is_passed = {} WHERE id = {}",
+            agree, disagree, passed, self.id
+        );
+        db.update(&sql)
+    }
+}
+
+/// DAO Proposal Vote Model.
+pub(crate) struct Vote {
+    /// db auto-increment id.
+    pub id: i64,
+    /// proposal's db id.
+    pub fid: i64,
+    /// voter's account.
+    pub pid: PeerId,
+    /// is agree the proposal.
+    pub is_agree: bool,
+    /// vote time.
+    pub datetime: i64,
+}
+
+impl Vote {
+    pub fn new(fid: i64, pid: PeerId, is_agree: bool, datetime: i64) -> Self {
+        Self {
+            fid,
+            pid,
+            is_agree,
+            datetime,
+            id: 0,
+        }
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            is_agree: v.pop().unwrap().as_bool(),
+            pid: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            fid: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<Vote> {
+        let mut matrix = db.query(&format!(
+            "SELECT id, fid, pid, is_agree, datetime FROM votes WHERE id = {}",
+            id
+        ))?;
+        if matrix.len() > 0 {
+            Ok(Self::from_values(matrix.pop().unwrap())) // safe unwrap.
+        } else {
+            Err(anyhow!("missing vote"))
+        }
+    }
+
+    pub fn exist(db: &DStorage, fid: &i64, pid: &PeerId) -> Result<bool> {
+        let matrix = db.query(&format!(
+            "SELECT id FROM votes WHERE fid = {} AND pid = '{}'",
+            fid,
+            id_to_str(pid)
+        ))?;
+        Ok(matrix.len() > 0)
+    }
+
+    /// count the proposal's votes. return (agree, disagree).
+    pub fn count(db: &DStorage, fid: &i64) -> Result<(i64, i64)> {
+        let matrix = db.query(&format!("SELECT is_agree FROM votes WHERE fid = {}", fid))?;
+        let mut agree = 0;
+        let mut disagree = 0;
+        for mut values in matrix {
+            if values.pop().unwrap().as_bool() {
+                agree += 1;
+            } else {
+                disagree += 1;
+            }
+        }
+        Ok((agree, disagree))
+    }
+
+    /// one member one vote, if had voted, update it.
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let mut unique_check = db.query(&format!(
+            "SELECT id from votes WHERE fid = {} AND pid = '{}'",
+            self.fid,
+            id_to_str(&self.pid)
+        ))?;
+        if unique_check.len() > 0 {
+            let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
+            self.id = id;
+            let sql = format!(
+                "UPDATE votes SET is_agree = {}, datetime = {} WHERE id = {}",
+                self.is_agree, self.datetime, self.id,
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!(
+                "INSERT INTO votes (fid, pid, is_agree, datetime) VALUES ({}, '{}', {}, {})",
+                self.fid,
+                id_to_str(&self.pid),
+                self.is_agree,
+                self.datetime,
+            );
+            let id = db.insert(&sql)?;
+            self.id = id;
+        }
+        Ok(())
+    }
+}
@@ -1,35 +1,35 @@
+use dao_types::DaoType;
+use esse_primitives::{id_from_str, id_to_str};
 use tdn::types::{
-    primitive::{PeerId, Result},
+    primitives::{PeerId, Result},
     rpc::{json, RpcParam},
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-use group_types::GroupType;
-
-/// Group Chat Provider Model.
+/// DAO Provider Model.
 pub(crate) struct Provider {
     pub id: i64,
     name: String,
     addr: PeerId,
-    kinds: Vec<GroupType>,
+    kinds: Vec<DaoType>,
     remain: i64,
     is_ok: bool,
 }
 
-fn parse_kinds(kinds: i64) -> Vec<GroupType> {
+fn parse_kinds(kinds: i64) -> Vec<DaoType> {
     let s = kinds.to_string();
     s.chars()
         .filter_map(|c| match c {
-            '0' => Some(GroupType::Encrypted),
-            '1' => Some(GroupType::Private),
-            '2' => Some(GroupType::Open),
+            '0' => Some(DaoType::Open),
+            '1' => Some(DaoType::Private),
+            '2' => Some(DaoType::Encrypted),
             _ => None,
         })
         .collect()
 }
 
-fn kinds_print(kinds: &Vec<GroupType>) -> i64 {
-    let mut v: Vec<u32> = kinds.iter().map(|k| k.to_u32()).collect();
+fn kinds_print(kinds: &Vec<DaoType>) -> i64 {
+    let mut v: Vec<i64> = kinds.iter().map(|k| k.to_i64()).collect();
     v.sort_by(|a, b| b.cmp(a));
     let s = v
         .iter()
@@ -52,23 +52,24 @@ impl Provider {
     }
 
     pub fn to_rpc(&self) -> RpcParam {
-        let kinds: Vec<u32> = self.kinds.iter().map(|v| v.to_u32()).collect();
+        let kinds: Vec<i64> = self.kinds.iter().map(|v| v.to_i64()).collect();
         json!([
             self.id,
             self.name,
-            self.addr.to_hex(),
+            id_to_str(&self.addr),
             kinds,
             self.remain,
             self.is_ok
         ])
     }
 
+    /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Self {
         Self {
             is_ok: v.pop().unwrap().as_bool(),
             remain: v.pop().unwrap().as_i64(),
             kinds: parse_kinds(v.pop().unwrap().as_i64()),
-            addr: PeerId::from_hex(v.pop().unwrap().as_string()).unwrap_or(Default::default()),
+            addr: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
             name: v.pop().unwrap().as_string(),
             id: v.pop().unwrap().as_i64(),
         }
@@ -86,7 +87,7 @@ impl Provider {
     pub fn get_by_addr(db: &DStorage, addr: &PeerId) -> Result<Self> {
         let sql = format!(
             "SELECT id, name, addr, kinds, remain, is_ok FROM providers WHERE addr = '{}'",
-            addr.to_hex()
+            id_to_str(addr)
         );
         let mut matrix = db.query(&sql)?;
         if matrix.len() > 0 {
@@ -100,7 +101,7 @@ impl Provider {
         let sql = format!(
             "INSERT INTO providers (name,addr,kinds,remain,is_ok) VALUES ('{}','{}',{},{},{})",
             self.name,
-            self.addr.to_hex(),
+            id_to_str(&self.addr),
             kinds_print(&self.kinds),
             self.remain,
             self.is_ok,
@@ -114,7 +115,7 @@ impl Provider {
         &mut self,
         db: &DStorage,
         name: String,
-        kinds: Vec<GroupType>,
+        kinds: Vec<DaoType>,
         remain: i64,
     ) -> Result<()> {
         self.name = name;
@@ -141,7 +142,7 @@ impl Provider {
 
     pub fn delete(db: &DStorage, id: &i64) -> Result<()> {
         let sql = format!("DELETE FROM providers WHERE id = {}", id);
-        db.update(&sql)?;
+        db.delete(&sql)?;
         Ok(())
     }
 }
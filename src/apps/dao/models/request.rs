@@ -1,35 +1,34 @@
+use dao_types::DaoId;
+use esse_primitives::{id_from_str, id_to_str};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    group::GroupId,
-    primitive::{PeerId, Result},
+    primitives::{PeerId, Result},
     rpc::{json, RpcParam},
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-use super::GroupChatKey;
-
-/// Group Join Request model. include my requests and other requests.
-/// When fid is 0, it's my requests.
+/// DAO Join Request model. include my requests and other requests.
+/// When fid is 0, it's my requests, and pid is the dao's server.
 pub(crate) struct Request {
     pub id: i64,
-    fid: i64,
+    pub fid: i64,
     pub rid: i64,
-    pub gid: GroupId,
-    pub addr: PeerId,
+    pub did: DaoId,
+    pub pid: PeerId,
     pub name: String,
-    key: GroupChatKey,
-    remark: String,
+    pub remark: String,
+    key: Vec<u8>,
     is_ok: bool,
-    is_over: bool,
-    datetime: i64,
+    pub is_over: bool,
+    pub datetime: i64,
 }
 
 impl Request {
     pub fn new_by_remote(
         fid: i64,
         rid: i64,
-        gid: GroupId,
-        addr: PeerId,
+        did: DaoId,
+        pid: PeerId,
         name: String,
         remark: String,
         datetime: i64,
@@ -37,72 +36,32 @@ impl Request {
         Self {
             fid,
             rid,
-            gid,
-            addr,
+            did,
+            pid,
             name,
             remark,
             datetime,
-            key: GroupChatKey(vec![]),
+            key: vec![],
             is_ok: false,
             is_over: false,
             id: 0,
         }
     }
 
-    pub fn new_by_server(
-        fid: i64,
-        gid: GroupId,
-        addr: PeerId,
-        name: String,
-        remark: String,
-    ) -> Self {
+    pub fn new_by_server(fid: i64, did: DaoId, pid: PeerId, name: String, remark: String) -> Self {
         let start = SystemTime::now();
         let datetime = start
             .duration_since(UNIX_EPOCH)
             .map(|s| s.as_secs())
             .unwrap_or(0) as i64; // safe for all life.
 
-        Self {
-            fid,
-            gid,
-            addr,
-            name,
-            remark,
-            datetime,
-            key: GroupChatKey(vec![]),
-            is_ok: false,
-            is_over: false,
-            id: 0,
-            rid: 0,
-        }
+        Self::new_by_remote(fid, 0, did, pid, name, remark, datetime)
     }
 
-    pub fn new_by_me(
-        gid: GroupId,
-        addr: PeerId,
-        name: String,
-        remark: String,
-        key: GroupChatKey,
-    ) -> Self {
-        let start = SystemTime::now();
-        let datetime = start
-            .duration_since(UNIX_EPOCH)
-            .map(|s| s.as_secs())
-            .unwrap_or(0) as i64; // safe for all life.
-
-        Self {
-            gid,
-            addr,
-            name,
-            remark,
-            datetime,
-            key,
-            is_ok: false,
-            is_over: false,
-            fid: 0,
-            rid: 0,
-            id: 0,
-        }
+    pub fn new_by_me(did: DaoId, pid: PeerId, name: String, remark: String, key: Vec<u8>) -> Self {
+        let mut request = Self::new_by_server(0, did, pid, name, remark);
+        request.key = key;
+        request
     }
 
     pub fn to_rpc(&self) -> RpcParam {
@@ -110,8 +69,8 @@ impl Request {
             self.id,
             self.fid,
             self.rid,
-            self.gid.to_hex(),
-            self.addr.to_hex(),
+            self.did,
+            id_to_str(&self.pid),
             self.name,
             self.remark,
             self.is_ok,
@@ -120,16 +79,17 @@ impl Request {
         ])
     }
 
+    /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Self {
         Self {
-            key: GroupChatKey(vec![]),
             datetime: v.pop().unwrap().as_i64(),
             is_over: v.pop().unwrap().as_bool(),
             is_ok: v.pop().unwrap().as_bool(),
+            key: hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]),
             remark: v.pop().unwrap().as_string(),
             name: v.pop().unwrap().as_string(),
-            addr: PeerId::from_hex(v.pop().unwrap().as_string()).unwrap_or(Default::default()),
-            gid: GroupId::from_hex(v.pop().unwrap().as_string()).unwrap_or(Default::default()),
+            pid: id_from_str(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            did: v.pop().unwrap().as_i64() as DaoId,
             rid: v.pop().unwrap().as_i64(),
             fid: v.pop().unwrap().as_i64(),
             id: v.pop().unwrap().as_i64(),
@@ -138,27 +98,36 @@ impl Request {
 
     pub fn list(db: &DStorage, is_all: bool) -> Result<Vec<Request>> {
         let sql = if is_all {
-            format!("SELECT id, fid, rid, gid, addr, name, remark, is_ok, is_over, datetime FROM requests WHERE is_deleted = false")
+            format!("SELECT id, fid, rid, did, pid, name, remark, key, is_ok, is_over, datetime FROM requests WHERE is_deleted = false")
         } else {
-            format!("SELECT id, fid, rid, gid, addr, name, remark, is_ok, is_over, datetime FROM requests WHERE is_deleted = false AND is_over = 0")
+            format!("SELECT id, fid, rid, did, pid, name, remark, key, is_ok, is_over, datetime FROM requests WHERE is_deleted = false AND is_over = false")
         };
         let matrix = db.query(&sql)?;
         let mut requests = vec![];
         for values in matrix {
-            requests.push(Request::from_values(values));
+            requests.push(Self::from_values(values));
         }
         Ok(requests)
     }
 
+    pub fn get(db: &DStorage, id: &i64) -> Result<Request> {
+        let mut matrix = db.query(&format!("SELECT id, fid, rid, did, pid, name, remark, key, is_ok, is_over, datetime FROM requests WHERE id = {}", id))?;
+        if matrix.len() > 0 {
+            Ok(Self::from_values(matrix.pop().unwrap())) // safe unwrap.
+        } else {
+            Err(anyhow!("request is missing"))
+        }
+    }
+
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
-        let sql = format!("INSERT INTO requests (fid, rid, gid, addr, name, remark, key, is_ok, is_over, datetime, is_deleted) VALUES ({}, {}, '{}', '{}', '{}', '{}', '{}', {}, {}, {}, false)",
+        let sql = format!("INSERT INTO requests (fid, rid, did, pid, name, remark, key, is_ok, is_over, datetime, is_deleted) VALUES ({}, {}, {}, '{}', '{}', '{}', '{}', {}, {}, {}, false)",
             self.fid,
             self.rid,
-            self.gid.to_hex(),
-            self.addr.to_hex(),
+            self.did,
+            id_to_str(&self.pid),
             self.name,
             self.remark,
-            self.key.to_hex(),
+            hex::encode(&self.key),
             self.is_ok,
             self.is_over,
             self.datetime,
@@ -168,57 +137,68 @@ impl Request {
         Ok(())
     }
 
-    pub fn exist(db: &DStorage, gcd: &GroupId) -> Result<bool> {
+    /// check if my request to the dao is exist and not over.
+    pub fn exist(db: &DStorage, did: &DaoId, pid: &PeerId) -> Result<bool> {
         let matrix = db.query(&format!(
-            "SELECT id from requests WHERE gid = '{}' AND is_over = 0",
-            gcd.to_hex(),
+            "SELECT id from requests WHERE fid = 0 AND did = {} AND pid = '{}' AND is_over = false",
+            did,
+            id_to_str(pid),
         ))?;
-        if matrix.len() == 0 {
-            Ok(false)
-        } else {
-            Ok(true)
-        }
+        Ok(matrix.len() > 0)
     }
 
-    pub fn over_rid(db: &DStorage, gid: &i64, rid: &i64, is_ok: bool) -> Result<i64> {
+    /// check if the member's request to the dao is waiting.
+    pub fn exist_remote(db: &DStorage, fid: &i64, pid: &PeerId) -> Result<bool> {
+        let matrix = db.query(&format!(
+            "SELECT id from requests WHERE fid = {} AND pid = '{}' AND is_over = false",
+            fid,
+            id_to_str(pid),
+        ))?;
+        Ok(matrix.len() > 0)
+    }
+
+    pub fn over_id(db: &DStorage, id: &i64, is_ok: bool) -> Result<usize> {
+        let sql = format!(
+            "UPDATE requests SET is_ok = {}, is_over = true WHERE id = {}",
+            is_ok, id,
+        );
+        db.update(&sql)
+    }
+
+    /// over the remote request which is synced from dao's server.
+    pub fn over_rid(db: &DStorage, fid: &i64, rid: &i64, is_ok: bool) -> Result<i64> {
         let mut matrix = db.query(&format!(
-            "SELECT id from requests WHERE fid = {} AND rid = {} AND is_over = 0",
-            gid, rid
+            "SELECT id from requests WHERE fid = {} AND rid = {} AND is_over = false",
+            fid, rid
         ))?;
         if matrix.len() == 0 {
             return Err(anyhow!("request is missing"));
         }
         let id = matrix.pop().unwrap().pop().unwrap().as_i64(); // safe.
-        let sql = format!(
-            "UPDATE requests SET is_ok={}, is_over=1 WHERE id = {}",
-            is_ok, id,
-        );
-        db.update(&sql)?;
+        Self::over_id(db, &id, is_ok)?;
         Ok(id)
     }
 
-    pub fn over(db: &DStorage, gcd: &GroupId, is_ok: bool) -> Result<(i64, GroupChatKey)> {
-        let matrix = db.query(&format!(
-            "SELECT id, key from requests WHERE gid = '{}' AND is_over = 0 ORDER BY id",
-            gcd.to_hex()
+    /// over my request, return the request id and dao key.
+    pub fn over(db: &DStorage, did: &DaoId, pid: &PeerId, is_ok: bool) -> Result<(i64, Vec<u8>)> {
+        let mut matrix = db.query(&format!(
+            "SELECT id, key from requests WHERE fid = 0 AND did = {} AND pid = '{}' AND is_over = false ORDER BY id",
+            did,
+            id_to_str(pid)
         ))?;
-        let mut requests = vec![];
-        for mut values in matrix {
-            let id = values.pop().unwrap().as_i64();
-            let key = GroupChatKey::from_hex(values.pop().unwrap().as_string())
-                .unwrap_or(GroupChatKey::new(vec![]));
-            requests.push((id, key));
-        }
 
         let sql = format!(
-            "UPDATE requests SET is_ok={}, is_over=1 WHERE gid = '{}' AND is_over = 0",
+            "UPDATE requests SET is_ok = {}, is_over = true WHERE fid = 0 AND did = {} AND pid = '{}' AND is_over = false",
             is_ok,
-            gcd.to_hex(),
+            did,
+            id_to_str(pid)
         );
         db.update(&sql)?;
 
-        if requests.len() > 0 {
-            Ok(requests.pop().unwrap()) // safe.
+        if let Some(mut values) = matrix.pop() {
+            let key = hex::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
+            let id = values.pop().unwrap().as_i64();
+            Ok((id, key))
         } else {
             Err(anyhow!("no requests"))
         }
@@ -1,128 +1,110 @@
+use dao_types::{DaoType, Event, JoinProof, LayerEvent, ProposalType, ZkpProof, DAO_ID};
+use esse_primitives::{id_from_str, MessageType};
 use std::sync::Arc;
 use tdn::types::{
-    group::GroupId,
-    message::{NetworkType, SendType},
-    primitive::{HandleResult, PeerId},
+    message::{RpcSendMessage, SendType},
+    primitives::HandleResult,
     rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
-use tdn_did::Proof;
 
-use group_types::{Event, GroupLocation, GroupType, JoinProof, LayerEvent};
-
-use crate::group::{Friend, MessageType};
-use crate::layer::Online;
-use crate::rpc::{session_close, session_create, session_delete, session_last, RpcState};
+use crate::global::Global;
+use crate::rpc::{session_create, session_delete};
 use crate::session::{Session, SessionType};
-use crate::storage::{chat_db, group_db, read_avatar, session_db, write_avatar};
+use crate::storage::{dao_db, release_content, session_db, write_avatar, write_image};
 
-use super::add_layer;
+use super::layer::{
+    broadcast, proposal_create as layer_proposal_create, proposal_vote as layer_proposal_vote,
+    request_handle as layer_request_handle, update_session,
+};
 use super::models::{
-    to_network_message, Consensus, ConsensusType, GroupChat, GroupChatKey, Member, Message,
-    Provider, Request,
+    to_network_message, Consensus, ConsensusType, Dao, Member, Message, Proposal, Provider, Request,
 };
 
 #[inline]
-pub(crate) fn provider_check(mgid: GroupId, provider: &Provider) -> RpcParam {
-    rpc_response(
-        0,
-        "group-chat-provider-check",
-        json!(provider.to_rpc()),
-        mgid,
-    )
+pub(crate) fn provider_check(provider: &Provider) -> RpcParam {
+    rpc_response(0, "dao-provider-check", json!(provider.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn provider_delete(id: i64) -> RpcParam {
+    rpc_response(0, "dao-provider-delete", json!([id]))
+}
+
+#[inline]
+pub(crate) fn create_result(id: i64, ok: bool) -> RpcParam {
+    rpc_response(0, "dao-create-result", json!([id, ok]))
 }
 
 #[inline]
-pub(crate) fn provider_delete(mgid: GroupId, id: i64) -> RpcParam {
-    rpc_response(0, "group-chat-provider-delete", json!([id]), mgid)
+pub(crate) fn dao_create(dao: &Dao) -> RpcParam {
+    rpc_response(0, "dao-create", json!(dao.to_rpc()))
 }
 
 #[inline]
-pub(crate) fn create_result(mgid: GroupId, gid: i64, ok: bool) -> RpcParam {
-    rpc_response(0, "group-chat-result", json!([gid, ok]), mgid)
+pub(crate) fn request_create(req: &Request) -> RpcParam {
+    rpc_response(0, "dao-request-create", json!(req.to_rpc()))
 }
 
 #[inline]
-pub(crate) fn group_create(mgid: GroupId, group: GroupChat) -> RpcParam {
-    rpc_response(0, "group-chat-create", json!(group.to_rpc()), mgid)
+pub(crate) fn request_handle(id: i64, rid: i64, ok: bool, efficacy: bool) -> RpcParam {
+    rpc_response(0, "dao-request-handle", json!([id, rid, ok, efficacy]))
 }
 
 #[inline]
-pub(crate) fn request_create(mgid: GroupId, req: &Request) -> RpcParam {
-    rpc_response(0, "group-chat-join", json!(req.to_rpc()), mgid)
+pub(crate) fn member_join(member: &Member) -> RpcParam {
+    rpc_response(0, "dao-member-join", json!(member.to_rpc()))
 }
 
 #[inline]
-pub(crate) fn request_handle(
-    mgid: GroupId,
-    id: i64,
-    rid: i64,
-    ok: bool,
-    efficacy: bool,
-) -> RpcParam {
-    rpc_response(
-        0,
-        "group-chat-join-handle",
-        json!([id, rid, ok, efficacy]),
-        mgid,
-    )
+pub(crate) fn member_leave(id: i64, mid: i64) -> RpcParam {
+    rpc_response(0, "dao-member-leave", json!([id, mid]))
 }
 
 #[inline]
-pub(crate) fn member_join(mgid: GroupId, member: &Member) -> RpcParam {
-    rpc_response(0, "group-chat-member-join", json!(member.to_rpc()), mgid)
+pub(crate) fn member_info(id: i64, mid: i64, name: &str) -> RpcParam {
+    rpc_response(0, "dao-member-info", json!([id, mid, name]))
 }
 
 #[inline]
-pub(crate) fn member_leave(mgid: GroupId, id: i64, mid: i64) -> RpcParam {
-    rpc_response(0, "group-chat-member-leave", json!([id, mid]), mgid)
+pub(crate) fn member_online(id: i64, mid: i64) -> RpcParam {
+    rpc_response(0, "dao-member-online", json!([id, mid]))
 }
 
 #[inline]
-pub(crate) fn member_info(
-    mgid: GroupId,
-    id: i64,
-    mid: i64,
-    addr: PeerId,
-    name: String,
-) -> RpcParam {
-    rpc_response(
-        0,
-        "group-chat-member-info",
-        json!([id, mid, addr.to_hex(), name]),
-        mgid,
-    )
+pub(crate) fn member_offline(id: i64, mid: i64) -> RpcParam {
+    rpc_response(0, "dao-member-offline", json!([id, mid]))
 }
 
 #[inline]
-pub(crate) fn member_online(mgid: GroupId, gid: i64, mid: GroupId, maddr: PeerId) -> RpcParam {
-    rpc_response(
-        0,
-        "group-chat-member-online",
-        json!([gid, mid.to_hex(), maddr.to_hex()]),
-        mgid,
-    )
+pub(crate) fn message_create(msg: &Message) -> RpcParam {
+    rpc_response(0, "dao-message-create", json!(msg.to_rpc()))
 }
 
 #[inline]
-pub(crate) fn member_offline(mgid: GroupId, gid: i64, mid: GroupId) -> RpcParam {
-    rpc_response(
-        0,
-        "group-chat-member-offline",
-        json!([gid, mid.to_hex()]),
-        mgid,
-    )
+pub(crate) fn dao_info(dao: &Dao) -> RpcParam {
+    rpc_response(0, "dao-info", json!(dao.to_rpc()))
 }
 
 #[inline]
-pub(crate) fn message_create(mgid: GroupId, msg: &Message) -> RpcParam {
-    rpc_response(0, "group-chat-message-create", json!(msg.to_rpc()), mgid)
+pub(crate) fn member_manager(id: i64, mid: i64, is_manager: bool) -> RpcParam {
+    rpc_response(0, "dao-member-manager", json!([id, mid, is_manager]))
 }
 
 #[inline]
-fn group_list(groups: Vec<GroupChat>) -> RpcParam {
+pub(crate) fn proposal_create(proposal: &Proposal) -> RpcParam {
+    rpc_response(0, "dao-proposal-create", json!(proposal.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn proposal_update(proposal: &Proposal) -> RpcParam {
+    rpc_response(0, "dao-proposal-update", json!(proposal.to_rpc()))
+}
+
+#[inline]
+fn dao_list(daos: Vec<Dao>) -> RpcParam {
     let mut results = vec![];
-    for group in groups {
-        results.push(group.to_rpc());
+    for dao in daos {
+        results.push(dao.to_rpc());
     }
 
     json!(results)
@@ -139,7 +121,7 @@ fn request_list(requests: Vec<Request>) -> RpcParam {
 }
 
 #[inline]
-fn detail_list(members: Vec<Member>, messages: Vec<Message>) -> RpcParam {
+fn detail_list(dao: Dao, members: Vec<Member>, messages: Vec<Message>) -> RpcParam {
     let mut member_results = vec![];
     for m in members {
         member_results.push(m.to_rpc());
@@ -150,182 +132,174 @@ fn detail_list(members: Vec<Member>, messages: Vec<Message>) -> RpcParam {
         message_results.push(msg.to_rpc());
     }
 
-    json!([member_results, message_results])
+    json!([dao.to_rpc(), member_results, message_results])
 }
 
-pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<RpcState>) {
-    handler.add_method("group-chat-echo", |_, params, _| async move {
-        Ok(HandleResult::rpc(json!(params)))
-    });
-
+pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
     handler.add_method(
-        "group-chat-list",
-        |gid: GroupId, _params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let layer_lock = state.layer.read().await;
-            let db = group_db(&layer_lock.base, &gid)?;
-            Ok(HandleResult::rpc(group_list(GroupChat::all(&db)?)))
+        "dao-list",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            Ok(HandleResult::rpc(dao_list(Dao::all(&db)?)))
         },
     );
 
     handler.add_method(
-        "group-chat-request-list",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let is_all = params[0].as_bool().ok_or(RpcError::ParseError)?;
-            let layer_lock = state.layer.read().await;
-            let db = group_db(&layer_lock.base, &gid)?;
-            Ok(HandleResult::rpc(request_list(Request::list(&db, is_all)?)))
+        "dao-detail",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let dao = Dao::get(&db, &id)?;
+            let members = Member::list(&db, &id)?;
+            let messages = Message::list(&db, &id)?;
+            Ok(HandleResult::rpc(detail_list(dao, members, messages)))
         },
     );
 
     handler.add_method(
-        "group-chat-detail",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let g_did = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            let members = Member::all(&db, &g_did)?;
-            let messages = Message::all(&db, &g_did)?;
-            Ok(HandleResult::rpc(detail_list(members, messages)))
+        "dao-request-list",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let is_all = params[0].as_bool().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            Ok(HandleResult::rpc(request_list(Request::list(&db, is_all)?)))
         },
     );
 
     handler.add_method(
-        "group-chat-provider-list",
-        |gid: GroupId, _params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            let providers: Vec<RpcParam> =
-                Provider::list(&db)?.iter().map(|p| p.to_rpc()).collect();
-            Ok(HandleResult::rpc(json!(providers)))
+        "dao-provider-list",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            let mut results = vec![];
+            for provider in Provider::list(&db)? {
+                results.push(provider.to_rpc());
+            }
+            Ok(HandleResult::rpc(json!(results)))
         },
     );
 
     handler.add_method(
-        "group-chat-provider-check",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
+        "dao-provider-check",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let addr = PeerId::from_hex(params[1].as_str().ok_or(RpcError::ParseError)?)?;
+            let addr = id_from_str(params[1].as_str().ok_or(RpcError::ParseError)?)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
 
+            let mut results = HandleResult::new();
             if id == 0 {
-                // insert into database.
-                let db = group_db(state.layer.read().await.base(), &gid)?;
-                if Provider::get_by_addr(&db, &addr).is_err() {
-                    let mut provider = Provider::new(addr);
-                    provider.insert(&db)?;
-                }
+                // new provider.
+                let mut provider = Provider::new(addr);
+                provider.insert(&db)?;
+                results.rpcs.push(provider.to_rpc());
             }
 
-            let mut results = HandleResult::new();
             let data = bincode::serialize(&LayerEvent::Check)?;
             let s = SendType::Event(0, addr, data);
-            add_layer(&mut results, gid, s);
+            results.layers.push((DAO_ID, s));
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-provider-delete",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
+        "dao-provider-delete",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let db = group_db(state.layer.read().await.base(), &gid)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
             Provider::delete(&db, &id)?;
-            Ok(HandleResult::new())
+
+            Ok(HandleResult::rpc(json!([id])))
         },
     );
 
     handler.add_method(
-        "group-chat-create",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let glocation =
-                GroupLocation::from_u32(params[0].as_i64().ok_or(RpcError::ParseError)? as u32);
-            let gtype = GroupType::from_u32(params[1].as_i64().ok_or(RpcError::ParseError)? as u32);
-            let my_name = params[2].as_str().ok_or(RpcError::ParseError)?.to_owned();
-            // params[3] is remote addr next parse.
-            let name = params[4].as_str().ok_or(RpcError::ParseError)?.to_owned();
-            let bio = params[5].as_str().ok_or(RpcError::ParseError)?.to_owned();
-            let need_agree = params[6].as_bool().ok_or(RpcError::ParseError)?;
-            let avatar = params[7].as_str().ok_or(RpcError::ParseError)?;
+        "dao-create",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let d_type = DaoType::from_i64(params[0].as_i64().ok_or(RpcError::ParseError)?);
+            let addr_str = params[1].as_str().ok_or(RpcError::ParseError)?;
+            let name = params[2].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let bio = params[3].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let need_agree = params[4].as_bool().ok_or(RpcError::ParseError)?;
+            let avatar = params[5].as_str().ok_or(RpcError::ParseError)?;
             let avatar_bytes = base64::decode(avatar).unwrap_or(vec![]);
 
-            let base = state.layer.read().await.base().clone();
-            let db = group_db(&base, &gid)?;
-            let addr = match glocation {
-                GroupLocation::Remote => {
-                    PeerId::from_hex(params[3].as_str().ok_or(RpcError::ParseError)?)?
-                }
-                GroupLocation::Local => state.layer.read().await.addr.clone(),
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let me = own_lock.clone_user(&pid)?;
+            drop(own_lock);
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let s_db = session_db(&state.base, &pid, &db_key)?;
+
+            // when addr is empty, it's local dao.
+            let (addr, local) = if addr_str.len() > 0 {
+                (id_from_str(addr_str)?, false)
+            } else {
+                (pid, true)
             };
-            let mut gc = GroupChat::new(
-                gid,
-                gtype,
-                addr,
-                name,
-                bio,
-                need_agree,
-                glocation == GroupLocation::Local,
-                glocation == GroupLocation::Remote,
-            );
-            let gcd = gc.g_id;
-            let gheight = gc.height;
-
-            // save db
-            gc.insert(&db)?;
-            let gdid = gc.id;
-
-            // save avatar
-            let _ = write_avatar(&base, &gid, &gcd, &avatar_bytes).await;
-
-            let mut results = HandleResult::new();
-            let me = state.own.read().await.clone_user(&gid)?;
 
-            // add to rpcs.
-            results.rpcs.push(json!(gc.to_rpc()));
+            let mut dao = Dao::new(pid, d_type, addr, name, bio, need_agree, local);
+            if avatar_bytes.len() > 0 {
+                dao.avatar = write_image(&state.base, &pid, &db_key, &avatar_bytes).await?;
+            }
+            if let Err(e) = dao.insert(&db) {
+                let _ =
+                    release_content(&state.base, &pid, &db_key, &MessageType::Image, &dao.avatar);
+                return Err(e.into());
+            }
+            let id = dao.id;
+            let did = dao.did;
 
-            if glocation == GroupLocation::Remote {
-                let info = gc.to_group_info(my_name, avatar_bytes, me.avatar);
-                // TODO create proof.
-                let proof: Proof = Default::default();
+            let mut results = HandleResult::new();
+            results.rpcs.push(dao.to_rpc());
 
-                let data = bincode::serialize(&LayerEvent::Create(info, proof))?;
-                let s = SendType::Event(0, addr, data);
-                add_layer(&mut results, gid, s);
-            } else {
-                let mut m = Member::new(gc.id, gid, me.addr, me.name, true, gc.datetime);
+            if local {
+                // add myself as the first manager.
+                let mut m = Member::new_notime(id, pid, me.name, true);
                 m.insert(&db)?;
-                let mid = m.id;
-                let _ = write_avatar(&base, &gid, &gid, &me.avatar).await;
+                let _ = write_avatar(&state.base, &pid, &db_key, &pid, &me.avatar).await;
+                let height = 1;
+                Consensus::insert(&db, &id, &height, &m.id, &ConsensusType::MemberJoin)?;
+                Dao::add_height(&db, id, height)?;
 
                 // Add new session.
-                let s_db = session_db(state.layer.read().await.base(), &gid)?;
-                let mut session = gc.to_session();
+                let mut session = dao.to_session();
                 session.insert(&s_db)?;
-                results.rpcs.push(session_create(gid, &session));
-
-                // Add frist member join.
-                let mut layer_lock = state.layer.write().await;
-                layer_lock.add_running(&gcd, gid, gdid, gheight)?;
-                let height = layer_lock.running_mut(&gcd)?.increased();
-
-                // Add online to layers.
-                layer_lock.running_mut(&gcd)?.check_add_online(
-                    gid,
-                    Online::Direct(addr),
-                    gdid,
-                    mid,
-                )?;
-                layer_lock.running_mut(&gid)?.check_add_online(
-                    gcd,
-                    Online::Direct(addr),
-                    session.id,
-                    gdid,
-                )?;
-
-                drop(layer_lock);
-
-                // Update consensus.
-                Consensus::insert(&db, &gdid, &height, &m.id, &ConsensusType::MemberJoin)?;
-                GroupChat::add_height(&db, gdid, height)?;
-
-                // Online local group.
-                results.networks.push(NetworkType::AddGroup(gcd));
+                let sid = session.id;
+                let sender = state.rpc_send.clone();
+                tokio::spawn(async move {
+                    let _ = sender
+                        .send(RpcSendMessage(0, session_create(&session), true))
+                        .await;
+                });
+
+                // online this dao.
+                state.layer.write().await.dao_add(did, pid, sid, id, height);
+            } else {
+                // send to the provider.
+                let info = dao
+                    .to_info(&state.base, &pid, &db_key, me.name, me.avatar)
+                    .await;
+                let data = bincode::serialize(&LayerEvent::Create(info))?;
+                let s = SendType::Event(0, addr, data);
+                results.layers.push((DAO_ID, s));
             }
 
             Ok(results)
@@ -333,267 +307,304 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<RpcState>) {
     );
 
     handler.add_method(
-        "group-chat-resend",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
+        "dao-resend",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let mname = params[1].as_str().ok_or(RpcError::ParseError)?.to_owned();
 
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            let gc = GroupChat::get_id(&db, &id)?.ok_or(RpcError::ParseError)?;
-            drop(db);
-
-            // load avatar
-            let avatar = read_avatar(state.own.read().await.base(), &gid, &gc.g_id).await?;
-            let owner_avatar = state.own.read().await.clone_user(&gid)?.avatar;
-            let addr = gc.g_addr;
-            let info = gc.to_group_info(mname, avatar, owner_avatar);
-
-            // TODO create proof.
-            let proof: Proof = Default::default();
-
-            let data = bincode::serialize(&LayerEvent::Create(info, proof))?;
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let me = own_lock.clone_user(&pid)?;
+            drop(own_lock);
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            let dao = Dao::get(&db, &id)?;
+            let addr = dao.addr;
+            let info = dao
+                .to_info(&state.base, &pid, &db_key, me.name, me.avatar)
+                .await;
+            let data = bincode::serialize(&LayerEvent::Create(info))?;
             let s = SendType::Event(0, addr, data);
+
             let mut results = HandleResult::new();
-            add_layer(&mut results, gid, s);
+            results.layers.push((DAO_ID, s));
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-join",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let gtype = GroupType::from_u32(params[0].as_i64().ok_or(RpcError::ParseError)? as u32);
-            let gcd = GroupId::from_hex(params[1].as_str().ok_or(RpcError::ParseError)?)?;
-            let gaddr = PeerId::from_hex(params[2].as_str().ok_or(RpcError::ParseError)?)?;
-            let gname = params[3].as_str().ok_or(RpcError::ParseError)?.to_owned();
-            let gremark = params[4].as_str().ok_or(RpcError::ParseError)?;
-            let gproof = params[5].as_str().ok_or(RpcError::ParseError)?;
-            let proof = Proof::from_hex(gproof).unwrap_or(Proof::default());
-            let gkey = params[6].as_str().ok_or(RpcError::ParseError)?;
-            let key = GroupChatKey::from_hex(gkey).unwrap_or(GroupChatKey::new(vec![]));
-
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            if GroupChat::get(&db, &gcd)?.is_some() {
-                debug!("Had joined this group.");
-                return Ok(HandleResult::new()); // had join this group.
+        "dao-join",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let d_type = DaoType::from_i64(params[0].as_i64().ok_or(RpcError::ParseError)?);
+            let did = params[1].as_i64().ok_or(RpcError::ParseError)? as u64;
+            let addr = id_from_str(params[2].as_str().ok_or(RpcError::ParseError)?)?;
+            let name = params[3].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let remark = params[4].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let key =
+                hex::decode(params[5].as_str().ok_or(RpcError::ParseError)?).unwrap_or(vec![]);
+
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let me = own_lock.clone_user(&pid)?;
+            drop(own_lock);
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            if Request::exist(&db, &did, &addr)? {
+                return Ok(HandleResult::new());
             }
 
-            let mut results = HandleResult::new();
-            // check request is exsit.
-            if !Request::exist(&db, &gcd)? {
-                let mut request = Request::new_by_me(gcd, gaddr, gname, gremark.to_owned(), key);
-                request.insert(&db)?;
-                results.rpcs.push(request.to_rpc());
+            // remark is the inviter, when it's empty, join by the dao key or the open dao.
+            let proof = if remark.len() > 0 {
+                let inviter = id_from_str(&remark)?;
+                JoinProof::Invite(inviter, me.name, me.avatar)
+            } else if d_type == DaoType::Encrypted && key.len() > 0 {
+                let proof = ZkpProof::prove(&key, &did, &pid);
+                JoinProof::Zkp(proof, me.name, me.avatar)
+            } else if d_type == DaoType::Open {
+                JoinProof::Open(me.name, me.avatar)
             } else {
-                debug!("Had request again.");
-            }
-            drop(db);
-
-            let me = state.own.read().await.clone_user(&gid)?;
-            let join_proof = match gtype {
-                GroupType::Encrypted => {
-                    // remark is inviter did.
-                    let _fgid = GroupId::from_hex(gremark)?;
-                    // TODO
-                    JoinProof::Zkp(proof)
-                }
-                GroupType::Private => {
-                    // remark is inviter did.
-                    let fgid = GroupId::from_hex(gremark)?;
-                    JoinProof::Invite(fgid, proof, me.name, me.avatar)
-                }
-                GroupType::Open => JoinProof::Open(me.name, me.avatar),
+                return Err(RpcError::Custom("Need invite!".to_owned()));
             };
 
-            let data = bincode::serialize(&LayerEvent::Request(gcd, join_proof))?;
-            let s = SendType::Event(0, gaddr, data);
-            add_layer(&mut results, gid, s);
+            let mut request = Request::new_by_me(did, addr, name, remark, key);
+            request.insert(&db)?;
+
+            let mut results = HandleResult::rpc(request.to_rpc());
+            let data = bincode::serialize(&LayerEvent::Request(did, proof))?;
+            let s = SendType::Event(0, addr, data);
+            results.layers.push((DAO_ID, s));
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-invite",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
+        "dao-request-handle",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let gcd = GroupId::from_hex(params[1].as_str().ok_or(RpcError::ParseError)?)?;
-            let ids: Vec<i64> = params[2]
-                .as_array()
-                .ok_or(RpcError::ParseError)?
-                .iter()
-                .filter_map(|v| v.as_i64())
-                .collect();
+            let ok = params[1].as_bool().ok_or(RpcError::ParseError)?;
 
-            let own_lock = state.own.read().await;
-            let base = own_lock.base().clone();
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
 
-            let chat = chat_db(&base, &gid)?;
-            let group_db = group_db(&base, &gid)?;
+            let req = Request::get(&db, &id)?;
+            let dao = Dao::get(&db, &req.fid)?;
 
-            let mut invites = vec![];
-            for fid in ids {
-                let friend = Friend::get_id(&chat, fid)?.ok_or(RpcError::ParseError)?;
-                if Member::get_id(&group_db, &id, &friend.gid).is_err() {
-                    let proof = own_lock.prove_addr(&gid, &friend.gid.into())?;
-                    invites.push((friend.id, friend.gid, friend.addr, proof));
-                }
+            let mut results = HandleResult::new();
+            if dao.local {
+                layer_request_handle(&state, &db, dao.did, dao.id, id, ok, &mut results).await?;
+            } else {
+                // send to the server, waiting server handle it.
+                let event = LayerEvent::RequestResult(dao.did, req.rid, ok);
+                let data = bincode::serialize(&event)?;
+                let s = SendType::Event(0, dao.addr, data);
+                results.layers.push((DAO_ID, s));
             }
-            drop(chat);
 
-            let gc = GroupChat::get_id(&group_db, &id)?.ok_or(RpcError::ParseError)?;
-            let tmp_name = gc.g_name.replace(";", "-;");
+            Ok(results)
+        },
+    );
 
-            let s_db = session_db(&base, &gid)?;
+    handler.add_method(
+        "dao-member-update",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let is_block = params[1].as_bool().ok_or(RpcError::ParseError)?;
 
-            let mut results = HandleResult::new();
-            let mut layer_lock = state.layer.write().await;
-            for (fid, fgid, mut faddr, proof) in invites {
-                let contact_values = format!(
-                    "{};;{};;{};;{};;{};;{}",
-                    gc.g_type.to_u32(),
-                    gcd.to_hex(),
-                    gc.g_addr.to_hex(),
-                    tmp_name,
-                    proof.to_hex(),
-                    gc.key.to_hex(),
-                );
-
-                // check if encrypted group type. need online.
-                if gc.g_type == GroupType::Encrypted {
-                    if let Ok(addr) = layer_lock.running(&gid)?.online(&fgid) {
-                        faddr = addr;
-                    } else {
-                        continue;
-                    }
-                }
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            Member::block(&db, &id, is_block)?;
 
-                let (msg, nw, sc) = crate::group::GroupEvent::from_message(
-                    &base,
-                    gid,
-                    fid,
-                    MessageType::Invite,
-                    contact_values,
-                )
-                .await?;
-                let event = crate::group::GroupEvent::Message(msg.hash, nw);
-                let s = crate::group::event_message(&mut layer_lock, msg.id, gid, faddr, &event);
-                results.layers.push((gid, fgid, s));
-
-                if let Ok(id) =
-                    Session::last(&s_db, &fid, &SessionType::Chat, &msg.datetime, &sc, true)
-                {
-                    results
-                        .rpcs
-                        .push(session_last(gid, &id, &msg.datetime, &sc, false));
-                }
-            }
-            Ok(results)
+            // TODO sync to others.
+            Ok(HandleResult::new())
         },
     );
 
     handler.add_method(
-        "group-chat-request-handle",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let gcd = GroupId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
-            let id = params[1].as_i64().ok_or(RpcError::ParseError)?;
-            let rid = params[2].as_i64().ok_or(RpcError::ParseError)?;
-            let ok = params[3].as_bool().ok_or(RpcError::ParseError)?;
+        "dao-message-create",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let m_type = MessageType::from_int(params[1].as_i64().ok_or(RpcError::ParseError)?);
+            let m_content = params[2].as_str().ok_or(RpcError::ParseError)?;
 
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            let gc = GroupChat::get_id(&db, &id)?.ok_or(RpcError::ParseError)?;
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let s_db = session_db(&state.base, &pid, &db_key)?;
+
+            let dao = Dao::get(&db, &id)?;
+            let did = dao.did;
+            let (mid, _) = Member::get_id(&db, &id, &pid)?;
 
             let mut results = HandleResult::new();
-            let data = bincode::serialize(&LayerEvent::RequestResult(gcd, rid, ok))?;
-            let s = SendType::Event(0, gc.g_addr, data);
-            add_layer(&mut results, gid, s);
+            let (nmsg, datetime, raw) =
+                to_network_message(&pid, &state.base, &db_key, m_type, m_content).await?;
+            let event = Event::MessageCreate(pid, nmsg, datetime);
+
+            if dao.local {
+                // local save.
+                let new_h = state.layer.write().await.dao_mut(&did)?.increased();
+
+                let mut msg = Message::new_with_time(new_h, id, mid, true, m_type, raw, datetime);
+                if let Err(e) = msg.insert(&db) {
+                    let _ = release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content);
+                    return Err(e.into());
+                }
+                results.rpcs.push(msg.to_rpc());
+                Consensus::insert(&db, &id, &new_h, &msg.id, &ConsensusType::MessageCreate)?;
+                Dao::add_height(&db, id, new_h)?;
+
+                // UPDATE SESSION.
+                update_session(&s_db, &id, &msg, &mut results);
+
+                // broadcast.
+                let data = LayerEvent::Sync(did, new_h, event);
+                broadcast(&did, &state, &data, &mut results).await?;
+            } else {
+                // the message saved when synced from server, with a new reference.
+                release_content(&state.base, &pid, &db_key, &m_type, &raw)?;
+
+                // send to server.
+                let data = bincode::serialize(&LayerEvent::Sync(did, 0, event))?;
+                let msg = SendType::Event(0, dao.addr, data);
+                results.layers.push((DAO_ID, msg));
+            }
+
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-member-update",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
+        "dao-proposal-list",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            let is_block = params[1].as_bool().ok_or(RpcError::ParseError)?;
 
-            let db = group_db(state.layer.read().await.base(), &gid)?;
-            Member::block(&db, &id, is_block)?;
-            Ok(HandleResult::new())
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+
+            let mut results = vec![];
+            for proposal in Proposal::list(&db, &id)? {
+                results.push(proposal.to_rpc());
+            }
+            Ok(HandleResult::rpc(json!(results)))
         },
     );
 
     handler.add_method(
-        "group-chat-message-create",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let gcd = GroupId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
-            let m_type = MessageType::from_int(params[1].as_i64().ok_or(RpcError::ParseError)?);
-            let m_content = params[2].as_str().ok_or(RpcError::ParseError)?;
+        "dao-proposal-create",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let p_type = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            let content: Vec<&str> = params[2]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|v| v.as_str())
+                .collect();
+            let end = params[3].as_i64().ok_or(RpcError::ParseError)?;
+
+            let p_type = match (p_type, content.as_slice()) {
+                (0, [title, content]) => ProposalType::Text(title.to_string(), content.to_string()),
+                (1, [mpid]) => ProposalType::ManagerAdd(id_from_str(mpid)?),
+                (2, [mpid]) => ProposalType::ManagerDel(id_from_str(mpid)?),
+                (3, [name, bio, avatar]) => ProposalType::Info(
+                    name.to_string(),
+                    bio.to_string(),
+                    base64::decode(avatar).unwrap_or(vec![]),
+                ),
+                (4, [mpid]) => ProposalType::Transfer(id_from_str(mpid)?),
+                _ => return Err(RpcError::ParseError),
+            };
 
-            let addr = state.layer.read().await.running(&gid)?.online(&gcd)?;
-            let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let dao = Dao::get(&db, &id)?;
 
-            let base = state.own.read().await.base().clone();
-            let (nmsg, datetime) = to_network_message(&base, &gid, m_type, m_content).await?;
-            let event = Event::MessageCreate(gid, nmsg, datetime);
-            let data = bincode::serialize(&LayerEvent::Sync(gcd, 0, event))?;
-            let msg = SendType::Event(0, addr, data);
-            add_layer(&mut results, gid, msg);
+            let mut results = HandleResult::new();
+            if dao.local {
+                layer_proposal_create(&state, &db, dao.did, id, pid, p_type, end, &mut results)
+                    .await?;
+            } else {
+                // send to server, the voting window will be fixed by server.
+                let event = Event::ProposalCreate(pid, p_type, 0, end);
+                let data = bincode::serialize(&LayerEvent::Sync(dao.did, 0, event))?;
+                let msg = SendType::Event(0, dao.addr, data);
+                results.layers.push((DAO_ID, msg));
+            }
 
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-close",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let gcd = GroupId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
-            let id = params[1].as_i64().ok_or(RpcError::ParseError)?;
-
-            let addr = state
-                .layer
-                .write()
-                .await
-                .remove_online(&gid, &gcd)
-                .ok_or(RpcError::ParseError)?;
+        "dao-proposal-vote",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let proposal = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            let agree = params[2].as_bool().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let dao = Dao::get(&db, &id)?;
+            let p = Proposal::get(&db, &proposal)?;
+            if p.is_over {
+                return Err(RpcError::Custom("Proposal voting is over!".to_owned()));
+            }
 
             let mut results = HandleResult::new();
-            let base = state.layer.read().await.base().clone();
-            let sid = Session::close(&session_db(&base, &gid)?, &id, &SessionType::Group)?;
-            results.rpcs.push(session_close(gid, &sid));
-
-            let db = group_db(&base, &gid)?;
-            GroupChat::close(&db, &id)?;
+            if dao.local {
+                let did = dao.did;
+                layer_proposal_vote(&state, &db, did, id, pid, p.height, agree, &mut results)
+                    .await?;
+            } else {
+                // send to server, the vote will be tallied by server.
+                let event = Event::ProposalVote(pid, p.height, agree, 0);
+                let data = bincode::serialize(&LayerEvent::Sync(dao.did, 0, event))?;
+                let msg = SendType::Event(0, dao.addr, data);
+                results.layers.push((DAO_ID, msg));
+            }
 
-            let event = Event::MemberLeave(gid);
-            let data = bincode::serialize(&LayerEvent::Sync(gcd, 0, event))?;
-            let msg = SendType::Event(0, addr, data);
-            add_layer(&mut results, gid, msg);
             Ok(results)
         },
     );
 
     handler.add_method(
-        "group-chat-delete",
-        |gid: GroupId, params: Vec<RpcParam>, state: Arc<RpcState>| async move {
-            let gcd = GroupId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
-            let id = params[1].as_i64().ok_or(RpcError::ParseError)?;
+        "dao-delete",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
 
             let mut results = HandleResult::new();
-            let base = state.layer.read().await.base().clone();
-            let sid = Session::delete(&session_db(&base, &gid)?, &id, &SessionType::Group)?;
-            results.rpcs.push(session_delete(gid, &sid));
-            let db = group_db(&base, &gid)?;
-            if GroupChat::delete(&db, &id)? {
-                let addr = state
-                    .layer
-                    .write()
-                    .await
-                    .remove_online(&gid, &gcd)
-                    .ok_or(RpcError::ParseError)?;
-                let event = Event::MemberLeave(gid);
-                let data = bincode::serialize(&LayerEvent::Sync(gcd, 0, event))?;
-                let msg = SendType::Event(0, addr, data);
-                add_layer(&mut results, gid, msg);
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = dao_db(&state.base, &pid, &db_key)?;
+            let s_db = session_db(&state.base, &pid, &db_key)?;
+
+            let dao = Dao::delete(&db, &id)?;
+            release_content(&state.base, &pid, &db_key, &MessageType::Image, &dao.avatar)?;
+            let sid = Session::delete(&s_db, &id, &SessionType::Dao)?;
+            results.rpcs.push(session_delete(&sid));
+
+            if dao.local {
+                // close the dao.
+                let data = bincode::serialize(&LayerEvent::Offline(dao.did))?;
+                if let Some(addrs) = state.layer.write().await.dao_del(&dao.did) {
+                    for addr in addrs {
+                        let s = SendType::Event(0, addr, data.clone());
+                        results.layers.push((DAO_ID, s));
+                    }
+                }
+            } else {
+                // leave the dao.
+                let event = Event::MemberLeave(pid);
+                let d = bincode::serialize(&LayerEvent::Sync(dao.did, 0, event))?;
+                let msg = SendType::Event(0, dao.addr, d);
+                results.layers.push((DAO_ID, msg));
+                state.layer.write().await.dao_del(&dao.did);
             }
 
             Ok(results)
@@ -1,5 +1,5 @@
 mod models;
 
 pub(crate) mod rpc;
-pub(crate) use models::Device;
+pub(crate) use models::{CommandStatus, Device, DeviceCommand, DeviceLog};
 pub(crate) use rpc::new_rpc_handler;
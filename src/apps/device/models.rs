@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::primitives::{Peer, PeerId, Result};
 use tdn::types::rpc::{json, RpcParam};
@@ -11,6 +12,8 @@ pub(crate) struct Device {
     pub peer: Peer,
     pub lasttime: i64,
     pub online: bool,
+    /// the pair key with this device, current device is empty.
+    pub pair: Vec<u8>,
 }
 
 impl Device {
@@ -30,12 +33,14 @@ impl Device {
             name: String::new(),
             info: String::new(),
             online: true,
+            pair: vec![],
         }
     }
 
     /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Device {
         Device {
+            pair: hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]),
             lasttime: v.pop().unwrap().as_i64(),
             peer: Peer::from_string(v.pop().unwrap().as_str()).unwrap_or(Peer::default()),
             assist: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(PeerId::default()),
@@ -55,12 +60,14 @@ impl Device {
             self.peer.to_string(),
             self.lasttime,
             if self.online { "1" } else { "0" },
+            !self.pair.is_empty(),
         ])
     }
 
     /// load account devices.
     pub fn list(db: &DStorage) -> Result<Vec<Device>> {
-        let matrix = db.query("SELECT id, name, info, assist, peer, lasttime FROM devices")?;
+        let matrix =
+            db.query("SELECT id, name, info, assist, peer, lasttime, pair FROM devices")?;
         let mut devices = vec![];
         for values in matrix {
             devices.push(Device::from_values(values));
@@ -68,9 +75,9 @@ impl Device {
         Ok(devices)
     }
 
-    pub fn _get(db: &DStorage, aid: &PeerId) -> Result<Option<Device>> {
+    pub fn get(db: &DStorage, aid: &PeerId) -> Result<Option<Device>> {
         let mut matrix = db.query(&format!(
-            "SELECT id, name, info, assist, peer, lasttime FROM devices WHERE assist = '{}'",
+            "SELECT id, name, info, assist, peer, lasttime, pair FROM devices WHERE assist = '{}'",
             aid.to_hex()
         ))?;
         if let Some(values) = matrix.pop() {
@@ -82,18 +89,79 @@ impl Device {
 
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
         let sql = format!(
-            "INSERT INTO devices (name, info, assist, peer, lasttime) VALUES ('{}', '{}', '{}', '{}', {})",
+            "INSERT INTO devices (name, info, assist, peer, lasttime, pair) VALUES ('{}', '{}', '{}', '{}', {}, '{}')",
             self.name,
             self.info,
             self.assist.to_hex(),
             self.peer.to_string(),
             self.lasttime,
+            hex::encode(&self.pair),
         );
         let id = db.insert(&sql)?;
         self.id = id;
         Ok(())
     }
 
+    /// save the new pair key with the device.
+    pub fn paired(&mut self, db: &DStorage, key: Vec<u8>) -> Result<()> {
+        self.pair = key;
+        db.update(&format!(
+            "UPDATE devices SET pair = '{}' WHERE id = {}",
+            hex::encode(&self.pair),
+            self.id
+        ))?;
+        db.delete(&format!(
+            "DELETE FROM revokes WHERE assist = '{}'",
+            self.assist.to_hex()
+        ))?;
+        Ok(())
+    }
+
+    pub fn delete(db: &DStorage, id: &i64) -> Result<usize> {
+        db.delete(&format!("DELETE FROM devices WHERE id = {}", id))
+    }
+
+    /// revoke the device, it cannot connect again, only receive the pending commands,
+    /// so keep the pair key.
+    pub fn revoke(db: &DStorage, aid: &PeerId, pair: &[u8]) -> Result<()> {
+        if Self::is_revoked(db, aid)? {
+            return Ok(());
+        }
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+        db.insert(&format!(
+            "INSERT INTO revokes (assist, pair, datetime) VALUES ('{}', '{}', {})",
+            aid.to_hex(),
+            hex::encode(pair),
+            datetime
+        ))?;
+        Ok(())
+    }
+
+    /// the revoked device's pair key.
+    pub fn revoked_pair(db: &DStorage, aid: &PeerId) -> Result<Vec<u8>> {
+        let mut matrix = db.query(&format!(
+            "SELECT pair FROM revokes WHERE assist = '{}'",
+            aid.to_hex()
+        ))?;
+        if let Some(mut values) = matrix.pop() {
+            Ok(hex::decode(values.pop().unwrap().as_str()).unwrap_or(vec![])) // safe unwrap.
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    pub fn is_revoked(db: &DStorage, aid: &PeerId) -> Result<bool> {
+        let matrix = db.query(&format!(
+            "SELECT id FROM revokes WHERE assist = '{}'",
+            aid.to_hex()
+        ))?;
+        Ok(matrix.len() > 0)
+    }
+
     pub fn update(db: &DStorage, id: i64, name: &str, info: &str) -> Result<usize> {
         let sql = format!(
             "UPDATE devices SET name='{}', info = '{}' WHERE id = {}",
@@ -102,3 +170,201 @@ impl Device {
         db.update(&sql)
     }
 }
+
+/// remote command to other own device, used when the device is lost.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum DeviceCommand {
+    /// logout the account, need the PIN to login again.
+    Lock,
+    /// delete the account's data in the device.
+    Wipe,
+}
+
+impl DeviceCommand {
+    pub fn to_int(&self) -> i64 {
+        match self {
+            DeviceCommand::Lock => 0,
+            DeviceCommand::Wipe => 1,
+        }
+    }
+
+    pub fn from_int(i: i64) -> Self {
+        match i {
+            0 => DeviceCommand::Lock,
+            1 => DeviceCommand::Wipe,
+            _ => DeviceCommand::Lock,
+        }
+    }
+
+    /// the proof's label of the command.
+    pub fn label(&self) -> &'static [u8] {
+        match self {
+            DeviceCommand::Lock => b"lock",
+            DeviceCommand::Wipe => b"wipe",
+        }
+    }
+}
+
+#[derive(Eq, PartialEq)]
+pub(crate) enum CommandStatus {
+    /// waiting the device online and acknowledge.
+    Pending,
+    /// the device acknowledged and executed.
+    Done,
+    /// the device rejected it.
+    Failed,
+}
+
+impl CommandStatus {
+    pub fn to_int(&self) -> i64 {
+        match self {
+            CommandStatus::Pending => 0,
+            CommandStatus::Done => 1,
+            CommandStatus::Failed => 2,
+        }
+    }
+
+    pub fn from_int(i: i64) -> Self {
+        match i {
+            0 => CommandStatus::Pending,
+            1 => CommandStatus::Done,
+            2 => CommandStatus::Failed,
+            _ => CommandStatus::Pending,
+        }
+    }
+}
+
+/// the device event log of the remote commands, sent and received.
+pub(crate) struct DeviceLog {
+    pub id: i64,
+    /// the target device when sent by me, or the sender device.
+    pub assist: PeerId,
+    pub command: DeviceCommand,
+    pub is_me: bool,
+    pub status: CommandStatus,
+    pub datetime: i64,
+}
+
+impl DeviceLog {
+    pub fn new(assist: PeerId, command: DeviceCommand, is_me: bool, status: CommandStatus) -> Self {
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        Self {
+            assist,
+            command,
+            is_me,
+            status,
+            datetime,
+            id: 0,
+        }
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> DeviceLog {
+        DeviceLog {
+            datetime: v.pop().unwrap().as_i64(),
+            status: CommandStatus::from_int(v.pop().unwrap().as_i64()),
+            is_me: v.pop().unwrap().as_bool(),
+            command: DeviceCommand::from_int(v.pop().unwrap().as_i64()),
+            assist: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(PeerId::default()),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([
+            self.id,
+            self.assist.to_hex(),
+            self.command.to_int(),
+            self.is_me,
+            self.status.to_int(),
+            self.datetime,
+        ])
+    }
+
+    pub fn list(db: &DStorage) -> Result<Vec<DeviceLog>> {
+        let matrix = db.query(
+            "SELECT id, assist, command, is_me, status, datetime FROM device_logs ORDER BY id DESC",
+        )?;
+        let mut logs = vec![];
+        for values in matrix {
+            logs.push(DeviceLog::from_values(values));
+        }
+        Ok(logs)
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<DeviceLog> {
+        let mut matrix = db.query(&format!(
+            "SELECT id, assist, command, is_me, status, datetime FROM device_logs WHERE id = {}",
+            id
+        ))?;
+        if let Some(values) = matrix.pop() {
+            Ok(DeviceLog::from_values(values))
+        } else {
+            Err(anyhow!("device log missing"))
+        }
+    }
+
+    /// the sent commands which the device not acknowledged.
+    pub fn pending(db: &DStorage, aid: &PeerId) -> Result<Vec<DeviceLog>> {
+        let matrix = db.query(&format!(
+            "SELECT id, assist, command, is_me, status, datetime FROM device_logs WHERE assist = '{}' AND is_me = true AND status = {}",
+            aid.to_hex(),
+            CommandStatus::Pending.to_int(),
+        ))?;
+        let mut logs = vec![];
+        for values in matrix {
+            logs.push(DeviceLog::from_values(values));
+        }
+        Ok(logs)
+    }
+
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO device_logs (assist, command, is_me, status, datetime) VALUES ('{}', {}, {}, {}, {})",
+            self.assist.to_hex(),
+            self.command.to_int(),
+            self.is_me,
+            self.status.to_int(),
+            self.datetime,
+        );
+        let id = db.insert(&sql)?;
+        self.id = id;
+        Ok(())
+    }
+
+    /// keep the received command's nonce, return false if it is used (replayed).
+    pub fn nonce(db: &DStorage, nonce: &[u8]) -> Result<bool> {
+        let nonce = hex::encode(nonce);
+        let matrix = db.query(&format!(
+            "SELECT id FROM command_nonces WHERE nonce = '{}'",
+            nonce
+        ))?;
+        if matrix.len() > 0 {
+            return Ok(false);
+        }
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+        db.insert(&format!(
+            "INSERT INTO command_nonces (nonce, datetime) VALUES ('{}', {})",
+            nonce, datetime
+        ))?;
+        Ok(true)
+    }
+
+    pub fn update_status(&mut self, db: &DStorage, status: CommandStatus) -> Result<usize> {
+        self.status = status;
+        db.update(&format!(
+            "UPDATE device_logs SET status = {} WHERE id = {}",
+            self.status.to_int(),
+            self.id
+        ))
+    }
+}
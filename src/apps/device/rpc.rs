@@ -1,14 +1,16 @@
 use std::sync::Arc;
 use tdn::types::{
+    message::SendType,
     primitives::HandleResult,
     rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
 
 use crate::global::Global;
-//use crate::own::OwnEvent;
+use crate::own::{OwnEvent, Pairing};
+use crate::storage::consensus_db;
 use crate::utils::device_status::device_status as local_device_status;
 
-use super::Device;
+use super::{CommandStatus, Device, DeviceCommand, DeviceLog};
 
 #[inline]
 pub(crate) fn device_create(device: &Device) -> RpcParam {
@@ -25,6 +27,21 @@ pub(crate) fn device_offline(id: i64) -> RpcParam {
     rpc_response(0, "device-offline", json!([id]))
 }
 
+#[inline]
+pub(crate) fn device_delete(id: i64) -> RpcParam {
+    rpc_response(0, "device-delete", json!([id]))
+}
+
+#[inline]
+pub(crate) fn device_pair(ok: bool) -> RpcParam {
+    rpc_response(0, "device-pair", json!([ok]))
+}
+
+#[inline]
+pub(crate) fn device_log(log: &DeviceLog) -> RpcParam {
+    rpc_response(0, "device-log", json!(log.to_rpc()))
+}
+
 #[inline]
 pub(crate) fn device_status(
     id: i64,
@@ -54,6 +71,51 @@ fn device_list(devices: &[Device]) -> RpcParam {
     json!(results)
 }
 
+#[inline]
+fn device_logs(logs: Vec<DeviceLog>) -> RpcParam {
+    let mut results = vec![];
+    for log in logs {
+        results.push(log.to_rpc());
+    }
+    json!(results)
+}
+
+/// send the lock or wipe command to the device, need the PIN of the account.
+/// if the device is offline, it will be sent when the device is online.
+async fn device_command(
+    state: &Arc<Global>,
+    id: i64,
+    lock: &str,
+    command: DeviceCommand,
+) -> Result<HandleResult, RpcError> {
+    let pid = state.pid().await;
+    let own_lock = state.own.read().await;
+    if !own_lock.check_lock(&pid, lock) {
+        return Err(RpcError::Custom("Lock is invalid!".to_owned()));
+    }
+    if id == own_lock.current_device()?.0 {
+        return Err(RpcError::Custom("cannot command current device".to_owned()));
+    }
+    let (aid, online, pair) = own_lock
+        .distributes
+        .iter()
+        .find(|d| d.id == id)
+        .map(|d| (d.assist, d.online, d.pair.clone()))
+        .ok_or(RpcError::Custom("device is missing".to_owned()))?;
+
+    let db_key = own_lock.db_key(&pid)?;
+    let db = consensus_db(&state.base, &pid, &db_key)?;
+    let mut log = DeviceLog::new(aid, command, true, CommandStatus::Pending);
+    log.insert(&db)?;
+
+    // the acknowledge will response in device-log.
+    let mut results = HandleResult::rpc(log.to_rpc());
+    if online {
+        results.owns.push(own_lock.command(&pair, &aid, &log)?);
+    }
+    Ok(results)
+}
+
 pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
     handler.add_method("device-echo", |params, _| async move {
         Ok(HandleResult::rpc(json!(params)))
@@ -81,29 +143,126 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
                     cpu, memory, swap, disk, cpu_p, memory_p, swap_p, disk_p, uptime
                 ])));
             }
-            drop(own_lock);
 
-            //let msg = state.own.write().await.event_message(addr, &OwnEvent::StatusRequest)?;
-            //Ok(HandleResult::group(msg))
-            Ok(HandleResult::new())
+            // remote device status will response in device-status.
+            let device = own_lock
+                .distributes
+                .iter()
+                .find(|d| d.id == id)
+                .ok_or(RpcError::Custom("device is missing".to_owned()))?;
+            if !device.online {
+                return Err(RpcError::Custom("device is offline".to_owned()));
+            }
+            let data = bincode::serialize(&OwnEvent::StatusRequest)?;
+            let mut results = HandleResult::new();
+            results.owns.push(SendType::Event(0, device.assist, data));
+            Ok(results)
         },
     );
 
     handler.add_method(
         "device-search",
-        |_params: Vec<RpcParam>, _state: Arc<Global>| async move {
-            //let msg = state.own.read().await.create_message(&gid, Peer::peer(addr))?;
-            //Ok(HandleResult::group(gid, msg))
-            Ok(HandleResult::new())
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            // connect the offline paired devices, new devices need pairing first.
+            // the found devices will response in device-online.
+            let height = *state.peer_own_height.read().await;
+            let mut results = HandleResult::new();
+            state
+                .own
+                .read()
+                .await
+                .connect_devices(height, &mut results)?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "device-pair-code",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            // display the one-time code or QR in this device, new device input it.
+            let mut own_lock = state.own.write().await;
+            let aid = own_lock.current_assist()?;
+            let (pairing, code) = Pairing::host();
+            own_lock.pairing = Some(pairing);
+            Ok(HandleResult::rpc(json!([code, Pairing::qr(&aid, &code)])))
+        },
+    );
+
+    handler.add_method(
+        "device-pair",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let code = params[0].as_str().ok_or(RpcError::ParseError)?;
+
+            // pairing result will response in device-pair.
+            let pid = state.pid().await;
+            let pairing = Pairing::join(code)?;
+            let msg = state.own.write().await.pair_devices(pid, pairing)?;
+            let mut results = HandleResult::new();
+            results.owns.push(msg);
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "device-lock",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let lock = params[1].as_str().ok_or(RpcError::ParseError)?;
+            device_command(&state, id, lock, DeviceCommand::Lock).await
+        },
+    );
+
+    handler.add_method(
+        "device-wipe",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let lock = params[1].as_str().ok_or(RpcError::ParseError)?;
+            device_command(&state, id, lock, DeviceCommand::Wipe).await
+        },
+    );
+
+    handler.add_method(
+        "device-logs",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = consensus_db(&state.base, &pid, &db_key)?;
+            Ok(HandleResult::rpc(device_logs(DeviceLog::list(&db)?)))
         },
     );
 
     handler.add_method(
         "device-delete",
-        |params: Vec<RpcParam>, _state: Arc<Global>| async move {
-            let _id = params[0].as_i64().ok_or(RpcError::ParseError)?;
-            // TODO delete a device.
-            Ok(HandleResult::new())
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            if id == own_lock.current_device()?.0 {
+                return Err(RpcError::Custom("cannot delete current device".to_owned()));
+            }
+            let db_key = own_lock.db_key(&pid)?;
+            let (aid, pair) = own_lock
+                .distributes
+                .iter()
+                .find(|d| d.id == id)
+                .map(|d| (d.assist, d.pair.clone()))
+                .ok_or(RpcError::Custom("device is missing".to_owned()))?;
+            drop(own_lock);
+
+            // the pending commands still can be sent to the revoked device.
+            let db = consensus_db(&state.base, &pid, &db_key)?;
+            Device::delete(&db, &id)?;
+            Device::revoke(&db, &aid, &pair)?;
+
+            // the others devices also delete it, and the deleted device will disconnect.
+            let mut results = HandleResult::rpc(json!([id]));
+            let mut own_lock = state.own.write().await;
+            own_lock.broadcast(&OwnEvent::DeviceDelete(aid), &mut results)?;
+            own_lock.remove_device(&aid);
+            drop(own_lock);
+            results.owns.push(SendType::Disconnect(aid));
+            Ok(results)
         },
     );
 }
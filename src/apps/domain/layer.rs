@@ -1,16 +1,25 @@
-use domain_types::LayerServerEvent;
+use domain_types::{check_name, LayerServerEvent};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    message::RecvType,
-    primitives::{HandleResult, Result},
+    message::{RecvType, SendType},
+    primitives::{HandleResult, PeerId, Result},
 };
 
 use crate::global::Global;
-use crate::storage::domain_db;
+use crate::group::{notice_request, rpc as chat_rpc, Friend, GroupEvent, Request as ChatRequest};
+use crate::rpc::session_create;
+use crate::storage::{chat_db, domain_db, session_db};
 
-use super::models::{Name, Provider};
+use super::models::{Name, Provider, Record, Request};
 use super::rpc;
 
+/// remind before the name expire (seconds), 7 days.
+const REMIND_BEFORE: i64 = 604800;
+
+/// remind interval (seconds), one day.
+const REMIND_INTERVAL: i64 = 86400;
+
 pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<HandleResult> {
     let mut results = HandleResult::new();
 
@@ -50,13 +59,50 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
                         results.rpcs.push(rpc::register_failure(&name));
                     }
                 }
-                LayerServerEvent::Info(upid, uname, ubio, uavatar) => {
-                    results
-                        .rpcs
-                        .push(rpc::search_result(&upid, &uname, &ubio, &uavatar));
+                LayerServerEvent::Info(record) => {
+                    let start = SystemTime::now();
+                    let now = start
+                        .duration_since(UNIX_EPOCH)
+                        .map(|s| s.as_secs())
+                        .unwrap_or(0) as i64; // safe for all life.
+
+                    // only trust the record which signed by the owner.
+                    if !check_name(&record.name) || record.is_expired(now) || !record.verify() {
+                        warn!("domain provider {} send invalid record.", addr.to_hex());
+                        return Ok(results);
+                    }
+
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+                    let name = record.name.clone();
+                    // ignore the older record from the provider.
+                    let cached = Record::version(&db, &name, &provider.id)?;
+                    if cached.map(|v| v <= record.version).unwrap_or(true) {
+                        Record::new(provider.id, record).insert(&db)?;
+                    }
+
+                    let records = Record::list_by_name(&db, &name)?;
+                    if let Some(msg) = rpc::search_resolve(&name, &records) {
+                        results.rpcs.push(msg);
+                    }
                 }
                 LayerServerEvent::None(uname) => {
-                    results.rpcs.push(rpc::search_none(&uname));
+                    if let Ok(provider) = Provider::get_by_addr(&db, &addr) {
+                        if check_name(&uname) {
+                            Record::delete(&db, &uname, &provider.id)?;
+                        }
+                    }
+
+                    // other providers maybe has the name.
+                    let records = if check_name(&uname) {
+                        Record::list_by_name(&db, &uname)?
+                    } else {
+                        vec![]
+                    };
+                    if let Some(msg) = rpc::search_resolve(&uname, &records) {
+                        results.rpcs.push(msg);
+                    } else {
+                        results.rpcs.push(rpc::search_none(&uname));
+                    }
                 }
                 LayerServerEvent::Actived(uname, is_actived) => {
                     let provider = Provider::get_by_addr(&db, &addr)?;
@@ -76,7 +122,69 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
                     let names = Name::list(&db)?;
                     results.rpcs.push(rpc::domain_list(&ps, &names));
                 }
-                LayerServerEvent::Response(_ugid, _uname, _is_ok) => {}
+                LayerServerEvent::Request(rid, uname, remark) => {
+                    if !check_name(&uname) {
+                        return Ok(results);
+                    }
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+                    let mut request = Request::new(provider.id, rid, uname, remark, false);
+                    request.insert(&db)?;
+                    results.rpcs.push(rpc::request_create(&request));
+                    let s_db = session_db(&global.base, &pid, &db_key)?;
+                    notice_request(&s_db, None, &mut results);
+                }
+                LayerServerEvent::Response(upid, uname, is_ok) => {
+                    if !check_name(&uname) {
+                        return Ok(results);
+                    }
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+
+                    if let Ok(mut request) = Request::get_by_name(&db, &provider.id, &uname, true) {
+                        // my request is handled by remote.
+                        if request.is_over {
+                            return Ok(results);
+                        }
+                        request.over(&db, is_ok)?;
+                        results.rpcs.push(rpc::request_update(&request));
+                        if is_ok {
+                            chat_request(global, upid, uname, request.remark, &mut results).await?;
+                        }
+                    } else if let Ok(request) =
+                        Request::get_by_name(&db, &provider.id, &uname, false)
+                    {
+                        // i accepted the request, now known the requester.
+                        if request.is_ok && is_ok {
+                            chat_agree(global, upid, uname, request.remark, &mut results).await?;
+                        }
+                    }
+                }
+                LayerServerEvent::Renewed(uname, expire) => {
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+                    let name = Name::get_by_name_provider(&db, &uname, &provider.id)?;
+                    Name::renew(&db, &name.id, name.version + 1, expire)?;
+
+                    let ps = Provider::list(&db)?;
+                    let names = Name::list(&db)?;
+                    results.rpcs.push(rpc::domain_list(&ps, &names));
+                }
+                LayerServerEvent::Transfer(uname, from, version) => {
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+                    results
+                        .rpcs
+                        .push(rpc::transfer_request(&provider, &uname, &from, version));
+                }
+                LayerServerEvent::Transferred(uname, to, is_ok) => {
+                    let provider = Provider::get_by_addr(&db, &addr)?;
+                    if is_ok {
+                        if let Ok(name) = Name::get_by_name_provider(&db, &uname, &provider.id) {
+                            name.delete(&db)?;
+                        }
+                        let ps = Provider::list(&db)?;
+                        let names = Name::list(&db)?;
+                        results.rpcs.push(rpc::domain_list(&ps, &names));
+                    }
+                    results.rpcs.push(rpc::transfer_result(&uname, &to, is_ok));
+                }
             }
         }
         RecvType::Delivery(_t, _tid, _is_ok) => {
@@ -86,3 +194,268 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
 
     Ok(results)
 }
+
+/// remind the names which will expire soon, one time one day.
+pub(crate) async fn name_remind(global: &Arc<Global>) -> Result<HandleResult> {
+    let mut results = HandleResult::new();
+
+    let pid = global.pid().await;
+    let db_key = if let Ok(db_key) = global.own.read().await.db_key(&pid) {
+        db_key
+    } else {
+        return Ok(results);
+    };
+    let db = domain_db(&global.base, &pid, &db_key)?;
+
+    let start = SystemTime::now();
+    let now = start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64; // safe for all life.
+
+    for name in Name::expiring(&db, now + REMIND_BEFORE, now - REMIND_INTERVAL)? {
+        Name::reminded(&db, &name.id, now)?;
+        results.rpcs.push(rpc::name_expiring(&name));
+    }
+
+    Ok(results)
+}
+
+/// the request is accepted, continue the chat request to the remote.
+async fn chat_request(
+    global: &Arc<Global>,
+    remote: PeerId,
+    remote_name: String,
+    remark: String,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let own_lock = global.own.read().await;
+    let db_key = own_lock.db_key(&pid)?;
+    let name = own_lock.account(&pid)?.name.clone();
+    drop(own_lock);
+    let db = chat_db(&global.base, &pid, &db_key)?;
+
+    if Friend::is_friend(&db, &remote)? {
+        return Ok(());
+    }
+    if let Ok(req) = ChatRequest::get_id(&db, &remote) {
+        ChatRequest::delete(&db, &req.id)?;
+    }
+
+    let mut request = ChatRequest::new(remote, remote_name, remark, true, false);
+    request.insert(&db)?;
+    results.rpcs.push(chat_rpc::request_create(&request));
+
+    let data = bincode::serialize(&GroupEvent::Request(name, request.remark))?;
+    results.groups.push(SendType::Event(0, remote, data));
+    Ok(())
+}
+
+/// accepted the request by domain, add the requester as friend, and
+/// the requester's chat request will be agreed auto.
+async fn chat_agree(
+    global: &Arc<Global>,
+    remote: PeerId,
+    remote_name: String,
+    remark: String,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = chat_db(&global.base, &pid, &db_key)?;
+
+    if Friend::is_friend(&db, &remote)? {
+        return Ok(());
+    }
+    if let Ok(req) = ChatRequest::get_id(&db, &remote) {
+        ChatRequest::delete(&db, &req.id)?;
+    }
+
+    let mut request = ChatRequest::new(remote, remote_name, remark, false, true);
+    request.is_ok = true;
+    request.is_over = true;
+    request.insert(&db)?;
+    results.rpcs.push(chat_rpc::request_create(&request));
+
+    let friend = Friend::from_remote(&db, remote, request.name, PeerId::default(), [0u8; 32])?;
+    results
+        .rpcs
+        .push(chat_rpc::request_agree(request.id, &friend));
+
+    // ADD NEW SESSION.
+    let s_db = session_db(&global.base, &pid, &db_key)?;
+    let mut session = friend.to_session();
+    session.insert(&s_db)?;
+    results.rpcs.push(session_create(&session));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::init_rpc;
+    use crate::utils::testing::Node;
+    use domain_types::{LayerPeerEvent, NameRecord};
+    use tdn::types::rpc::{json, RpcParam};
+    use tdn_storage::local::DStorage;
+
+    use super::super::provider;
+
+    /// the provider's address.
+    const PROVIDER: PeerId = PeerId([7u8; 32]);
+
+    /// the provider running with the node's temporary storage.
+    fn provider_db(node: &Node) -> DStorage {
+        let mut path = node.global.base.clone();
+        path.push("domain_provider.db");
+        let db = DStorage::open(path, "provider").unwrap();
+        provider::migrate(&db).unwrap();
+        db
+    }
+
+    /// call the rpc in the node, send the events to provider, and handle the
+    /// provider's responses which to the node. returns the rpcs to the UI.
+    async fn call(node: &Node, pdb: &DStorage, method: &str, params: RpcParam) -> Vec<RpcParam> {
+        let rpc = init_rpc(node.global.clone());
+        let params = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let results = rpc.handle(params).await.unwrap();
+
+        let mut rpcs = vec![];
+        for (_, msg) in results.layers {
+            if let SendType::Event(_, to, data) = msg {
+                if to != PROVIDER {
+                    continue;
+                }
+                let mut replies = HandleResult::new();
+                provider::handle(
+                    node.pid,
+                    bincode::deserialize(&data).unwrap(),
+                    pdb,
+                    &mut replies,
+                )
+                .unwrap();
+                for (_, reply) in replies.layers {
+                    if let SendType::Event(_, to, data) = reply {
+                        if to == node.pid {
+                            let res = handle(RecvType::Event(PROVIDER, data), &node.global)
+                                .await
+                                .unwrap();
+                            rpcs.extend(res.rpcs);
+                        }
+                    }
+                }
+            }
+        }
+        rpcs
+    }
+
+    /// the first rpc of the method.
+    fn find<'a>(rpcs: &'a [RpcParam], method: &str) -> Option<&'a RpcParam> {
+        rpcs.iter().find(|r| r["method"] == method)
+    }
+
+    /// add the provider and return its local id.
+    async fn add_provider(node: &Node, pdb: &DStorage) -> i64 {
+        let rpcs = call(node, pdb, "domain-provider-add", json!([PROVIDER.to_hex()])).await;
+        assert!(find(&rpcs, "domain-provider-add").is_some());
+        let db = node.db(domain_db).await;
+        let provider = Provider::get_by_addr(&db, &PROVIDER).unwrap();
+        assert!(provider.is_ok);
+        provider.id
+    }
+
+    #[tokio::test]
+    async fn register_and_search() {
+        let alice = Node::new("alice").await;
+        let bob = Node::new("bob").await;
+        let pdb = provider_db(&alice);
+        let a_provider = add_provider(&alice, &pdb).await;
+        add_provider(&bob, &pdb).await;
+
+        // register success.
+        let params = json!([a_provider, PROVIDER.to_hex(), "Alice", "hello"]);
+        let rpcs = call(&alice, &pdb, "domain-register", params).await;
+        assert!(find(&rpcs, "domain-register-success").is_some());
+        let db = alice.db(domain_db).await;
+        let name = Name::get_by_name_provider(&db, "Alice", &a_provider).unwrap();
+        assert!(name.is_ok && name.is_actived);
+
+        // search is case-insensitive, and resolved to the owner.
+        let rpcs = call(
+            &bob,
+            &pdb,
+            "domain-search",
+            json!([PROVIDER.to_hex(), "alice"]),
+        )
+        .await;
+        let res = find(&rpcs, "domain-search").unwrap();
+        assert_eq!(res["result"][0], "Alice");
+        assert_eq!(res["result"][1], alice.pid.to_hex());
+        assert_eq!(res["result"][2], "hello");
+
+        // unknown name.
+        let rpcs = call(
+            &bob,
+            &pdb,
+            "domain-search",
+            json!([PROVIDER.to_hex(), "carol"]),
+        )
+        .await;
+        let res = find(&rpcs, "domain-search").unwrap();
+        assert_eq!(res["result"], json!(["carol"]));
+    }
+
+    #[tokio::test]
+    async fn register_taken_name() {
+        let alice = Node::new("alice").await;
+        let bob = Node::new("bob").await;
+        let pdb = provider_db(&alice);
+        let a_provider = add_provider(&alice, &pdb).await;
+        let b_provider = add_provider(&bob, &pdb).await;
+
+        let params = json!([a_provider, PROVIDER.to_hex(), "alice", ""]);
+        let rpcs = call(&alice, &pdb, "domain-register", params).await;
+        assert!(find(&rpcs, "domain-register-success").is_some());
+
+        // the name is case-insensitive unique.
+        let params = json!([b_provider, PROVIDER.to_hex(), "ALICE", ""]);
+        let rpcs = call(&bob, &pdb, "domain-register", params).await;
+        assert!(find(&rpcs, "domain-register-failure").is_some());
+        let db = bob.db(domain_db).await;
+        assert!(Name::get_by_name_provider(&db, "ALICE", &b_provider).is_err());
+
+        // the forged record is rejected by provider.
+        let key = alice.global.own.read().await.keypair();
+        let record = NameRecord::sign(
+            &key,
+            "forged".to_owned(),
+            "".to_owned(),
+            vec![],
+            1,
+            i64::MAX,
+        );
+        let mut replies = HandleResult::new();
+        provider::handle(
+            bob.pid,
+            LayerPeerEvent::Register(record),
+            &pdb,
+            &mut replies,
+        )
+        .unwrap();
+        let (_, reply) = replies.layers.pop().unwrap();
+        match reply {
+            SendType::Event(_, to, data) => {
+                assert_eq!(to, bob.pid);
+                match bincode::deserialize(&data).unwrap() {
+                    LayerServerEvent::Result(name, ok) => {
+                        assert_eq!(name, "forged");
+                        assert!(!ok);
+                    }
+                    _ => panic!("invalid provider response"),
+                }
+            }
+            _ => panic!("invalid provider response"),
+        }
+    }
+}
@@ -1,6 +1,8 @@
 mod layer;
 mod models;
+#[cfg(test)]
+mod provider;
 
 pub(crate) mod rpc;
-pub(crate) use layer::handle;
+pub(crate) use layer::{handle, name_remind};
 pub(crate) use rpc::new_rpc_handler;
@@ -1,3 +1,5 @@
+use domain_types::NameRecord;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
     primitives::{PeerId, Result},
     rpc::{json, RpcParam},
@@ -13,13 +15,13 @@ pub(crate) struct Provider {
     /// address.
     pub addr: PeerId,
     /// is add ok.
-    is_ok: bool,
+    pub is_ok: bool,
     /// is default.
     is_default: bool,
     /// support request proxy.
-    is_proxy: bool,
+    pub is_proxy: bool,
     /// is actived.
-    is_actived: bool,
+    pub is_actived: bool,
 }
 
 impl Provider {
@@ -190,6 +192,12 @@ pub(crate) struct Name {
     pub is_ok: bool,
     /// is actived.
     pub is_actived: bool,
+    /// signed record version.
+    pub version: i64,
+    /// expire time.
+    pub expire: i64,
+    /// last expire reminded time.
+    reminded: i64,
 }
 
 impl Name {
@@ -200,6 +208,9 @@ impl Name {
             provider,
             is_ok: false,
             is_actived: false,
+            version: 0,
+            expire: 0,
+            reminded: 0,
             id: 0,
         }
     }
@@ -212,11 +223,15 @@ impl Name {
             self.bio,
             self.is_ok,
             self.is_actived,
+            self.expire,
         ])
     }
 
     fn from_values(mut v: Vec<DsValue>) -> Self {
         Self {
+            reminded: v.pop().unwrap().as_i64(),
+            expire: v.pop().unwrap().as_i64(),
+            version: v.pop().unwrap().as_i64(),
             is_actived: v.pop().unwrap().as_bool(),
             is_ok: v.pop().unwrap().as_bool(),
             bio: v.pop().unwrap().as_string(),
@@ -229,7 +244,7 @@ impl Name {
     /// use in rpc when load providers.
     pub fn list(db: &DStorage) -> Result<Vec<Self>> {
         let matrix = db.query(
-            "SELECT id, provider, name, bio, is_ok, is_actived FROM names WHERE is_ok = true",
+            "SELECT id, provider, name, bio, is_ok, is_actived, version, expire, reminded FROM names WHERE is_ok = true",
         )?;
         let mut names = vec![];
         for values in matrix {
@@ -241,7 +256,7 @@ impl Name {
     /// get name register.
     pub fn get_by_provider(db: &DStorage, provider: &i64) -> Result<Vec<Self>> {
         let sql = format!(
-            "SELECT id, provider, name, bio, is_ok, is_actived FROM names WHERE provider = {}",
+            "SELECT id, provider, name, bio, is_ok, is_actived, version, expire, reminded FROM names WHERE provider = {}",
             provider
         );
         let matrix = db.query(&sql)?;
@@ -255,7 +270,7 @@ impl Name {
     /// get name register.
     pub fn get_by_name_provider(db: &DStorage, name: &str, provider: &i64) -> Result<Self> {
         let sql = format!(
-            "SELECT id, provider, name, bio, is_ok, is_actived FROM names WHERE name = '{}' AND provider = {}",
+            "SELECT id, provider, name, bio, is_ok, is_actived, version, expire, reminded FROM names WHERE name = '{}' AND provider = {}",
             name, provider
         );
         let mut matrix = db.query(&sql)?;
@@ -275,18 +290,20 @@ impl Name {
             let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
             self.id = id;
             let sql = format!(
-                "UPDATE names SET bio = '{}', is_ok = {}, is_actived = {} WHERE id = {}",
-                self.bio, self.is_ok, self.is_actived, self.id
+                "UPDATE names SET bio = '{}', is_ok = {}, is_actived = {}, version = {}, expire = {} WHERE id = {}",
+                self.bio, self.is_ok, self.is_actived, self.version, self.expire, self.id
             );
             db.update(&sql)?;
         } else {
             let sql = format!(
-                "INSERT INTO names (provider, name, bio, is_ok, is_actived) VALUES ({}, '{}', '{}', {}, {})",
+                "INSERT INTO names (provider, name, bio, is_ok, is_actived, version, expire, reminded) VALUES ({}, '{}', '{}', {}, {}, {}, {}, 0)",
                 self.provider,
                 self.name,
                 self.bio,
                 self.is_ok,
                 self.is_actived,
+                self.version,
+                self.expire,
             );
             let id = db.insert(&sql)?;
             self.id = id;
@@ -301,6 +318,52 @@ impl Name {
         Ok(())
     }
 
+    /// get name by id.
+    pub fn get(db: &DStorage, id: &i64) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, provider, name, bio, is_ok, is_actived, version, expire, reminded FROM names WHERE id = {}",
+            id
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("name is missing"))
+    }
+
+    /// list the registered names which will expire before the time,
+    /// and not reminded after the remind time.
+    pub fn expiring(db: &DStorage, before: i64, reminded: i64) -> Result<Vec<Self>> {
+        let sql = format!(
+            "SELECT id, provider, name, bio, is_ok, is_actived, version, expire, reminded FROM names WHERE is_ok = true AND expire > 0 AND expire < {} AND reminded < {}",
+            before, reminded
+        );
+        let matrix = db.query(&sql)?;
+        let mut names = vec![];
+        for values in matrix {
+            names.push(Self::from_values(values));
+        }
+        Ok(names)
+    }
+
+    /// update the record version and expire time.
+    pub fn renew(db: &DStorage, id: &i64, version: i64, expire: i64) -> Result<()> {
+        let sql = format!(
+            "UPDATE names SET version = {}, expire = {}, reminded = 0 WHERE id = {}",
+            version, expire, id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    /// mark the name expire had reminded.
+    pub fn reminded(db: &DStorage, id: &i64, time: i64) -> Result<()> {
+        let sql = format!("UPDATE names SET reminded = {} WHERE id = {}", time, id);
+        db.update(&sql)?;
+        Ok(())
+    }
+
     /// active/suspend the name.
     pub fn active(db: &DStorage, id: &i64, active: bool) -> Result<()> {
         let sql = format!(
@@ -311,3 +374,271 @@ impl Name {
         Ok(())
     }
 }
+
+/// Name Record cache Model, the signed record from providers.
+pub(crate) struct Record {
+    /// db auto-increment id.
+    pub id: i64,
+    /// provider database id.
+    pub provider: i64,
+    /// the signed record.
+    pub record: NameRecord,
+    /// cached time.
+    pub datetime: i64,
+}
+
+impl Record {
+    pub fn new(provider: i64, record: NameRecord) -> Self {
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        Self {
+            provider,
+            record,
+            datetime,
+            id: 0,
+        }
+    }
+
+    /// bio & avatar are saved as hex, they are from remote.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        let datetime = v.pop().unwrap().as_i64();
+        let signature = hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]);
+        let expire = v.pop().unwrap().as_i64();
+        let version = v.pop().unwrap().as_i64();
+        let avatar = hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]);
+        let bio = String::from_utf8(hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]))
+            .unwrap_or(String::new());
+        let name = v.pop().unwrap().as_string();
+        let pid = PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default());
+        Self {
+            datetime,
+            record: NameRecord {
+                pid,
+                name,
+                bio,
+                avatar,
+                version,
+                expire,
+                signature,
+            },
+            provider: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    /// list all providers cached records of the name.
+    pub fn list_by_name(db: &DStorage, name: &str) -> Result<Vec<Self>> {
+        let sql = format!(
+            "SELECT id, provider, pid, name, bio, avatar, version, expire, signature, datetime FROM records WHERE name = '{}'",
+            name.to_lowercase()
+        );
+        let matrix = db.query(&sql)?;
+        let mut records = vec![];
+        for values in matrix {
+            records.push(Self::from_values(values));
+        }
+        Ok(records)
+    }
+
+    /// get the cached record version from the provider.
+    pub fn version(db: &DStorage, name: &str, provider: &i64) -> Result<Option<i64>> {
+        let mut matrix = db.query(&format!(
+            "SELECT version FROM records WHERE name = '{}' AND provider = {}",
+            name.to_lowercase(),
+            provider
+        ))?;
+        Ok(matrix.pop().map(|mut v| v.pop().unwrap().as_i64())) // safe unwrap.
+    }
+
+    /// one provider one record of the name.
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let name = self.record.name.to_lowercase();
+        let mut unique_check = db.query(&format!(
+            "SELECT id from records WHERE name = '{}' AND provider = {}",
+            name, self.provider
+        ))?;
+        if unique_check.len() > 0 {
+            let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
+            self.id = id;
+            let sql = format!("UPDATE records SET pid = '{}', bio = '{}', avatar = '{}', version = {}, expire = {}, signature = '{}', datetime = {} WHERE id = {}",
+                self.record.pid.to_hex(),
+                hex::encode(self.record.bio.as_bytes()),
+                hex::encode(&self.record.avatar),
+                self.record.version,
+                self.record.expire,
+                hex::encode(&self.record.signature),
+                self.datetime,
+                self.id
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!("INSERT INTO records (provider, pid, name, bio, avatar, version, expire, signature, datetime) VALUES ({}, '{}', '{}', '{}', '{}', {}, {}, '{}', {})",
+                self.provider,
+                self.record.pid.to_hex(),
+                name,
+                hex::encode(self.record.bio.as_bytes()),
+                hex::encode(&self.record.avatar),
+                self.record.version,
+                self.record.expire,
+                hex::encode(&self.record.signature),
+                self.datetime,
+            );
+            let id = db.insert(&sql)?;
+            self.id = id;
+        }
+        Ok(())
+    }
+
+    /// delete the provider's record of the name.
+    pub fn delete(db: &DStorage, name: &str, provider: &i64) -> Result<()> {
+        let sql = format!(
+            "DELETE FROM records WHERE name = '{}' AND provider = {}",
+            name.to_lowercase(),
+            provider
+        );
+        db.delete(&sql)?;
+        Ok(())
+    }
+}
+
+/// Friend Request by domain name Model, relayed by provider.
+pub(crate) struct Request {
+    /// db auto-increment id.
+    pub id: i64,
+    /// provider database id.
+    pub provider: i64,
+    /// provider's request id, only has in received request.
+    pub rid: i64,
+    /// remote name when is_me, otherwise is requester name.
+    pub name: String,
+    /// request remark.
+    pub remark: String,
+    /// is request by me.
+    pub is_me: bool,
+    /// is accepted.
+    pub is_ok: bool,
+    /// is handled.
+    pub is_over: bool,
+    /// request time.
+    pub datetime: i64,
+}
+
+impl Request {
+    pub fn new(provider: i64, rid: i64, name: String, remark: String, is_me: bool) -> Self {
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        Self {
+            provider,
+            rid,
+            name,
+            remark,
+            is_me,
+            datetime,
+            is_ok: false,
+            is_over: false,
+            id: 0,
+        }
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([
+            self.id,
+            self.provider,
+            self.name,
+            self.remark,
+            self.is_me,
+            self.is_ok,
+            self.is_over,
+            self.datetime,
+        ])
+    }
+
+    /// remark is saved as hex, it is from remote.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            is_over: v.pop().unwrap().as_bool(),
+            is_ok: v.pop().unwrap().as_bool(),
+            is_me: v.pop().unwrap().as_bool(),
+            remark: String::from_utf8(hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]))
+                .unwrap_or(String::new()),
+            name: v.pop().unwrap().as_string(),
+            rid: v.pop().unwrap().as_i64(),
+            provider: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn list(db: &DStorage) -> Result<Vec<Self>> {
+        let matrix = db.query(
+            "SELECT id, provider, rid, name, remark, is_me, is_ok, is_over, datetime FROM requests ORDER BY id DESC",
+        )?;
+        let mut requests = vec![];
+        for values in matrix {
+            requests.push(Self::from_values(values));
+        }
+        Ok(requests)
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<Self> {
+        let mut matrix = db.query(&format!(
+            "SELECT id, provider, rid, name, remark, is_me, is_ok, is_over, datetime FROM requests WHERE id = {}",
+            id
+        ))?;
+        if matrix.len() > 0 {
+            return Ok(Self::from_values(matrix.pop().unwrap())); // safe unwrap()
+        }
+        Err(anyhow!("request is missing"))
+    }
+
+    /// get the latest request of the name in the provider, the name had checked.
+    pub fn get_by_name(db: &DStorage, provider: &i64, name: &str, is_me: bool) -> Result<Self> {
+        let mut matrix = db.query(&format!(
+            "SELECT id, provider, rid, name, remark, is_me, is_ok, is_over, datetime FROM requests WHERE provider = {} AND name = '{}' AND is_me = {} ORDER BY id DESC LIMIT 1",
+            provider, name, is_me
+        ))?;
+        if matrix.len() > 0 {
+            return Ok(Self::from_values(matrix.pop().unwrap())); // safe unwrap()
+        }
+        Err(anyhow!("request is missing"))
+    }
+
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let sql = format!("INSERT INTO requests (provider, rid, name, remark, is_me, is_ok, is_over, datetime) VALUES ({}, {}, '{}', '{}', {}, {}, {}, {})",
+            self.provider,
+            self.rid,
+            self.name,
+            hex::encode(self.remark.as_bytes()),
+            self.is_me,
+            self.is_ok,
+            self.is_over,
+            self.datetime,
+        );
+        self.id = db.insert(&sql)?;
+        Ok(())
+    }
+
+    /// handle the request.
+    pub fn over(&mut self, db: &DStorage, is_ok: bool) -> Result<usize> {
+        self.is_ok = is_ok;
+        self.is_over = true;
+        let sql = format!(
+            "UPDATE requests SET is_ok = {}, is_over = true WHERE id = {}",
+            is_ok, self.id
+        );
+        db.update(&sql)
+    }
+
+    pub fn delete(db: &DStorage, id: &i64) -> Result<usize> {
+        let sql = format!("DELETE FROM requests WHERE id = {}", id);
+        db.delete(&sql)
+    }
+}
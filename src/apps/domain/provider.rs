@@ -0,0 +1,557 @@
+//! ESSE domain name service provider's storage and handler, used by the
+//! standalone provider (`src/domain.rs`) and the tests.
+
+use domain_types::{check_name, LayerPeerEvent, LayerServerEvent, NameRecord, RECORD_GRACE};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::{
+    message::SendType,
+    primitives::{HandleResult, PeerId, Result},
+};
+use tdn_storage::local::{DStorage, DsValue};
+
+/// Provider's name, response in Check.
+const PROVIDER_NAME: &'static str = "domain.esse";
+
+/// Pending transfer valid time (seconds), 7 days.
+const TRANSFER_TTL: i64 = 604800;
+
+/// Pending friend request valid time (seconds), 7 days.
+const REQUEST_TTL: i64 = 604800;
+
+/// Max names one peer can register.
+const MAX_NAMES: i64 = 5;
+
+#[rustfmt::skip]
+const PROVIDER_VERSIONS: [&str; 4] = [
+  "CREATE TABLE IF NOT EXISTS names(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pid TEXT NOT NULL,
+    name TEXT NOT NULL,
+    lname TEXT NOT NULL UNIQUE,
+    bio TEXT NOT NULL,
+    avatar TEXT NOT NULL,
+    is_actived INTEGER NOT NULL,
+    datetime INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    expire INTEGER NOT NULL,
+    signature TEXT NOT NULL);",
+  "CREATE INDEX IF NOT EXISTS names_pid ON names (pid);",
+  "CREATE TABLE IF NOT EXISTS transfers(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    lname TEXT NOT NULL UNIQUE,
+    from_pid TEXT NOT NULL,
+    to_pid TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS requests(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    from_pid TEXT NOT NULL,
+    from_name TEXT NOT NULL,
+    to_name TEXT NOT NULL,
+    remark TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+];
+
+/// Registered name model.
+struct Name {
+    id: i64,
+    pid: PeerId,
+    name: String,
+    bio: String,
+    avatar: Vec<u8>,
+    is_actived: bool,
+    datetime: i64,
+    version: i64,
+    expire: i64,
+    signature: Vec<u8>,
+}
+
+impl Name {
+    fn new(record: NameRecord) -> Self {
+        Self {
+            pid: record.pid,
+            name: record.name,
+            bio: record.bio,
+            avatar: record.avatar,
+            version: record.version,
+            expire: record.expire,
+            signature: record.signature,
+            datetime: now(),
+            is_actived: true,
+            id: 0,
+        }
+    }
+
+    fn to_record(self) -> NameRecord {
+        NameRecord {
+            pid: self.pid,
+            name: self.name,
+            bio: self.bio,
+            avatar: self.avatar,
+            version: self.version,
+            expire: self.expire,
+            signature: self.signature,
+        }
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            signature: hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]),
+            expire: v.pop().unwrap().as_i64(),
+            version: v.pop().unwrap().as_i64(),
+            datetime: v.pop().unwrap().as_i64(),
+            is_actived: v.pop().unwrap().as_bool(),
+            avatar: hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]),
+            bio: String::from_utf8(hex::decode(v.pop().unwrap().as_str()).unwrap_or(vec![]))
+                .unwrap_or(String::new()),
+            name: v.pop().unwrap().as_string(),
+            pid: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    /// name is case-insensitive unique.
+    fn get(db: &DStorage, name: &str) -> Result<Name> {
+        if !check_name(name) {
+            return Err(anyhow!("invalid name"));
+        }
+        let mut matrix = db.query(&format!(
+            "SELECT id, pid, name, bio, avatar, is_actived, datetime, version, expire, signature FROM names WHERE lname = '{}'",
+            name.to_lowercase()
+        ))?;
+        if matrix.len() > 0 {
+            Ok(Self::from_values(matrix.pop().unwrap())) // safe unwrap.
+        } else {
+            Err(anyhow!("missing name"))
+        }
+    }
+
+    fn count(db: &DStorage, pid: &PeerId) -> Result<i64> {
+        let matrix = db.query(&format!(
+            "SELECT id FROM names WHERE pid = '{}'",
+            pid.to_hex()
+        ))?;
+        Ok(matrix.len() as i64)
+    }
+
+    fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let sql = format!("INSERT INTO names (pid, name, lname, bio, avatar, is_actived, datetime, version, expire, signature) VALUES ('{}', '{}', '{}', '{}', '{}', {}, {}, {}, {}, '{}')",
+            self.pid.to_hex(),
+            self.name,
+            self.name.to_lowercase(),
+            hex::encode(self.bio.as_bytes()),
+            hex::encode(&self.avatar),
+            self.is_actived,
+            self.datetime,
+            self.version,
+            self.expire,
+            hex::encode(&self.signature),
+        );
+        self.id = db.insert(&sql)?;
+        Ok(())
+    }
+
+    fn update(&self, db: &DStorage) -> Result<usize> {
+        let sql = format!(
+            "UPDATE names SET pid = '{}', bio = '{}', avatar = '{}', version = {}, expire = {}, signature = '{}' WHERE id = {}",
+            self.pid.to_hex(),
+            hex::encode(self.bio.as_bytes()),
+            hex::encode(&self.avatar),
+            self.version,
+            self.expire,
+            hex::encode(&self.signature),
+            self.id
+        );
+        db.update(&sql)
+    }
+
+    fn active(db: &DStorage, id: &i64, active: bool) -> Result<usize> {
+        let sql = format!("UPDATE names SET is_actived = {} WHERE id = {}", active, id);
+        db.update(&sql)
+    }
+
+    fn delete(&self, db: &DStorage) -> Result<usize> {
+        Transfer::delete(db, &self.name)?;
+        let sql = format!("DELETE FROM names WHERE id = {}", self.id);
+        db.delete(&sql)
+    }
+
+    /// use the new signed record, the version must be increased.
+    fn renew(&mut self, record: NameRecord) {
+        self.pid = record.pid;
+        self.bio = record.bio;
+        self.avatar = record.avatar;
+        self.version = record.version;
+        self.expire = record.expire;
+        self.signature = record.signature;
+    }
+}
+
+/// Pending name transfer model.
+struct Transfer {
+    from: PeerId,
+    to: PeerId,
+    datetime: i64,
+}
+
+impl Transfer {
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            to: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+            from: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+        }
+    }
+
+    fn get(db: &DStorage, name: &str) -> Result<Transfer> {
+        if !check_name(name) {
+            return Err(anyhow!("invalid name"));
+        }
+        let mut matrix = db.query(&format!(
+            "SELECT from_pid, to_pid, datetime FROM transfers WHERE lname = '{}'",
+            name.to_lowercase()
+        ))?;
+        match matrix.pop() {
+            Some(values) => {
+                let t = Self::from_values(values);
+                if now() - t.datetime < TRANSFER_TTL {
+                    Ok(t)
+                } else {
+                    Err(anyhow!("transfer expired"))
+                }
+            }
+            None => Err(anyhow!("missing transfer")),
+        }
+    }
+
+    /// one name only has one pending transfer, the name had checked.
+    fn insert(db: &DStorage, name: &str, from: &PeerId, to: &PeerId) -> Result<()> {
+        Self::delete(db, name)?;
+        let sql = format!(
+            "INSERT INTO transfers (lname, from_pid, to_pid, datetime) VALUES ('{}', '{}', '{}', {})",
+            name.to_lowercase(),
+            from.to_hex(),
+            to.to_hex(),
+            now()
+        );
+        db.insert(&sql)?;
+        Ok(())
+    }
+
+    fn delete(db: &DStorage, name: &str) -> Result<usize> {
+        if !check_name(name) {
+            return Err(anyhow!("invalid name"));
+        }
+        let sql = format!(
+            "DELETE FROM transfers WHERE lname = '{}'",
+            name.to_lowercase()
+        );
+        db.delete(&sql)
+    }
+}
+
+/// Pending friend request model, the requester's identity is hidden
+/// until the request is accepted.
+struct Request {
+    from: PeerId,
+    from_name: String,
+    to_name: String,
+}
+
+impl Request {
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            to_name: v.pop().unwrap().as_string(),
+            from_name: v.pop().unwrap().as_string(),
+            from: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(Default::default()),
+        }
+    }
+
+    fn get(db: &DStorage, id: &i64) -> Result<Request> {
+        let mut matrix = db.query(&format!(
+            "SELECT from_pid, from_name, to_name FROM requests WHERE id = {} AND datetime > {}",
+            id,
+            now() - REQUEST_TTL
+        ))?;
+        if matrix.len() > 0 {
+            Ok(Self::from_values(matrix.pop().unwrap())) // safe unwrap.
+        } else {
+            Err(anyhow!("missing request"))
+        }
+    }
+
+    /// one requester one request to the name, the names had checked.
+    fn insert(
+        db: &DStorage,
+        from: &PeerId,
+        from_name: &str,
+        to_name: &str,
+        remark: &str,
+    ) -> Result<i64> {
+        db.delete(&format!(
+            "DELETE FROM requests WHERE from_pid = '{}' AND to_name = '{}'",
+            from.to_hex(),
+            to_name.to_lowercase()
+        ))?;
+        let sql = format!(
+            "INSERT INTO requests (from_pid, from_name, to_name, remark, datetime) VALUES ('{}', '{}', '{}', '{}', {})",
+            from.to_hex(),
+            from_name,
+            to_name.to_lowercase(),
+            hex::encode(remark.as_bytes()),
+            now()
+        );
+        db.insert(&sql)
+    }
+
+    fn delete(db: &DStorage, id: &i64) -> Result<usize> {
+        db.delete(&format!("DELETE FROM requests WHERE id = {}", id))
+    }
+}
+
+#[inline]
+pub(crate) fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
+
+#[inline]
+fn reply(results: &mut HandleResult, addr: PeerId, event: LayerServerEvent) -> Result<()> {
+    let data = bincode::serialize(&event)?;
+    results.layers.push((0, SendType::Event(0, addr, data)));
+    Ok(())
+}
+
+/// check the record is signed by the sender, and still valid.
+#[inline]
+fn check_record(record: &NameRecord, addr: &PeerId) -> bool {
+    &record.pid == addr && check_name(&record.name) && !record.is_expired(now()) && record.verify()
+}
+
+/// get the name which owned by the peer.
+#[inline]
+fn owned(db: &DStorage, name: &str, addr: &PeerId) -> Option<Name> {
+    match Name::get(db, name) {
+        Ok(n) if &n.pid == addr => Some(n),
+        _ => None,
+    }
+}
+
+pub(crate) fn handle(
+    addr: PeerId,
+    event: LayerPeerEvent,
+    db: &DStorage,
+    results: &mut HandleResult,
+) -> Result<()> {
+    match event {
+        LayerPeerEvent::Check => {
+            reply(
+                results,
+                addr,
+                LayerServerEvent::Status(PROVIDER_NAME.to_owned(), true),
+            )?;
+        }
+        LayerPeerEvent::Register(record) => {
+            let name = record.name.clone();
+            // the name expired and out of grace time, can be registered by others.
+            if let Ok(n) = Name::get(db, &name) {
+                if n.expire + RECORD_GRACE < now() {
+                    n.delete(db)?;
+                }
+            }
+
+            let ok = if !check_record(&record, &addr) || Name::get(db, &name).is_ok() {
+                false
+            } else if Name::count(db, &addr)? >= MAX_NAMES {
+                false
+            } else {
+                let mut n = Name::new(record);
+                n.insert(db).is_ok()
+            };
+            debug!("Register name: {} by {}, {}", name, addr.to_hex(), ok);
+            reply(results, addr, LayerServerEvent::Result(name, ok))?;
+        }
+        LayerPeerEvent::Update(record) => {
+            if let Some(mut n) = owned(db, &record.name, &addr) {
+                if check_record(&record, &addr) && record.version > n.version {
+                    let name = record.name.clone();
+                    n.renew(record);
+                    n.update(db)?;
+                    reply(results, addr, LayerServerEvent::Result(name, true))?;
+                }
+            }
+        }
+        LayerPeerEvent::Renew(record) => {
+            if let Some(mut n) = owned(db, &record.name, &addr) {
+                if check_record(&record, &addr)
+                    && record.version > n.version
+                    && record.expire > n.expire
+                {
+                    let name = record.name.clone();
+                    let expire = record.expire;
+                    n.renew(record);
+                    n.update(db)?;
+                    reply(results, addr, LayerServerEvent::Renewed(name, expire))?;
+                }
+            }
+        }
+        LayerPeerEvent::Transfer(name, to) => match owned(db, &name, &addr) {
+            Some(n) if n.is_actived && n.expire > now() && to != addr => {
+                Transfer::insert(db, &name, &addr, &to)?;
+                reply(
+                    results,
+                    to,
+                    LayerServerEvent::Transfer(name, addr, n.version),
+                )?;
+            }
+            _ => reply(
+                results,
+                addr,
+                LayerServerEvent::Transferred(name, to, false),
+            )?,
+        },
+        LayerPeerEvent::TransferAccept(record) => {
+            let name = record.name.clone();
+            let t = match Transfer::get(db, &name) {
+                Ok(t) if t.to == addr => t,
+                _ => return Ok(()),
+            };
+            let ok = match owned(db, &name, &t.from) {
+                Some(mut n) => {
+                    if check_record(&record, &addr)
+                        && record.version > n.version
+                        && Name::count(db, &addr)? < MAX_NAMES
+                    {
+                        n.renew(record);
+                        n.update(db)?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                None => false,
+            };
+            Transfer::delete(db, &name)?;
+            debug!("Transfer name: {} to {}, {}", name, addr.to_hex(), ok);
+            reply(results, addr, LayerServerEvent::Result(name.clone(), ok))?;
+            reply(
+                results,
+                t.from,
+                LayerServerEvent::Transferred(name, addr, ok),
+            )?;
+        }
+        LayerPeerEvent::TransferReject(name) => {
+            if let Ok(t) = Transfer::get(db, &name) {
+                if t.to == addr {
+                    Transfer::delete(db, &name)?;
+                    reply(
+                        results,
+                        t.from,
+                        LayerServerEvent::Transferred(name, addr, false),
+                    )?;
+                }
+            }
+        }
+        LayerPeerEvent::Search(name) => match Name::get(db, &name) {
+            Ok(n) if n.is_actived && n.expire > now() => {
+                reply(results, addr, LayerServerEvent::Info(n.to_record()))?;
+            }
+            _ => reply(results, addr, LayerServerEvent::None(name))?,
+        },
+        LayerPeerEvent::Request(remote, me, remark) => {
+            // the requester must own the actived name, then relay to the remote.
+            let ok = owned(db, &me, &addr).map(|n| n.is_actived).unwrap_or(false);
+            match Name::get(db, &remote) {
+                Ok(n) if n.is_actived && n.expire > now() && ok && n.pid != addr => {
+                    let rid = Request::insert(db, &addr, &me, &remote, &remark)?;
+                    reply(results, n.pid, LayerServerEvent::Request(rid, me, remark))?;
+                }
+                Ok(n) => {
+                    reply(
+                        results,
+                        addr,
+                        LayerServerEvent::Response(n.pid, remote, false),
+                    )?;
+                }
+                _ => reply(
+                    results,
+                    addr,
+                    LayerServerEvent::Response(PeerId::default(), remote, false),
+                )?,
+            }
+        }
+        LayerPeerEvent::RequestHandle(rid, is_ok) => {
+            let req = Request::get(db, &rid)?;
+            if owned(db, &req.to_name, &addr).is_none() {
+                return Ok(());
+            }
+            Request::delete(db, &rid)?;
+
+            let to_name = Name::get(db, &req.to_name)?.name;
+            reply(
+                results,
+                req.from,
+                LayerServerEvent::Response(addr, to_name, is_ok),
+            )?;
+            // accepted, the requester's identity can be known.
+            if is_ok {
+                reply(
+                    results,
+                    addr,
+                    LayerServerEvent::Response(req.from, req.from_name, true),
+                )?;
+            }
+        }
+        LayerPeerEvent::Suspend(name) => {
+            if let Some(n) = owned(db, &name, &addr) {
+                Name::active(db, &n.id, false)?;
+                reply(results, addr, LayerServerEvent::Actived(name, false))?;
+            }
+        }
+        LayerPeerEvent::Active(name) => {
+            if let Some(n) = owned(db, &name, &addr) {
+                Name::active(db, &n.id, true)?;
+                reply(results, addr, LayerServerEvent::Actived(name, true))?;
+            }
+        }
+        LayerPeerEvent::Delete(name) => {
+            if let Some(n) = owned(db, &name, &addr) {
+                n.delete(db)?;
+                reply(results, addr, LayerServerEvent::Deleted(name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn migrate(db: &DStorage) -> Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS migrates(
+           id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+           version INTEGER NOT NULL);",
+    )?;
+    let mut matrix = db.query("SELECT version FROM migrates")?;
+    let version = if let Some(mut values) = matrix.pop() {
+        values.pop().unwrap().as_i64() as usize // safe unwrap.
+    } else {
+        db.insert("INSERT INTO migrates (version) VALUES (0)")?;
+        0
+    };
+
+    if version != PROVIDER_VERSIONS.len() {
+        for i in &PROVIDER_VERSIONS[version..] {
+            db.execute(i)?;
+        }
+        db.update(&format!(
+            "UPDATE migrates SET version = {}",
+            PROVIDER_VERSIONS.len()
+        ))?;
+    }
+    Ok(())
+}
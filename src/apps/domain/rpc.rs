@@ -1,6 +1,7 @@
-use domain_types::{LayerPeerEvent, DOMAIN_ID};
-use esse_primitives::id_to_str;
+use domain_types::{check_name, LayerPeerEvent, NameRecord, DOMAIN_ID, RECORD_TTL};
+use esse_primitives::{id_from_str, id_to_str};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
     message::SendType,
     primitives::{HandleResult, PeerId},
@@ -10,7 +11,7 @@ use tdn::types::{
 use crate::global::Global;
 use crate::storage::domain_db;
 
-use super::models::{Name, Provider};
+use super::models::{Name, Provider, Record, Request};
 
 #[inline]
 pub(crate) fn add_provider(provider: &Provider) -> RpcParam {
@@ -35,23 +36,93 @@ pub(crate) fn domain_list(providers: &[Provider], names: &[Name]) -> RpcParam {
 }
 
 #[inline]
-pub(crate) fn search_result(pid: &PeerId, name: &str, bio: &str, avatar: &Vec<u8>) -> RpcParam {
+pub(crate) fn search_result(record: &NameRecord) -> RpcParam {
+    rpc_response(0, "domain-search", record_to_rpc(record))
+}
+
+#[inline]
+pub(crate) fn transfer_request(
+    provider: &Provider,
+    name: &str,
+    from: &PeerId,
+    version: i64,
+) -> RpcParam {
     rpc_response(
         0,
-        "domain-search",
-        json!([
-            name,
-            id_to_str(pid),
-            bio,
-            if avatar.len() > 0 {
-                base64::encode(avatar)
-            } else {
-                "".to_owned()
-            }
-        ]),
+        "domain-transfer-request",
+        json!([provider.id, name, id_to_str(from), version]),
+    )
+}
+
+#[inline]
+pub(crate) fn transfer_result(name: &str, to: &PeerId, is_ok: bool) -> RpcParam {
+    rpc_response(
+        0,
+        "domain-transfer-result",
+        json!([name, id_to_str(to), is_ok]),
     )
 }
 
+#[inline]
+pub(crate) fn request_create(request: &Request) -> RpcParam {
+    rpc_response(0, "domain-request-create", json!(request.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn request_update(request: &Request) -> RpcParam {
+    rpc_response(0, "domain-request-update", json!(request.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn name_expiring(name: &Name) -> RpcParam {
+    rpc_response(0, "domain-name-expiring", json!(name.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn search_conflict(name: &str, records: &[&Record]) -> RpcParam {
+    let records: Vec<RpcParam> = records
+        .iter()
+        .map(|r| json!([r.provider, record_to_rpc(&r.record), r.record.version]))
+        .collect();
+    rpc_response(0, "domain-search-conflict", json!([name, records]))
+}
+
+#[inline]
+fn record_to_rpc(record: &NameRecord) -> RpcParam {
+    json!([
+        record.name,
+        id_to_str(&record.pid),
+        record.bio,
+        if record.avatar.len() > 0 {
+            base64::encode(&record.avatar)
+        } else {
+            "".to_owned()
+        }
+    ])
+}
+
+/// resolve the name by all providers cached records. when all valid records
+/// point to the same identity, use the newest one, otherwise it is conflict.
+pub(crate) fn search_resolve(name: &str, records: &[Record]) -> Option<RpcParam> {
+    let start = SystemTime::now();
+    let now = start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64; // safe for all life.
+
+    let valid: Vec<&Record> = records
+        .iter()
+        .filter(|r| !r.record.is_expired(now))
+        .collect();
+    let newest = valid.iter().max_by_key(|r| r.record.version)?;
+
+    if valid.iter().all(|r| r.record.pid == newest.record.pid) {
+        Some(search_result(&newest.record))
+    } else {
+        Some(search_conflict(name, &valid))
+    }
+}
+
 #[inline]
 pub(crate) fn search_none(name: &str) -> RpcParam {
     rpc_response(0, "domain-search", json!([name]))
@@ -143,6 +214,9 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let addr = PeerId::from_hex(params[1].as_str().ok_or(RpcError::ParseError)?)?;
             let name = params[2].as_str().ok_or(RpcError::ParseError)?.to_string();
             let bio = params[3].as_str().ok_or(RpcError::ParseError)?.to_string();
+            if !check_name(&name) {
+                return Ok(HandleResult::rpc(register_failure(&name)));
+            }
 
             // save to db.
             let mut results = HandleResult::new();
@@ -150,19 +224,236 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = domain_db(&state.base, &pid, &db_key)?;
 
-            let me = state.own.read().await.clone_user(&pid)?;
+            let own_lock = state.own.read().await;
+            let me = own_lock.clone_user(&pid)?;
+            let key = own_lock.keypair();
+            drop(own_lock);
+
+            let start = SystemTime::now();
+            let now = start
+                .duration_since(UNIX_EPOCH)
+                .map(|s| s.as_secs())
+                .unwrap_or(0) as i64; // safe for all life.
 
             let mut u = Name::prepare(name, bio, provider);
+            u.version = 1;
+            u.expire = now + RECORD_TTL;
             u.insert(&db)?;
 
-            // send to server.
-            let data = bincode::serialize(&LayerPeerEvent::Register(u.name, u.bio, me.avatar))?;
+            // sign the record, send to server.
+            let record = NameRecord::sign(&key, u.name, u.bio, me.avatar, u.version, u.expire);
+            let data = bincode::serialize(&LayerPeerEvent::Register(record))?;
             let msg = SendType::Event(0, addr, data);
             results.layers.push((DOMAIN_ID, msg));
             Ok(results)
         },
     );
 
+    handler.add_method(
+        "domain-renew",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let me = own_lock.clone_user(&pid)?;
+            let key = own_lock.keypair();
+            drop(own_lock);
+            let db = domain_db(&state.base, &pid, &db_key)?;
+
+            let name = Name::get(&db, &id)?;
+            let provider = Provider::get(&db, &name.provider)?;
+
+            // renew from now when the name is expired.
+            let start = SystemTime::now();
+            let now = start
+                .duration_since(UNIX_EPOCH)
+                .map(|s| s.as_secs())
+                .unwrap_or(0) as i64; // safe for all life.
+            let expire = std::cmp::max(now, name.expire) + RECORD_TTL;
+
+            let record = NameRecord::sign(
+                &key,
+                name.name,
+                name.bio,
+                me.avatar,
+                name.version + 1,
+                expire,
+            );
+            let data = bincode::serialize(&LayerPeerEvent::Renew(record))?;
+            let msg = SendType::Event(0, provider.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-transfer",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let to = id_from_str(params[1].as_str().ok_or(RpcError::ParseError)?)?;
+
+            let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+
+            let name = Name::get(&db, &id)?;
+            let provider = Provider::get(&db, &name.provider)?;
+
+            let data = bincode::serialize(&LayerPeerEvent::Transfer(name.name, to))?;
+            let msg = SendType::Event(0, provider.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-transfer-accept",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let provider = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let name = params[1].as_str().ok_or(RpcError::ParseError)?.to_string();
+            let bio = params[2].as_str().ok_or(RpcError::ParseError)?.to_string();
+            let version = params[3].as_i64().ok_or(RpcError::ParseError)?;
+            if !check_name(&name) {
+                return Err(RpcError::ParseError);
+            }
+
+            let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let me = own_lock.clone_user(&pid)?;
+            let key = own_lock.keypair();
+            drop(own_lock);
+            let db = domain_db(&state.base, &pid, &db_key)?;
+
+            let p = Provider::get(&db, &provider)?;
+
+            let start = SystemTime::now();
+            let now = start
+                .duration_since(UNIX_EPOCH)
+                .map(|s| s.as_secs())
+                .unwrap_or(0) as i64; // safe for all life.
+
+            // it will be ok when provider send result.
+            let mut u = Name::prepare(name, bio, provider);
+            u.version = version + 1;
+            u.expire = now + RECORD_TTL;
+            u.insert(&db)?;
+
+            let record = NameRecord::sign(&key, u.name, u.bio, me.avatar, u.version, u.expire);
+            let data = bincode::serialize(&LayerPeerEvent::TransferAccept(record))?;
+            let msg = SendType::Event(0, p.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-transfer-reject",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let provider = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let name = params[1].as_str().ok_or(RpcError::ParseError)?.to_string();
+
+            let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+            let p = Provider::get(&db, &provider)?;
+
+            let data = bincode::serialize(&LayerPeerEvent::TransferReject(name))?;
+            let msg = SendType::Event(0, p.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-request-list",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+            let requests: Vec<RpcParam> = Request::list(&db)?.iter().map(|r| r.to_rpc()).collect();
+            Ok(HandleResult::rpc(json!(requests)))
+        },
+    );
+
+    handler.add_method(
+        "domain-request",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let remote = params[1].as_str().ok_or(RpcError::ParseError)?.to_string();
+            let remark = params[2].as_str().ok_or(RpcError::ParseError)?.to_string();
+            if !check_name(&remote) {
+                return Err(RpcError::ParseError);
+            }
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+
+            // request with my name, the provider must support proxy.
+            let name = Name::get(&db, &id)?;
+            let provider = Provider::get(&db, &name.provider)?;
+            if !provider.is_proxy {
+                return Err(RpcError::Custom("provider not support request".to_owned()));
+            }
+
+            let mut request = Request::new(provider.id, 0, remote, remark, true);
+            request.insert(&db)?;
+            let mut results = HandleResult::rpc(request.to_rpc());
+
+            let event = LayerPeerEvent::Request(request.name, name.name, request.remark);
+            let data = bincode::serialize(&event)?;
+            let msg = SendType::Event(0, provider.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-request-handle",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let is_ok = params[1].as_bool().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+
+            let mut request = Request::get(&db, &id)?;
+            if request.is_me || request.is_over {
+                return Ok(HandleResult::new());
+            }
+            let provider = Provider::get(&db, &request.provider)?;
+            request.over(&db, is_ok)?;
+            let mut results = HandleResult::rpc(request.to_rpc());
+
+            // when accepted, provider will send the requester's identity.
+            let data = bincode::serialize(&LayerPeerEvent::RequestHandle(request.rid, is_ok))?;
+            let msg = SendType::Event(0, provider.addr, data);
+            results.layers.push((DOMAIN_ID, msg));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "domain-request-delete",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
+            Request::delete(&db, &id)?;
+            Ok(HandleResult::new())
+        },
+    );
+
     handler.add_method(
         "domain-active",
         |params: Vec<RpcParam>, _state: Arc<Global>| async move {
@@ -201,17 +492,36 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
 
     handler.add_method(
         "domain-search",
-        |params: Vec<RpcParam>, _state: Arc<Global>| async move {
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
             let addr = PeerId::from_hex(params[0].as_str().ok_or(RpcError::ParseError)?)?;
             let name = params[1].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            if !check_name(&name) {
+                return Ok(HandleResult::rpc(search_none(&name)));
+            }
 
             let mut results = HandleResult::new();
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = domain_db(&state.base, &pid, &db_key)?;
 
-            // send to server.
-            let event = LayerPeerEvent::Search(name);
-            let data = bincode::serialize(&event)?;
-            let msg = SendType::Event(0, addr, data);
-            results.layers.push((DOMAIN_ID, msg));
+            // response the cached records first.
+            let records = Record::list_by_name(&db, &name)?;
+            if let Some(msg) = search_resolve(&name, &records) {
+                results.rpcs.push(msg);
+            }
+
+            // send to all providers, the selected provider first.
+            let data = bincode::serialize(&LayerPeerEvent::Search(name))?;
+            let mut addrs = vec![addr];
+            for p in Provider::list(&db)? {
+                if p.is_ok && p.is_actived && p.addr != addr {
+                    addrs.push(p.addr);
+                }
+            }
+            for addr in addrs {
+                let msg = SendType::Event(0, addr, data.clone());
+                results.layers.push((DOMAIN_ID, msg));
+            }
             Ok(results)
         },
     );
@@ -1,5 +1,12 @@
+mod mime;
 mod models;
+mod own;
 mod rpc;
+mod share;
+mod transfer;
 
 //pub(crate) use models::{FileDid, RootDirectory};
-pub(crate) use rpc::new_rpc_handler;
+pub(crate) use models::Manifest;
+pub(crate) use own::{file_snapshot, handle as own_handle, FileEvent};
+pub(crate) use rpc::{file_expire, new_rpc_handler};
+pub(crate) use share::{share_fetch, share_manifest, share_read, share_save, share_sync};
@@ -3,14 +3,17 @@ use rand_chacha::{
     ChaChaRng,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    primitives::Result,
+    primitives::{PeerId, Result},
     rpc::{json, RpcParam},
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-#[derive(Serialize, Deserialize, Eq, PartialEq)]
+use super::mime::sniff;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
 pub(crate) enum RootDirectory {
     Star,
     Trash,
@@ -19,6 +22,9 @@ pub(crate) enum RootDirectory {
     Image,
     Music,
     Video,
+    /// smart views, computed from the files metadata.
+    Recent,
+    Large,
 }
 
 impl RootDirectory {
@@ -31,6 +37,8 @@ impl RootDirectory {
             RootDirectory::Image => 4,
             RootDirectory::Music => 5,
             RootDirectory::Video => 6,
+            RootDirectory::Recent => 7,
+            RootDirectory::Large => 8,
         }
     }
 
@@ -43,12 +51,26 @@ impl RootDirectory {
             4 => RootDirectory::Image,
             5 => RootDirectory::Music,
             6 => RootDirectory::Video,
+            7 => RootDirectory::Recent,
+            8 => RootDirectory::Large,
             _ => RootDirectory::Trash,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// default days of the trash files keeping.
+const TRASH_RETENTION: i64 = 30;
+
+/// max count of files in the smart views.
+const VIEW_LIMIT: i64 = 100;
+
+/// the min size of large files view, 10MB.
+const LARGE_SIZE: i64 = 10485760;
+
+/// default count of the file versions keeping.
+const VERSION_KEEP: i64 = 10;
+
+#[derive(Serialize, Deserialize, Default, Eq, PartialEq, Hash, Clone, Copy)]
 pub(crate) struct FileDid([u8; 32]);
 
 impl FileDid {
@@ -86,7 +108,13 @@ pub(crate) struct File {
     pub root: RootDirectory,
     pub name: String,
     pub starred: bool,
-    //pub device: Vec<i64>,
+    /// the devices which hold the content.
+    pub device: Vec<PeerId>,
+    /// content size, MIME type and hash.
+    pub size: i64,
+    pub mime: String,
+    pub hash: String,
+    pub tags: Vec<String>,
     pub datetime: i64,
 }
 
@@ -107,6 +135,11 @@ impl File {
             datetime,
             id: 0,
             starred: false,
+            device: vec![],
+            size: 0,
+            mime: String::new(),
+            hash: String::new(),
+            tags: vec![],
         }
     }
 
@@ -114,6 +147,18 @@ impl File {
         self.did.to_hex()
     }
 
+    /// sniff the content metadata, need save it by insert or set_meta.
+    pub fn content(&mut self, bytes: &[u8]) {
+        self.size = bytes.len() as i64;
+        self.mime = sniff(&self.name, bytes).to_owned();
+        self.hash = blake3::hash(bytes).to_hex().to_string();
+    }
+
+    /// directory has no content, so no devices hold it.
+    pub fn is_dir(&self) -> bool {
+        self.device.is_empty()
+    }
+
     fn _read(&self) -> Vec<u8> {
         todo!()
     }
@@ -131,12 +176,31 @@ impl File {
             self.name,
             self.starred,
             self.datetime,
+            self.device
+                .iter()
+                .map(|d| d.to_hex())
+                .collect::<Vec<String>>(),
+            self.size,
+            self.mime,
+            self.hash,
+            self.tags,
         ])
     }
 
     fn from_values(mut v: Vec<DsValue>) -> Self {
         Self {
             datetime: v.pop().unwrap().as_i64(),
+            tags: Self::split_tags(v.pop().unwrap().as_str()),
+            hash: v.pop().unwrap().as_string(),
+            mime: v.pop().unwrap().as_string(),
+            size: v.pop().unwrap().as_i64(),
+            device: v
+                .pop()
+                .unwrap()
+                .as_str()
+                .split(',')
+                .filter_map(|s| PeerId::from_hex(s).ok())
+                .collect(),
             starred: v.pop().unwrap().as_bool(),
             name: v.pop().unwrap().as_string(),
             root: RootDirectory::from_i64(v.pop().unwrap().as_i64()),
@@ -148,7 +212,7 @@ impl File {
 
     pub fn get(db: &DStorage, id: &i64) -> Result<Self> {
         let sql = format!(
-            "SELECT id, did, parent, root, name, starred, datetime FROM files WHERE id = {}",
+            "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE id = {}",
             id
         );
         let mut matrix = db.query(&sql)?;
@@ -159,15 +223,107 @@ impl File {
         Err(anyhow!("file is missing"))
     }
 
+    pub fn get_by_did(db: &DStorage, did: &FileDid) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE did = '{}'",
+            did.to_hex()
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("file is missing"))
+    }
+
+    /// the parent's did, if root directory, is default.
+    pub fn parent_did(db: &DStorage, parent: &i64) -> Result<FileDid> {
+        if parent == &0 {
+            Ok(FileDid::default())
+        } else {
+            Ok(Self::get(db, parent)?.did)
+        }
+    }
+
+    /// the parent's id by did, if parent is missing, is root directory.
+    pub fn parent_id(db: &DStorage, did: &FileDid) -> i64 {
+        if did == &FileDid::default() {
+            0
+        } else {
+            Self::get_by_did(db, did).map(|f| f.id).unwrap_or(0)
+        }
+    }
+
+    /// the file with same name in the directory, not includes trash.
+    pub fn get_by_name(
+        db: &DStorage,
+        root: &RootDirectory,
+        parent: &i64,
+        name: &str,
+    ) -> Result<Option<Self>> {
+        let sql = format!(
+            "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE root = {} AND parent = {} AND name = '{}'",
+            root.to_i64(),
+            parent,
+            name
+        );
+        let mut matrix = db.query(&sql)?;
+        Ok(matrix.pop().map(|values| Self::from_values(values)))
+    }
+
+    /// the file and all the parents dids.
+    pub fn ancestors(db: &DStorage, id: &i64) -> Result<Vec<FileDid>> {
+        let mut dids = vec![];
+        let mut next = *id;
+        while next != 0 && dids.len() < 256 {
+            let file = if let Ok(file) = Self::get(db, &next) {
+                file
+            } else {
+                break;
+            };
+            dids.push(file.did);
+            next = file.parent;
+        }
+        Ok(dids)
+    }
+
+    /// all files, used in devices sync.
+    pub fn all(db: &DStorage) -> Result<Vec<Self>> {
+        let matrix = db.query(
+            "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files ORDER BY id",
+        )?;
+        let mut files = vec![];
+        for values in matrix {
+            files.push(Self::from_values(values));
+        }
+        Ok(files)
+    }
+
     pub fn list(db: &DStorage, root: &RootDirectory, parent: &i64) -> Result<Vec<Self>> {
-        let sql = if root == &RootDirectory::Star {
+        let sql = if root == &RootDirectory::Recent {
+            format!(
+                "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE device != '' AND root != {} ORDER BY datetime DESC LIMIT {}",
+                RootDirectory::Trash.to_i64(), VIEW_LIMIT
+            )
+        } else if root == &RootDirectory::Large {
+            format!(
+                "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE size >= {} AND root != {} ORDER BY size DESC LIMIT {}",
+                LARGE_SIZE, RootDirectory::Trash.to_i64(), VIEW_LIMIT
+            )
+        } else if root == &RootDirectory::Star {
             format!(
-                "SELECT id, did, parent, root, name, starred, datetime FROM files WHERE starred = true AND root != {}",
+                "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE starred = true AND root != {}",
                 RootDirectory::Trash.to_i64()
             )
+        } else if root == &RootDirectory::Trash && parent == &0 {
+            // the top of trash, includes the files which parent is not in trash.
+            format!(
+                "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE root = {} AND (parent = 0 OR parent NOT IN (SELECT id FROM files WHERE root = {}))",
+                RootDirectory::Trash.to_i64(), RootDirectory::Trash.to_i64()
+            )
         } else {
             format!(
-                "SELECT id, did, parent, root, name, starred, datetime FROM files WHERE parent = {} AND root = {}",
+                "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE parent = {} AND root = {}",
                 parent, root.to_i64()
             )
         };
@@ -180,14 +336,61 @@ impl File {
         Ok(files)
     }
 
+    /// search the files (not in trash) by name, tag, MIME type and date range.
+    /// empty string or 0 is not filtered, MIME type can be the prefix, as `image/`.
+    pub fn search(
+        db: &DStorage,
+        name: &str,
+        tag: &str,
+        mime: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<Self>> {
+        let mut sql = format!(
+            "SELECT id, did, parent, root, name, starred, device, size, mime, hash, tags, datetime FROM files WHERE root != {}",
+            RootDirectory::Trash.to_i64()
+        );
+        if !name.is_empty() {
+            sql.push_str(&format!(" AND name LIKE '%{}%'", name.replace('\'', "''")));
+        }
+        if !tag.is_empty() {
+            sql.push_str(&format!(
+                " AND (',' || tags || ',') LIKE '%,{},%'",
+                tag.replace('\'', "''")
+            ));
+        }
+        if !mime.is_empty() {
+            sql.push_str(&format!(" AND mime LIKE '{}%'", mime.replace('\'', "''")));
+        }
+        if start > 0 {
+            sql.push_str(&format!(" AND datetime >= {}", start));
+        }
+        if end > 0 {
+            sql.push_str(&format!(" AND datetime <= {}", end));
+        }
+        sql.push_str(" ORDER BY datetime DESC");
+
+        let matrix = db.query(&sql)?;
+        let mut files = vec![];
+        for values in matrix {
+            files.push(Self::from_values(values));
+        }
+        Ok(files)
+    }
+
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
         let sql = format!(
-            "INSERT INTO files (did, parent, root, name, starred, device, datetime) VALUES ('{}', {}, {}, '{}', {}, '', {})",
+            "INSERT INTO files (did, parent, root, name, starred, device, size, mime, hash, tags, datetime) VALUES ('{}', {}, {}, '{}', {}, '{}', {}, '{}', '{}', '{}', {})",
             self.did.to_hex(),
             self.parent,
             self.root.to_i64(),
             self.name,
             self.starred,
+            Self::devices(&self.device),
+            self.size,
+            self.mime,
+            self.hash,
+            self.tags.join(","),
             self.datetime,
         );
         let id = db.insert(&sql)?;
@@ -195,31 +398,278 @@ impl File {
         Ok(())
     }
 
+    fn devices(devices: &[PeerId]) -> String {
+        devices
+            .iter()
+            .map(|d| d.to_hex())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    /// tags are saved with comma separated, no empty or repeated tag.
+    pub fn split_tags(s: &str) -> Vec<String> {
+        let mut tags: Vec<String> = vec![];
+        for tag in s.split(',') {
+            let tag = tag.trim().replace('\'', "");
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    pub fn set_tags(&mut self, db: &DStorage, tags: &[String]) -> Result<()> {
+        self.tags = Self::split_tags(&tags.join(","));
+        let sql = format!(
+            "UPDATE files SET tags = '{}', updated = strftime('%s', 'now') WHERE id = {}",
+            self.tags.join(","),
+            self.id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    /// save the content metadata, after the content replaced.
+    pub fn set_meta(&self, db: &DStorage) -> Result<()> {
+        let sql = format!(
+            "UPDATE files SET size = {}, mime = '{}', hash = '{}' WHERE id = {}",
+            self.size, self.mime, self.hash, self.id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    /// add the devices which hold the content.
+    pub fn add_device(&mut self, db: &DStorage, devices: &[PeerId]) -> Result<()> {
+        let mut changed = false;
+        for device in devices {
+            if !self.device.contains(device) {
+                self.device.push(*device);
+                changed = true;
+            }
+        }
+        if changed {
+            let sql = format!(
+                "UPDATE files SET device = '{}' WHERE id = {}",
+                Self::devices(&self.device),
+                self.id
+            );
+            db.update(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// reset the devices which hold the content, when content replaced.
+    pub fn set_device(&mut self, db: &DStorage, devices: Vec<PeerId>) -> Result<()> {
+        self.device = devices;
+        let sql = format!(
+            "UPDATE files SET device = '{}' WHERE id = {}",
+            Self::devices(&self.device),
+            self.id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
     pub fn star(db: &DStorage, id: &i64, starred: bool) -> Result<()> {
-        let sql = format!("UPDATE files SET starred = {} WHERE id = {}", starred, id);
+        let sql = format!(
+            "UPDATE files SET starred = {}, updated = strftime('%s', 'now') WHERE id = {}",
+            starred, id
+        );
         db.update(&sql)?;
         Ok(())
     }
 
-    pub fn trash(db: &DStorage, id: &i64) -> Result<()> {
+    /// the directory and all the descendants ids.
+    pub fn tree(db: &DStorage, id: &i64) -> Result<Vec<i64>> {
+        let mut ids = vec![*id];
+        let mut parents = vec![*id];
+        while let Some(parent) = parents.pop() {
+            let matrix = db.query(&format!("SELECT id FROM files WHERE parent = {}", parent))?;
+            for mut values in matrix {
+                let id = values.pop().unwrap().as_i64(); // safe unwrap.
+                ids.push(id);
+                parents.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn join(ids: &[i64]) -> String {
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// move the file or directory (with all descendants) to trash.
+    pub fn trash(db: &DStorage, id: &i64, now: i64) -> Result<()> {
+        let ids = Self::tree(db, id)?;
         let sql = format!(
-            "UPDATE files SET root = {} WHERE id = {}",
+            "UPDATE files SET origin = root, trashed = {}, root = {}, updated = strftime('%s', 'now') WHERE id IN ({}) AND root != {}",
+            now,
+            RootDirectory::Trash.to_i64(),
+            Self::join(&ids),
             RootDirectory::Trash.to_i64(),
-            id
         );
         db.update(&sql)?;
         Ok(())
     }
 
-    pub fn delete(db: &DStorage, id: &i64) -> Result<()> {
-        let sql = format!("DELETE FROM files WHERE id = {}", id);
+    /// restore the file or directory from trash, the descendants which trashed
+    /// at same time will restore together.
+    pub fn restore(db: &DStorage, id: &i64) -> Result<Self> {
+        let file = Self::get(db, id)?;
+        if file.root != RootDirectory::Trash {
+            return Err(anyhow!("file is not in trash"));
+        }
+        let mut matrix = db.query(&format!("SELECT trashed FROM files WHERE id = {}", id))?;
+        let trashed = matrix.pop().unwrap().pop().unwrap().as_i64(); // safe unwrap.
+
+        // if parent is trashed or deleted, restore to the top of root directory.
+        if file.parent != 0 {
+            let is_ok = Self::get(db, &file.parent)
+                .map(|p| p.root != RootDirectory::Trash)
+                .unwrap_or(false);
+            if !is_ok {
+                db.update(&format!("UPDATE files SET parent = 0 WHERE id = {}", id))?;
+            }
+        }
+
+        let ids = Self::tree(db, id)?;
+        let sql = format!(
+            "UPDATE files SET root = CASE WHEN origin > {} THEN origin ELSE {} END, trashed = 0, updated = strftime('%s', 'now') WHERE id IN ({}) AND root = {} AND trashed = {}",
+            RootDirectory::Trash.to_i64(),
+            RootDirectory::Document.to_i64(),
+            Self::join(&ids),
+            RootDirectory::Trash.to_i64(),
+            trashed,
+        );
+        db.update(&sql)?;
+
+        Self::get(db, id)
+    }
+
+    /// delete the file or directory (with all descendants), and keep the
+    /// tombstones, return the deleted files storage names.
+    pub fn delete(db: &DStorage, id: &i64) -> Result<Vec<String>> {
+        let ids = Self::join(&Self::tree(db, id)?);
+        let matrix = db.query(&format!("SELECT did FROM files WHERE id IN ({})", ids))?;
+        let mut names = vec![];
+        for mut values in matrix {
+            let name = values.pop().unwrap().as_string(); // safe unwrap.
+            Self::tombstone(db, &name)?;
+            names.push(name);
+        }
+
+        let sql = format!("DELETE FROM files WHERE id IN ({})", ids);
         db.delete(&sql)?;
+        Ok(names)
+    }
+
+    /// the trash files which keeping time is over retention.
+    pub fn expired(db: &DStorage, now: i64) -> Result<Vec<i64>> {
+        let retention = Self::retention(db)?;
+        if retention <= 0 {
+            return Ok(vec![]);
+        }
+
+        let matrix = db.query(&format!(
+            "SELECT id FROM files WHERE root = {} AND trashed > 0 AND trashed < {}",
+            RootDirectory::Trash.to_i64(),
+            now - retention * 86400
+        ))?;
+        let mut ids = vec![];
+        for mut values in matrix {
+            ids.push(values.pop().unwrap().as_i64()); // safe unwrap.
+        }
+        Ok(ids)
+    }
+
+    /// days of trash files keeping, 0 is keep forever.
+    pub fn retention(db: &DStorage) -> Result<i64> {
+        let mut matrix = db.query("SELECT retention FROM trash")?;
+        if let Some(mut values) = matrix.pop() {
+            Ok(values.pop().unwrap().as_i64()) // safe unwrap.
+        } else {
+            Ok(TRASH_RETENTION)
+        }
+    }
+
+    pub fn set_retention(db: &DStorage, days: i64) -> Result<()> {
+        db.update(&format!("UPDATE trash SET retention = {}", days))?;
+        Ok(())
+    }
+
+    /// the deleted file's did, so it not recreated by other devices.
+    fn tombstone(db: &DStorage, did: &str) -> Result<()> {
+        let sql = format!(
+            "INSERT OR IGNORE INTO tombstones (did, datetime) VALUES ('{}', strftime('%s', 'now'))",
+            did
+        );
+        db.insert(&sql)?;
+        Ok(())
+    }
+
+    pub fn is_tombstone(db: &DStorage, did: &FileDid) -> Result<bool> {
+        let sql = format!("SELECT id FROM tombstones WHERE did = '{}'", did.to_hex());
+        let matrix = db.query(&sql)?;
+        Ok(matrix.len() > 0)
+    }
+
+    /// all deleted files' dids.
+    pub fn tombstones(db: &DStorage) -> Result<Vec<FileDid>> {
+        let matrix = db.query("SELECT did FROM tombstones")?;
+        let mut dids = vec![];
+        for mut values in matrix {
+            dids.push(FileDid::from_hex(&values.pop().unwrap().as_string())?); // safe unwrap.
+        }
+        Ok(dids)
+    }
+
+    /// the last changed time and trashed time, the latest changed wins when sync.
+    pub fn state(db: &DStorage, id: &i64) -> Result<(i64, i64)> {
+        let sql = format!("SELECT updated, trashed FROM files WHERE id = {}", id);
+        let mut matrix = db.query(&sql)?;
+        if let Some(mut values) = matrix.pop() {
+            let trashed = values.pop().unwrap().as_i64(); // safe unwrap.
+            let updated = values.pop().unwrap().as_i64(); // safe unwrap.
+            Ok((updated, trashed))
+        } else {
+            Err(anyhow!("file is missing"))
+        }
+    }
+
+    pub fn set_state(db: &DStorage, id: &i64, updated: i64, trashed: i64) -> Result<()> {
+        let sql = format!(
+            "UPDATE files SET updated = {}, trashed = {} WHERE id = {}",
+            updated, trashed, id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    /// save the place, name, star and tags which changed later in other device.
+    pub fn apply(&self, db: &DStorage, updated: i64, trashed: i64) -> Result<()> {
+        let sql = format!(
+            "UPDATE files SET origin = CASE WHEN root != {} THEN root ELSE origin END, parent = {}, root = {}, name = '{}', starred = {}, tags = '{}', updated = {}, trashed = {} WHERE id = {}",
+            RootDirectory::Trash.to_i64(),
+            self.parent,
+            self.root.to_i64(),
+            self.name,
+            self.starred,
+            self.tags.join(","),
+            updated,
+            trashed,
+            self.id
+        );
+        db.update(&sql)?;
         Ok(())
     }
 
     pub fn update(&self, db: &DStorage) -> Result<()> {
         let sql = format!(
-            "UPDATE files SET parent = {}, root = {}, name = '{}' WHERE id = {}",
+            "UPDATE files SET parent = {}, root = {}, name = '{}', updated = strftime('%s', 'now') WHERE id = {}",
             self.parent,
             self.root.to_i64(),
             self.name,
@@ -229,3 +679,391 @@ impl File {
         Ok(())
     }
 }
+
+/// the file's prior content version.
+pub(crate) struct Version {
+    pub id: i64,
+    pub did: FileDid,
+    pub hash: String,
+    pub size: i64,
+    pub device: PeerId,
+    pub datetime: i64,
+}
+
+impl Version {
+    pub fn new(did: FileDid, hash: String, size: i64, device: PeerId, datetime: i64) -> Self {
+        Self {
+            did,
+            hash,
+            size,
+            device,
+            datetime,
+            id: 0,
+        }
+    }
+
+    /// the version content is stored with file did and content hash.
+    pub fn storage_name(&self) -> String {
+        format!("{}-{}", self.did.to_hex(), self.hash)
+    }
+
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([
+            self.id,
+            self.hash,
+            self.size,
+            self.device.to_hex(),
+            self.datetime,
+        ])
+    }
+
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            device: PeerId::from_hex(v.pop().unwrap().as_str()).unwrap_or(PeerId::default()),
+            size: v.pop().unwrap().as_i64(),
+            hash: v.pop().unwrap().as_string(),
+            did: FileDid::from_hex(&v.pop().unwrap().as_string()).unwrap_or(Default::default()),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn get(db: &DStorage, id: &i64) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, did, hash, size, device, datetime FROM versions WHERE id = {}",
+            id
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("version is missing"))
+    }
+
+    pub fn get_by_hash(db: &DStorage, did: &FileDid, hash: &str) -> Result<Option<Self>> {
+        let sql = format!(
+            "SELECT id, did, hash, size, device, datetime FROM versions WHERE did = '{}' AND hash = '{}'",
+            did.to_hex(),
+            hash
+        );
+        let mut matrix = db.query(&sql)?;
+        Ok(matrix.pop().map(|values| Self::from_values(values)))
+    }
+
+    /// the file's versions, the newest first.
+    pub fn list(db: &DStorage, did: &FileDid) -> Result<Vec<Self>> {
+        let sql = format!(
+            "SELECT id, did, hash, size, device, datetime FROM versions WHERE did = '{}' ORDER BY datetime DESC, id DESC",
+            did.to_hex()
+        );
+        let matrix = db.query(&sql)?;
+        let mut versions = vec![];
+        for values in matrix {
+            versions.push(Self::from_values(values));
+        }
+        Ok(versions)
+    }
+
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO versions (did, hash, size, device, datetime) VALUES ('{}', '{}', {}, '{}', {})",
+            self.did.to_hex(),
+            self.hash,
+            self.size,
+            self.device.to_hex(),
+            self.datetime,
+        );
+        let id = db.insert(&sql)?;
+        self.id = id;
+        Ok(())
+    }
+
+    /// same content archived again, update it to newest.
+    pub fn touch(&mut self, db: &DStorage, device: PeerId, datetime: i64) -> Result<()> {
+        self.device = device;
+        self.datetime = datetime;
+        let sql = format!(
+            "UPDATE versions SET device = '{}', datetime = {} WHERE id = {}",
+            self.device.to_hex(),
+            self.datetime,
+            self.id
+        );
+        db.update(&sql)?;
+        Ok(())
+    }
+
+    pub fn delete(db: &DStorage, id: &i64) -> Result<()> {
+        let sql = format!("DELETE FROM versions WHERE id = {}", id);
+        db.delete(&sql)?;
+        Ok(())
+    }
+
+    /// the versions which over the retention, if did is none, check all files.
+    pub fn expired(db: &DStorage, did: Option<&FileDid>, now: i64) -> Result<Vec<Self>> {
+        let (keep, days) = Self::retention(db)?;
+        let sql = if let Some(did) = did {
+            format!(
+                "SELECT id, did, hash, size, device, datetime FROM versions WHERE did = '{}' ORDER BY datetime DESC, id DESC",
+                did.to_hex()
+            )
+        } else {
+            "SELECT id, did, hash, size, device, datetime FROM versions ORDER BY did, datetime DESC, id DESC".to_owned()
+        };
+
+        let matrix = db.query(&sql)?;
+        let mut versions = vec![];
+        let mut last = FileDid::default();
+        let mut count = 0;
+        for values in matrix {
+            let version = Self::from_values(values);
+            if version.did != last {
+                last = version.did;
+                count = 0;
+            }
+            count += 1;
+            if (keep > 0 && count > keep) || (days > 0 && version.datetime < now - days * 86400) {
+                versions.push(version);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// the count and days of versions keeping, 0 is no limit.
+    pub fn retention(db: &DStorage) -> Result<(i64, i64)> {
+        let mut matrix = db.query("SELECT keep, days FROM history")?;
+        if let Some(mut values) = matrix.pop() {
+            let days = values.pop().unwrap().as_i64(); // safe unwrap.
+            let keep = values.pop().unwrap().as_i64(); // safe unwrap.
+            Ok((keep, days))
+        } else {
+            Ok((VERSION_KEEP, 0))
+        }
+    }
+
+    pub fn set_retention(db: &DStorage, keep: i64, days: i64) -> Result<()> {
+        db.update(&format!(
+            "UPDATE history SET keep = {}, days = {}",
+            keep, days
+        ))?;
+        Ok(())
+    }
+}
+
+/// Shared files manifest, saved in message's content.
+/// format: `sync;;owner;;did;;name;;did:parent:is_dir:name;;...`
+/// the file's is_dir is `0.hash`, and the shared file's hash is an item
+/// of itself without parent, so old versions still read it.
+pub(crate) struct Manifest {
+    pub sync: bool,
+    pub owner: PeerId,
+    pub did: FileDid,
+    pub name: String,
+    /// files in the shared directory: did, parent did, is directory, name.
+    pub files: Vec<(FileDid, FileDid, bool, String)>,
+    /// the files content hash.
+    pub hashes: HashMap<FileDid, String>,
+}
+
+impl Manifest {
+    /// build the manifest of the file or directory, not includes the trash files.
+    pub fn build(db: &DStorage, owner: PeerId, id: &i64, sync: bool) -> Result<Self> {
+        let root = File::get(db, id)?;
+        if root.root == RootDirectory::Trash {
+            return Err(anyhow!("file is in trash"));
+        }
+
+        let mut files = vec![];
+        let mut hashes = HashMap::new();
+        if !root.is_dir() && !root.hash.is_empty() {
+            hashes.insert(root.did, root.hash.clone());
+        }
+        for fid in File::tree(db, id)?.iter().skip(1) {
+            let file = File::get(db, fid)?;
+            if file.root == RootDirectory::Trash {
+                continue;
+            }
+            if !file.is_dir() && !file.hash.is_empty() {
+                hashes.insert(file.did, file.hash.clone());
+            }
+            let parent = File::parent_did(db, &file.parent)?;
+            files.push((
+                file.did,
+                parent,
+                file.is_dir(),
+                file.name.replace(";;", ";"),
+            ));
+        }
+
+        Ok(Self {
+            sync,
+            owner,
+            files,
+            hashes,
+            did: root.did,
+            name: root.name.replace(";;", ";"),
+        })
+    }
+
+    /// the prefix of manifest content, used to find the shared messages.
+    pub fn prefix(owner: &PeerId, did: &FileDid) -> String {
+        format!("_;;{};;{};;", owner.to_hex(), did.to_hex())
+    }
+
+    pub fn to_content(&self) -> String {
+        let mut content = format!(
+            "{};;{};;{};;{}",
+            self.sync as u8,
+            self.owner.to_hex(),
+            self.did.to_hex(),
+            self.name
+        );
+        if let Some(hash) = self.hashes.get(&self.did) {
+            content.push_str(&format!(
+                ";;{}:{}:0.{}:",
+                self.did.to_hex(),
+                FileDid::default().to_hex(),
+                hash
+            ));
+        }
+        for (did, parent, is_dir, name) in &self.files {
+            let kind = match self.hashes.get(did) {
+                Some(hash) if !is_dir => format!("0.{}", hash),
+                _ => (*is_dir as u8).to_string(),
+            };
+            content.push_str(&format!(
+                ";;{}:{}:{}:{}",
+                did.to_hex(),
+                parent.to_hex(),
+                kind,
+                name
+            ));
+        }
+        content
+    }
+
+    pub fn from_content(content: &str) -> Result<Self> {
+        let mut items = content.split(";;");
+        let sync = items.next().ok_or(anyhow!("manifest is invalid"))? == "1";
+        let owner = PeerId::from_hex(items.next().ok_or(anyhow!("manifest is invalid"))?)?;
+        let did = FileDid::from_hex(items.next().ok_or(anyhow!("manifest is invalid"))?)?;
+        let name = items
+            .next()
+            .ok_or(anyhow!("manifest is invalid"))?
+            .to_owned();
+
+        let mut files = vec![];
+        let mut hashes = HashMap::new();
+        for item in items {
+            let mut values = item.splitn(4, ':');
+            let fdid = FileDid::from_hex(values.next().ok_or(anyhow!("manifest is invalid"))?)?;
+            let parent = FileDid::from_hex(values.next().ok_or(anyhow!("manifest is invalid"))?)?;
+            let kind = values.next().ok_or(anyhow!("manifest is invalid"))?;
+            let is_dir = match kind.split_once('.') {
+                Some((is_dir, hash)) => {
+                    hashes.insert(fdid, hash.to_owned());
+                    is_dir == "1"
+                }
+                None => kind == "1",
+            };
+            let name = values
+                .next()
+                .ok_or(anyhow!("manifest is invalid"))?
+                .to_owned();
+            if fdid != did {
+                files.push((fdid, parent, is_dir, name));
+            }
+        }
+
+        Ok(Self {
+            sync,
+            owner,
+            did,
+            name,
+            files,
+            hashes,
+        })
+    }
+
+    /// the file's name in the manifest.
+    pub fn file_name(&self, did: &FileDid) -> Option<&str> {
+        if &self.did == did {
+            return Some(&self.name);
+        }
+        self.files
+            .iter()
+            .find(|(fdid, _, is_dir, _)| fdid == did && !is_dir)
+            .map(|(_, _, _, name)| name.as_str())
+    }
+}
+
+/// Shared file or directory, everyone has the manifest can fetch the files.
+pub(crate) struct Share {
+    pub id: i64,
+    pub did: FileDid,
+    pub sync: bool,
+    pub datetime: i64,
+}
+
+impl Share {
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            sync: v.pop().unwrap().as_bool(),
+            did: FileDid::from_hex(&v.pop().unwrap().as_string()).unwrap_or(Default::default()),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn get(db: &DStorage, did: &FileDid) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, did, sync, datetime FROM shares WHERE did = '{}'",
+            did.to_hex()
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            let values = matrix.pop().unwrap(); // safe unwrap()
+            return Ok(Self::from_values(values));
+        }
+        Err(anyhow!("share is missing"))
+    }
+
+    /// share the file, if shared, the sync will keep once enabled.
+    pub fn share(db: &DStorage, did: &FileDid, sync: bool, datetime: i64) -> Result<()> {
+        if let Ok(share) = Self::get(db, did) {
+            if sync && !share.sync {
+                db.update(&format!(
+                    "UPDATE shares SET sync = true WHERE id = {}",
+                    share.id
+                ))?;
+            }
+        } else {
+            db.insert(&format!(
+                "INSERT INTO shares (did, sync, datetime) VALUES ('{}', {}, {})",
+                did.to_hex(),
+                sync,
+                datetime
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// the synced shares in the dids.
+    pub fn syncs(db: &DStorage, dids: &[FileDid]) -> Result<Vec<FileDid>> {
+        let mut shares = vec![];
+        for did in dids {
+            if let Ok(share) = Self::get(db, did) {
+                if share.sync {
+                    shares.push(share.did);
+                }
+            }
+        }
+        Ok(shares)
+    }
+
+    pub fn delete(db: &DStorage, did: &FileDid) -> Result<()> {
+        let sql = format!("DELETE FROM shares WHERE did = '{}'", did.to_hex());
+        db.delete(&sql)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tdn::types::{
+    message::SendType,
+    primitives::{HandleResult, PeerId, Result},
+};
+use tdn_storage::local::DStorage;
+
+use crate::global::Global;
+use crate::own::OwnEvent;
+use crate::storage::{file_db, has_file, read_db_file, write_file};
+
+use super::models::{File, FileDid, RootDirectory};
+use super::rpc;
+
+/// File's sync information.
+/// params: did, parent did, root, name, starred, devices, datetime, metadata.
+type FileInfo = (
+    FileDid,
+    FileDid,
+    RootDirectory,
+    String,
+    bool,
+    Vec<PeerId>,
+    i64,
+    FileMeta,
+);
+
+/// File's sync state.
+/// params: last changed time, trashed time.
+type FileState = (i64, i64);
+
+/// File's metadata.
+/// params: size, MIME type, hash, tags.
+type FileMeta = (i64, String, String, Vec<String>);
+
+/// File app's own devices sync Event.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum FileEvent {
+    /// create file or directory.
+    Create(FileInfo),
+    /// update file.
+    /// params: did, parent did, root, name.
+    Update(FileDid, FileDid, RootDirectory, String),
+    /// the content metadata changed.
+    /// params: did, size, MIME type, hash.
+    Meta(FileDid, i64, String, String),
+    /// update the file's tags.
+    Tags(FileDid, Vec<String>),
+    /// star or unstar file.
+    Star(FileDid, bool),
+    /// move to trash.
+    /// params: did, trash time.
+    Trash(FileDid, i64),
+    /// restore from trash.
+    Restore(FileDid),
+    /// delete file or directory.
+    Delete(FileDid),
+    /// the device holds the file content.
+    Backup(FileDid, PeerId),
+    /// the file content replaced in the device, others copies become a version.
+    Replace(FileDid, PeerId),
+    /// all files and the deleted files when devices connected.
+    /// params: files with state, deleted files' did.
+    Snapshot(Vec<(FileInfo, FileState)>, Vec<FileDid>),
+    /// fetch the file content.
+    /// params: did, pin in local.
+    Fetch(FileDid, bool),
+    /// file content, if missing, content is empty.
+    /// params: did, pin in local, content.
+    Content(FileDid, bool, Vec<u8>),
+}
+
+impl FileEvent {
+    fn info(db: &DStorage, file: &File) -> Result<FileInfo> {
+        Ok((
+            file.did,
+            File::parent_did(db, &file.parent)?,
+            file.root,
+            file.name.clone(),
+            file.starred,
+            file.device.clone(),
+            file.datetime,
+            (
+                file.size,
+                file.mime.clone(),
+                file.hash.clone(),
+                file.tags.clone(),
+            ),
+        ))
+    }
+
+    pub fn create(db: &DStorage, file: &File) -> Result<Self> {
+        Ok(FileEvent::Create(Self::info(db, file)?))
+    }
+
+    pub fn meta(file: &File) -> Self {
+        FileEvent::Meta(file.did, file.size, file.mime.clone(), file.hash.clone())
+    }
+
+    pub fn update(db: &DStorage, file: &File) -> Result<Self> {
+        Ok(FileEvent::Update(
+            file.did,
+            File::parent_did(db, &file.parent)?,
+            file.root,
+            file.name.clone(),
+        ))
+    }
+
+    /// broadcast the event to other online devices.
+    pub async fn broadcast(self, global: &Arc<Global>, results: &mut HandleResult) -> Result<()> {
+        global
+            .own
+            .read()
+            .await
+            .broadcast(&OwnEvent::File(self), results)
+    }
+}
+
+fn from_info(db: &DStorage, info: FileInfo) -> File {
+    let (did, pdid, root, name, starred, device, datetime, meta) = info;
+    let (size, mime, hash, tags) = meta;
+    File {
+        did,
+        root,
+        name,
+        starred,
+        device,
+        datetime,
+        size,
+        mime,
+        hash,
+        tags,
+        id: 0,
+        parent: File::parent_id(db, &pdid),
+    }
+}
+
+/// all files metadata, send to the connected device.
+pub(crate) async fn file_snapshot(global: &Arc<Global>) -> Result<FileEvent> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    let mut infos = vec![];
+    for file in File::all(&db)? {
+        infos.push((FileEvent::info(&db, &file)?, File::state(&db, &file.id)?));
+    }
+    Ok(FileEvent::Snapshot(infos, File::tombstones(&db)?))
+}
+
+/// fetch the file content from online device which holds it.
+pub(super) async fn fetch(
+    global: &Arc<Global>,
+    file: &File,
+    pin: bool,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let online = global.own.read().await.online_devices();
+    let aid = online
+        .iter()
+        .find(|aid| file.device.contains(aid))
+        .ok_or(anyhow!("no online device holds the file"))?;
+
+    let data = bincode::serialize(&OwnEvent::File(FileEvent::Fetch(file.did, pin)))?;
+    results.owns.push(SendType::Event(0, *aid, data));
+    Ok(())
+}
+
+/// pin the file content in local, and broadcast to other devices.
+pub(super) async fn backup(
+    global: &Arc<Global>,
+    db: &DStorage,
+    file: &mut File,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let assist = global.own.read().await.current_assist()?;
+    file.add_device(db, &[assist])?;
+    FileEvent::Backup(file.did, assist)
+        .broadcast(global, results)
+        .await
+}
+
+pub(crate) async fn handle(
+    aid: PeerId,
+    event: FileEvent,
+    global: &Arc<Global>,
+) -> Result<HandleResult> {
+    let mut results = HandleResult::new();
+
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    match event {
+        FileEvent::Create(info) => {
+            if File::is_tombstone(&db, &info.0)? {
+                // deleted before the create received.
+            } else if let Ok(mut file) = File::get_by_did(&db, &info.0) {
+                file.add_device(&db, &info.5)?;
+            } else {
+                let mut file = from_info(&db, info);
+                file.insert(&db)?;
+                results.rpcs.push(rpc::file_create(&file));
+            }
+        }
+        FileEvent::Update(did, pdid, root, name) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            file.parent = File::parent_id(&db, &pdid);
+            file.root = root;
+            file.name = name;
+            file.update(&db)?;
+            results.rpcs.push(rpc::file_update(&file));
+        }
+        FileEvent::Meta(did, size, mime, hash) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            file.size = size;
+            file.mime = mime;
+            file.hash = hash;
+            file.set_meta(&db)?;
+            results.rpcs.push(rpc::file_update(&file));
+        }
+        FileEvent::Tags(did, tags) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            file.set_tags(&db, &tags)?;
+            results.rpcs.push(rpc::file_update(&file));
+        }
+        FileEvent::Star(did, starred) => {
+            let file = File::get_by_did(&db, &did)?;
+            File::star(&db, &file.id, starred)?;
+        }
+        FileEvent::Trash(did, now) => {
+            let file = File::get_by_did(&db, &did)?;
+            File::trash(&db, &file.id, now)?;
+        }
+        FileEvent::Restore(did) => {
+            let file = File::get_by_did(&db, &did)?;
+            let file = File::restore(&db, &file.id)?;
+            results.rpcs.push(rpc::file_update(&file));
+        }
+        FileEvent::Delete(did) => {
+            if let Ok(file) = File::get_by_did(&db, &did) {
+                rpc::delete(&global.base, &pid, &db, &file.id).await?;
+                results.rpcs.push(rpc::file_delete(file.id));
+            }
+        }
+        FileEvent::Backup(did, device) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            file.add_device(&db, &[device])?;
+        }
+        FileEvent::Replace(did, device) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            rpc::archive(global, &db, &file).await?;
+            file.set_device(&db, vec![device])?;
+            results.rpcs.push(rpc::file_update(&file));
+        }
+        FileEvent::Snapshot(infos, deleted) => {
+            // the files deleted in other device, delete them here too.
+            for did in deleted {
+                if let Ok(file) = File::get_by_did(&db, &did) {
+                    rpc::delete(&global.base, &pid, &db, &file.id).await?;
+                    results.rpcs.push(rpc::file_delete(file.id));
+                }
+            }
+
+            // insert the missing files first, then link the parents.
+            let mut news = vec![];
+            let mut changes = vec![];
+            for (info, (updated, trashed)) in infos {
+                if File::is_tombstone(&db, &info.0)? {
+                    continue;
+                }
+                if let Ok(mut file) = File::get_by_did(&db, &info.0) {
+                    file.add_device(&db, &info.5)?;
+                    // changed offline, the latest one wins.
+                    let (local, _) = File::state(&db, &file.id)?;
+                    if updated > local {
+                        changes.push((file, info, updated, trashed));
+                    }
+                } else {
+                    let pdid = info.1;
+                    let mut file = from_info(&db, info);
+                    file.insert(&db)?;
+                    news.push((file, pdid, updated, trashed));
+                }
+            }
+            for (mut file, pdid, updated, trashed) in news {
+                let parent = File::parent_id(&db, &pdid);
+                if file.parent != parent {
+                    file.parent = parent;
+                    file.update(&db)?;
+                }
+                File::set_state(&db, &file.id, updated, trashed)?;
+                results.rpcs.push(rpc::file_create(&file));
+            }
+            for (mut file, info, updated, trashed) in changes {
+                let (_, pdid, root, name, starred, _, _, (_, _, _, tags)) = info;
+                file.parent = File::parent_id(&db, &pdid);
+                file.root = root;
+                file.name = name;
+                file.starred = starred;
+                file.tags = tags;
+                file.apply(&db, updated, trashed)?;
+                results.rpcs.push(rpc::file_update(&file));
+            }
+        }
+        FileEvent::Fetch(did, pin) => {
+            let name = did.to_hex();
+            let bytes = if has_file(&global.base, &pid, &name) {
+                read_db_file(&global.base, &pid, &db_key, &name).await?
+            } else {
+                vec![]
+            };
+            let data = bincode::serialize(&OwnEvent::File(FileEvent::Content(did, pin, bytes)))?;
+            results.owns.push(SendType::Event(0, aid, data));
+        }
+        FileEvent::Content(did, pin, bytes) => {
+            let mut file = File::get_by_did(&db, &did)?;
+            if pin && !bytes.is_empty() {
+                write_file(&global.base, &pid, &db_key, &file.storage_name(), &bytes).await?;
+                backup(global, &db, &mut file, &mut results).await?;
+                results.rpcs.push(rpc::file_update(&file));
+            } else {
+                results.rpcs.push(rpc::file_fetch(file.id, &bytes));
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::Node;
+
+    fn snapshot(db: &DStorage) -> FileEvent {
+        let mut infos = vec![];
+        for file in File::all(db).unwrap() {
+            let state = File::state(db, &file.id).unwrap();
+            infos.push((FileEvent::info(db, &file).unwrap(), state));
+        }
+        FileEvent::Snapshot(infos, File::tombstones(db).unwrap())
+    }
+
+    #[tokio::test]
+    async fn snapshot_tombstone_and_latest() {
+        let a = Node::new("a").await;
+        let b = Node::device("b", &a.mnemonic).await;
+        let a_db = a.db(file_db).await;
+        let b_db = b.db(file_db).await;
+
+        let mut f1 = File::generate(RootDirectory::Document, 0, "f1".to_owned());
+        let mut f2 = File::generate(RootDirectory::Document, 0, "f2".to_owned());
+        f1.insert(&a_db).unwrap();
+        f2.insert(&a_db).unwrap();
+        handle(a.pid, snapshot(&a_db), &b.global).await.unwrap();
+        assert_eq!(File::all(&b_db).unwrap().len(), 2);
+
+        // a deletes f1, b renames f2 later, both offline.
+        rpc::delete(&a.global.base, &a.pid, &a_db, &f1.id)
+            .await
+            .unwrap();
+        let mut b_f2 = File::get_by_did(&b_db, &f2.did).unwrap();
+        b_f2.name = "renamed".to_owned();
+        b_f2.apply(&b_db, rpc::now() + 10, 0).unwrap();
+        let b_snapshot = snapshot(&b_db);
+
+        // the deleted not recreated, the latest rename wins.
+        handle(b.pid, b_snapshot, &a.global).await.unwrap();
+        assert!(File::get_by_did(&a_db, &f1.did).is_err());
+        assert_eq!(File::get(&a_db, &f2.id).unwrap().name, "renamed");
+
+        handle(a.pid, snapshot(&a_db), &b.global).await.unwrap();
+        assert!(File::get_by_did(&b_db, &f1.did).is_err());
+        assert!(File::is_tombstone(&b_db, &f1.did).unwrap());
+        assert_eq!(File::get(&b_db, &b_f2.id).unwrap().name, "renamed");
+    }
+}
@@ -1,14 +1,130 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    primitives::HandleResult,
-    rpc::{json, RpcError, RpcHandler, RpcParam},
+    primitives::{HandleResult, PeerId, Result},
+    rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
+use tdn_storage::local::DStorage;
 
 use crate::global::Global;
-use crate::storage::{copy_file, file_db, write_file};
+use crate::storage::{
+    delete_file, file_db, file_hash, has_file, read_db_file, read_file, rename_file, write_file,
+};
+
+use super::models::{File, FileDid, RootDirectory, Share, Version};
+use super::own::{backup, fetch, FileEvent};
+use super::share::share_sync;
+use super::transfer::{folder_export, folder_import, task_cancel};
+
+#[inline]
+pub(crate) fn file_create(file: &File) -> RpcParam {
+    rpc_response(0, "dc-file-create", json!(file.to_rpc()))
+}
+
+#[inline]
+pub(crate) fn file_update(file: &File) -> RpcParam {
+    rpc_response(0, "dc-file-update", json!(file.to_rpc()))
+}
 
-use super::models::{File, RootDirectory};
+#[inline]
+pub(crate) fn file_delete(id: i64) -> RpcParam {
+    rpc_response(0, "dc-file-delete", json!([id]))
+}
+
+#[inline]
+pub(crate) fn file_fetch(id: i64, bytes: &[u8]) -> RpcParam {
+    rpc_response(0, "dc-file-fetch", json!([id, base64::encode(bytes)]))
+}
+
+#[inline]
+pub(crate) fn trash_clean(ids: &[i64]) -> RpcParam {
+    rpc_response(0, "dc-trash-clean", json!(ids))
+}
+
+#[inline]
+pub(super) fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
+
+/// delete the files rows and the stored contents, includes the versions.
+pub(super) async fn delete(base: &PathBuf, pid: &PeerId, db: &DStorage, id: &i64) -> Result<()> {
+    for name in File::delete(db, id)? {
+        delete_file(base, pid, &name).await?;
+        let did = FileDid::from_hex(&name)?;
+        for version in Version::list(db, &did)? {
+            delete_file(base, pid, &version.storage_name()).await?;
+            Version::delete(db, &version.id)?;
+        }
+        Share::delete(db, &did)?;
+    }
+    Ok(())
+}
+
+/// delete the versions which over the retention.
+async fn prune(base: &PathBuf, pid: &PeerId, db: &DStorage, did: Option<&FileDid>) -> Result<()> {
+    for version in Version::expired(db, did, now())? {
+        delete_file(base, pid, &version.storage_name()).await?;
+        Version::delete(db, &version.id)?;
+    }
+    Ok(())
+}
+
+/// keep the current content of the file as a version, before it replaced.
+pub(super) async fn archive(global: &Arc<Global>, db: &DStorage, file: &File) -> Result<()> {
+    let pid = global.pid().await;
+    let name = file.storage_name();
+    if !has_file(&global.base, &pid, &name) {
+        return Ok(());
+    }
+
+    let own_lock = global.own.read().await;
+    let db_key = own_lock.db_key(&pid)?;
+    let device = own_lock.current_assist()?;
+    drop(own_lock);
+
+    let (hash, size) = file_hash(&global.base, &pid, &db_key, &name).await?;
+    if let Some(mut version) = Version::get_by_hash(db, &file.did, &hash)? {
+        version.touch(db, device, now())?;
+        delete_file(&global.base, &pid, &name).await?;
+    } else {
+        let mut version = Version::new(file.did, hash, size, device, now());
+        rename_file(&global.base, &pid, &name, &version.storage_name()).await?;
+        version.insert(db)?;
+    }
+
+    prune(&global.base, &pid, db, Some(&file.did)).await
+}
+
+/// auto-empty the trash files and delete the versions which over retention.
+pub(crate) async fn file_expire(global: &Arc<Global>) -> Result<HandleResult> {
+    let mut results = HandleResult::new();
+
+    let pid = global.pid().await;
+    let db_key = if let Ok(db_key) = global.own.read().await.db_key(&pid) {
+        db_key
+    } else {
+        return Ok(results);
+    };
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    prune(&global.base, &pid, &db, None).await?;
+
+    let ids = File::expired(&db, now())?;
+    if ids.is_empty() {
+        return Ok(results);
+    }
+    for id in &ids {
+        delete(&global.base, &pid, &db, id).await?;
+    }
+    results.rpcs.push(trash_clean(&ids));
+
+    Ok(results)
+}
 
 pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
     handler.add_method("dc-echo", |params, _| async move {
@@ -47,11 +163,20 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
 
             // genereate new file.
             let mut file = File::generate(root, parent, name);
+            file.device.push(state.own.read().await.current_assist()?);
+            file.content(&[]);
             file.insert(&db)?;
 
             // create file on disk.
-            let _ = write_file(&state.base, &pid, &file.storage_name(), &[]).await?;
-            Ok(HandleResult::rpc(file.to_rpc()))
+            let _ = write_file(&state.base, &pid, &db_key, &file.storage_name(), &[]).await?;
+
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::create(&db, &file)?
+                .broadcast(&state, &mut results)
+                .await?;
+            let dids = File::ancestors(&db, &file.id)?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
         },
     );
 
@@ -74,11 +199,44 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = file_db(&state.base, &pid, &db_key)?;
 
+            let device = state.own.read().await.current_assist()?;
+            let bytes = read_file(&file_path).await?;
+
+            // same name file in the directory, keep the current content as a version.
+            if let Some(mut file) = File::get_by_name(&db, &root, &parent, &name)? {
+                if !file.device.is_empty() || has_file(&state.base, &pid, &file.storage_name()) {
+                    archive(&state, &db, &file).await?;
+                    write_file(&state.base, &pid, &db_key, &file.storage_name(), &bytes).await?;
+                    file.set_device(&db, vec![device])?;
+                    file.content(&bytes);
+                    file.set_meta(&db)?;
+
+                    let mut results = HandleResult::rpc(file.to_rpc());
+                    FileEvent::Replace(file.did, device)
+                        .broadcast(&state, &mut results)
+                        .await?;
+                    FileEvent::meta(&file)
+                        .broadcast(&state, &mut results)
+                        .await?;
+                    let dids = File::ancestors(&db, &file.id)?;
+                    share_sync(&state, &db, &dids, &mut results).await?;
+                    return Ok(results);
+                }
+            }
+
             let mut file = File::generate(root, parent, name);
+            file.device.push(device);
+            file.content(&bytes);
             file.insert(&db)?;
-            copy_file(&file_path, &state.base, &pid, &file.storage_name()).await?;
-
-            Ok(HandleResult::rpc(file.to_rpc()))
+            write_file(&state.base, &pid, &db_key, &file.storage_name(), &bytes).await?;
+
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::create(&db, &file)?
+                .broadcast(&state, &mut results)
+                .await?;
+            let dids = File::ancestors(&db, &file.id)?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
         },
     );
 
@@ -97,7 +255,46 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let mut file = File::generate(root, parent, name);
             file.insert(&db)?;
 
-            Ok(HandleResult::rpc(file.to_rpc()))
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::create(&db, &file)?
+                .broadcast(&state, &mut results)
+                .await?;
+            let dids = File::ancestors(&db, &file.id)?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-folder-import",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let root = RootDirectory::from_i64(params[0].as_i64().ok_or(RpcError::ParseError)?);
+            let parent = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            let path = params[2].as_str().ok_or(RpcError::ParseError)?;
+
+            // progress will response in dc-folder-progress.
+            let task = folder_import(&state, root, parent, PathBuf::from(path)).await?;
+            Ok(HandleResult::rpc(json!([task])))
+        },
+    );
+
+    handler.add_method(
+        "dc-folder-export",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let path = params[1].as_str().ok_or(RpcError::ParseError)?;
+
+            // progress will response in dc-folder-progress.
+            let task = folder_export(&state, id, PathBuf::from(path)).await?;
+            Ok(HandleResult::rpc(json!([task])))
+        },
+    );
+
+    handler.add_method(
+        "dc-folder-cancel",
+        |params: Vec<RpcParam>, _state: Arc<Global>| async move {
+            let task = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            Ok(HandleResult::rpc(json!([task, task_cancel(&task)])))
         },
     );
 
@@ -113,13 +310,21 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = file_db(&state.base, &pid, &db_key)?;
 
+            // shares of the old and new parents both changed.
+            let mut dids = File::ancestors(&db, &id)?;
             let mut file = File::get(&db, &id)?;
             file.root = root;
             file.parent = parent;
             file.name = name;
             file.update(&db)?;
 
-            Ok(HandleResult::rpc(file.to_rpc()))
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::update(&db, &file)?
+                .broadcast(&state, &mut results)
+                .await?;
+            dids.extend(File::ancestors(&db, &file.id)?);
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
         },
     );
 
@@ -133,8 +338,62 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = file_db(&state.base, &pid, &db_key)?;
 
+            let file = File::get(&db, &id)?;
             File::star(&db, &id, starred)?;
-            Ok(HandleResult::new())
+
+            let mut results = HandleResult::new();
+            FileEvent::Star(file.did, starred)
+                .broadcast(&state, &mut results)
+                .await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-file-tags",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let tags: Vec<String> = params[1]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|t| t.as_str().map(|t| t.to_owned()))
+                .collect();
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let mut file = File::get(&db, &id)?;
+            file.set_tags(&db, &tags)?;
+
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::Tags(file.did, file.tags.clone())
+                .broadcast(&state, &mut results)
+                .await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-search",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let name = params[0].as_str().ok_or(RpcError::ParseError)?;
+            let tag = params[1].as_str().ok_or(RpcError::ParseError)?;
+            let mime = params[2].as_str().ok_or(RpcError::ParseError)?;
+            let start = params[3].as_i64().ok_or(RpcError::ParseError)?;
+            let end = params[4].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let files: Vec<RpcParam> = File::search(&db, name, tag, mime, start, end)?
+                .iter()
+                .map(|p| p.to_rpc())
+                .collect();
+
+            Ok(HandleResult::rpc(json!(files)))
         },
     );
 
@@ -147,10 +406,38 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = file_db(&state.base, &pid, &db_key)?;
 
-            // TODO trash a directory.
+            let file = File::get(&db, &id)?;
+            let dids = File::ancestors(&db, &id)?;
+            let now = now();
+            File::trash(&db, &id, now)?;
+
+            let mut results = HandleResult::new();
+            FileEvent::Trash(file.did, now)
+                .broadcast(&state, &mut results)
+                .await?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-trash-restore",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let file = File::restore(&db, &id)?;
 
-            File::trash(&db, &id)?;
-            Ok(HandleResult::new())
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::Restore(file.did)
+                .broadcast(&state, &mut results)
+                .await?;
+            let dids = File::ancestors(&db, &file.id)?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
         },
     );
 
@@ -163,10 +450,200 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = file_db(&state.base, &pid, &db_key)?;
 
-            // TODO deleted file & directory.
+            let file = File::get(&db, &id)?;
+            let dids = File::ancestors(&db, &file.parent)?;
+            delete(&state.base, &pid, &db, &id).await?;
+
+            let mut results = HandleResult::new();
+            FileEvent::Delete(file.did)
+                .broadcast(&state, &mut results)
+                .await?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-trash-empty",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let mut ids = vec![];
+            let mut results = HandleResult::new();
+            for file in File::list(&db, &RootDirectory::Trash, &0)? {
+                delete(&state.base, &pid, &db, &file.id).await?;
+                ids.push(file.id);
+                FileEvent::Delete(file.did)
+                    .broadcast(&state, &mut results)
+                    .await?;
+            }
+            results.rpcs.push(json!(ids));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-trash-retention",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let days = File::retention(&db)?;
+            Ok(HandleResult::rpc(json!(days)))
+        },
+    );
+
+    handler.add_method(
+        "dc-trash-retention-set",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let days = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            if days < 0 {
+                return Err(RpcError::ParseError);
+            }
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            File::set_retention(&db, days)?;
+            Ok(HandleResult::rpc(json!(days)))
+        },
+    );
+
+    handler.add_method(
+        "dc-file-fetch",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let file = File::get(&db, &id)?;
+            let name = file.storage_name();
+            if has_file(&state.base, &pid, &name) {
+                let bytes = read_db_file(&state.base, &pid, &db_key, &name).await?;
+                return Ok(HandleResult::rpc(json!([id, base64::encode(bytes)])));
+            }
+
+            // content will response in dc-file-fetch.
+            let mut results = HandleResult::new();
+            fetch(&state, &file, false, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-file-backup",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let mut file = File::get(&db, &id)?;
+            let mut results = HandleResult::new();
+            if has_file(&state.base, &pid, &file.storage_name()) {
+                backup(&state, &db, &mut file, &mut results).await?;
+                results.rpcs.push(file.to_rpc());
+            } else {
+                // content will pin when received, and response in dc-file-update.
+                fetch(&state, &file, true, &mut results).await?;
+            }
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-file-versions",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let file = File::get(&db, &id)?;
+            let versions: Vec<RpcParam> = Version::list(&db, &file.did)?
+                .iter()
+                .map(|v| v.to_rpc())
+                .collect();
+            Ok(HandleResult::rpc(json!([id, versions])))
+        },
+    );
+
+    handler.add_method(
+        "dc-file-restore",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let vid = params[1].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let own_lock = state.own.read().await;
+            let db_key = own_lock.db_key(&pid)?;
+            let device = own_lock.current_assist()?;
+            drop(own_lock);
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let mut file = File::get(&db, &id)?;
+            let version = Version::get(&db, &vid)?;
+            if version.did != file.did {
+                return Err(RpcError::Custom("version is not of the file".to_owned()));
+            }
+
+            // current content also keep as a version, so restore can be undone.
+            let bytes = read_db_file(&state.base, &pid, &db_key, &version.storage_name()).await?;
+            archive(&state, &db, &file).await?;
+            write_file(&state.base, &pid, &db_key, &file.storage_name(), &bytes).await?;
+            file.set_device(&db, vec![device])?;
+            file.content(&bytes);
+            file.set_meta(&db)?;
+
+            let mut results = HandleResult::rpc(file.to_rpc());
+            FileEvent::Replace(file.did, device)
+                .broadcast(&state, &mut results)
+                .await?;
+            FileEvent::meta(&file)
+                .broadcast(&state, &mut results)
+                .await?;
+            let dids = File::ancestors(&db, &file.id)?;
+            share_sync(&state, &db, &dids, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "dc-version-retention",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
+
+            let (keep, days) = Version::retention(&db)?;
+            Ok(HandleResult::rpc(json!([keep, days])))
+        },
+    );
+
+    handler.add_method(
+        "dc-version-retention-set",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let keep = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let days = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            if keep < 0 || days < 0 {
+                return Err(RpcError::ParseError);
+            }
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = file_db(&state.base, &pid, &db_key)?;
 
-            File::delete(&db, &id)?;
-            Ok(HandleResult::new())
+            Version::set_retention(&db, keep, days)?;
+            prune(&state.base, &pid, &db, None).await?;
+            Ok(HandleResult::rpc(json!([keep, days])))
         },
     );
 }
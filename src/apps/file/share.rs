@@ -0,0 +1,271 @@
+use group_types::{LayerEvent, GROUP_CHAT_ID};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tdn::types::{
+    message::SendType,
+    primitives::{HandleResult, PeerId, Result},
+    rpc::RpcParam,
+};
+use tdn_storage::local::DStorage;
+
+use crate::apps::group::{broadcast as group_broadcast, GroupChat, Message as GroupMessage};
+use crate::global::Global;
+use crate::group::{Friend, GroupEvent, Message};
+use crate::storage::{chat_db, file_db, group_db, has_file, read_db_file, write_file};
+
+use super::models::{File, FileDid, Manifest, RootDirectory, Share};
+use super::own::FileEvent;
+use super::rpc;
+
+/// the fetching shared files, key is the owner, share and file did,
+/// value is the file name and content hash in the manifest.
+static FETCHING: Lazy<Mutex<HashMap<(PeerId, FileDid, FileDid), (String, Option<String>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// build the shared manifest from message content `file_id;;sync`,
+/// and keep the file shared.
+pub(crate) fn share_manifest(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    content: &str,
+) -> Result<String> {
+    let (id, sync) = content.split_once(";;").unwrap_or((content, "0"));
+    let id: i64 = id.parse()?;
+
+    let db = file_db(base, pid, db_key)?;
+    let manifest = Manifest::build(&db, *pid, &id, sync == "1")?;
+    Share::share(&db, &manifest.did, manifest.sync, rpc::now())?;
+    Ok(manifest.to_content())
+}
+
+/// read the shared file content, the file must in the shared directory.
+pub(crate) async fn share_read(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    share: &str,
+    did: &str,
+) -> Result<(String, Vec<u8>)> {
+    let share = FileDid::from_hex(share)?;
+    let did = FileDid::from_hex(did)?;
+
+    let db = file_db(base, pid, db_key)?;
+    Share::get(&db, &share)?;
+    let file = File::get_by_did(&db, &did)?;
+    if file.root == RootDirectory::Trash || !File::ancestors(&db, &file.id)?.contains(&share) {
+        return Err(anyhow!("file is not shared"));
+    }
+
+    let name = file.storage_name();
+    let bytes = if has_file(base, pid, &name) {
+        read_db_file(base, pid, db_key, &name).await?
+    } else {
+        vec![]
+    };
+    Ok((file.name, bytes))
+}
+
+/// keep the fetching file of the manifest, only the response of it will be saved.
+pub(crate) fn share_fetch(manifest: &Manifest, did: &str) -> Result<()> {
+    let fdid = FileDid::from_hex(did)?;
+    let name = manifest
+        .file_name(&fdid)
+        .ok_or(anyhow!("file is not shared"))?
+        .to_owned();
+    let hash = manifest.hashes.get(&fdid).cloned();
+    if let Ok(mut fetching) = FETCHING.lock() {
+        fetching.insert((manifest.owner, manifest.did, fdid), (name, hash));
+    }
+    Ok(())
+}
+
+/// save the fetched shared file into the session directory,
+/// the file must be fetching and the content matches the manifest.
+pub(crate) async fn share_save(
+    global: &Arc<Global>,
+    owner: &PeerId,
+    share: &str,
+    did: &str,
+    bytes: Vec<u8>,
+    results: &mut HandleResult,
+) -> Result<RpcParam> {
+    let key = (*owner, FileDid::from_hex(share)?, FileDid::from_hex(did)?);
+    let (name, hash) = FETCHING
+        .lock()
+        .ok()
+        .and_then(|mut fetching| fetching.remove(&key))
+        .ok_or(anyhow!("shared file is not fetching"))?;
+
+    if bytes.is_empty() {
+        return Err(anyhow!("shared file is missing"));
+    }
+    if let Some(hash) = hash {
+        if blake3::hash(&bytes).to_hex().as_str() != hash {
+            return Err(anyhow!("shared file is invalid"));
+        }
+    }
+
+    let pid = global.pid().await;
+    let own_lock = global.own.read().await;
+    let db_key = own_lock.db_key(&pid)?;
+    let device = own_lock.current_assist()?;
+    drop(own_lock);
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    let mut file = File::generate(RootDirectory::Session, 0, name);
+    file.device.push(device);
+    file.content(&bytes);
+    file.insert(&db)?;
+    write_file(&global.base, &pid, &db_key, &file.storage_name(), &bytes).await?;
+
+    results.rpcs.push(rpc::file_create(&file));
+    FileEvent::create(&db, &file)?
+        .broadcast(global, results)
+        .await?;
+    Ok(file.to_rpc())
+}
+
+/// the file changed, send the new manifests of the synced shares to the receivers.
+/// dids is the changed file and its parents.
+pub(crate) async fn share_sync(
+    global: &Arc<Global>,
+    db: &DStorage,
+    dids: &[FileDid],
+    results: &mut HandleResult,
+) -> Result<()> {
+    let shares = Share::syncs(db, dids)?;
+    if shares.is_empty() {
+        return Ok(());
+    }
+
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let c_db = chat_db(&global.base, &pid, &db_key)?;
+    let g_db = group_db(&global.base, &pid, &db_key)?;
+
+    for did in shares {
+        let file = File::get_by_did(db, &did)?;
+        let content = match Manifest::build(db, pid, &file.id, true) {
+            Ok(manifest) => manifest.to_content(),
+            Err(_) => continue, // shared directory is trashed.
+        };
+        let prefix = Manifest::prefix(&pid, &did);
+
+        // friends chat.
+        for msg in Message::get_shared(&c_db, &prefix)? {
+            if !msg.is_me {
+                continue;
+            }
+            Message::update_content(&c_db, &msg.id, &content)?;
+            let friend = Friend::get(&c_db, &msg.fid)?;
+            let event = GroupEvent::ShareSync(content.clone());
+            let data = bincode::serialize(&event)?;
+            results.groups.push(SendType::Event(0, friend.pid, data));
+        }
+
+        // group chats.
+        let mut gids = vec![];
+        for msg in GroupMessage::get_shared(&g_db, &prefix)? {
+            if !msg.is_me {
+                continue;
+            }
+            GroupMessage::update_content(&g_db, &msg.id, &content)?;
+            if gids.contains(&msg.fid) {
+                continue;
+            }
+            gids.push(msg.fid);
+
+            let group = GroupChat::get(&g_db, &msg.fid)?;
+            let event = LayerEvent::ShareSync(group.gid, pid, content.clone());
+            if group.local {
+                group_broadcast(&group.gid, global, &event, results).await?;
+            } else {
+                let data = bincode::serialize(&event)?;
+                let msg = SendType::Event(0, group.addr, data);
+                results.layers.push((GROUP_CHAT_ID, msg));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::Node;
+
+    #[tokio::test]
+    async fn share_save_only_fetching() {
+        let owner = Node::new("owner").await;
+        let node = Node::new("node").await;
+        let db = owner.db(file_db).await;
+
+        let mut dir = File::generate(RootDirectory::Document, 0, "dir".to_owned());
+        dir.insert(&db).unwrap();
+        let mut file = File::generate(RootDirectory::Document, dir.id, "a.txt".to_owned());
+        file.device.push(owner.pid);
+        file.content(b"hello");
+        file.insert(&db).unwrap();
+
+        let content = Manifest::build(&db, owner.pid, &dir.id, false)
+            .unwrap()
+            .to_content();
+        let manifest = Manifest::from_content(&content).unwrap();
+        assert_eq!(manifest.file_name(&file.did), Some("a.txt"));
+        assert_eq!(manifest.hashes.get(&file.did), Some(&file.hash));
+
+        let share = dir.did.to_hex();
+        let did = file.did.to_hex();
+        let mut results = HandleResult::new();
+
+        // unsolicited response.
+        let res = share_save(
+            &node.global,
+            &owner.pid,
+            &share,
+            &did,
+            b"hello".to_vec(),
+            &mut results,
+        );
+        assert!(res.await.is_err());
+
+        // the content not matches the manifest.
+        share_fetch(&manifest, &did).unwrap();
+        let res = share_save(
+            &node.global,
+            &owner.pid,
+            &share,
+            &did,
+            b"bad".to_vec(),
+            &mut results,
+        );
+        assert!(res.await.is_err());
+
+        // from other peer.
+        share_fetch(&manifest, &did).unwrap();
+        let res = share_save(
+            &node.global,
+            &node.pid,
+            &share,
+            &did,
+            b"hello".to_vec(),
+            &mut results,
+        );
+        assert!(res.await.is_err());
+
+        let res = share_save(
+            &node.global,
+            &owner.pid,
+            &share,
+            &did,
+            b"hello".to_vec(),
+            &mut results,
+        );
+        let saved = res.await.unwrap();
+        assert_eq!(saved[4], "a.txt");
+    }
+}
@@ -0,0 +1,364 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use tdn::prelude::SendMessage;
+use tdn::types::{
+    message::RpcSendMessage,
+    primitives::{HandleResult, Result},
+    rpc::{json, rpc_response, RpcParam},
+};
+use tdn_storage::local::DStorage;
+use tokio::fs;
+
+use crate::global::Global;
+use crate::storage::{file_db, has_file, read_db_file, write_file};
+
+use super::models::{File, RootDirectory};
+use super::own::FileEvent;
+use super::rpc;
+use super::share::share_sync;
+
+/// running import & export tasks, value is the cancel flag.
+static TASKS: Lazy<Mutex<HashMap<i64, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// the next task id.
+static TASK_ID: AtomicI64 = AtomicI64::new(1);
+
+/// task status in progress notification.
+const RUNNING: i64 = 0;
+const FINISHED: i64 = 1;
+const CANCELLED: i64 = 2;
+const FAILED: i64 = 3;
+
+/// params: task, done count, total count, status, skipped files.
+#[inline]
+fn folder_progress(
+    task: i64,
+    done: usize,
+    total: usize,
+    status: i64,
+    skips: &[String],
+) -> RpcParam {
+    rpc_response(
+        0,
+        "dc-folder-progress",
+        json!([task, done, total, status, skips]),
+    )
+}
+
+fn task_new() -> (i64, Arc<AtomicBool>) {
+    let id = TASK_ID.fetch_add(1, Ordering::SeqCst);
+    let cancel = Arc::new(AtomicBool::new(false));
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.insert(id, cancel.clone());
+    }
+    (id, cancel)
+}
+
+fn task_done(id: &i64) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.remove(id);
+    }
+}
+
+/// cancel the running task, return false if the task is not running.
+pub(super) fn task_cancel(id: &i64) -> bool {
+    if let Ok(tasks) = TASKS.lock() {
+        if let Some(cancel) = tasks.get(id) {
+            cancel.store(true, Ordering::SeqCst);
+            return true;
+        }
+    }
+    false
+}
+
+/// send the results which generated in the background task.
+async fn flush(global: &Arc<Global>, results: HandleResult) {
+    for msg in results.rpcs {
+        let _ = global.rpc_send.send(RpcSendMessage(0, msg, true)).await;
+    }
+    for msg in results.owns {
+        let _ = global.send(SendMessage::Own(msg)).await;
+    }
+    for msg in results.groups {
+        let _ = global.send(SendMessage::Group(msg)).await;
+    }
+    for (tgid, msg) in results.layers {
+        let _ = global.send(SendMessage::Layer(tgid, msg)).await;
+    }
+}
+
+/// the local directory entries (not follow links), parents are before children.
+/// params: parent index (0 is the directory self), path, is directory.
+async fn walk(path: &PathBuf) -> Result<Vec<(usize, PathBuf, bool)>> {
+    let mut entries = vec![(0, path.clone(), true)];
+    let mut index = 0;
+    while index < entries.len() {
+        if entries[index].2 {
+            let mut dir = fs::read_dir(&entries[index].1).await?;
+            while let Some(entry) = dir.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() || file_type.is_file() {
+                    entries.push((index, entry.path(), file_type.is_dir()));
+                }
+            }
+        }
+        index += 1;
+    }
+    Ok(entries)
+}
+
+fn path_name(path: &PathBuf) -> String {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("")
+        .to_owned()
+}
+
+/// import the local directory tree into the root directory, keep the structure.
+pub(super) async fn folder_import(
+    global: &Arc<Global>,
+    root: RootDirectory,
+    parent: i64,
+    path: PathBuf,
+) -> Result<i64> {
+    if !path.is_dir() {
+        return Err(anyhow!("directory is missing"));
+    }
+
+    let pid = global.pid().await;
+    let own_lock = global.own.read().await;
+    let db_key = own_lock.db_key(&pid)?;
+    let device = own_lock.current_assist()?;
+    drop(own_lock);
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    let (task, cancel) = task_new();
+    let global = global.clone();
+    tokio::spawn(async move {
+        let entries = match walk(&path).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("folder import: {}", e);
+                task_done(&task);
+                flush(
+                    &global,
+                    HandleResult::rpc(folder_progress(task, 0, 0, FAILED, &[])),
+                )
+                .await;
+                return;
+            }
+        };
+        let total = entries.len();
+
+        // the imported files ids, same index as the entries.
+        let mut ids: Vec<i64> = vec![];
+        let mut skips = vec![];
+        let mut status = FINISHED;
+        for (index, (pindex, path, is_dir)) in entries.into_iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                status = CANCELLED;
+                break;
+            }
+
+            let fparent = if index == 0 { parent } else { ids[pindex] };
+            let mut file = File::generate(root, fparent, path_name(&path));
+            let mut results = HandleResult::new();
+            let res = if is_dir {
+                file.insert(&db).map(|_| ())
+            } else {
+                file.device.push(device);
+                import_file(&global, &db, &db_key, &path, &mut file).await
+            };
+            match res {
+                Ok(()) => {
+                    results.rpcs.push(rpc::file_create(&file));
+                    if let Ok(event) = FileEvent::create(&db, &file) {
+                        let _ = event.broadcast(&global, &mut results).await;
+                    }
+                }
+                Err(e) => {
+                    if is_dir {
+                        warn!("folder import: {}", e);
+                        status = FAILED;
+                        break;
+                    }
+                    skips.push(path.to_string_lossy().into_owned());
+                }
+            }
+            ids.push(file.id);
+
+            results
+                .rpcs
+                .push(folder_progress(task, index + 1, total, RUNNING, &[]));
+            flush(&global, results).await;
+        }
+
+        let mut results = HandleResult::new();
+        if let Ok(dids) = File::ancestors(&db, &parent) {
+            let _ = share_sync(&global, &db, &dids, &mut results).await;
+        }
+        task_done(&task);
+        results
+            .rpcs
+            .push(folder_progress(task, ids.len(), total, status, &skips));
+        flush(&global, results).await;
+    });
+
+    Ok(task)
+}
+
+async fn import_file(
+    global: &Arc<Global>,
+    db: &DStorage,
+    db_key: &str,
+    path: &PathBuf,
+    file: &mut File,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let bytes = fs::read(path).await?;
+    file.content(&bytes);
+    file.insert(db)?;
+    write_file(&global.base, &pid, db_key, &file.storage_name(), &bytes).await?;
+    Ok(())
+}
+
+/// the file name which is safe to export, no parent or separators in it,
+/// the names are synced from other devices.
+fn export_name(name: &str) -> Option<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return None;
+    }
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .filter(|n| *n == name)
+}
+
+/// export the file or directory subtree to the local directory.
+/// the files which content is not in this device are skipped.
+pub(super) async fn folder_export(global: &Arc<Global>, id: i64, path: PathBuf) -> Result<i64> {
+    if !path.is_dir() {
+        return Err(anyhow!("directory is missing"));
+    }
+
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = file_db(&global.base, &pid, &db_key)?;
+
+    let top = File::get(&db, &id)?;
+    let mut target = path.clone();
+    target.push(export_name(&top.name).ok_or(anyhow!("file name is invalid"))?);
+    if target.exists() {
+        return Err(anyhow!("target is exists"));
+    }
+
+    let (task, cancel) = task_new();
+    let global = global.clone();
+    tokio::spawn(async move {
+        let ids = File::tree(&db, &id).unwrap_or(vec![]);
+        let total = ids.len();
+
+        // the exported directories paths.
+        let mut paths: HashMap<i64, PathBuf> = HashMap::new();
+        let mut skips = vec![];
+        let mut done = 0;
+        let mut status = FINISHED;
+        for fid in ids {
+            if cancel.load(Ordering::SeqCst) {
+                status = CANCELLED;
+                break;
+            }
+
+            let file = if let Ok(file) = File::get(&db, &fid) {
+                file
+            } else {
+                continue;
+            };
+            if file.root == RootDirectory::Trash && top.root != RootDirectory::Trash {
+                continue;
+            }
+
+            let mut fpath = if fid == id {
+                path.clone()
+            } else if let Some(p) = paths.get(&file.parent) {
+                p.clone()
+            } else {
+                continue; // parent is skipped.
+            };
+            match export_name(&file.name) {
+                Some(name) => fpath.push(name),
+                None => {
+                    skips.push(file.name.clone());
+                    continue;
+                }
+            }
+            if !fpath.starts_with(&target) {
+                skips.push(file.name.clone());
+                continue;
+            }
+
+            let res = if file.is_dir() {
+                paths.insert(fid, fpath.clone());
+                fs::create_dir_all(&fpath)
+                    .await
+                    .map_err(anyhow::Error::from)
+            } else {
+                export_file(&global, &db_key, &file, &fpath).await
+            };
+            if let Err(e) = res {
+                if file.is_dir() {
+                    warn!("folder export: {}", e);
+                    status = FAILED;
+                    break;
+                }
+                skips.push(file.name.clone());
+            }
+
+            done += 1;
+            let results = HandleResult::rpc(folder_progress(task, done, total, RUNNING, &[]));
+            flush(&global, results).await;
+        }
+
+        task_done(&task);
+        let results = HandleResult::rpc(folder_progress(task, done, total, status, &skips));
+        flush(&global, results).await;
+    });
+
+    Ok(task)
+}
+
+async fn export_file(
+    global: &Arc<Global>,
+    db_key: &str,
+    file: &File,
+    path: &PathBuf,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let name = file.storage_name();
+    if !has_file(&global.base, &pid, &name) {
+        return Err(anyhow!("file content is not in this device"));
+    }
+    let bytes = read_db_file(&global.base, &pid, db_key, &name).await?;
+    Ok(fs::write(path, bytes).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_name_in_directory() {
+        assert_eq!(export_name("a.txt"), Some("a.txt"));
+        assert_eq!(export_name("..a"), Some("..a"));
+        assert_eq!(export_name(""), None);
+        assert_eq!(export_name("."), None);
+        assert_eq!(export_name(".."), None);
+        assert_eq!(export_name("../a"), None);
+        assert_eq!(export_name("a/b"), None);
+        assert_eq!(export_name("a\\b"), None);
+        assert_eq!(export_name("/etc"), None);
+    }
+}
@@ -7,10 +7,11 @@ use tdn::types::{
 };
 use tdn_storage::local::DStorage;
 
+use crate::apps::file::{share_read, share_save, Manifest};
 use crate::global::Global;
-use crate::group::Friend;
+use crate::group::{Friend, Poll, Vote};
 use crate::rpc::{
-    session_close, session_connect, session_last, session_lost, session_suspend,
+    notice_mention, session_close, session_connect, session_last, session_lost, session_suspend,
     session_update_name,
 };
 use crate::session::{connect_session, Session, SessionType};
@@ -192,7 +193,7 @@ async fn handle_event(
         return Err(anyhow!("NOT THE SERVER EVENT"));
     }
 
-    match event {
+    match event.with_mentions() {
         LayerEvent::Offline(gid) => {
             // SERVER & PEER
             if is_server {
@@ -300,7 +301,7 @@ async fn handle_event(
                     if let Ok(mid) = mid_res {
                         Member::update(&db, &h, &mid, &mname)?;
                         if mavatar.len() > 0 {
-                            write_avatar_sync(&global.base, &pid, &mpid, mavatar.clone())?;
+                            write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar.clone())?;
                         }
                         let mem = Member::info(mid, id, mpid, mname.clone());
                         results.rpcs.push(rpc::member_join(&mem));
@@ -308,7 +309,7 @@ async fn handle_event(
                         let mut member = Member::new(h, id, mpid, mname.clone());
                         member.insert(&db)?;
                         if mavatar.len() > 0 {
-                            write_avatar_sync(&global.base, &pid, &mpid, mavatar.clone())?;
+                            write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar.clone())?;
                         }
                         results.rpcs.push(rpc::member_join(&member));
                     }
@@ -332,7 +333,7 @@ async fn handle_event(
                     // check mid is my chat friend. if not, delete avatar.
                     let c_db = chat_db(&global.base, &pid, &db_key)?;
                     if Friend::get_id(&c_db, &mpid).is_err() {
-                        let _ = delete_avatar(&global.base, &pid, &mpid).await;
+                        let _ = delete_avatar(&global.base, &pid, &db_key, &mpid).await;
                     }
                     results.rpcs.push(rpc::member_leave(id, mid));
 
@@ -342,7 +343,8 @@ async fn handle_event(
                         broadcast(&gid, global, &LayerEvent::Sync(gid, h, event), results).await?;
                     }
                 }
-                Event::MessageCreate(mpid, nmsg, mtime) => {
+                Event::MessageCreate(..) => {} // upgraded to MessageCreateMention.
+                Event::MessageCreateMention(mpid, nmsg, mtime, mentions) => {
                     debug!("Sync: create message start");
                     let _mid = Member::get_id(&db, &id, &mpid)?;
                     let h = if is_server {
@@ -360,6 +362,7 @@ async fn handle_event(
                         mpid,
                         nmsg.clone(),
                         mtime,
+                        mentions.clone(),
                         results,
                     )
                     .await?;
@@ -368,14 +371,137 @@ async fn handle_event(
 
                     // UPDATE SESSION.
                     let s_db = session_db(&global.base, &pid, &db_key)?;
-                    update_session(&s_db, &id, &msg, results);
+                    update_session(&s_db, &pid, &id, &msg, results);
 
                     GroupChat::add_height(&db, id, h)?;
                     if is_server {
-                        let new_e = Event::MessageCreate(mpid, nmsg, mtime);
+                        let new_e = Event::message(mpid, nmsg, mtime, mentions);
                         broadcast(&gid, global, &LayerEvent::Sync(gid, h, new_e), results).await?;
                     }
                 }
+                Event::PollVote(mpid, mheight, options) => {
+                    // SERVER
+                    if !is_server || mpid != addr {
+                        return Err(anyhow!("poll vote is invalid"));
+                    }
+                    let _mid = Member::get_id(&db, &id, &mpid)?;
+                    let (msg, poll, votes) = poll_vote(&db, &id, mpid, &mheight, options)?;
+                    results
+                        .rpcs
+                        .push(rpc::poll_result(&id, &msg.id, &poll, &votes));
+
+                    let res =
+                        LayerEvent::PollRes(gid, mheight, poll.deadline, Vote::to_network(&votes));
+                    broadcast(&gid, global, &res, results).await?;
+                }
+                Event::PollClose(mpid, mheight) => {
+                    // SERVER
+                    if !is_server || mpid != addr {
+                        return Err(anyhow!("poll close is invalid"));
+                    }
+                    let (msg, poll, votes) = poll_close(&db, &id, &gaddr, &mpid, &mheight)?;
+                    results
+                        .rpcs
+                        .push(rpc::poll_result(&id, &msg.id, &poll, &votes));
+
+                    let res =
+                        LayerEvent::PollRes(gid, mheight, poll.deadline, Vote::to_network(&votes));
+                    broadcast(&gid, global, &res, results).await?;
+                }
+            }
+        }
+        LayerEvent::PollReq(gid, mheight) => {
+            // SERVER
+            let _mid = Member::get_id(&db, &id, &addr)?;
+            let msg = Message::get_by_height(&db, &id, &mheight)?;
+            if msg.m_type != MessageType::Poll {
+                return Err(anyhow!("poll is invalid"));
+            }
+            let poll = Poll::from_content(&msg.content)?;
+            let votes = Vote::list(&db, &msg.id)?;
+
+            let res = LayerEvent::PollRes(gid, mheight, poll.deadline, Vote::to_network(&votes));
+            let data = bincode::serialize(&res).unwrap_or(vec![]);
+            let s = SendType::Event(0, addr, data);
+            results.layers.push((GROUP_CHAT_ID, s));
+        }
+        LayerEvent::PollRes(_gid, mheight, deadline, votes) => {
+            // PEER
+            let msg = Message::get_by_height(&db, &id, &mheight)?;
+            if msg.m_type != MessageType::Poll {
+                return Err(anyhow!("poll is invalid"));
+            }
+            let mut poll = Poll::from_content(&msg.content)?;
+            if poll.deadline != deadline {
+                poll.deadline = deadline;
+                Message::update_content(&db, &msg.id, &poll.to_content())?;
+            }
+
+            Vote::reset(&db, &msg.id, votes)?;
+            let votes = Vote::list(&db, &msg.id)?;
+            results
+                .rpcs
+                .push(rpc::poll_result(&id, &msg.id, &poll, &votes));
+        }
+        LayerEvent::ShareReq(gid, mpid, share, did) => {
+            if is_server && mpid != pid {
+                // SERVER: relay to the owner, mpid is owner.
+                let _mid = Member::get_id(&db, &id, &addr)?;
+                let event = LayerEvent::ShareReq(gid, addr, share, did);
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                let s = SendType::Event(0, mpid, data);
+                results.layers.push((GROUP_CHAT_ID, s));
+            } else {
+                // OWNER: response to the requester, server will relay it.
+                let requester = if is_server { addr } else { mpid };
+                let (name, bytes) = share_read(&global.base, &pid, &db_key, &share, &did)
+                    .await
+                    .unwrap_or((String::new(), vec![]));
+
+                let event = LayerEvent::ShareRes(gid, requester, share, did, name, bytes);
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                let to = if is_server { addr } else { gaddr };
+                let s = SendType::Event(0, to, data);
+                results.layers.push((GROUP_CHAT_ID, s));
+            }
+        }
+        LayerEvent::ShareRes(gid, mpid, share, did, name, bytes) => {
+            if is_server && mpid != pid {
+                // SERVER: relay to the requester, mpid is requester.
+                let event = LayerEvent::ShareRes(gid, addr, share, did, name, bytes);
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                let s = SendType::Event(0, mpid, data);
+                results.layers.push((GROUP_CHAT_ID, s));
+            } else {
+                // REQUESTER, the owner relayed by server, or sent directly when i'm server.
+                let owner = if is_server { addr } else { mpid };
+                let file = share_save(global, &owner, &share, &did, bytes, results).await?;
+                results.rpcs.push(rpc::share_file(&id, &share, &did, file));
+            }
+        }
+        LayerEvent::ShareSync(gid, mpid, content) => {
+            // SERVER & PEER
+            if is_server && mpid != addr {
+                return Err(anyhow!("share sync is invalid"));
+            }
+            let manifest = Manifest::from_content(&content)?;
+            if manifest.owner != mpid {
+                return Err(anyhow!("share sync is invalid"));
+            }
+
+            let mid = Member::get_id(&db, &id, &mpid)?;
+            let prefix = Manifest::prefix(&manifest.owner, &manifest.did);
+            for msg in Message::get_shared(&db, &prefix)? {
+                if msg.fid != id || msg.mid != mid || msg.is_me {
+                    continue;
+                }
+                Message::update_content(&db, &msg.id, &content)?;
+                results.rpcs.push(rpc::share_sync(&id, &msg.id, &content));
+            }
+
+            if is_server {
+                let event = LayerEvent::ShareSync(gid, mpid, content);
+                broadcast(&gid, global, &event, results).await?;
             }
         }
         LayerEvent::SyncReq(gid, from) => {
@@ -390,16 +516,18 @@ async fn handle_event(
                 };
 
                 let (members, leaves) =
-                    Member::sync(&global.base, &pid, &db, &id, &from, &to).await?;
-                let messages = Message::sync(&global.base, &pid, &db, &id, &from, &to).await?;
-                let event = LayerEvent::SyncRes(gid, height, from, to, members, leaves, messages);
+                    Member::sync(&global.base, &pid, &db_key, &db, &id, &from, &to).await?;
+                let messages =
+                    Message::sync(&global.base, &pid, &db_key, &db, &id, &from, &to).await?;
+                let event = LayerEvent::sync_res(gid, height, from, to, members, leaves, messages);
                 let data = bincode::serialize(&event).unwrap_or(vec![]);
                 let s = SendType::Event(0, addr, data);
                 results.layers.push((GROUP_CHAT_ID, s));
                 debug!("Sended sync request results. from: {}, to: {}", from, to);
             }
         }
-        LayerEvent::SyncRes(gid, height, from, to, adds, leaves, messages) => {
+        LayerEvent::SyncRes(..) => {} // upgraded to SyncResMention.
+        LayerEvent::SyncResMention(gid, height, from, to, adds, leaves, messages) => {
             // PEER
             if to >= height {
                 results.layers.push((GROUP_CHAT_ID, sync_online(gid, addr)));
@@ -414,7 +542,7 @@ async fn handle_event(
                 if let Ok(mid) = mid_res {
                     Member::update(&db, &height, &mid, &mname)?;
                     if mavatar.len() > 0 {
-                        write_avatar_sync(&global.base, &pid, &mpid, mavatar)?;
+                        write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar)?;
                     }
                     let mem = Member::info(mid, id, mpid, mname);
                     results.rpcs.push(rpc::member_join(&mem));
@@ -422,7 +550,7 @@ async fn handle_event(
                     let mut member = Member::new(height, id, mpid, mname);
                     member.insert(&db)?;
                     if mavatar.len() > 0 {
-                        write_avatar_sync(&global.base, &pid, &mpid, mavatar)?;
+                        write_avatar_sync(&global.base, &pid, &db_key, &mpid, mavatar)?;
                     }
                     results.rpcs.push(rpc::member_join(&member));
                 }
@@ -434,13 +562,13 @@ async fn handle_event(
                     Member::leave(&db, &height, &mid)?;
                     // check mid is my chat friend. if not, delete avatar.
                     if Friend::get_id(&c_db, &mpid).is_err() {
-                        let _ = delete_avatar(&global.base, &pid, &mpid).await;
+                        let _ = delete_avatar(&global.base, &pid, &db_key, &mpid).await;
                     }
                     results.rpcs.push(rpc::member_leave(id, mid));
                 }
             }
 
-            for (height, mpid, nm, time) in messages {
+            for (height, mpid, nm, time, mentions) in messages {
                 if let Ok(msg) = handle_network_message(
                     &pid,
                     &global.base,
@@ -450,6 +578,7 @@ async fn handle_event(
                     mpid,
                     nm,
                     time,
+                    mentions,
                     results,
                 )
                 .await
@@ -471,7 +600,7 @@ async fn handle_event(
             // UPDATE SESSION.
             if let Some(msg) = last_message {
                 let s_db = session_db(&global.base, &pid, &db_key)?;
-                update_session(&s_db, &id, &msg, results);
+                update_session(&s_db, &pid, &id, &msg, results);
             }
             debug!("Over handle sync packed... {}, {}, {}", height, from, to);
         }
@@ -480,6 +609,51 @@ async fn handle_event(
     Ok(())
 }
 
+/// SERVER: tally the member's vote.
+pub(crate) fn poll_vote(
+    db: &DStorage,
+    id: &i64,
+    mpid: PeerId,
+    height: &i64,
+    options: Vec<u32>,
+) -> Result<(Message, Poll, Vec<Vote>)> {
+    let msg = Message::get_by_height(db, id, height)?;
+    if msg.m_type != MessageType::Poll {
+        return Err(anyhow!("poll is invalid"));
+    }
+    let poll = Poll::from_content(&msg.content)?;
+    poll.check(&options)?;
+
+    Vote::new(msg.id, mpid, options).insert(db)?;
+    let votes = Vote::list(db, &msg.id)?;
+    Ok((msg, poll, votes))
+}
+
+/// SERVER: close the poll, only the poll's creator or the server can close it.
+pub(crate) fn poll_close(
+    db: &DStorage,
+    id: &i64,
+    gaddr: &PeerId,
+    mpid: &PeerId,
+    height: &i64,
+) -> Result<(Message, Poll, Vec<Vote>)> {
+    let msg = Message::get_by_height(db, id, height)?;
+    if msg.m_type != MessageType::Poll {
+        return Err(anyhow!("poll is invalid"));
+    }
+    let mid = Member::get_id(db, id, mpid)?;
+    if mpid != gaddr && msg.mid != mid {
+        return Err(anyhow!("poll close permission denied"));
+    }
+
+    let mut poll = Poll::from_content(&msg.content)?;
+    if poll.close() {
+        Message::update_content(db, &msg.id, &poll.to_content())?;
+    }
+    let votes = Vote::list(db, &msg.id)?;
+    Ok((msg, poll, votes))
+}
+
 pub(crate) async fn broadcast(
     gid: &GroupChatId,
     global: &Arc<Global>,
@@ -497,8 +671,14 @@ pub(crate) async fn broadcast(
     Ok(())
 }
 
-// UPDATE SESSION.
-pub(crate) fn update_session(s_db: &DStorage, id: &i64, msg: &Message, results: &mut HandleResult) {
+// UPDATE SESSION. follow the session's notification rules.
+pub(crate) fn update_session(
+    s_db: &DStorage,
+    pid: &PeerId,
+    id: &i64,
+    msg: &Message,
+    results: &mut HandleResult,
+) {
     let scontent = match msg.m_type {
         MessageType::String => {
             format!("{}:{}", msg.m_type.to_int(), msg.content)
@@ -506,17 +686,27 @@ pub(crate) fn update_session(s_db: &DStorage, id: &i64, msg: &Message, results:
         _ => format!("{}:", msg.m_type.to_int()),
     };
 
+    let session = match Session::get_by_fid(s_db, id, &SessionType::Group) {
+        Ok(session) => session,
+        Err(_) => return,
+    };
+    let mentioned = msg.mentions.contains(pid);
+    let notice = !msg.is_me && session.is_notice(mentioned);
+
     if let Ok(sid) = Session::last(
         &s_db,
         id,
         &SessionType::Group,
         &msg.datetime,
         &scontent,
-        true,
+        !notice,
     ) {
         results
             .rpcs
-            .push(session_last(&sid, &msg.datetime, &scontent, false));
+            .push(session_last(&sid, &msg.datetime, &scontent, !notice));
+        if notice && mentioned {
+            results.rpcs.push(notice_mention(&sid, &msg.id));
+        }
     }
 }
 
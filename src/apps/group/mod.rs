@@ -2,6 +2,6 @@ mod layer;
 mod models;
 
 pub(crate) mod rpc;
-pub(crate) use layer::{group_conn, handle};
-pub(crate) use models::{GroupChat, Member};
+pub(crate) use layer::{broadcast, group_conn, handle};
+pub(crate) use models::{GroupChat, Member, Message};
 pub(crate) use rpc::new_rpc_handler;
@@ -156,6 +156,7 @@ impl Member {
     pub async fn sync(
         base: &PathBuf,
         gid: &PeerId,
+        db_key: &str,
         db: &DStorage,
         fid: &i64,
         from: &i64,
@@ -170,7 +171,9 @@ impl Member {
             if m.leave {
                 leaves.push((m.height, m.pid));
             } else {
-                let mavatar = read_avatar(base, gid, &m.pid).await.unwrap_or(vec![]);
+                let mavatar = read_avatar(base, gid, db_key, &m.pid)
+                    .await
+                    .unwrap_or(vec![]);
                 adds.push((m.height, m.pid, m.name, mavatar))
             }
         }
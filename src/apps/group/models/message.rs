@@ -1,4 +1,4 @@
-use esse_primitives::{id_from_str, MessageType, NetworkMessage};
+use esse_primitives::{id_from_str, id_to_str, MessageType, NetworkMessage};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -8,8 +8,8 @@ use tdn::types::{
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-use crate::group::{from_network_message, raw_to_network_message, to_network_message as tnm};
-use crate::storage::group_db;
+use crate::group::{from_network_message, raw_to_network_message, to_network_message as tnm, Poll};
+use crate::storage::{group_db, release_content};
 
 use super::Member;
 
@@ -18,13 +18,13 @@ pub(crate) struct Message {
     /// db auto-increment id.
     pub id: i64,
     /// group message consensus height.
-    height: i64,
+    pub height: i64,
     /// group's db id.
-    fid: i64,
+    pub fid: i64,
     /// member's db id.
     pub mid: i64,
     /// message is mine.
-    is_me: bool,
+    pub is_me: bool,
     /// message type.
     pub m_type: MessageType,
     /// message content.
@@ -33,6 +33,8 @@ pub(crate) struct Message {
     is_delivery: bool,
     /// message created time.
     pub datetime: i64,
+    /// message mentioned members.
+    pub mentions: Vec<PeerId>,
 }
 
 impl Message {
@@ -55,12 +57,20 @@ impl Message {
             is_me,
             is_delivery: true,
             id: 0,
+            mentions: vec![],
         }
     }
 
     /// here is zero-copy and unwrap is safe. checked.
     fn from_values(mut v: Vec<DsValue>) -> Message {
         Message {
+            mentions: v
+                .pop()
+                .unwrap()
+                .as_str()
+                .split(',')
+                .filter_map(|s| id_from_str(s).ok())
+                .collect(),
             datetime: v.pop().unwrap().as_i64(),
             is_delivery: v.pop().unwrap().as_bool(),
             content: v.pop().unwrap().as_string(),
@@ -81,23 +91,46 @@ impl Message {
             self.mid,
             self.is_me,
             self.m_type.to_int(),
-            self.content,
+            Poll::content_rpc(&self.m_type, &self.content),
             self.is_delivery,
             self.datetime,
+            self.mentions
+                .iter()
+                .map(|p| id_to_str(p))
+                .collect::<Vec<String>>(),
         ])
     }
 
-    pub fn _get(db: &DStorage, id: &i64) -> Result<Message> {
-        let mut matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE id = {}", id))?;
+    pub fn get(db: &DStorage, id: &i64) -> Result<Message> {
+        let mut matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions FROM messages WHERE id = {}", id))?;
+        if matrix.len() > 0 {
+            Ok(Message::from_values(matrix.pop().unwrap())) // safe unwrap.
+        } else {
+            Err(anyhow!("missing message"))
+        }
+    }
+
+    pub fn get_by_height(db: &DStorage, fid: &i64, height: &i64) -> Result<Message> {
+        let mut matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions FROM messages WHERE fid = {} AND height = {}", fid, height))?;
         if matrix.len() > 0 {
             Ok(Message::from_values(matrix.pop().unwrap())) // safe unwrap.
         } else {
-            Err(anyhow!("missing member"))
+            Err(anyhow!("missing message"))
+        }
+    }
+
+    /// the shared manifest messages, prefix is the manifest content's prefix.
+    pub fn get_shared(db: &DStorage, prefix: &str) -> Result<Vec<Message>> {
+        let matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions FROM messages WHERE m_type = {} AND content LIKE '{}%'", MessageType::Share.to_int(), prefix))?;
+        let mut messages = vec![];
+        for values in matrix {
+            messages.push(Message::from_values(values));
         }
+        Ok(messages)
     }
 
     pub fn list(db: &DStorage, fid: &i64) -> Result<Vec<Message>> {
-        let matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE fid = {}", fid))?;
+        let matrix = db.query(&format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions FROM messages WHERE fid = {}", fid))?;
         let mut groups = vec![];
         for values in matrix {
             groups.push(Message::from_values(values));
@@ -114,7 +147,7 @@ impl Message {
             let id = unique_check.pop().unwrap().pop().unwrap().as_i64();
             self.id = id;
         } else {
-            let sql = format!("INSERT INTO messages (height, fid, mid, is_me, m_type, content, is_delivery, datetime) VALUES ({}, {}, {}, {}, {}, '{}', {}, {})",
+            let sql = format!("INSERT INTO messages (height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions) VALUES ({}, {}, {}, {}, {}, '{}', {}, {}, '{}')",
                 self.height,
                 self.fid,
                 self.mid,
@@ -123,6 +156,7 @@ impl Message {
                 self.content,
                 self.is_delivery,
                 self.datetime,
+                self.mentions.iter().map(|p| id_to_str(p)).collect::<Vec<String>>().join(","),
             );
             let id = db.insert(&sql)?;
             self.id = id;
@@ -130,6 +164,14 @@ impl Message {
         Ok(())
     }
 
+    pub fn update_content(db: &DStorage, id: &i64, content: &str) -> Result<usize> {
+        let sql = format!(
+            "UPDATE messages SET content = '{}' WHERE id = {}",
+            content, id,
+        );
+        db.update(&sql)
+    }
+
     pub fn delete(db: &DStorage, fid: &i64) -> Result<usize> {
         let sql = format!("DELETE FROM messages WHERE fid = {}", fid);
         db.delete(&sql)
@@ -138,11 +180,12 @@ impl Message {
     pub async fn sync(
         base: &PathBuf,
         own: &PeerId,
+        db_key: &str,
         db: &DStorage,
         fid: &i64,
         from: &i64,
         to: &i64,
-    ) -> Result<Vec<(i64, PeerId, NetworkMessage, i64)>> {
+    ) -> Result<Vec<(i64, PeerId, NetworkMessage, i64, Vec<PeerId>)>> {
         let sql = format!("SELECT id, pid FROM members WHERE fid = {}", fid);
         let m = db.query(&sql)?;
         let mut members = HashMap::new();
@@ -152,14 +195,14 @@ impl Message {
             members.insert(id, mid);
         }
 
-        let sql = format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE fid = {} AND height BETWEEN {} AND {}", fid, from, to);
+        let sql = format!("SELECT id, height, fid, mid, is_me, m_type, content, is_delivery, datetime, mentions FROM messages WHERE fid = {} AND height BETWEEN {} AND {}", fid, from, to);
         let matrix = db.query(&sql)?;
         let mut messages = vec![];
         for values in matrix {
             let msg = Message::from_values(values);
-            if let Ok(nmsg) = tnm(own, base, msg.m_type, msg.content).await {
+            if let Ok(nmsg) = tnm(own, base, db_key, msg.m_type, msg.content).await {
                 let mid = members.get(&msg.mid).cloned().unwrap_or(PeerId::default());
-                messages.push((msg.height, mid, nmsg, msg.datetime))
+                messages.push((msg.height, mid, nmsg, msg.datetime, msg.mentions))
             }
         }
 
@@ -193,6 +236,7 @@ pub(crate) async fn handle_network_message(
     mid: PeerId,
     msg: NetworkMessage,
     datetime: i64,
+    mentions: Vec<PeerId>,
     results: &mut HandleResult,
 ) -> Result<Message> {
     let db = group_db(base, own, db_key)?;
@@ -200,6 +244,11 @@ pub(crate) async fn handle_network_message(
     let is_me = &mid == own;
     let (m_type, raw) = from_network_message(own, base, db_key, msg, results).await?;
     let mut msg = Message::new_with_time(height, id, mdid, is_me, m_type, raw, datetime);
-    msg.insert(&db)?;
+    msg.mentions = mentions;
+    if let Err(e) = msg.insert(&db) {
+        // the blob reference only kept by the saved message.
+        let _ = release_content(base, own, db_key, &msg.m_type, &msg.content);
+        return Err(e);
+    }
     Ok(msg)
 }
@@ -1,19 +1,20 @@
-use esse_primitives::MessageType;
+use esse_primitives::{id_from_str, MessageType};
 use group_types::{Event, LayerEvent, GROUP_CHAT_ID};
 use std::sync::Arc;
 use tdn::types::{
     message::{RpcSendMessage, SendType},
-    primitives::HandleResult,
+    primitives::{HandleResult, PeerId},
     rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
 
+use crate::apps::file::{share_fetch, Manifest};
 use crate::global::Global;
-use crate::group::{raw_to_network_message, Friend, InviteType};
+use crate::group::{raw_to_network_message, Friend, InviteType, Poll, Vote};
 use crate::rpc::{session_create, session_delete, session_update_name};
 use crate::session::{Session, SessionType};
-use crate::storage::{chat_db, group_db, read_avatar, session_db, write_avatar};
+use crate::storage::{chat_db, group_db, read_avatar, release_content, session_db, write_avatar};
 
-use super::layer::{broadcast, update_session};
+use super::layer::{broadcast, poll_close, poll_vote, update_session};
 use super::models::{to_network_message, GroupChat, Member, Message};
 
 #[inline]
@@ -46,6 +47,25 @@ pub(crate) fn message_create(msg: &Message) -> RpcParam {
     rpc_response(0, "group-message-create", json!(msg.to_rpc()))
 }
 
+#[inline]
+pub(crate) fn poll_result(id: &i64, mid: &i64, poll: &Poll, votes: &[Vote]) -> RpcParam {
+    rpc_response(
+        0,
+        "group-poll-result",
+        json!([id, Vote::to_rpc(mid, poll, votes)]),
+    )
+}
+
+#[inline]
+pub(crate) fn share_file(id: &i64, share: &str, did: &str, file: RpcParam) -> RpcParam {
+    rpc_response(0, "group-share-file", json!([id, share, did, file]))
+}
+
+#[inline]
+pub(crate) fn share_sync(id: &i64, mid: &i64, content: &str) -> RpcParam {
+    rpc_response(0, "group-share-sync", json!([id, mid, content]))
+}
+
 #[inline]
 fn group_list(groups: Vec<GroupChat>) -> RpcParam {
     let mut results = vec![];
@@ -124,7 +144,7 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
 
             let mut m = Member::new(gh, id, pid, me.name);
             m.insert(&db)?;
-            let _ = write_avatar(&state.base, &pid, &pid, &me.avatar).await;
+            let _ = write_avatar(&state.base, &pid, &db_key, &pid, &me.avatar).await;
 
             // Add new session.
             let mut session = gc.to_session();
@@ -183,7 +203,7 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             crate::group::update_session(&s_db, &fid, &msg, &mut results);
 
             // handle group member
-            let avatar = read_avatar(&state.base, &pid, &f.pid)
+            let avatar = read_avatar(&state.base, &pid, &db_key, &f.pid)
                 .await
                 .unwrap_or(vec![]);
             let event = Event::MemberJoin(f.pid, f.name.clone(), avatar);
@@ -217,6 +237,16 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
             let m_type = MessageType::from_int(params[1].as_i64().ok_or(RpcError::ParseError)?);
             let m_content = params[2].as_str().ok_or(RpcError::ParseError)?;
+            // mentions is optional, older UI only sends 3 params.
+            let mentions: Vec<PeerId> = params
+                .get(3)
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str().and_then(|s| id_from_str(s).ok()))
+                        .collect()
+                })
+                .unwrap_or(vec![]);
 
             let pid = state.pid().await;
             let db_key = state.own.read().await.db_key(&pid)?;
@@ -230,6 +260,73 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let mut results = HandleResult::new();
             let (nmsg, datetime, raw) =
                 to_network_message(&pid, &state.base, &db_key, m_type, m_content).await?;
+            let event = Event::message(pid, nmsg, datetime, mentions.clone());
+
+            if group.local {
+                // local save.
+                let new_h = state.layer.write().await.group_mut(&gid)?.increased();
+
+                let mut msg = Message::new_with_time(new_h, id, mid, true, m_type, raw, datetime);
+                msg.mentions = mentions;
+                if let Err(e) = msg.insert(&db) {
+                    let _ = release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content);
+                    return Err(e.into());
+                }
+                results.rpcs.push(msg.to_rpc());
+                GroupChat::add_height(&db, id, new_h)?;
+
+                // UPDATE SESSION.
+                update_session(&s_db, &pid, &id, &msg, &mut results);
+
+                // broadcast.
+                let data = LayerEvent::Sync(gid, new_h, event);
+                broadcast(&gid, &state, &data, &mut results).await?;
+            } else {
+                // the message saved when synced from server, with a new reference.
+                release_content(&state.base, &pid, &db_key, &m_type, &raw)?;
+
+                // send to server.
+                let data = bincode::serialize(&LayerEvent::Sync(gid, 0, event))?;
+                let msg = SendType::Event(0, group.addr, data);
+                results.layers.push((GROUP_CHAT_ID, msg));
+            }
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "group-poll-create",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let question = params[1].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let options: Vec<String> = params[2]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_owned()))
+                .collect();
+            let multiple = params[3].as_bool().ok_or(RpcError::ParseError)?;
+            let deadline = params[4].as_i64().ok_or(RpcError::ParseError)?;
+
+            let poll = Poll::new(question, options, multiple, deadline);
+            if poll.options.len() < 2 || poll.is_closed() {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = group_db(&state.base, &pid, &db_key)?;
+            let s_db = session_db(&state.base, &pid, &db_key)?;
+
+            let group = GroupChat::get(&db, &id)?;
+            let gid = group.gid;
+            let mid = Member::get_id(&db, &id, &pid)?;
+
+            let mut results = HandleResult::new();
+            let m_type = MessageType::Poll;
+            let (nmsg, datetime, raw) =
+                to_network_message(&pid, &state.base, &db_key, m_type, &poll.to_content()).await?;
             let event = Event::MessageCreate(pid, nmsg, datetime);
 
             if group.local {
@@ -242,7 +339,7 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
                 GroupChat::add_height(&db, id, new_h)?;
 
                 // UPDATE SESSION.
-                update_session(&s_db, &id, &msg, &mut results);
+                update_session(&s_db, &pid, &id, &msg, &mut results);
 
                 // broadcast.
                 let data = LayerEvent::Sync(gid, new_h, event);
@@ -258,6 +355,148 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
         },
     );
 
+    handler.add_method(
+        "group-poll-vote",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let mid = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            let options: Vec<u32> = params[2]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|v| v.as_u64().map(|o| o as u32))
+                .collect();
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = group_db(&state.base, &pid, &db_key)?;
+
+            let group = GroupChat::get(&db, &id)?;
+            let gid = group.gid;
+            let height = Message::get(&db, &mid)?.height;
+
+            let mut results = HandleResult::new();
+            if group.local {
+                // local tally.
+                let (msg, poll, votes) = poll_vote(&db, &id, pid, &height, options)?;
+                results.rpcs.push(Vote::to_rpc(&msg.id, &poll, &votes));
+
+                // broadcast.
+                let data =
+                    LayerEvent::PollRes(gid, height, poll.deadline, Vote::to_network(&votes));
+                broadcast(&gid, &state, &data, &mut results).await?;
+            } else {
+                // send to server, the result will be tallied by server.
+                let event = Event::PollVote(pid, height, options);
+                let data = bincode::serialize(&LayerEvent::Sync(gid, 0, event))?;
+                let msg = SendType::Event(0, group.addr, data);
+                results.layers.push((GROUP_CHAT_ID, msg));
+            }
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "group-poll-close",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let mid = params[1].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = group_db(&state.base, &pid, &db_key)?;
+
+            let group = GroupChat::get(&db, &id)?;
+            let gid = group.gid;
+            let height = Message::get(&db, &mid)?.height;
+
+            let mut results = HandleResult::new();
+            if group.local {
+                let (msg, poll, votes) = poll_close(&db, &id, &pid, &pid, &height)?;
+                results.rpcs.push(Vote::to_rpc(&msg.id, &poll, &votes));
+
+                // broadcast.
+                let data =
+                    LayerEvent::PollRes(gid, height, poll.deadline, Vote::to_network(&votes));
+                broadcast(&gid, &state, &data, &mut results).await?;
+            } else {
+                let event = Event::PollClose(pid, height);
+                let data = bincode::serialize(&LayerEvent::Sync(gid, 0, event))?;
+                let msg = SendType::Event(0, group.addr, data);
+                results.layers.push((GROUP_CHAT_ID, msg));
+            }
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "group-poll-result",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let mid = params[1].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = group_db(&state.base, &pid, &db_key)?;
+
+            let group = GroupChat::get(&db, &id)?;
+            let msg = Message::get(&db, &mid)?;
+            if msg.fid != id || msg.m_type != MessageType::Poll {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+            let poll = Poll::from_content(&msg.content)?;
+            let votes = Vote::list(&db, &msg.id)?;
+
+            let mut results = HandleResult::rpc(Vote::to_rpc(&msg.id, &poll, &votes));
+            if !group.local {
+                // refresh the tally from server.
+                let data = bincode::serialize(&LayerEvent::PollReq(group.gid, msg.height))?;
+                let s = SendType::Event(0, group.addr, data);
+                results.layers.push((GROUP_CHAT_ID, s));
+            }
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "group-share-fetch",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let mid = params[1].as_i64().ok_or(RpcError::ParseError)?;
+            let did = params[2].as_str().ok_or(RpcError::ParseError)?.to_owned();
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = group_db(&state.base, &pid, &db_key)?;
+
+            let group = GroupChat::get(&db, &id)?;
+            let msg = Message::get(&db, &mid)?;
+            if msg.m_type != MessageType::Share || msg.is_me {
+                return Err(RpcError::Custom("Share is invalid!".to_owned()));
+            }
+            let manifest = Manifest::from_content(&msg.content)?;
+            share_fetch(&manifest, &did)?;
+
+            // file content will response in group-share-file.
+            // server relay the request to owner, if i'm the server, send to owner directly.
+            let (to, mpid) = if group.local {
+                (manifest.owner, pid)
+            } else {
+                (group.addr, manifest.owner)
+            };
+            let event = LayerEvent::ShareReq(group.gid, mpid, manifest.did.to_hex(), did);
+            let data = bincode::serialize(&event)?;
+            let mut results = HandleResult::new();
+            results
+                .layers
+                .push((GROUP_CHAT_ID, SendType::Event(0, to, data)));
+            Ok(results)
+        },
+    );
+
     handler.add_method(
         "group-name",
         |params: Vec<RpcParam>, state: Arc<Global>| async move {
@@ -302,7 +541,11 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let db = group_db(&state.base, &pid, &db_key)?;
             let s_db = session_db(&state.base, &pid, &db_key)?;
 
+            let messages = Message::list(&db, &id)?;
             let g = GroupChat::delete(&db, &id)?;
+            for msg in messages {
+                release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content)?;
+            }
             let sid = Session::delete(&s_db, &id, &SessionType::Group)?;
             results.rpcs.push(session_delete(&sid));
 
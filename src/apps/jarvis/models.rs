@@ -63,6 +63,19 @@ impl Message {
         Ok(messages)
     }
 
+    pub fn get(db: &DStorage, id: i64) -> Result<Message> {
+        let sql = format!(
+            "SELECT id, is_me, m_type, content, datetime FROM messages WHERE id = {}",
+            id
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            Ok(Message::from_values(matrix.pop().unwrap())) // safe unwrap()
+        } else {
+            Err(anyhow!("message is missing."))
+        }
+    }
+
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
         let sql = format!(
             "INSERT INTO messages (is_me, m_type, content, datetime) VALUES ({}, {}, '{}',{})",
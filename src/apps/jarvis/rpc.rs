@@ -16,7 +16,7 @@ use tokio::sync::mpsc::Sender;
 use crate::account::lang_from_i64;
 use crate::global::Global;
 use crate::group::raw_to_network_message;
-use crate::storage::jarvis_db;
+use crate::storage::{jarvis_db, release_content};
 use crate::utils::answer::load_answer;
 
 use super::models::Message;
@@ -82,7 +82,10 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let (_, raw) =
                 raw_to_network_message(&pid, &state.base, &db_key, &m_type, content).await?;
             let mut msg = Message::new(m_type, raw, true);
-            msg.insert(&db)?;
+            if let Err(e) = msg.insert(&db) {
+                let _ = release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content);
+                return Err(e.into());
+            }
 
             let results = HandleResult::rpc(msg.to_rpc());
             tokio::spawn(reply(state.rpc_send.clone(), db, lang, msg));
@@ -98,8 +101,10 @@ pub(crate) fn new_rpc_handler(handler: &mut RpcHandler<Global>) {
             let pid = state.pid().await;
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = jarvis_db(&state.base, &pid, &db_key)?;
+            let msg = Message::get(&db, id)?;
             Message::delete(&db, id)?;
             db.close()?;
+            release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content)?;
             Ok(HandleResult::new())
         },
     );
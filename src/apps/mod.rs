@@ -13,16 +13,16 @@ use tdn::types::{
 
 use crate::global::Global;
 use crate::rpc::session_lost;
-use crate::storage::group_db;
+use crate::storage::{dao_db, group_db};
 
 pub(crate) mod cloud;
+pub(crate) mod dao;
 pub(crate) mod device;
 pub(crate) mod domain;
 pub(crate) mod file;
 pub(crate) mod group;
 pub(crate) mod jarvis;
 pub(crate) mod wallet;
-//pub(crate) mod dao;
 
 pub(crate) fn app_rpc_inject(handler: &mut RpcHandler<Global>) {
     device::new_rpc_handler(handler);
@@ -32,7 +32,7 @@ pub(crate) fn app_rpc_inject(handler: &mut RpcHandler<Global>) {
     group::new_rpc_handler(handler);
     wallet::new_rpc_handler(handler);
     cloud::new_rpc_handler(handler);
-    //dao::new_rpc_handler(handler);
+    dao::new_rpc_handler(handler);
 }
 
 pub(crate) async fn app_layer_handle(
@@ -46,7 +46,7 @@ pub(crate) async fn app_layer_handle(
         (GROUP_CHAT_ID, 0) | (0, GROUP_CHAT_ID) => group::handle(msg, global).await,
         (DOMAIN_ID, 0) | (0, DOMAIN_ID) => domain::handle(msg, global).await,
         (CLOUD_ID, 0) | (0, CLOUD_ID) => cloud::handle(msg, global).await,
-        (DAO_ID, 0) | (0, DAO_ID) => cloud::handle(msg, global).await, // TODO DAO
+        (DAO_ID, 0) | (0, DAO_ID) => dao::handle(msg, global).await,
         _ => match msg {
             RecvType::Leave(peer) => {
                 debug!("Peer leaved: {}", peer.id.to_hex());
@@ -89,6 +89,30 @@ pub(crate) async fn app_layer_handle(
                     }
                 }
 
+                // dao sessions.
+                let d_db = dao_db(&global.base, &pid, &db_key)?;
+                let mut lost = vec![];
+                let mut offline = vec![];
+                for (did, session) in &layer.daos {
+                    if session.addrs[0] == peer.id {
+                        lost.push(*did);
+                        results.rpcs.push(session_lost(&session.sid));
+                    } else if session.addrs.contains(&peer.id) {
+                        offline.push(*did);
+                        if let Ok((mid, _)) = dao::Member::get_id(&d_db, &session.db_id, &peer.id) {
+                            results
+                                .rpcs
+                                .push(dao::rpc::member_offline(session.db_id, mid));
+                        }
+                    }
+                }
+                for did in lost {
+                    let _ = layer.dao_del(&did);
+                }
+                for did in offline {
+                    let _ = layer.dao_del_online(&did, &peer.id);
+                }
+
                 Ok(results)
             }
             _ => {
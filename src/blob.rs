@@ -0,0 +1,141 @@
+use esse_primitives::id_to_str;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::primitives::{PeerId, Result};
+use tdn_storage::local::{DStorage, DsValue};
+
+/// Content-addressed blob, the local file name is the blake3 hash of content.
+/// It is referenced by messages, when no reference, it will be removed by gc.
+pub(crate) struct Blob {
+    /// db auto-increment id.
+    pub id: i64,
+    /// blake3 hash hex of the plain content.
+    pub hash: String,
+    /// stored directory.
+    pub dir: String,
+    /// stored file name.
+    pub name: String,
+    /// content size.
+    pub size: i64,
+    /// reference count.
+    pub refs: i64,
+    /// created time.
+    pub datetime: i64,
+}
+
+impl Blob {
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            refs: v.pop().unwrap().as_i64(),
+            size: v.pop().unwrap().as_i64(),
+            name: v.pop().unwrap().as_string(),
+            dir: v.pop().unwrap().as_string(),
+            hash: v.pop().unwrap().as_string(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    pub fn get(db: &DStorage, dir: &str, hash: &str) -> Result<Self> {
+        let sql = format!(
+            "SELECT id, hash, dir, name, size, refs, datetime FROM blobs WHERE dir = '{}' AND hash = '{}'",
+            dir, hash
+        );
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            Ok(Self::from_values(matrix.pop().unwrap())) // safe unwrap()
+        } else {
+            Err(anyhow!("blob is missing"))
+        }
+    }
+
+    /// add a reference to the blob, if it is new blob, return true.
+    pub fn add(db: &DStorage, dir: &str, hash: &str, name: &str, size: usize) -> Result<bool> {
+        if let Ok(blob) = Self::get(db, dir, hash) {
+            let sql = format!("UPDATE blobs SET refs = refs + 1 WHERE id = {}", blob.id);
+            db.update(&sql)?;
+            return Ok(false);
+        }
+
+        let start = SystemTime::now();
+        let datetime = start
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0) as i64; // safe for all life.
+
+        let sql = format!(
+            "INSERT INTO blobs (hash, dir, name, size, refs, datetime) VALUES ('{}', '{}', '{}', {}, 1, {})",
+            hash, dir, name, size, datetime
+        );
+        db.insert(&sql)?;
+        Ok(true)
+    }
+
+    /// release a reference of the blob.
+    pub fn release(db: &DStorage, dir: &str, hash: &str) -> Result<usize> {
+        let sql = format!(
+            "UPDATE blobs SET refs = refs - 1 WHERE dir = '{}' AND hash = '{}' AND refs > 0",
+            dir, hash
+        );
+        db.update(&sql)
+    }
+
+    /// the blobs which have no reference.
+    pub fn orphans(db: &DStorage) -> Result<Vec<Self>> {
+        let matrix = db
+            .query("SELECT id, hash, dir, name, size, refs, datetime FROM blobs WHERE refs <= 0")?;
+        let mut blobs = vec![];
+        for values in matrix {
+            blobs.push(Self::from_values(values));
+        }
+        Ok(blobs)
+    }
+
+    pub fn delete(db: &DStorage, id: &i64) -> Result<usize> {
+        let sql = format!("DELETE FROM blobs WHERE id = {}", id);
+        db.delete(&sql)
+    }
+}
+
+/// The peer's avatar, it is a reference of the avatar blob.
+pub(crate) struct Avatar;
+
+impl Avatar {
+    /// the avatar blob hash of the peer.
+    pub fn get(db: &DStorage, pid: &PeerId) -> Result<String> {
+        let sql = format!("SELECT hash FROM avatars WHERE pid = '{}'", id_to_str(pid));
+        let mut matrix = db.query(&sql)?;
+        match matrix.pop() {
+            Some(mut values) => Ok(values.pop().unwrap().as_string()), // safe unwrap()
+            None => Err(anyhow!("avatar is missing")),
+        }
+    }
+
+    /// set the peer's avatar blob, return the old blob hash.
+    pub fn set(db: &DStorage, pid: &PeerId, hash: &str) -> Result<Option<String>> {
+        let old = Self::get(db, pid).ok();
+        if old.is_some() {
+            let sql = format!(
+                "UPDATE avatars SET hash = '{}' WHERE pid = '{}'",
+                hash,
+                id_to_str(pid)
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!(
+                "INSERT INTO avatars (pid, hash) VALUES ('{}', '{}')",
+                id_to_str(pid),
+                hash
+            );
+            db.insert(&sql)?;
+        }
+        Ok(old)
+    }
+
+    /// delete the peer's avatar, return the blob hash.
+    pub fn delete(db: &DStorage, pid: &PeerId) -> Result<Option<String>> {
+        let old = Self::get(db, pid).ok();
+        let sql = format!("DELETE FROM avatars WHERE pid = '{}'", id_to_str(pid));
+        db.delete(&sql)?;
+        Ok(old)
+    }
+}
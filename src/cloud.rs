@@ -0,0 +1,447 @@
+//! ESSE personal data cloud service provider.
+//! Running as a standalone TDN node, serves the `cloud_types::LayerPeerEvent`.
+//! All events and files are encrypted by peers, provider only stores them.
+
+#[macro_use]
+extern crate tracing;
+
+#[macro_use]
+extern crate anyhow;
+
+use cloud_types::{LayerPeerEvent, LayerServerEvent, CLOUD_ID, SYNC_BATCH};
+use std::env::args;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::{
+    prelude::*,
+    types::{
+        message::RecvType,
+        primitives::{HandleResult, PeerId, Result},
+    },
+};
+use tdn_storage::local::DStorage;
+use tokio::sync::mpsc::Sender;
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
+
+/// Provider's database name.
+const PROVIDER_DB: &'static str = "cloud_provider.db";
+
+/// Provider's files directory.
+const FILES_DIR: &'static str = "cloud_files";
+
+/// Provider's name, response in Check.
+const PROVIDER_NAME: &'static str = "cloud.esse";
+
+/// Free space for every peer, 100MB.
+const FREE_SPACE: u64 = 104857600;
+
+/// VIP space & monthly price (cent).
+const VIP_SPACES: [(u64, u64); 3] = [
+    (1073741824, 100),    // 1GB
+    (10737418240, 500),   // 10GB
+    (107374182400, 2000), // 100GB
+];
+
+/// VIP months duration (seconds), 30 days.
+const VIP_MONTH: i64 = 2592000;
+
+/// Max size of one event.
+const MAX_EVENT_SIZE: usize = 1048576;
+
+/// Max size of one file.
+const MAX_FILE_SIZE: usize = 16777216;
+
+#[rustfmt::skip]
+const PROVIDER_VERSIONS: [&str; 4] = [
+  "CREATE TABLE IF NOT EXISTS events(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pid TEXT NOT NULL,
+    height INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE INDEX IF NOT EXISTS events_pid ON events (pid, height);",
+  "CREATE TABLE IF NOT EXISTS files(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pid TEXT NOT NULL,
+    name TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS vips(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pid TEXT NOT NULL UNIQUE,
+    tier INTEGER NOT NULL,
+    expire INTEGER NOT NULL);",
+];
+
+#[inline]
+fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
+
+/// the peer's space, VIP tier's space when not expired, otherwise free space.
+fn space(db: &DStorage, pid: &PeerId) -> Result<u64> {
+    let mut matrix = db.query(&format!(
+        "SELECT tier FROM vips WHERE pid = '{}' AND expire > {}",
+        pid.to_hex(),
+        now()
+    ))?;
+    let tier = matrix.pop().map(|mut v| v.pop().unwrap().as_i64()); // safe unwrap.
+    Ok(tier
+        .and_then(|t| VIP_SPACES.get(t as usize))
+        .map(|(s, _)| *s)
+        .unwrap_or(FREE_SPACE))
+}
+
+/// set the peer's VIP tier for months, renew the same tier from its expire.
+fn vip(db: &DStorage, pid: &PeerId, tier: usize, months: i64) -> Result<i64> {
+    if tier >= VIP_SPACES.len() || months < 1 {
+        return Err(anyhow!("vip tier is invalid"));
+    }
+    let mut matrix = db.query(&format!(
+        "SELECT id, tier, expire FROM vips WHERE pid = '{}'",
+        pid.to_hex()
+    ))?;
+    let start = now();
+    if let Some(mut values) = matrix.pop() {
+        let expire = values.pop().unwrap().as_i64(); // safe unwrap.
+        let old = values.pop().unwrap().as_i64() as usize; // safe unwrap.
+        let id = values.pop().unwrap().as_i64(); // safe unwrap.
+        let from = if old == tier && expire > start {
+            expire
+        } else {
+            start
+        };
+        let expire = from + months * VIP_MONTH;
+        db.update(&format!(
+            "UPDATE vips SET tier = {}, expire = {} WHERE id = {}",
+            tier, expire, id
+        ))?;
+        Ok(expire)
+    } else {
+        let expire = start + months * VIP_MONTH;
+        db.insert(&format!(
+            "INSERT INTO vips (pid, tier, expire) VALUES ('{}', {}, {})",
+            pid.to_hex(),
+            tier,
+            expire
+        ))?;
+        Ok(expire)
+    }
+}
+
+/// the peer's used space, events and files.
+fn used(db: &DStorage, pid: &PeerId) -> Result<u64> {
+    let mut used = 0;
+    for table in ["events", "files"] {
+        let matrix = db.query(&format!(
+            "SELECT size FROM {} WHERE pid = '{}'",
+            table,
+            pid.to_hex()
+        ))?;
+        for mut values in matrix {
+            used += values.pop().unwrap().as_i64() as u64; // safe unwrap.
+        }
+    }
+    Ok(used)
+}
+
+/// file is saved with name hash, name is saved as hex.
+fn file_path(base: &PathBuf, pid: &PeerId, name: &str) -> PathBuf {
+    let mut path = base.clone();
+    path.push(FILES_DIR);
+    path.push(pid.to_hex());
+    path.push(blake3::hash(name.as_bytes()).to_hex().as_str());
+    path
+}
+
+/// stored file size, if not exist, return None.
+fn file_size(db: &DStorage, pid: &PeerId, name: &str) -> Result<Option<(i64, u64)>> {
+    let mut matrix = db.query(&format!(
+        "SELECT id, size FROM files WHERE pid = '{}' AND name = '{}'",
+        pid.to_hex(),
+        hex::encode(name.as_bytes())
+    ))?;
+    Ok(matrix.pop().map(|mut v| {
+        let size = v.pop().unwrap().as_i64() as u64; // safe unwrap.
+        (v.pop().unwrap().as_i64(), size) // safe unwrap.
+    }))
+}
+
+#[inline]
+fn reply(results: &mut HandleResult, addr: PeerId, event: LayerServerEvent) -> Result<()> {
+    let data = bincode::serialize(&event)?;
+    results.layers.push((0, SendType::Event(0, addr, data)));
+    Ok(())
+}
+
+async fn handle(
+    addr: PeerId,
+    event: LayerPeerEvent,
+    db: &DStorage,
+    base: &PathBuf,
+    results: &mut HandleResult,
+) -> Result<()> {
+    match event {
+        LayerPeerEvent::Check => {
+            reply(
+                results,
+                addr,
+                LayerServerEvent::Status(PROVIDER_NAME.to_owned(), FREE_SPACE, VIP_SPACES.to_vec()),
+            )?;
+        }
+        LayerPeerEvent::PeerCheck(pid) => {
+            let matrix = db.query(&format!(
+                "SELECT id FROM events WHERE pid = '{}' LIMIT 1",
+                pid.to_hex()
+            ))?;
+            reply(
+                results,
+                addr,
+                LayerServerEvent::PeerStatus(pid, matrix.len() > 0),
+            )?;
+        }
+        LayerPeerEvent::Event(data) => {
+            let total = space(db, &addr)?;
+            let used = used(db, &addr)?;
+            let size = data.len() as u64;
+            if data.len() > MAX_EVENT_SIZE || used + size > total {
+                return reply(results, addr, LayerServerEvent::Full(used, total));
+            }
+
+            let mut matrix = db.query(&format!(
+                "SELECT height FROM events WHERE pid = '{}' ORDER BY height DESC LIMIT 1",
+                addr.to_hex()
+            ))?;
+            let height = matrix
+                .pop()
+                .map(|mut v| v.pop().unwrap().as_i64()) // safe unwrap.
+                .unwrap_or(0)
+                + 1;
+            db.insert(&format!(
+                "INSERT INTO events (pid, height, size, data, datetime) VALUES ('{}', {}, {}, '{}', {})",
+                addr.to_hex(),
+                height,
+                size,
+                hex::encode(&data),
+                now()
+            ))?;
+            reply(results, addr, LayerServerEvent::Stored(used + size, total))?;
+        }
+        LayerPeerEvent::File(name, data) => {
+            let total = space(db, &addr)?;
+            let used = used(db, &addr)?;
+            let old = file_size(db, &addr, &name)?;
+            let size = data.len() as u64;
+            let new_used = used - old.map(|(_, s)| s).unwrap_or(0) + size;
+            if data.len() > MAX_FILE_SIZE || new_used > total {
+                return reply(results, addr, LayerServerEvent::Full(used, total));
+            }
+
+            let path = file_path(base, &addr, &name);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, data).await?;
+
+            if let Some((id, _)) = old {
+                db.update(&format!(
+                    "UPDATE files SET size = {}, datetime = {} WHERE id = {}",
+                    size,
+                    now(),
+                    id
+                ))?;
+            } else {
+                db.insert(&format!(
+                    "INSERT INTO files (pid, name, size, datetime) VALUES ('{}', '{}', {}, {})",
+                    addr.to_hex(),
+                    hex::encode(name.as_bytes()),
+                    size,
+                    now()
+                ))?;
+            }
+            reply(results, addr, LayerServerEvent::Stored(new_used, total))?;
+        }
+        LayerPeerEvent::Pull(height) => {
+            let matrix = db.query(&format!(
+                "SELECT height, data FROM events WHERE pid = '{}' AND height > {} ORDER BY height LIMIT {}",
+                addr.to_hex(),
+                height,
+                SYNC_BATCH + 1
+            ))?;
+            let more = matrix.len() > SYNC_BATCH;
+            let mut events = vec![];
+            for mut values in matrix.into_iter().take(SYNC_BATCH) {
+                let data = hex::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
+                let height = values.pop().unwrap().as_i64() as u64;
+                events.push((height, data));
+            }
+            reply(results, addr, LayerServerEvent::SyncEvent(events, more))?;
+        }
+        LayerPeerEvent::PullFile(name) => {
+            let data = if file_size(db, &addr, &name)?.is_some() {
+                tokio::fs::read(file_path(base, &addr, &name))
+                    .await
+                    .unwrap_or(vec![])
+            } else {
+                vec![]
+            };
+            reply(results, addr, LayerServerEvent::SyncFile(name, data))?;
+        }
+        LayerPeerEvent::FileList => {
+            let matrix = db.query(&format!(
+                "SELECT name, size FROM files WHERE pid = '{}' ORDER BY id",
+                addr.to_hex()
+            ))?;
+            let mut files = vec![];
+            for mut values in matrix {
+                let size = values.pop().unwrap().as_i64() as u64;
+                let name = hex::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
+                files.push((String::from_utf8(name).unwrap_or(String::new()), size));
+            }
+            reply(results, addr, LayerServerEvent::Files(files))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn migrate(db: &DStorage) -> Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS migrates(
+           id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+           version INTEGER NOT NULL);",
+    )?;
+    let mut matrix = db.query("SELECT version FROM migrates")?;
+    let version = if let Some(mut values) = matrix.pop() {
+        values.pop().unwrap().as_i64() as usize // safe unwrap.
+    } else {
+        db.insert("INSERT INTO migrates (version) VALUES (0)")?;
+        0
+    };
+
+    if version != PROVIDER_VERSIONS.len() {
+        for i in &PROVIDER_VERSIONS[version..] {
+            db.execute(i)?;
+        }
+        db.update(&format!(
+            "UPDATE migrates SET version = {}",
+            PROVIDER_VERSIONS.len()
+        ))?;
+    }
+    Ok(())
+}
+
+async fn send(sender: &Sender<SendMessage>, results: HandleResult) {
+    for (tgid, msg) in results.layers {
+        sender
+            .send(SendMessage::Layer(tgid, msg))
+            .await
+            .expect("TDN channel closed");
+    }
+}
+
+async fn open(db_path: &PathBuf) -> Result<(Config, DStorage)> {
+    let mut config = Config::default();
+    config.db_path = Some(db_path.clone());
+    let config = Config::load_save(db_path.clone(), config).await?;
+    let secret = hex::encode(&config.secret);
+
+    let mut path = db_path.clone();
+    path.push(PROVIDER_DB);
+    let db = DStorage::open(path, &secret)?;
+    migrate(&db)?;
+    Ok((config, db))
+}
+
+/// grant the peer's VIP tier, params: peer id, tier index, months.
+async fn grant(db_path: PathBuf, params: Vec<String>) -> Result<()> {
+    if params.len() != 3 {
+        return Err(anyhow!(
+            "usage: esse-cloud [path] vip [peer id] [tier] [months]"
+        ));
+    }
+    let pid = PeerId::from_hex(&params[0]).map_err(|_| anyhow!("peer id is invalid"))?;
+    let tier: usize = params[1].parse()?;
+    let months: i64 = params[2].parse()?;
+
+    let (_, db) = open(&db_path).await?;
+    let expire = vip(&db, &pid, tier, months)?;
+    info!(
+        "VIP tier {} granted to {}, expire: {}",
+        tier,
+        pid.to_hex(),
+        expire
+    );
+    Ok(())
+}
+
+async fn start(db_path: PathBuf) -> Result<()> {
+    let (config, db) = open(&db_path).await?;
+
+    info!(
+        "Config P2P      : {} {:?}",
+        config.p2p_peer.transport.to_str(),
+        config.p2p_peer.socket
+    );
+
+    info!("Provider storage path {:?}", db_path);
+
+    let (_, _, p2p_config, _) = config.split();
+    let (self_send, mut self_recv) = new_receive_channel();
+    let (sender, tdn_recv) = new_send_channel();
+    let pid = start_main(vec![CLOUD_ID], p2p_config, self_send, tdn_recv, None, None).await?;
+    info!("Cloud provider running: {}", pid.to_hex());
+
+    while let Some(message) = self_recv.recv().await {
+        match message {
+            ReceiveMessage::Layer(_fgid, _tgid, RecvType::Event(addr, bytes)) => {
+                let mut results = HandleResult::new();
+                match bincode::deserialize::<LayerPeerEvent>(&bytes) {
+                    Ok(event) => {
+                        if let Err(e) = handle(addr, event, &db, &db_path, &mut results).await {
+                            warn!("Cloud handle error: {}", e);
+                        }
+                    }
+                    Err(_) => warn!("Cloud got invalid event from {}", addr.to_hex()),
+                }
+                send(&sender, results).await;
+            }
+            ReceiveMessage::NetworkLost => {
+                sender
+                    .send(SendMessage::Network(NetworkType::NetworkReboot))
+                    .await?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_level(true)
+                .with_filter(LevelFilter::INFO),
+        )
+        .init();
+
+    let db_path = PathBuf::from(&args().nth(1).unwrap_or("./.tdn_cloud".to_owned()));
+    if !db_path.exists() {
+        tokio::fs::create_dir_all(&db_path).await.unwrap();
+    }
+
+    // esse-cloud [path] vip [peer id] [tier] [months]
+    if args().nth(2).as_deref() == Some("vip") {
+        grant(db_path, args().skip(3).collect()).await.unwrap();
+    } else {
+        start(db_path).await.unwrap();
+    }
+}
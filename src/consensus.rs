@@ -1,124 +1,374 @@
-use serde::{Deserialize, Serialize};
-use tdn::types::{group::EventId, primitive::Result, rpc::RpcParam};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::{group::EventId, primitives::Result};
 use tdn_storage::local::DStorage;
 
-#[derive(Serialize, Deserialize)]
-pub(crate) enum SyncModel {
-    Request(RpcParam),
-    Friend(RpcParam),
-    Message(RpcParam),
-}
+/// compact the log when it is longer than interval + keep after the snapshot.
+/// snapshot heights are multiples of the interval, so same in all devices.
+const SNAPSHOT_INTERVAL: u64 = 1000;
+
+/// the newest events are kept in the log, used in gap filling.
+const SNAPSHOT_KEEP: u64 = 200;
 
-#[derive(Serialize, Deserialize)]
+/// the own consensus event log, height is the index in the events order.
+/// events are ordered by (create time, hash), same events will get same heights
+/// in all devices, and the chain hash is used to check the heights are same.
 pub(crate) struct Event {
-    pub height: u64,
     pub hash: EventId,
-    path: i64,
-    row: i64,
+    pub data: Vec<u8>,
 }
 
 impl Event {
+    /// the event's order, event_id is create time (16-bytes) + content hash (16-bytes).
+    pub fn order(hash: &EventId) -> (u128, u128) {
+        let mut time_bytes = [0u8; 16];
+        time_bytes.copy_from_slice(&hash.0[0..16]);
+        let mut next_bytes = [0u8; 16];
+        next_bytes.copy_from_slice(&hash.0[16..32]);
+        (
+            u128::from_le_bytes(time_bytes),
+            u128::from_le_bytes(next_bytes),
+        )
+    }
+
+    /// the chain hash of the height, from previous chain hash and the event hash.
+    pub fn chain(pre: &EventId, hash: &EventId) -> EventId {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&pre.0);
+        hasher.update(&hash.0);
+        EventId(*hasher.finalize().as_bytes())
+    }
+
+    /// check the event in the log or in the snapshot.
     pub fn contains_hash(db: &DStorage, hash: &EventId) -> Result<bool> {
         let sql = format!("SELECT id from events WHERE hash = '{}'", hash.to_hex());
+        if db.query(&sql)?.len() > 0 {
+            return Ok(true);
+        }
+        let sql = format!("SELECT id from snapshots WHERE hash = '{}'", hash.to_hex());
         Ok(db.query(&sql)?.len() > 0)
     }
 
-    pub fn get_nexts(db: &DStorage, id: u64) -> Result<Vec<Event>> {
+    /// current height and the chain hash.
+    pub fn tip(db: &DStorage) -> Result<(u64, EventId)> {
+        let mut matrix = db.query("SELECT id, chain from events ORDER BY id DESC LIMIT 1")?;
+        if let Some(mut values) = matrix.pop() {
+            let chain =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let id = values.pop().unwrap().as_i64() as u64; // safe
+            Ok((id, chain))
+        } else {
+            let snapshot = Snapshot::get(db)?;
+            Ok((snapshot.height, snapshot.chain))
+        }
+    }
+
+    /// the chain hashes in the heights, same order as heights, missing is default.
+    pub fn get_assign_chain(db: &DStorage, assigns: &[u64]) -> Result<Vec<EventId>> {
+        if assigns.len() == 0 {
+            return Ok(vec![]);
+        }
+        let ids: Vec<String> = assigns.iter().map(|u| u.to_string()).collect();
+        let sql = format!(
+            "SELECT id, chain from events WHERE id IN ({})",
+            ids.join(",")
+        );
+        let matrix = db.query(&sql)?;
+        let mut chains = vec![EventId::default(); assigns.len()];
+        for mut values in matrix {
+            let chain =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let id = values.pop().unwrap().as_i64() as u64; // safe
+            for (i, height) in assigns.iter().enumerate() {
+                if *height == id {
+                    chains[i] = chain;
+                }
+            }
+        }
+
+        // the events before the snapshot are truncated, only has the snapshot's chain.
+        let snapshot = Snapshot::get(db)?;
+        if snapshot.height > 0 {
+            for (i, height) in assigns.iter().enumerate() {
+                if *height == snapshot.height {
+                    chains[i] = snapshot.chain;
+                }
+            }
+        }
+        Ok(chains)
+    }
+
+    /// the events between heights (include from and to).
+    pub fn get_between(db: &DStorage, from: u64, to: u64) -> Result<Vec<Event>> {
         let sql = format!(
-            "SELECT id, hash, db_table, row from events WHERE id >= {} ORDER BY id",
-            id
+            "SELECT hash, data from events WHERE id BETWEEN {} AND {} ORDER BY id",
+            from, to
         );
         let matrix = db.query(&sql)?;
         let mut events = vec![];
         for mut values in matrix {
-            let row = values.pop().unwrap().as_i64(); // safe
-            let path = values.pop().unwrap().as_i64(); // safe
+            let data = base64::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
             let hash =
                 EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
-            let id = values.pop().unwrap().as_i64(); // safe
-
-            events.push(Self {
-                height: id as u64,
-                hash,
-                path,
-                row,
-            })
+            events.push(Self { hash, data })
         }
 
         Ok(events)
     }
 
-    pub fn get_assign_hash(db: &DStorage, assigns: &Vec<u64>) -> Result<Vec<EventId>> {
-        let sql = if assigns.len() == 1 {
-            format!("SELECT id, hash from events WHERE id = {}", assigns[0])
-        } else {
-            let last = assigns.len() - 1;
-            let mut sql = format!("SELECT id, hash from events WHERE id IN (");
-            for (k, u) in assigns.iter().enumerate() {
-                if last == k {
-                    sql.push_str(&format!("{})", u));
-                } else {
-                    sql.push_str(&format!("{},", u));
+    /// the height which the new event will be merged in.
+    pub fn position(db: &DStorage, hash: &EventId) -> Result<u64> {
+        let order = Self::order(hash);
+        let mut before = i64::MAX;
+        loop {
+            let sql = format!(
+                "SELECT id, hash from events WHERE id < {} ORDER BY id DESC LIMIT 100",
+                before
+            );
+            let matrix = db.query(&sql)?;
+            if matrix.len() == 0 {
+                return Ok(Snapshot::get(db)?.height + 1);
+            }
+            for mut values in matrix {
+                let ehash =
+                    EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+                let id = values.pop().unwrap().as_i64(); // safe
+                if Self::order(&ehash) <= order {
+                    return Ok(id as u64 + 1);
                 }
+                before = id;
             }
-            sql
-        };
-
-        let matrix = db.query(&sql)?;
-        let mut hashes = vec![];
-        for mut values in matrix {
-            hashes.push(
-                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default()),
-            );
         }
+    }
 
-        if assigns.len() > hashes.len() {
-            for _ in 0..(assigns.len() - hashes.len()) {
-                hashes.push(EventId::default());
+    /// check if had newer event of the same model (table & key), it is last-writer-wins.
+    pub fn is_overridden(db: &DStorage, path: i64, key: &str, height: u64) -> Result<bool> {
+        let sql = format!(
+            "SELECT id from events WHERE db_table = {} AND key = '{}' AND id >= {}",
+            path, key, height
+        );
+        Ok(db.query(&sql)?.len() > 0)
+    }
+
+    /// check if the log had newer event of the same model than the hash.
+    pub fn is_newer(db: &DStorage, path: i64, key: &str, hash: &EventId) -> Result<bool> {
+        let sql = format!(
+            "SELECT hash from events WHERE db_table = {} AND key = '{}'",
+            path, key
+        );
+        let order = Self::order(hash);
+        for mut values in db.query(&sql)? {
+            let ehash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            if Self::order(&ehash) > order {
+                return Ok(true);
             }
         }
-
-        Ok(hashes)
+        Ok(false)
     }
 
+    /// merge the event into the height, return the new height and chain hash.
+    /// the log is compacted into the snapshot when it is too long.
     pub(crate) fn merge(
         db: &DStorage,
         hash: EventId,
         path: i64,
-        row: i64,
+        key: &str,
+        data: &[u8],
         index: u64,
-    ) -> Result<()> {
-        // check if height is had.
-        let check_sql = format!("SELECT id from events WHERE id = {}", index);
-        let check_matrix = db.query(&check_sql)?;
-        if check_matrix.len() > 0 {
-            let first_sql = format!(
-                "SELECT id from events WHERE id >= {} ORDER BY id DESC",
-                index
+    ) -> Result<(u64, EventId)> {
+        // move the later events to next height.
+        let sql = format!("UPDATE events SET id = id + 1 WHERE id >= {}", index);
+        db.update(&sql)?;
+
+        let pre = Self::get_assign_chain(db, &[index - 1])?.pop().unwrap(); // safe
+        let mut chain = Self::chain(&pre, &hash);
+        let sql = format!(
+            "INSERT INTO events (id, hash, db_table, row, key, chain, data) VALUES ({}, '{}', {}, 0, '{}', '{}', '{}')",
+            index,
+            hash.to_hex(),
+            path,
+            key,
+            chain.to_hex(),
+            base64::encode(data),
+        );
+        db.insert(&sql)?;
+
+        // rebuild the chain hashes after the height.
+        let sql = format!(
+            "SELECT id, hash from events WHERE id > {} ORDER BY id",
+            index
+        );
+        let matrix = db.query(&sql)?;
+        let mut height = index;
+        for mut values in matrix {
+            let ehash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            height = values.pop().unwrap().as_i64() as u64; // safe
+            chain = Self::chain(&chain, &ehash);
+            let sql = format!(
+                "UPDATE events SET chain = '{}' WHERE id = {}",
+                chain.to_hex(),
+                height
             );
-            let matrix = db.query(&first_sql)?;
-            for mut values in matrix {
-                if let Some(id) = values.pop() {
-                    let now_id = id.as_i64();
-                    let sql = format!(
-                        "UPDATE events SET id = {} WHERE id = {}",
-                        now_id + 1,
-                        now_id
-                    );
-                    db.update(&sql)?;
-                }
-            }
+            db.update(&sql)?;
+        }
+
+        Snapshot::compact(db, height, SNAPSHOT_INTERVAL, SNAPSHOT_KEEP)?;
+        Ok((height, chain))
+    }
+}
+
+/// the snapshot of the account data, it has the newest event of every model
+/// before the height, and the events before the height are truncated.
+pub(crate) struct Snapshot {
+    pub height: u64,
+    /// the last event's hash, the events older than it are in the snapshot.
+    pub hash: EventId,
+    pub chain: EventId,
+}
+
+impl Snapshot {
+    pub fn get(db: &DStorage) -> Result<Snapshot> {
+        let mut matrix = db
+            .query("SELECT height, hash, chain FROM snapshot_infos ORDER BY height DESC LIMIT 1")?;
+        if let Some(mut values) = matrix.pop() {
+            let chain =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let hash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let height = values.pop().unwrap().as_i64() as u64; // safe
+            Ok(Snapshot {
+                height,
+                hash,
+                chain,
+            })
+        } else {
+            Ok(Snapshot {
+                height: 0,
+                hash: EventId::default(),
+                chain: EventId::default(),
+            })
         }
+    }
 
+    pub fn save(&self, db: &DStorage) -> Result<()> {
+        let datetime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|s| s.as_secs())
+            .unwrap_or(0); // safe for all life.
+        db.delete("DELETE FROM snapshot_infos")?;
         let sql = format!(
-            "INSERT INTO events (id, hash, db_table, row) VALUES ({}, '{}', {}, {})",
-            index,
+            "INSERT INTO snapshot_infos (height, hash, chain, datetime) VALUES ({}, '{}', '{}', {})",
+            self.height,
+            self.hash.to_hex(),
+            self.chain.to_hex(),
+            datetime,
+        );
+        db.insert(&sql)?;
+        Ok(())
+    }
+
+    /// the models number in the snapshot.
+    pub fn count(db: &DStorage) -> Result<u64> {
+        let mut matrix = db.query("SELECT COUNT(*) FROM snapshots")?;
+        let count = matrix.pop().and_then(|mut v| v.pop()).map(|v| v.as_i64());
+        Ok(count.unwrap_or(0) as u64)
+    }
+
+    /// the snapshot's events, paged.
+    pub fn entries(db: &DStorage, offset: u64, limit: u64) -> Result<Vec<Event>> {
+        let sql = format!(
+            "SELECT hash, data FROM snapshots ORDER BY id LIMIT {} OFFSET {}",
+            limit, offset
+        );
+        let matrix = db.query(&sql)?;
+        let mut events = vec![];
+        for mut values in matrix {
+            let data = base64::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
+            let hash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            events.push(Event { hash, data })
+        }
+        Ok(events)
+    }
+
+    /// check if the snapshot had newer event of the same model.
+    pub fn is_overridden(db: &DStorage, path: i64, key: &str, hash: &EventId) -> Result<bool> {
+        let sql = format!(
+            "SELECT hash FROM snapshots WHERE db_table = {} AND key = '{}'",
+            path, key
+        );
+        let mut matrix = db.query(&sql)?;
+        if let Some(mut values) = matrix.pop() {
+            let ehash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            Ok(Event::order(&ehash) > Event::order(hash))
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// save the model's event, return false if the snapshot had newer one.
+    pub fn upsert(
+        db: &DStorage,
+        hash: &EventId,
+        path: i64,
+        key: &str,
+        data: &[u8],
+    ) -> Result<bool> {
+        if Self::is_overridden(db, path, key, hash)? {
+            return Ok(false);
+        }
+        let sql = format!(
+            "DELETE FROM snapshots WHERE db_table = {} AND key = '{}'",
+            path, key
+        );
+        db.delete(&sql)?;
+        let sql = format!(
+            "INSERT INTO snapshots (hash, db_table, key, data) VALUES ('{}', {}, '{}', '{}')",
             hash.to_hex(),
             path,
-            row,
+            key,
+            base64::encode(data),
         );
         db.insert(&sql)?;
+        Ok(true)
+    }
+
+    /// move the old events into the snapshot, and truncate the log.
+    /// the newest `keep` events are kept, snapshot height is multiple of `interval`.
+    pub fn compact(db: &DStorage, tip: u64, interval: u64, keep: u64) -> Result<()> {
+        let mut snapshot = Self::get(db)?;
+        if tip < snapshot.height + interval + keep {
+            return Ok(());
+        }
+        let height = (tip - keep) / interval * interval;
+
+        let sql = format!(
+            "SELECT id, hash, db_table, key, chain, data FROM events WHERE id <= {} ORDER BY id",
+            height
+        );
+        let matrix = db.query(&sql)?;
+        for mut values in matrix {
+            let data = base64::decode(values.pop().unwrap().as_str()).unwrap_or(vec![]);
+            let chain =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let key = values.pop().unwrap().as_string();
+            let path = values.pop().unwrap().as_i64();
+            let hash =
+                EventId::from_hex(values.pop().unwrap().as_str()).unwrap_or(EventId::default());
+            let id = values.pop().unwrap().as_i64() as u64; // safe
+
+            Self::upsert(db, &hash, path, &key, &data)?;
+            if id == height {
+                snapshot.hash = hash;
+                snapshot.chain = chain;
+            }
+        }
+        snapshot.height = height;
+        snapshot.save(db)?;
 
+        db.delete(&format!("DELETE FROM events WHERE id <= {}", height))?;
         Ok(())
     }
 }
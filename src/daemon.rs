@@ -10,8 +10,9 @@ use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
 mod account;
 mod apps;
-//mod consensus;
-//mod event;
+mod blob;
+mod consensus;
+mod event;
 mod global;
 mod group;
 mod layer;
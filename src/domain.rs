@@ -0,0 +1,150 @@
+//! ESSE domain name service provider.
+//! Running as a standalone TDN node, serves the `domain_types::LayerPeerEvent`.
+
+#[macro_use]
+extern crate tracing;
+
+#[macro_use]
+extern crate anyhow;
+
+use domain_types::{LayerPeerEvent, DOMAIN_ID};
+use std::collections::HashMap;
+use std::env::args;
+use std::path::PathBuf;
+use tdn::{
+    prelude::*,
+    types::{
+        message::RecvType,
+        primitives::{HandleResult, PeerId, Result},
+    },
+};
+use tdn_storage::local::DStorage;
+use tokio::sync::mpsc::Sender;
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
+
+#[path = "apps/domain/provider.rs"]
+mod provider;
+
+use provider::{handle, migrate, now};
+
+/// Provider's database name.
+const PROVIDER_DB: &'static str = "domain_provider.db";
+
+/// Max events one peer can send in one rate window.
+const RATE_LIMIT: u32 = 30;
+
+/// Rate window seconds.
+const RATE_WINDOW: i64 = 60;
+
+/// Per-peer events counter in current rate window.
+struct RateLimit(HashMap<PeerId, (i64, u32)>);
+
+impl RateLimit {
+    /// check & count the peer event, return false when over limit.
+    fn check(&mut self, pid: &PeerId) -> bool {
+        let now = now();
+        let (start, count) = self.0.entry(*pid).or_insert((now, 0));
+        if now - *start >= RATE_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= RATE_LIMIT
+    }
+
+    /// clear the expired peers.
+    fn clear(&mut self) {
+        let now = now();
+        self.0.retain(|_, (start, _)| now - *start < RATE_WINDOW);
+    }
+}
+
+async fn send(sender: &Sender<SendMessage>, results: HandleResult) {
+    for (tgid, msg) in results.layers {
+        sender
+            .send(SendMessage::Layer(tgid, msg))
+            .await
+            .expect("TDN channel closed");
+    }
+}
+
+async fn start(db_path: PathBuf) -> Result<()> {
+    let mut config = Config::default();
+    config.db_path = Some(db_path.clone());
+    let config = Config::load_save(db_path.clone(), config).await?;
+    let secret = hex::encode(&config.secret);
+
+    info!(
+        "Config P2P      : {} {:?}",
+        config.p2p_peer.transport.to_str(),
+        config.p2p_peer.socket
+    );
+
+    let mut path = db_path.clone();
+    path.push(PROVIDER_DB);
+    let db = DStorage::open(path, &secret)?;
+    migrate(&db)?;
+    info!("Provider storage path {:?}", db_path);
+
+    let (_, _, p2p_config, _) = config.split();
+    let (self_send, mut self_recv) = new_receive_channel();
+    let (sender, tdn_recv) = new_send_channel();
+    let pid = start_main(vec![DOMAIN_ID], p2p_config, self_send, tdn_recv, None, None).await?;
+    info!("Domain provider running: {}", pid.to_hex());
+
+    let mut limits = RateLimit(HashMap::new());
+    let mut last_clear = now();
+
+    while let Some(message) = self_recv.recv().await {
+        match message {
+            ReceiveMessage::Layer(_fgid, _tgid, RecvType::Event(addr, bytes)) => {
+                if !limits.check(&addr) {
+                    warn!("Peer {} over rate limit.", addr.to_hex());
+                    continue;
+                }
+
+                let mut results = HandleResult::new();
+                match bincode::deserialize::<LayerPeerEvent>(&bytes) {
+                    Ok(event) => {
+                        if let Err(e) = handle(addr, event, &db, &mut results) {
+                            warn!("Domain handle error: {}", e);
+                        }
+                    }
+                    Err(_) => warn!("Domain got invalid event from {}", addr.to_hex()),
+                }
+                send(&sender, results).await;
+
+                if now() - last_clear > RATE_WINDOW {
+                    limits.clear();
+                    last_clear = now();
+                }
+            }
+            ReceiveMessage::NetworkLost => {
+                sender
+                    .send(SendMessage::Network(NetworkType::NetworkReboot))
+                    .await?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_level(true)
+                .with_filter(LevelFilter::INFO),
+        )
+        .init();
+
+    let db_path = PathBuf::from(&args().nth(1).unwrap_or("./.tdn_domain".to_owned()));
+    if !db_path.exists() {
+        tokio::fs::create_dir_all(&db_path).await.unwrap();
+    }
+
+    start(db_path).await.unwrap();
+}
@@ -1,143 +1,63 @@
-use esse_primitives::NetworkMessage;
+use esse_primitives::{id_to_str, NetworkMessage};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
-    group::{EventId, GroupId},
-    message::{SendMessage, SendType},
-    primitive::{HandleResult, PeerId, Result},
+    group::EventId,
+    message::SendType,
+    primitives::{HandleResult, PeerId, Result},
 };
 use tdn_storage::local::DStorage;
-use tokio::sync::{mpsc::Sender, RwLock};
 
-use crate::account::{Account, User};
-use crate::consensus::Event as OldEvent;
-use crate::group::GroupEvent;
-use crate::layer::Layer;
+use crate::consensus::{Event, Snapshot};
+use crate::global::Global;
+use crate::group::rpc as chat_rpc;
+use crate::group::{handle_nmsg, update_session, Friend, GroupEvent, Message, Request, Vote};
 use crate::migrate::consensus::{
-    ACCOUNT_TABLE_PATH, FILE_TABLE_PATH, FRIEND_TABLE_PATH, MESSAGE_TABLE_PATH, REQUEST_TABLE_PATH,
+    ACCOUNT_TABLE_PATH, FRIEND_TABLE_PATH, MESSAGE_TABLE_PATH, REQUEST_TABLE_PATH,
+    SESSION_TABLE_PATH,
+};
+use crate::own::OwnEvent;
+use crate::rpc::{account_update, session_close, session_create};
+use crate::session::{Session, SessionNotice, SessionType};
+use crate::storage::{
+    account_db, chat_db, consensus_db, delete_avatar, release_content, session_db,
 };
-use crate::own::{Own, OwnEvent};
-
-use crate::apps::file::{FileDid, RootDirectory};
-use crate::group::rpc as chat_rpc;
-use crate::group::{from_model, Friend, Message, Request};
-use crate::rpc;
-use crate::storage::{delete_avatar_sync, read_avatar_sync, write_avatar_sync};
-
-/// Online state synchronization.
-#[derive(Serialize, Deserialize)]
-pub(crate) enum State {
-    Account,
-    Session,
-    ChatMessage,
-    OwnMessage,
-}
 
-pub(crate) async fn _handle_state(
-    _gid: GroupId,
-    _addr: PeerId,
-    state: State,
-    _group: &Arc<RwLock<Own>>,
-    _layer: &Arc<RwLock<Layer>>,
-    _results: &mut HandleResult,
-) -> Result<()> {
-    match state {
-        State::Account => {}
-        State::Session => {}
-        State::ChatMessage => {}
-        State::OwnMessage => {}
-    }
-    Ok(())
-}
+/// every time sync MAX is 100.
+const SYNC_LIMIT: u64 = 100;
 
-/// Event that will update data.
+/// Event that will update data, replicated between own devices.
+/// event has the whole state of the model, the newest event of a model wins.
 #[derive(Serialize, Deserialize)]
 pub(crate) enum InnerEvent {
     /// account info update.
     /// params: name, avatar.
     UserInfo(String, Vec<u8>),
-    /// Session's request create.
-    /// params: is_me, remote, remark.
-    SessionRequestCreate(bool, User, String),
-    /// Session's request agree or reject, if agree with avatar. friend's addr.
-    SessionRequestHandle(GroupId, bool, Vec<u8>),
+    /// Session's request state.
+    /// params: remote, name, remark, is_me, is_ok, is_over.
+    SessionRequest(PeerId, String, String, bool, bool, bool),
     /// Session's request delete.
-    SessionRequestDelete(GroupId),
-    /// Session's friend update by friend.
-    /// params: f_gid, addr, name, avatar. (TODO addrs as list to store. keep others device)
-    SessionFriendInfo(GroupId, PeerId, String, Vec<u8>),
-    /// Session's friend update by me.
-    /// params: f_gid, remark
-    SessionFriendUpdate(GroupId, String),
-    /// Session's friend close.
-    /// params: f_gid.
-    SessionFriendClose(GroupId),
+    SessionRequestDelete(PeerId),
+    /// Session's friend state.
+    /// params: friend, name, remark, is_closed.
+    SessionFriend(PeerId, String, String, bool),
     /// Sesson's friend delete.
-    SessionFriendDelete(GroupId),
+    SessionFriendDelete(PeerId),
     /// Session's message create.
-    /// params: f_gid, is_me, message_type,
-    SessionMessageCreate(GroupId, bool, EventId, NetworkMessage),
+    /// params: friend, message hash, is_me, message.
+    SessionMessageCreate(PeerId, EventId, bool, NetworkMessage),
     /// Session's message delete.
     SessionMessageDelete(EventId),
-    /// create a file.
-    /// params: file_id, file_parent_id, file_directory, file_name, file_desc, device_addr.
-    FileCreate(FileDid, FileDid, RootDirectory, String, String, PeerId),
-    /// update file info. file_id, file_name, file_desc.
-    FileUpdate(FileDid, String, String),
-    /// update file's parent id (move file to other directory).
-    FileParent(FileDid, FileDid),
-    /// backup file in new device.
-    FileBackup(FileDid, PeerId),
-    /// delete a file.
-    FileDelete(FileDid),
-}
-
-/// Event that not update status. only change UI.
-#[derive(Serialize, Deserialize)]
-pub(crate) enum StatusEvent {
-    /// Session's friend online.
-    SessionFriendOnline(GroupId),
-    /// Session's friend offline.
-    SessionFriendOffline(GroupId),
-}
-
-/// event for sync models. use in sync consensus.
-#[derive(Serialize, Deserialize)]
-pub(crate) enum SyncEvent {
-    /// account info.
-    Account(EventId, String, Vec<u8>),
-    AccountHad(EventId),
-    /// eid, request_gid, addr, name, avatar, remark, is_me, is_ok
-    Request(
-        EventId,
-        GroupId,
-        PeerId,
-        String,
-        Vec<u8>,
-        String,
-        bool,
-        bool,
-        bool,
-    ),
-    RequestHad(EventId, GroupId),
-    /// eid, friend_gid, addr, name, avatar, remark, is_closed
-    Friend(EventId, GroupId, PeerId, String, Vec<u8>, String, bool),
-    FriendHad(EventId, GroupId),
-    /// eid, friend_gid, msg_id, is_me, message.
-    Message(EventId, GroupId, EventId, bool, NetworkMessage),
-    None,
+    /// Session's top & close.
+    /// params: session pid, session type, is_top, is_close.
+    SessionUpdate(String, i64, bool, bool),
+    /// Session's notification rule.
+    /// params: session pid, session type, notice, mute_until.
+    SessionNotice(String, i64, i64, i64),
 }
 
 impl InnerEvent {
-    pub fn event_time(eid: &EventId) -> u128 {
-        let mut bytes = [0u8; 16];
-        bytes.copy_from_slice(&eid.0[0..16]);
-        u128::from_le_bytes(bytes)
-    }
-
     pub fn generate_event_id(&self) -> EventId {
         let mut bytes = [0u8; 32];
         let start = SystemTime::now();
@@ -151,722 +71,671 @@ impl InnerEvent {
         EventId(bytes)
     }
 
-    pub async fn direct_layer_session(
-        sender: Sender<SendMessage>,
-        layer: Arc<RwLock<Layer>>,
-        gid: GroupId,
-        fgid: GroupId,
-        event: GroupEvent,
-    ) -> Result<()> {
-        let addr = layer.read().await.running(&gid)?.online_direct(&fgid)?;
-        let data = bincode::serialize(&event).unwrap_or(vec![]);
-        let msg = SendType::Event(0, addr, data);
-        let _ = sender.send(SendMessage::Layer(gid, fgid, msg)).await;
-        Ok(())
-    }
-
-    pub fn merge_event(
-        db: &DStorage,
-        addr: &PeerId,
-        results: &mut HandleResult,
-        our_height: u64,
-        our_event: EventId,
-        event_height: u64,
-        event_id: EventId,
-        need_sync: Option<GroupId>,
-    ) -> Result<(u64, u64, EventId)> {
-        if our_height + 1 < event_height {
-            // TODO request for sync self_height + 1.
-            if let Some(gid) = need_sync {
-                let event = GroupEvent::SyncRequest(our_height + 1, event_height);
-                let data = bincode::serialize(&event).unwrap_or(vec![]);
-                results.groups.push((gid, SendType::Event(0, *addr, data)));
-                return Ok((event_height, our_height, our_event));
-            } else {
-                return Ok((event_height, event_height, event_id));
+    /// the event's model (table & key), used in last-writer-wins.
+    fn model(&self) -> (i64, String) {
+        match self {
+            InnerEvent::UserInfo(..) => (ACCOUNT_TABLE_PATH, "".to_owned()),
+            InnerEvent::SessionRequest(pid, ..) | InnerEvent::SessionRequestDelete(pid) => {
+                (REQUEST_TABLE_PATH, id_to_str(pid))
+            }
+            InnerEvent::SessionFriend(pid, ..) | InnerEvent::SessionFriendDelete(pid) => {
+                (FRIEND_TABLE_PATH, id_to_str(pid))
+            }
+            InnerEvent::SessionMessageCreate(_, hash, ..)
+            | InnerEvent::SessionMessageDelete(hash) => (MESSAGE_TABLE_PATH, hash.to_hex()),
+            InnerEvent::SessionUpdate(pid, s_type, ..) => {
+                (SESSION_TABLE_PATH, format!("{}-{}-update", s_type, pid))
+            }
+            InnerEvent::SessionNotice(pid, s_type, ..) => {
+                (SESSION_TABLE_PATH, format!("{}-{}-notice", s_type, pid))
             }
         }
+    }
 
-        let mut merge_height = event_height;
-
-        if our_height >= event_height {
-            // load current event_hegiht.
-            let events = OldEvent::get_nexts(db, event_height)?;
-            for event in events {
-                let our_event_time = Self::event_time(&event.hash);
-                let remote_event_time = Self::event_time(&event_id);
-
-                if our_event_time > remote_event_time {
-                    break;
-                }
+    /// save the local event into consensus, and broadcast to other online devices.
+    pub async fn broadcast(self, global: &Arc<Global>, results: &mut HandleResult) -> Result<()> {
+        let pid = global.pid().await;
+        let db_key = global.own.read().await.db_key(&pid)?;
+        let db = consensus_db(&global.base, &pid, &db_key)?;
+
+        let eid = self.generate_event_id();
+        let (path, key) = self.model();
+        let data = bincode::serialize(&self)?;
+        let index = Event::position(&db, &eid)?;
+        let pre = Event::get_assign_chain(&db, &[index - 1])?.pop().unwrap(); // safe
+        let (height, chain) = Event::merge(&db, eid, path, &key, &data, index)?;
+        drop(db);
+        update_consensus(global, &pid, height, chain).await?;
 
-                if our_event_time == remote_event_time {
-                    let mut remote_bytes = [0u8; 16];
-                    remote_bytes.copy_from_slice(&event_id.0[16..32]);
-                    let remote_next = u128::from_le_bytes(remote_bytes);
+        let event = OwnEvent::Event(index, eid, pre, self);
+        global.own.read().await.broadcast(&event, results)
+    }
 
-                    let mut our_bytes = [0u8; 16];
-                    our_bytes.copy_from_slice(&event.hash.0[16..32]);
-                    let our_next = u128::from_le_bytes(our_bytes);
+    /// handle the event from other device, if the previous is not same, check consensus.
+    pub async fn handle(
+        self,
+        aid: PeerId,
+        eheight: u64,
+        eid: EventId,
+        pre: EventId,
+        global: &Arc<Global>,
+        results: &mut HandleResult,
+    ) -> Result<()> {
+        let pid = global.pid().await;
+        let db_key = global.own.read().await.db_key(&pid)?;
+        let db = consensus_db(&global.base, &pid, &db_key)?;
+        if Event::contains_hash(&db, &eid)? {
+            return Ok(());
+        }
 
-                    if remote_next > our_next {
-                        break;
-                    }
-                }
+        let (height, chain) = Event::tip(&db)?;
+        self.merge(global, &db, eid, results).await?;
 
-                merge_height = event.height;
-            }
+        if height + 1 != eheight || chain != pre {
+            let data = bincode::serialize(&sync_check(&db, false)?)?;
+            results.owns.push(SendType::Event(0, aid, data));
         }
-
-        Ok((merge_height, our_height + 1, our_event))
+        Ok(())
     }
 
-    pub fn handle(
+    /// merge the event in the events order, the data only changed when it is the newest
+    /// of the model, so all devices will have same data when they had same events.
+    async fn merge(
         self,
-        group: &mut Group,
-        gid: GroupId,
-        addr: PeerId,
-        eheight: u64,
+        global: &Arc<Global>,
+        db: &DStorage,
         eid: EventId,
-        pre_event: EventId,
         results: &mut HandleResult,
-        layer: &Arc<RwLock<Layer>>,
     ) -> Result<()> {
-        let account = group.account(&gid)?;
-        let db = group.consensus_db(&gid)?;
-        if OldEvent::contains_hash(&db, &eid)? {
+        let pid = global.pid().await;
+        let (path, key) = self.model();
+        let data = bincode::serialize(&self)?;
+        let index = Event::position(db, &eid)?;
+        let is_overridden = Event::is_overridden(db, path, &key, index)?
+            || Snapshot::is_overridden(db, path, &key, &eid)?;
+
+        // the event is older than the snapshot, only keep it when it is the newest.
+        let snapshot = Snapshot::get(db)?;
+        let is_late = snapshot.height > 0
+            && index == snapshot.height + 1
+            && Event::order(&eid) < Event::order(&snapshot.hash);
+        if is_late && is_overridden {
             return Ok(());
         }
 
-        let (merge_height, next_height, next_eid) =
-            if account.own_height + 1 == eheight && account.event == pre_event {
-                (eheight, eheight, eid)
-            } else {
-                Self::merge_event(
-                    &db,
-                    &addr,
-                    results,
-                    account.own_height,
-                    account.event,
-                    eheight,
-                    eid,
-                    Some(gid),
-                )?
-            };
-
-        let (path, id) = match self {
+        if !is_overridden {
+            if let Err(e) = self.apply(global, results).await {
+                warn!("consensus event: {}", e);
+            }
+        }
+
+        let (height, chain) = Event::merge(db, eid, path, &key, &data, index)?;
+        update_consensus(global, &pid, height, chain).await
+    }
+
+    async fn apply(self, global: &Arc<Global>, results: &mut HandleResult) -> Result<()> {
+        let pid = global.pid().await;
+        let db_key = global.own.read().await.db_key(&pid)?;
+
+        match self {
             InnerEvent::UserInfo(name, avatar) => {
                 results
                     .rpcs
-                    .push(rpc::account_update(gid, &name, base64::encode(&avatar)));
-                group.update_account(gid, &name, avatar)?;
-                (ACCOUNT_TABLE_PATH, 0)
-            }
-            InnerEvent::SessionRequestCreate(is_me, remote, remark) => {
-                let db = group.chat_db(&gid)?;
-                // check if exist request.
-                if Friend::get_id(&db, &remote.id).is_ok() {
-                    return Ok(());
-                }
-                if let Ok(req) = Request::get_id(&db, &remote.id) {
-                    Request::delete(&db, &req.id)?; // delete the old request.
-                    results.rpcs.push(chat_rpc::request_delete(gid, req.id));
-                }
-                let mut request =
-                    Request::new(remote.id, remote.addr, remote.name, remark, is_me, true);
-                // save to db.
-                request.insert(&db)?;
-                drop(db);
-                // save the avatar.
-                write_avatar_sync(group.base(), &gid, &remote.id, remote.avatar)?;
-                results.rpcs.push(chat_rpc::request_create(gid, &request));
-                (REQUEST_TABLE_PATH, request.id)
+                    .push(account_update(&pid, &name, base64::encode(&avatar)));
+                global.own.write().await.update_account(
+                    pid,
+                    &name,
+                    avatar,
+                    &global.base,
+                    &global.secret,
+                )?;
             }
-            InnerEvent::SessionRequestHandle(rgid, is_ok, avatar) => {
-                let db = group.chat_db(&gid)?;
-                if Friend::get_id(&db, &rgid).is_ok() {
-                    return Ok(());
-                }
-
-                if let Ok(mut request) = Request::get_id(&db, &rgid) {
-                    let rid = request.id;
-                    request.is_over = true;
+            InnerEvent::SessionRequest(rpid, name, remark, is_me, is_ok, is_over) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                if let Ok(mut request) = Request::get_id(&db, &rpid) {
+                    request.name = name;
+                    request.remark = remark;
+                    request.is_me = is_me;
                     request.is_ok = is_ok;
+                    request.is_over = is_over;
                     request.update(&db)?;
-                    if is_ok {
-                        if avatar.len() > 0 {
-                            write_avatar_sync(group.base(), &gid, &request.gid, avatar)?;
-                        }
-                        let friend = Friend::from_remote(
-                            &db,
-                            request.gid,
-                            request.name,
-                            request.addr,
-                            "".to_owned(),
-                        )?;
-                        results
-                            .rpcs
-                            .push(chat_rpc::request_agree(gid, rid, &friend));
-                    } else {
-                        results.rpcs.push(chat_rpc::request_reject(gid, rid));
+                    if is_over && !is_ok {
+                        results.rpcs.push(chat_rpc::request_reject(request.id));
                     }
-                    (REQUEST_TABLE_PATH, rid)
                 } else {
-                    return Ok(());
+                    let mut request = Request::new(rpid, name, remark, is_me, true);
+                    request.is_ok = is_ok;
+                    request.is_over = is_over;
+                    request.insert(&db)?;
+                    results.rpcs.push(chat_rpc::request_create(&request));
                 }
             }
-            InnerEvent::SessionRequestDelete(rgid) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(request) = Request::get_id(&db, &rgid) {
-                    let rid = request.id;
+            InnerEvent::SessionRequestDelete(rpid) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                if let Ok(request) = Request::get_id(&db, &rpid) {
                     Request::delete(&db, &request.id)?;
+                    results.rpcs.push(chat_rpc::request_delete(request.id));
+
                     // delete avatar. check had friend.
-                    if Friend::get_id(&db, &request.gid).is_err() {
-                        delete_avatar_sync(group.base(), &gid, &request.gid)?;
+                    if Friend::get_id(&db, &rpid).is_err() {
+                        delete_avatar(&global.base, &pid, &db_key, &rpid).await?;
                     }
-                    results.rpcs.push(chat_rpc::request_delete(gid, rid));
-                    (REQUEST_TABLE_PATH, rid)
-                } else {
-                    return Ok(());
                 }
             }
-            InnerEvent::SessionMessageCreate(rgid, is_me, hash, m) => {
-                let db = group.chat_db(&gid)?;
-                if Message::exist(&db, &hash)? {
-                    return Ok(());
-                }
-
-                if let Ok(f) = Friend::get_id(&db, &rgid) {
-                    if is_me {
-                        let layer_lock = layer.clone();
-                        let ggid = gid.clone();
-                        let fgid = f.gid;
-                        let sender = group.sender();
-                        let layer_event = GroupEvent::Message(hash, m.clone());
-                        tokio::spawn(InnerEvent::direct_layer_session(
-                            sender,
-                            layer_lock,
-                            ggid,
-                            fgid,
-                            layer_event,
-                        ));
+            InnerEvent::SessionFriend(fpid, name, remark, is_closed) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                let friend = if let Ok(mut friend) = Friend::get_id(&db, &fpid) {
+                    friend.name = name;
+                    friend.remark = remark;
+                    friend.is_closed = is_closed;
+                    friend.update(&db)?;
+                    results.rpcs.push(chat_rpc::friend_info(&friend));
+                    friend
+                } else {
+                    let mut friend =
+                        Friend::new(fpid, name, PeerId::default(), [0u8; 32], remark, 0);
+                    friend.is_closed = is_closed;
+                    friend.insert(&db)?;
+                    if let Ok(request) = Request::get_id(&db, &fpid) {
+                        results
+                            .rpcs
+                            .push(chat_rpc::request_agree(request.id, &friend));
+                    } else {
+                        results.rpcs.push(chat_rpc::friend_info(&friend));
                     }
 
-                    // Need Refactor
-                    // let msg =
-                    //     handle_nmsg(group, m, is_me, gid, group.base(), &db, f.id, hash, results)
-                    //         .await?;
-                    // results.rpcs.push(chat_rpc::message_create(gid, &msg));
-                    (MESSAGE_TABLE_PATH, 0)
-                } else {
-                    return Ok(());
+                    // ADD NEW SESSION.
+                    let s_db = session_db(&global.base, &pid, &db_key)?;
+                    let mut session = friend.to_session();
+                    session.insert(&s_db)?;
+                    results.rpcs.push(session_create(&session));
+                    friend
+                };
+
+                if friend.is_closed {
+                    results.rpcs.push(chat_rpc::friend_close(friend.id));
+                    friend_offline(global, &fpid, results).await?;
                 }
             }
-            InnerEvent::SessionMessageDelete(hash) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(m) = Message::get_by_hash(&db, &hash) {
-                    Message::delete(&db, &m.id)?;
-                    results.rpcs.push(chat_rpc::message_delete(gid, m.id));
-                    (MESSAGE_TABLE_PATH, m.id)
-                } else {
-                    return Ok(());
-                }
-            }
-            InnerEvent::SessionFriendInfo(rgid, raddr, rname, ravatar) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(mut f) = Friend::get_id(&db, &rgid) {
-                    f.addr = raddr;
-                    f.name = rname;
-                    f.remote_update(&db)?;
-                    if ravatar.len() > 0 {
-                        write_avatar_sync(group.base(), &gid, &rgid, ravatar)?;
+            InnerEvent::SessionFriendDelete(fpid) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                if let Ok(friend) = Friend::get_id(&db, &fpid) {
+                    let messages = Message::get_by_fid(&db, &friend.id)?;
+                    Friend::delete(&db, &friend.id)?;
+                    drop(db);
+                    for msg in messages {
+                        release_content(&global.base, &pid, &db_key, &msg.m_type, &msg.content)?;
                     }
-                    results.rpcs.push(chat_rpc::friend_info(gid, &f));
-                    (FRIEND_TABLE_PATH, f.id)
-                } else {
-                    return Ok(());
+                    delete_avatar(&global.base, &pid, &db_key, &fpid).await?;
+                    results.rpcs.push(chat_rpc::friend_delete(friend.id));
+                    friend_offline(global, &fpid, results).await?;
                 }
             }
-            InnerEvent::SessionFriendUpdate(rgid, remark) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(mut f) = Friend::get_id(&db, &rgid) {
-                    f.remark = remark;
-                    f.me_update(&db)?;
-                    results
-                        .rpcs
-                        .push(chat_rpc::friend_update(gid, f.id, &f.remark));
-                    (FRIEND_TABLE_PATH, f.id)
-                } else {
+            InnerEvent::SessionMessageCreate(fpid, hash, is_me, nmsg) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                if Message::exist(&db, &hash)? {
                     return Ok(());
                 }
+
+                let friend = Friend::get_id(&db, &fpid)?;
+                let msg = handle_nmsg(
+                    &pid,
+                    &global.base,
+                    &db_key,
+                    nmsg,
+                    is_me,
+                    &db,
+                    friend.id,
+                    hash,
+                    results,
+                )
+                .await?;
+                results.rpcs.push(chat_rpc::message_create(&msg));
+
+                // UPDATE SESSION.
+                let s_db = session_db(&global.base, &pid, &db_key)?;
+                update_session(&s_db, &friend.id, &msg, results);
             }
-            InnerEvent::SessionFriendClose(rgid) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(f) = Friend::get_id(&db, &rgid) {
-                    f.close(&db)?;
-                    results.rpcs.push(chat_rpc::friend_close(gid, f.id));
-
-                    let rfid = f.id;
-                    let layer_lock = layer.clone();
-                    let ggid = gid.clone();
-                    let sender = group.sender();
-                    tokio::spawn(async move {
-                        let online = layer_lock.write().await.remove_online(&ggid, &f.gid);
-                        if let Some(faddr) = online {
-                            let mut addrs: HashMap<PeerId, GroupId> = HashMap::new();
-                            addrs.insert(faddr, f.gid);
-                            tokio::spawn(rpc::sleep_waiting_close_stable(
-                                sender,
-                                HashMap::new(),
-                                addrs,
-                            ));
-                        }
-                    });
-                    (FRIEND_TABLE_PATH, rfid)
-                } else {
-                    return Ok(());
+            InnerEvent::SessionMessageDelete(hash) => {
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                if let Ok(msg) = Message::get_by_hash(&db, &hash) {
+                    Message::delete(&db, &msg.id)?;
+                    Vote::delete(&db, &msg.id)?;
+                    drop(db);
+                    release_content(&global.base, &pid, &db_key, &msg.m_type, &msg.content)?;
+                    results.rpcs.push(chat_rpc::message_delete(msg.id));
                 }
             }
-            InnerEvent::SessionFriendDelete(rgid) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(f) = Friend::get_id(&db, &rgid) {
-                    Friend::delete(&db, &f.id)?;
-                    results.rpcs.push(chat_rpc::friend_delete(gid, f.id));
-                    delete_avatar_sync(group.base(), &gid, &f.gid)?;
-
-                    let rfid = f.id;
-                    let layer_lock = layer.clone();
-                    let ggid = gid.clone();
-                    let sender = group.sender();
-                    tokio::spawn(async move {
-                        let online = layer_lock.write().await.remove_online(&ggid, &f.gid);
-                        if let Some(faddr) = online {
-                            let mut addrs: HashMap<PeerId, GroupId> = HashMap::new();
-                            addrs.insert(faddr, f.gid);
-                            tokio::spawn(rpc::sleep_waiting_close_stable(
-                                sender,
-                                HashMap::new(),
-                                addrs,
-                            ));
-                        }
-                    });
-
-                    (FRIEND_TABLE_PATH, rfid)
-                } else {
-                    return Ok(());
+            InnerEvent::SessionUpdate(spid, s_type, is_top, is_close) => {
+                let db = session_db(&global.base, &pid, &db_key)?;
+                let session = Session::get_by_pid(&db, &spid, &SessionType::from_int(s_type))?;
+                Session::update(&db, &session.id, is_top, is_close)?;
+                if is_close {
+                    results.rpcs.push(session_close(&session.id));
                 }
             }
-            InnerEvent::FileCreate(_fid, _fpid, _ftype, _fname, _fdesc, _faddr) => {
-                // TOOD
-                (FILE_TABLE_PATH, 0)
-            }
-            InnerEvent::FileUpdate(_fid, _fname, _fdesc) => {
-                // TODO
-                (FILE_TABLE_PATH, 0)
-            }
-            InnerEvent::FileParent(_fid, _fpid) => {
-                // TODO
-                (FILE_TABLE_PATH, 0)
+            InnerEvent::SessionNotice(spid, s_type, notice, mute_until) => {
+                let db = session_db(&global.base, &pid, &db_key)?;
+                let session = Session::get_by_pid(&db, &spid, &SessionType::from_int(s_type))?;
+                let notice = SessionNotice::from_int(notice);
+                Session::update_notice(&db, &session.id, &notice, mute_until)?;
             }
-            InnerEvent::FileBackup(_fid, _faddr) => {
-                // TODO
-                (FILE_TABLE_PATH, 0)
-            }
-            InnerEvent::FileDelete(_fid) => {
-                // TODO
-                (FILE_TABLE_PATH, 0)
-            }
-        };
+        }
 
-        OldEvent::merge(&db, eid, path, id, merge_height)?;
-        drop(db);
-        drop(layer);
+        Ok(())
+    }
+}
 
-        let account_db = group.account_db()?;
-        let account = group.account_mut(&gid)?;
-        account.update_consensus(&account_db, next_height, next_eid)?;
-        account_db.close()?;
+/// the friend is closed or deleted by other device, stop the connection.
+async fn friend_offline(
+    global: &Arc<Global>,
+    fpid: &PeerId,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let online = global.group.write().await.rm_online(fpid);
+    if online {
+        let data = bincode::serialize(&GroupEvent::Close)?;
+        results.groups.push(SendType::Event(0, *fpid, data));
+        results.groups.push(SendType::Disconnect(*fpid));
+    }
+    Ok(())
+}
 
-        Ok(())
+/// update the account's consensus height and event.
+async fn update_consensus(
+    global: &Arc<Global>,
+    pid: &PeerId,
+    height: u64,
+    chain: EventId,
+) -> Result<()> {
+    let db = account_db(&global.base, &global.secret)?;
+    let mut own_lock = global.own.write().await;
+    own_lock
+        .account_mut(pid)?
+        .update_consensus(&db, height, chain)?;
+    drop(own_lock);
+    db.close()
+}
+
+/// the consensus check, heights are from the tip, and the step is doubled.
+pub(crate) fn sync_check(db: &DStorage, is_reply: bool) -> Result<OwnEvent> {
+    let (height, _) = Event::tip(db)?;
+    let mut heights = vec![];
+    let mut next = height;
+    let mut step = 1;
+    while next > 0 {
+        heights.push(next);
+        next = next.saturating_sub(step);
+        step *= 2;
     }
+    let chains = Event::get_assign_chain(db, &heights)?;
+    Ok(OwnEvent::SyncCheck(heights, chains, is_reply))
 }
 
-impl StatusEvent {
-    pub fn handle(
-        self,
-        group: &mut Group,
-        gid: GroupId,
-        addr: PeerId,
-        _results: &mut HandleResult,
-        layer: &Arc<RwLock<Layer>>,
-        _uid: u64,
-    ) -> Result<()> {
-        match self {
-            StatusEvent::SessionFriendOnline(rgid) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(_f) = Friend::get_id(&db, &rgid) {
-                    // TODO
-                }
-            }
-            StatusEvent::SessionFriendOffline(rgid) => {
-                let db = group.chat_db(&gid)?;
-                if let Ok(f) = Friend::get_id(&db, &rgid) {
-                    let layer_lock = layer.clone();
-                    let rgid = f.gid;
-                    let _rid = f.id;
-                    let ggid = gid.clone();
-                    let _sender = group.sender();
-                    tokio::spawn(async move {
-                        if let Ok(running) = layer_lock.write().await.running_mut(&ggid) {
-                            if running.check_offline(&rgid, &addr) {
-                                // TODO
-                            }
-                        }
-                    });
-                }
-            }
-        }
-        Ok(())
+/// handle the consensus check, the same chain hash is the common ancestor,
+/// request the remote events after it, and check back if remote missing ours.
+pub(crate) async fn sync_check_handle(
+    aid: PeerId,
+    heights: Vec<u64>,
+    chains: Vec<EventId>,
+    is_reply: bool,
+    global: &Arc<Global>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = consensus_db(&global.base, &pid, &db_key)?;
+
+    let ours = Event::get_assign_chain(&db, &heights)?;
+    let ancestor = heights
+        .iter()
+        .zip(chains.iter().zip(ours.iter()))
+        .find(|(_, (remote, our))| remote == our)
+        .map(|(height, _)| *height)
+        .unwrap_or(0);
+
+    let remote_height = heights.first().cloned().unwrap_or(0);
+    let remote_chain = chains.first().cloned().unwrap_or(EventId::default());
+    if remote_height > ancestor {
+        let event = OwnEvent::SyncRequest(ancestor + 1, remote_height);
+        let data = bincode::serialize(&event)?;
+        results.owns.push(SendType::Event(0, aid, data));
     }
+
+    let (height, chain) = Event::tip(&db)?;
+    if !is_reply && (height != remote_height || chain != remote_chain) {
+        let data = bincode::serialize(&sync_check(&db, true)?)?;
+        results.owns.push(SendType::Event(0, aid, data));
+    }
+    Ok(())
 }
 
-impl SyncEvent {
-    pub async fn sync(
-        group: &Group,
-        base: &PathBuf,
-        gid: &GroupId,
-        account: &Account,
-        from: u64,
-        to: u64,
-    ) -> Result<Vec<Self>> {
-        let db = group.consensus_db(gid)?;
-        let sql = format!(
-            "SELECT id, hash, db_table, row from events WHERE id BETWEEN {} AND {}",
-            from, to
-        );
-        let matrix = db.query(&sql)?;
-        drop(db);
-        let mut pre_keys: Vec<(i64, i64)> = vec![];
-        let mut events: Vec<SyncEvent> = vec![];
-        let mut next = from;
-        for mut v in matrix {
-            let row = v.pop().unwrap().as_i64(); // safe
-            let path = v.pop().unwrap().as_i64(); // safe
-            let hash = EventId::from_hex(v.pop().unwrap().as_str()).unwrap_or(EventId::default());
-            let id = v.pop().unwrap().as_i64() as u64;
-            if id != next {
-                events.push(SyncEvent::None);
-            }
-            next += 1;
+/// the events in from..to, every time sync MAX is 100.
+/// if the events are truncated, send the snapshot first.
+pub(crate) async fn sync_request(global: &Arc<Global>, from: u64, to: u64) -> Result<OwnEvent> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = consensus_db(&global.base, &pid, &db_key)?;
+    if from <= Snapshot::get(&db)?.height {
+        return snapshot_page(&db, 0, to);
+    }
 
-            match path {
-                ACCOUNT_TABLE_PATH => {
-                    if pre_keys.contains(&(path, row)) {
-                        events.push(SyncEvent::AccountHad(hash));
-                        continue;
-                    } else {
-                        pre_keys.push((path, row));
-                    };
+    let last_to = if to >= from + SYNC_LIMIT {
+        from + SYNC_LIMIT - 1
+    } else {
+        to
+    };
+    let mut events = vec![];
+    for event in Event::get_between(&db, from, last_to)? {
+        if let Ok(inner) = bincode::deserialize(&event.data) {
+            events.push((event.hash, inner));
+        }
+    }
+    Ok(OwnEvent::SyncResponse(from, last_to, to, events))
+}
 
-                    let name = account.name.clone();
-                    let avatar = account.avatar.clone();
-                    events.push(SyncEvent::Account(hash, name, avatar));
-                }
-                REQUEST_TABLE_PATH => {
-                    let db = group.chat_db(gid)?;
-                    let event = if let Ok(request) = Request::get(&db, &row) {
-                        if pre_keys.contains(&(path, row)) {
-                            events.push(SyncEvent::RequestHad(hash, request.gid));
-                            continue;
-                        } else {
-                            pre_keys.push((path, row));
-                        };
-
-                        let avatar = if !request.is_ok {
-                            vec![]
-                        } else {
-                            read_avatar_sync(base, gid, &request.gid)?
-                        };
-
-                        // request_gid, addr, name, avatar, remark, is_me, is_ok, is_delete
-                        SyncEvent::Request(
-                            hash,
-                            request.gid,
-                            request.addr,
-                            request.name,
-                            avatar,
-                            request.remark,
-                            request.is_me,
-                            request.is_ok,
-                            request.is_over,
-                        )
-                    } else {
-                        SyncEvent::None
-                    };
+/// merge the synced events, and request the next events, when all merged, check again.
+pub(crate) async fn sync_response(
+    aid: PeerId,
+    last_to: u64,
+    to: u64,
+    events: Vec<(EventId, InnerEvent)>,
+    global: &Arc<Global>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = consensus_db(&global.base, &pid, &db_key)?;
+
+    let mut merged = false;
+    for (eid, event) in events {
+        if !Event::contains_hash(&db, &eid)? {
+            event.merge(global, &db, eid, results).await?;
+            merged = true;
+        }
+    }
 
-                    events.push(event);
-                }
-                FRIEND_TABLE_PATH => {
-                    let db = group.chat_db(gid)?;
-                    let event = if let Ok(friend) = Friend::get(&db, &row) {
-                        if pre_keys.contains(&(path, row)) {
-                            events.push(SyncEvent::FriendHad(hash, friend.gid));
-                            continue;
-                        } else {
-                            pre_keys.push((path, row));
-                        };
-
-                        let avatar = if friend.is_closed {
-                            vec![]
-                        } else {
-                            read_avatar_sync(base, gid, &friend.gid)?
-                        };
-
-                        SyncEvent::Friend(
-                            hash,
-                            friend.gid,
-                            friend.addr,
-                            friend.name,
-                            avatar,
-                            friend.remark,
-                            friend.is_closed,
-                        )
-                    } else {
-                        SyncEvent::None
-                    };
+    let event = if last_to < to {
+        OwnEvent::SyncRequest(last_to + 1, to)
+    } else if merged {
+        // the heights changed, check again, remote may missing ours before them.
+        sync_check(&db, false)?
+    } else {
+        return Ok(());
+    };
+    let data = bincode::serialize(&event)?;
+    results.owns.push(SendType::Event(0, aid, data));
+    Ok(())
+}
 
-                    events.push(event);
-                }
-                MESSAGE_TABLE_PATH => {
-                    let db = group.chat_db(gid)?;
-                    let event = if let Ok(msg) = Message::get(&db, &row) {
-                        let fgid = if let Ok(f) = Friend::get(&db, &msg.fid) {
-                            f.gid
-                        } else {
-                            GroupId::default()
-                        };
-
-                        // create
-                        let mid = msg.hash;
-                        let is_me = msg.is_me;
-                        let nm = from_model(base, gid, msg).await?;
-                        SyncEvent::Message(hash, fgid, mid, is_me, nm)
-                    } else {
-                        SyncEvent::None
-                    };
+/// the snapshot's events from the offset, every time sync MAX is 100.
+fn snapshot_page(db: &DStorage, offset: u64, to: u64) -> Result<OwnEvent> {
+    let snapshot = Snapshot::get(db)?;
+    let total = Snapshot::count(db)?;
+    let mut events = vec![];
+    for event in Snapshot::entries(db, offset, SYNC_LIMIT)? {
+        if let Ok(inner) = bincode::deserialize(&event.data) {
+            events.push((event.hash, inner));
+        }
+    }
+    Ok(OwnEvent::SyncSnapshot(
+        snapshot.height,
+        snapshot.hash,
+        snapshot.chain,
+        offset,
+        total,
+        to,
+        events,
+    ))
+}
 
-                    events.push(event);
-                }
-                FILE_TABLE_PATH => {
-                    //
-                }
-                _ => {}
+/// the snapshot's next page request.
+pub(crate) async fn snapshot_request(
+    global: &Arc<Global>,
+    offset: u64,
+    to: u64,
+) -> Result<OwnEvent> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = consensus_db(&global.base, &pid, &db_key)?;
+    snapshot_page(&db, offset, to)
+}
+
+/// handle the snapshot. new device (no events) bootstrap from it, others keep
+/// their events until the last page, then reset the log to the snapshot and
+/// replay the local events after it, so all will have the same heights as the remote.
+/// after the snapshot, request the events after the snapshot.
+pub(crate) async fn sync_snapshot(
+    aid: PeerId,
+    snapshot: Snapshot,
+    offset: u64,
+    total: u64,
+    to: u64,
+    events: Vec<(EventId, InnerEvent)>,
+    global: &Arc<Global>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let pid = global.pid().await;
+    let db_key = global.own.read().await.db_key(&pid)?;
+    let db = consensus_db(&global.base, &pid, &db_key)?;
+
+    let (height, _) = Event::tip(&db)?;
+    let is_new = height == 0;
+    let next = offset + events.len() as u64;
+    let is_last = events.is_empty() || next >= total;
+    for (eid, event) in events {
+        let (path, key) = event.model();
+        let data = bincode::serialize(&event)?;
+        // the known events had applied, and the newer local events win.
+        let is_known = !is_new && Event::contains_hash(&db, &eid)?;
+        if Snapshot::upsert(&db, &eid, path, &key, &data)?
+            && !is_known
+            && !Event::is_newer(&db, path, &key, &eid)?
+        {
+            if let Err(e) = event.apply(global, results).await {
+                warn!("consensus snapshot: {}", e);
             }
         }
+    }
 
-        if events.len() as u64 != to + 1 - from {
-            return Err(anyhow!("events number not matching."));
+    let event = if !is_last {
+        OwnEvent::SnapshotRequest(next, to)
+    } else {
+        let (height, chain) = if is_new {
+            snapshot.save(&db)?;
+            (snapshot.height, snapshot.chain)
+        } else {
+            snapshot_reset(&db, &snapshot)?
+        };
+        update_consensus(global, &pid, height, chain).await?;
+        if to <= snapshot.height {
+            return Ok(());
         }
+        OwnEvent::SyncRequest(snapshot.height + 1, to)
+    };
+    let data = bincode::serialize(&event)?;
+    results.owns.push(SendType::Event(0, aid, data));
+    Ok(())
+}
 
-        Ok(events)
+/// reset the log to the snapshot, and replay the local events which not in it,
+/// the events had applied, so only merge them into the log. return the new tip.
+fn snapshot_reset(db: &DStorage, snapshot: &Snapshot) -> Result<(u64, EventId)> {
+    let (height, _) = Event::tip(db)?;
+    let locals = Event::get_between(db, 0, height)?;
+    db.delete("DELETE FROM events")?;
+    snapshot.save(db)?;
+
+    for event in locals {
+        if Event::contains_hash(db, &event.hash)? {
+            continue;
+        }
+        let inner: InnerEvent = match bincode::deserialize(&event.data) {
+            Ok(inner) => inner,
+            Err(_) => continue,
+        };
+        let (path, key) = inner.model();
+        let index = Event::position(db, &event.hash)?;
+
+        // the event is older than the snapshot, only keep it when it is the newest.
+        let is_late = index == snapshot.height + 1
+            && Event::order(&event.hash) < Event::order(&snapshot.hash);
+        if is_late
+            && (Event::is_overridden(db, path, &key, index)?
+                || Snapshot::is_overridden(db, path, &key, &event.hash)?)
+        {
+            continue;
+        }
+        Event::merge(db, event.hash, path, &key, &event.data, index)?;
     }
 
-    pub fn handle(
-        gid: GroupId,
-        from: u64,
-        to: u64,
-        events: Vec<SyncEvent>,
-        group: &mut Group,
-        layer: &Arc<RwLock<Layer>>,
-        results: &mut HandleResult,
-        addr: PeerId,
-    ) -> Result<()> {
-        if events.len() as u64 != to + 1 - from {
-            return Ok(());
+    Event::tip(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apps::device::Device;
+    use crate::utils::testing::Node;
+    use std::collections::VecDeque;
+    use tdn::types::primitives::Peer;
+
+    const A_AID: PeerId = PeerId([1u8; 32]);
+    const B_AID: PeerId = PeerId([2u8; 32]);
+
+    /// the other device is online in the node.
+    async fn online(node: &Node, aid: PeerId) {
+        let mut own_lock = node.global.own.write().await;
+        own_lock.add_device(Device::new(Peer::peer(aid)));
+        own_lock.online(&aid).unwrap();
+    }
+
+    /// the new event in the node, the broadcast is dropped if offline.
+    async fn event(node: &Node, key: &str) -> HandleResult {
+        let mut results = HandleResult::new();
+        InnerEvent::SessionNotice(key.to_owned(), 0, 1, 0)
+            .broadcast(&node.global, &mut results)
+            .await
+            .unwrap();
+        results
+    }
+
+    /// deliver the own events between the devices, until no more events.
+    async fn pump(a: &Node, b: &Node, results: HandleResult, to_b: bool) {
+        let mut queue = VecDeque::new();
+        for msg in results.owns {
+            if let SendType::Event(_, _, data) = msg {
+                queue.push_back((to_b, data));
+            }
         }
-        let base = group.base().clone();
-        let consensus_db = group.consensus_db(&gid)?;
-
-        let mut next = from;
-        for event in events {
-            let height = next;
-            next += 1;
-
-            match &event {
-                SyncEvent::Account(eid, ..)
-                | SyncEvent::AccountHad(eid)
-                | SyncEvent::Request(eid, ..)
-                | SyncEvent::RequestHad(eid, ..)
-                | SyncEvent::Friend(eid, ..)
-                | SyncEvent::FriendHad(eid, ..)
-                | SyncEvent::Message(eid, ..) => {
-                    if OldEvent::contains_hash(&consensus_db, eid)? {
-                        continue;
-                    }
-                }
-                SyncEvent::None => {
-                    continue;
+
+        let mut count = 0;
+        while let Some((to_b, data)) = queue.pop_front() {
+            count += 1;
+            assert!(count < 100, "sync is not finished");
+            let (node, from) = if to_b { (b, A_AID) } else { (a, B_AID) };
+            let event = bincode::deserialize(&data).unwrap();
+            let results = OwnEvent::handle(from, event, &node.global).await.unwrap();
+            for msg in results.owns {
+                if let SendType::Event(_, _, data) = msg {
+                    queue.push_back((!to_b, data));
                 }
             }
+        }
+    }
 
-            let (eid, path, id) = match event {
-                SyncEvent::Account(eid, name, avatar) => {
-                    results
-                        .rpcs
-                        .push(rpc::account_update(gid, &name, base64::encode(&avatar)));
-                    group.update_account(gid, &name, avatar)?;
-                    (eid, ACCOUNT_TABLE_PATH, 0)
-                }
-                SyncEvent::AccountHad(eid) => (eid, ACCOUNT_TABLE_PATH, 0),
-                SyncEvent::Request(
-                    eid,
-                    rgid,
-                    raddr,
-                    rname,
-                    avatar,
-                    remark,
-                    is_me,
-                    is_ok,
-                    is_over,
-                ) => {
-                    let chat_db = group.chat_db(&gid)?;
-                    let request = if let Ok(mut req) = Request::get_id(&chat_db, &rgid) {
-                        req.is_ok = is_ok;
-                        req.is_over = is_over;
-                        req.update(&chat_db)?;
-                        req
-                    } else {
-                        let mut request = Request::new(rgid, raddr, rname, remark, is_me, true);
-                        request.is_ok = is_ok;
-                        request.is_over = is_over;
-
-                        // save to db.
-                        request.insert(&chat_db)?;
-                        results.rpcs.push(chat_rpc::request_create(gid, &request));
-                        request
-                    };
-
-                    let rid = request.id;
-                    if is_ok {
-                        if avatar.len() > 0 {
-                            write_avatar_sync(&base, &gid, &request.gid, avatar)?;
-                        }
-                        let friend = Friend::from_remote(
-                            &chat_db,
-                            request.gid,
-                            request.name,
-                            request.addr,
-                            "".to_owned(),
-                        )?;
-                        results
-                            .rpcs
-                            .push(chat_rpc::request_agree(gid, rid, &friend));
-                    } else {
-                        results.rpcs.push(chat_rpc::request_reject(gid, rid));
-                    }
-                    chat_db.close()?;
+    fn events(db: &DStorage) -> Vec<EventId> {
+        let (height, _) = Event::tip(db).unwrap();
+        Event::get_between(db, 1, height)
+            .unwrap()
+            .iter()
+            .map(|e| e.hash)
+            .collect()
+    }
 
-                    (eid, REQUEST_TABLE_PATH, rid)
-                }
-                SyncEvent::RequestHad(eid, rgid) => {
-                    let chat_db = group.chat_db(&gid)?;
-                    let id = if let Ok(req) = Request::get_id(&chat_db, &rgid) {
-                        req.id
-                    } else {
-                        -1
-                    };
-                    (eid, REQUEST_TABLE_PATH, id)
-                }
-                SyncEvent::Friend(eid, fgid, faddr, fname, avatar, remark, is_closed) => {
-                    let chat_db = group.chat_db(&gid)?;
-                    let id = if let Ok(mut friend) = Friend::get_id(&chat_db, &fgid) {
-                        friend.addr = faddr;
-                        friend.name = fname;
-                        friend.remark = remark;
-                        friend.is_closed = is_closed;
-                        friend.update(&chat_db)?;
-
-                        if avatar.len() > 0 {
-                            write_avatar_sync(&base, &gid, &friend.gid, avatar)?;
-                        }
-
-                        if friend.is_closed {
-                            let layer_lock = layer.clone();
-                            let ggid = gid.clone();
-                            let fgid = friend.gid;
-                            let sender = group.sender();
-                            tokio::spawn(async move {
-                                let online = layer_lock.write().await.remove_online(&ggid, &fgid);
-                                if let Some(faddr) = online {
-                                    let mut addrs: HashMap<PeerId, GroupId> = HashMap::new();
-                                    addrs.insert(faddr, fgid);
-                                    tokio::spawn(rpc::sleep_waiting_close_stable(
-                                        sender,
-                                        HashMap::new(),
-                                        addrs,
-                                    ));
-                                }
-                            });
-                        }
-
-                        results.rpcs.push(chat_rpc::friend_info(gid, &friend));
-
-                        friend.id
-                    } else {
-                        -1
-                    };
-                    (eid, FRIEND_TABLE_PATH, id)
-                }
-                SyncEvent::FriendHad(eid, fgid) => {
-                    let chat_db = group.chat_db(&gid)?;
-                    let id = if let Ok(friend) = Friend::get_id(&chat_db, &fgid) {
-                        friend.id
-                    } else {
-                        -1
-                    };
-                    (eid, FRIEND_TABLE_PATH, id)
-                }
-                SyncEvent::Message(eid, fgid, meid, _is_me, _m) => {
-                    let chat_db = group.chat_db(&gid)?;
-                    if Message::exist(&chat_db, &meid)? {
-                        continue;
-                    }
+    #[tokio::test]
+    async fn two_devices_sync() {
+        let a = Node::new("a").await;
+        let b = Node::device("b", &a.mnemonic).await;
+        online(&a, B_AID).await;
+        online(&b, A_AID).await;
+
+        // both devices changed when offline, the events are interleaved in time.
+        event(&a, "a1").await;
+        event(&b, "b1").await;
+        event(&a, "a2").await;
+        event(&b, "b2").await;
+        event(&a, "a3").await;
+
+        // the online event is merged in order, the gaps filled by sync check.
+        let results = event(&b, "b3").await;
+        pump(&a, &b, results, false).await;
+
+        let a_db = a.db(consensus_db).await;
+        let b_db = b.db(consensus_db).await;
+        assert_eq!(Event::tip(&a_db).unwrap().0, 6);
+        assert_eq!(Event::tip(&a_db).unwrap(), Event::tip(&b_db).unwrap());
+        assert_eq!(events(&a_db), events(&b_db));
+
+        // the later event keeps same heights and chains.
+        let results = event(&a, "a4").await;
+        pump(&a, &b, results, true).await;
+        assert_eq!(Event::tip(&b_db).unwrap().0, 7);
+        assert_eq!(Event::tip(&a_db).unwrap(), Event::tip(&b_db).unwrap());
+    }
 
-                    let id = if let Ok(_f) = Friend::get_id(&chat_db, &fgid) {
-                        //let msg = handle_nmsg(m, is_me, gid, &base, &chat_db, f.id, eid, results)?;
-                        //results.rpcs.push(chat_rpc::message_create(gid, &msg));
-                        -1
-                    } else {
-                        -1
-                    };
+    #[tokio::test]
+    async fn snapshot_reset_local_events() {
+        let a = Node::new("a").await;
+        let b = Node::device("b", &a.mnemonic).await;
+        online(&a, B_AID).await;
+        online(&b, A_AID).await;
+
+        event(&a, "a1").await;
+        event(&a, "a2").await;
+        event(&b, "b1").await;
+        event(&a, "a3").await;
+        let a_db = a.db(consensus_db).await;
+        let b_db = b.db(consensus_db).await;
+        Snapshot::compact(&a_db, 3, 3, 0).unwrap();
+        assert_eq!(Snapshot::get(&a_db).unwrap().height, 3);
+        event(&a, "a4").await;
+
+        // the device has events, the remote log is truncated.
+        let results = event(&b, "b2").await;
+        pump(&a, &b, results, false).await;
+
+        assert_eq!(Snapshot::get(&b_db).unwrap().height, 3);
+        assert_eq!(Event::tip(&a_db).unwrap().0, 6);
+        assert_eq!(Event::tip(&a_db).unwrap(), Event::tip(&b_db).unwrap());
+        assert_eq!(events(&a_db), events(&b_db));
+    }
 
-                    (eid, MESSAGE_TABLE_PATH, id)
-                }
-                SyncEvent::None => {
-                    continue;
+    #[tokio::test]
+    async fn merge_out_of_order() {
+        let a = Node::new("a").await;
+        let b = Node::device("b", &a.mnemonic).await;
+        online(&a, B_AID).await;
+
+        let mut sent = vec![];
+        for key in ["k1", "k2", "k3"] {
+            sent.push(event(&a, key).await);
+        }
+
+        // the later events arrive first.
+        for results in sent.into_iter().rev() {
+            for msg in results.owns {
+                if let SendType::Event(_, _, data) = msg {
+                    let event = bincode::deserialize(&data).unwrap();
+                    OwnEvent::handle(A_AID, event, &b.global).await.unwrap();
                 }
-            };
-
-            let account_db = group.account_db()?;
-            let account = group.account_mut(&gid)?;
-            let (merge_height, next_height, next_eid) = InnerEvent::merge_event(
-                &consensus_db,
-                &addr,
-                results,
-                account.own_height,
-                account.event,
-                height,
-                eid,
-                None,
-            )?;
-
-            account.update_consensus(&account_db, next_height, next_eid)?;
-            account_db.close()?;
-
-            OldEvent::merge(&consensus_db, eid, path, id, merge_height)?;
+            }
         }
 
-        consensus_db.close()?;
-        Ok(())
+        let a_db = a.db(consensus_db).await;
+        let b_db = b.db(consensus_db).await;
+        assert_eq!(Event::tip(&b_db).unwrap().0, 3);
+        assert_eq!(Event::tip(&a_db).unwrap(), Event::tip(&b_db).unwrap());
+        assert_eq!(events(&a_db), events(&b_db));
     }
 }
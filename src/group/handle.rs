@@ -1,4 +1,4 @@
-use esse_primitives::MessageType;
+use esse_primitives::{id_to_str, MessageType, NetworkMessage};
 use std::sync::Arc;
 use tdn::types::{
     message::{RecvType, SendType},
@@ -7,16 +7,21 @@ use tdn::types::{
 use tdn_storage::local::DStorage;
 
 use crate::account::{Account, User};
+use crate::apps::cloud::{cloud_push, CloudEvent};
+use crate::apps::file::{share_read, share_save, Manifest};
+use crate::event::InnerEvent;
 use crate::global::Global;
 use crate::rpc::{
     notice_menu, session_connect, session_create, session_last, session_lost, session_suspend,
     session_update_name,
 };
 use crate::session::{connect_session, Session, SessionType};
-use crate::storage::{account_db, chat_db, session_db, write_avatar_sync};
+use crate::storage::{
+    account_db, chat_db, session_db, write_avatar_sync, write_previewed_image_sync,
+};
 
 use super::rpc;
-use super::{handle_nmsg, Friend, GroupEvent, Message, Request};
+use super::{handle_nmsg, Friend, GroupEvent, Message, Poll, Request, Vote, CHAT_VERSION};
 
 pub(crate) async fn group_handle(msg: RecvType, global: &Arc<Global>) -> Result<HandleResult> {
     debug!("---------DEBUG--------- GOT GROUP MESSAGE");
@@ -24,11 +29,12 @@ pub(crate) async fn group_handle(msg: RecvType, global: &Arc<Global>) -> Result<
     let pid = global.pid().await;
 
     match msg {
-        RecvType::Connect(peer, _) | RecvType::ResultConnect(peer, _) => {
+        RecvType::Connect(peer, data) | RecvType::ResultConnect(peer, data) => {
             // ESSE group connect date structure.
-            if let Ok(height) = handle_connect(pid, &peer, global, &mut results).await {
+            let version = data.first().copied().unwrap_or(0);
+            if let Ok(height) = handle_connect(pid, &peer, version, global, &mut results).await {
                 let peer_id = peer.id;
-                let msg = SendType::Result(0, peer, true, false, vec![]);
+                let msg = SendType::Result(0, peer, true, false, vec![CHAT_VERSION]);
                 results.groups.push(msg);
 
                 let info = GroupEvent::InfoReq(height);
@@ -40,10 +46,12 @@ pub(crate) async fn group_handle(msg: RecvType, global: &Arc<Global>) -> Result<
                 results.groups.push(msg);
             }
         }
-        RecvType::Result(peer, is_ok, _) => {
+        RecvType::Result(peer, is_ok, data) => {
             // ESSE group result date structure.
             if is_ok {
-                if let Ok(height) = handle_connect(pid, &peer, global, &mut results).await {
+                let version = data.first().copied().unwrap_or(0);
+                if let Ok(height) = handle_connect(pid, &peer, version, global, &mut results).await
+                {
                     let info = GroupEvent::InfoReq(height);
                     let data = bincode::serialize(&info).unwrap_or(vec![]);
                     let msg = SendType::Event(0, peer.id, data);
@@ -108,6 +116,7 @@ pub(crate) async fn group_handle(msg: RecvType, global: &Arc<Global>) -> Result<
 async fn handle_connect(
     pid: PeerId,
     peer: &Peer,
+    version: u8,
     global: &Arc<Global>,
     results: &mut HandleResult,
 ) -> Result<u64> {
@@ -133,7 +142,11 @@ async fn handle_connect(
     results.rpcs.push(session_connect(&sid, &peer.id));
 
     // 4. active this session.
-    global.group.write().await.add(peer.id, sid, f.id, 0);
+    global
+        .group
+        .write()
+        .await
+        .add(peer.id, sid, f.id, 0, version);
 
     Ok(f.height as u64)
 }
@@ -183,7 +196,18 @@ impl GroupEvent {
                     drop(db);
 
                     results.rpcs.push(rpc::request_create(&request));
-                    results.rpcs.push(notice_menu(&SessionType::Chat));
+                    let s_db = session_db(&global.base, &pid, &db_key)?;
+                    notice_request(&s_db, Some(&fpid), &mut results);
+
+                    let event = InnerEvent::SessionRequest(
+                        fpid,
+                        request.name,
+                        request.remark,
+                        false,
+                        false,
+                        false,
+                    );
+                    event.broadcast(global, &mut results).await?;
                     return Ok(results);
                 } else {
                     let data = bincode::serialize(&GroupEvent::Agree).unwrap_or(vec![]);
@@ -202,8 +226,13 @@ impl GroupEvent {
                         r.is_over = true;
                         r.is_ok = true;
                         r.update(&db)?;
-                        let friend =
-                            Friend::from_remote(&db, fpid, r.name, PeerId::default(), [0u8; 32])?;
+                        let friend = Friend::from_remote(
+                            &db,
+                            fpid,
+                            r.name.clone(),
+                            PeerId::default(),
+                            [0u8; 32],
+                        )?;
                         results.rpcs.push(rpc::request_agree(r.id, &friend));
 
                         // ADD NEW SESSION.
@@ -211,6 +240,21 @@ impl GroupEvent {
                         let mut session = friend.to_session();
                         session.insert(&s_db)?;
                         results.rpcs.push(session_create(&session));
+
+                        let event =
+                            InnerEvent::SessionRequest(fpid, r.name, r.remark, r.is_me, true, true);
+                        event.broadcast(global, &mut results).await?;
+                        let event = InnerEvent::SessionFriend(
+                            fpid,
+                            friend.name.clone(),
+                            friend.remark.clone(),
+                            false,
+                        );
+                        event.broadcast(global, &mut results).await?;
+
+                        // sync to all devices by cloud.
+                        let event = CloudEvent::Friend(friend.pid, friend.name.clone());
+                        cloud_push(global, event, &mut results).await?;
                     }
                     drop(db);
                 }
@@ -224,6 +268,16 @@ impl GroupEvent {
                     request.is_ok = false;
                     request.update(&db)?;
                     results.rpcs.push(rpc::request_reject(request.id));
+
+                    let event = InnerEvent::SessionRequest(
+                        fpid,
+                        request.name,
+                        request.remark,
+                        request.is_me,
+                        false,
+                        true,
+                    );
+                    event.broadcast(global, &mut results).await?;
                 }
             }
             GroupEvent::Message(hash, m) => {
@@ -249,8 +303,27 @@ impl GroupEvent {
                     // UPDATE SESSION.
                     let s_db = session_db(&global.base, &pid, &db_key)?;
                     update_session(&s_db, &fid, &msg, &mut results);
+
+                    // the previewed image sync to own devices when the image received.
+                    if !matches!(m, NetworkMessage::ImagePreview(..)) {
+                        let event = InnerEvent::SessionMessageCreate(fpid, hash, false, m);
+                        event.broadcast(global, &mut results).await?;
+                    }
                 }
             }
+            GroupEvent::Image(name, bytes) => {
+                let (_sid, fid) = global.group.read().await.get(&fpid)?;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = chat_db(&global.base, &pid, &db_key)?;
+
+                // only the image which preview had received.
+                let msg = Message::get_image(&db, &fid, &name)?;
+                write_previewed_image_sync(&global.base, &pid, &db_key, &name, bytes.clone())?;
+
+                let nm = NetworkMessage::Image(bytes);
+                let event = InnerEvent::SessionMessageCreate(fpid, msg.hash, false, nm);
+                event.broadcast(global, &mut results).await?;
+            }
             GroupEvent::InfoReq(height) => {
                 // check sync remote height.
                 let a_db = account_db(&global.base, &global.secret)?;
@@ -281,7 +354,7 @@ impl GroupEvent {
                 f.cloud_key = remote.cloud_key;
                 f.remote_update(&db)?;
                 drop(db);
-                write_avatar_sync(&global.base, &pid, &f.pid, remote.avatar)?;
+                write_avatar_sync(&global.base, &pid, &db_key, &f.pid, remote.avatar)?;
                 results.rpcs.push(rpc::friend_info(&f));
 
                 let s_db = session_db(&global.base, &pid, &db_key)?;
@@ -299,13 +372,91 @@ impl GroupEvent {
                 let db = chat_db(&global.base, &pid, &db_key)?;
 
                 Friend::id_close(&db, fid)?;
+                let friend = Friend::get(&db, &fid)?;
                 drop(db);
                 results.rpcs.push(rpc::friend_close(fid));
+
+                let event = InnerEvent::SessionFriend(fpid, friend.name, friend.remark, true);
+                event.broadcast(global, &mut results).await?;
                 if !keep {
                     results.groups.push(SendType::Disconnect(fpid))
                 }
                 // TODO close session
             }
+            GroupEvent::PollVote(hash, options) => {
+                let (_sid, fid) = global.group.read().await.get(&fpid)?;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = chat_db(&global.base, &pid, &db_key)?;
+
+                // only the poll's creator tally the votes.
+                let msg = Message::get_by_hash(&db, &hash)?;
+                if !msg.is_me || msg.fid != fid || msg.m_type != MessageType::Poll {
+                    return Err(anyhow!("poll is invalid"));
+                }
+                let poll = Poll::from_content(&msg.content)?;
+                poll.check(&options)?;
+
+                Vote::new(msg.id, fpid, options).insert(&db)?;
+                let votes = Vote::list(&db, &msg.id)?;
+                results.rpcs.push(rpc::poll_result(&msg.id, &poll, &votes));
+
+                let event = GroupEvent::PollResult(hash, poll.deadline, Vote::to_network(&votes));
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                results.groups.push(SendType::Event(0, fpid, data));
+            }
+            GroupEvent::PollResult(hash, deadline, votes) => {
+                let (_sid, fid) = global.group.read().await.get(&fpid)?;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = chat_db(&global.base, &pid, &db_key)?;
+
+                let msg = Message::get_by_hash(&db, &hash)?;
+                if msg.is_me || msg.fid != fid || msg.m_type != MessageType::Poll {
+                    return Err(anyhow!("poll is invalid"));
+                }
+                let mut poll = Poll::from_content(&msg.content)?;
+                if poll.deadline != deadline {
+                    poll.deadline = deadline;
+                    Message::update_content(&db, &msg.id, &poll.to_content())?;
+                }
+
+                Vote::reset(&db, &msg.id, votes)?;
+                let votes = Vote::list(&db, &msg.id)?;
+                results.rpcs.push(rpc::poll_result(&msg.id, &poll, &votes));
+            }
+            GroupEvent::ShareReq(share, did) => {
+                let _ = global.group.read().await.get(&fpid)?;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let (name, bytes) = share_read(&global.base, &pid, &db_key, &share, &did)
+                    .await
+                    .unwrap_or((String::new(), vec![]));
+
+                let event = GroupEvent::ShareRes(share, did, name, bytes);
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                results.groups.push(SendType::Event(0, fpid, data));
+            }
+            GroupEvent::ShareRes(share, did, _name, bytes) => {
+                let _ = global.group.read().await.get(&fpid)?;
+                let file = share_save(global, &fpid, &share, &did, bytes, &mut results).await?;
+                results.rpcs.push(rpc::share_file(&share, &did, file));
+            }
+            GroupEvent::ShareSync(content) => {
+                let (_sid, fid) = global.group.read().await.get(&fpid)?;
+                let manifest = Manifest::from_content(&content)?;
+                if manifest.owner != fpid {
+                    return Err(anyhow!("share is invalid"));
+                }
+
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = chat_db(&global.base, &pid, &db_key)?;
+                let prefix = Manifest::prefix(&manifest.owner, &manifest.did);
+                for msg in Message::get_shared(&db, &prefix)? {
+                    if msg.is_me || msg.fid != fid {
+                        continue;
+                    }
+                    Message::update_content(&db, &msg.id, &content)?;
+                    results.rpcs.push(rpc::share_sync(&msg.id, &content));
+                }
+            }
         }
 
         Ok(results)
@@ -315,10 +466,23 @@ impl GroupEvent {
 pub(crate) fn group_conn(pid: PeerId, results: &mut HandleResult) {
     results
         .groups
-        .push(SendType::Connect(0, Peer::peer(pid), vec![]));
+        .push(SendType::Connect(0, Peer::peer(pid), vec![CHAT_VERSION]));
 }
 
-// UPDATE SESSION.
+// NOTICE REQUEST. follow the remote's session notification rules when had a session,
+// the request relayed by name has no identity, so there is no rule for it.
+pub(crate) fn notice_request(s_db: &DStorage, remote: Option<&PeerId>, results: &mut HandleResult) {
+    let notice = match remote.map(|r| Session::get_by_pid(s_db, &id_to_str(r), &SessionType::Chat))
+    {
+        Some(Ok(session)) => session.is_notice(false),
+        _ => true,
+    };
+    if notice {
+        results.rpcs.push(notice_menu(&SessionType::Chat));
+    }
+}
+
+// UPDATE SESSION. follow the session's notification rules.
 pub(crate) fn update_session(s_db: &DStorage, id: &i64, msg: &Message, results: &mut HandleResult) {
     let scontent = match msg.m_type {
         MessageType::String => {
@@ -327,16 +491,21 @@ pub(crate) fn update_session(s_db: &DStorage, id: &i64, msg: &Message, results:
         _ => format!("{}:", msg.m_type.to_int()),
     };
 
+    let notice = match Session::get_by_fid(s_db, id, &SessionType::Chat) {
+        Ok(session) => !msg.is_me && session.is_notice(false),
+        Err(_) => return,
+    };
+
     if let Ok(sid) = Session::last(
         &s_db,
         id,
         &SessionType::Chat,
         &msg.datetime,
         &scontent,
-        true,
+        !notice,
     ) {
         results
             .rpcs
-            .push(session_last(&sid, &msg.datetime, &scontent, false));
+            .push(session_last(&sid, &msg.datetime, &scontent, !notice));
     }
 }
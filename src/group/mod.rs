@@ -8,18 +8,25 @@ use tdn::types::{
 };
 
 use crate::account::User;
+use crate::storage::{image_name, image_preview};
 
 mod handle;
 mod models;
-mod rpc;
+pub(crate) mod rpc;
 
-pub(crate) use handle::{group_conn, group_handle, update_session};
+pub(crate) use handle::{group_conn, group_handle, notice_request, update_session};
 pub(crate) use models::{
     from_network_message, handle_nmsg, raw_to_network_message, to_network_message, Friend,
-    InviteType, Message, Request,
+    InviteType, Message, Poll, Request, Vote,
 };
 pub(crate) use rpc::group_rpc;
 
+/// chat protocol version, sent in the connect data, the old peers send empty (0).
+pub(crate) const CHAT_VERSION: u8 = 1;
+
+/// the chat protocol version which supports the image preview.
+const PREVIEW_VERSION: u8 = 1;
+
 /// ESSE groups.
 pub(crate) struct Group {
     /// friend pid => Session
@@ -44,6 +51,8 @@ pub(crate) struct GroupSession {
     pub suspend_remote: bool,
     /// keep alive remain minutes.
     pub remain: u16,
+    /// remote's chat protocol version.
+    pub version: u8,
 }
 
 /// ESSE group Event (Chat).
@@ -71,6 +80,50 @@ pub(crate) enum GroupEvent {
     InfoRes(User),
     /// close friendship.
     Close,
+    /// vote the poll, send to poll's creator.
+    /// params is poll message hash, choosed options.
+    PollVote(EventId, Vec<u32>),
+    /// poll result tallied by poll's creator.
+    /// params is poll message hash, poll deadline, votes.
+    PollResult(EventId, i64, Vec<(PeerId, Vec<u32>)>),
+    /// the image which preview had sent in message.
+    /// params is image name, image bytes.
+    Image(String, Vec<u8>),
+    /// request the shared file, send to share's owner.
+    /// params is share did, file did.
+    ShareReq(String, String),
+    /// the shared file content, if missing, bytes is empty.
+    /// params is share did, file did, file name, file bytes.
+    ShareRes(String, String, String, Vec<u8>),
+    /// the synced shared folder is updated by owner.
+    /// params is shared manifest.
+    ShareSync(String),
+}
+
+/// the message event to the friend, and the image which sent later. when the
+/// friend's version supports, the image is sent as a small preview first.
+pub(crate) fn message_event(
+    hash: EventId,
+    nm: NetworkMessage,
+    version: u8,
+) -> (GroupEvent, Option<GroupEvent>) {
+    match nm {
+        NetworkMessage::Image(bytes) if version >= PREVIEW_VERSION => match image_preview(&bytes) {
+            Ok(preview) => {
+                let name = image_name(&bytes);
+                let nm = NetworkMessage::ImagePreview(preview, name.clone());
+                (
+                    GroupEvent::Message(hash, nm),
+                    Some(GroupEvent::Image(name, bytes)),
+                )
+            }
+            Err(_) => (
+                GroupEvent::Message(hash, NetworkMessage::Image(bytes)),
+                None,
+            ),
+        },
+        nm => (GroupEvent::Message(hash, nm), None),
+    }
 }
 
 impl Group {
@@ -95,15 +148,21 @@ impl Group {
         self.delivery_count = 1;
     }
 
-    pub fn add(&mut self, pid: PeerId, sid: i64, fid: i64, h: i64) {
+    pub fn add(&mut self, pid: PeerId, sid: i64, fid: i64, h: i64, version: u8) {
         self.sessions
             .entry(pid)
             .and_modify(|s| {
                 s.sid = sid;
                 s.fid = fid;
                 s.height = h;
+                s.version = version;
             })
-            .or_insert(GroupSession::new(sid, fid, h));
+            .or_insert(GroupSession::new(sid, fid, h, version));
+    }
+
+    /// the remote's chat protocol version, 0 when offline.
+    pub fn version(&self, pid: &PeerId) -> u8 {
+        self.sessions.get(pid).map(|s| s.version).unwrap_or(0)
     }
 
     pub fn get(&self, pid: &PeerId) -> Result<(i64, i64)> {
@@ -155,7 +214,7 @@ impl Group {
 }
 
 impl GroupSession {
-    fn new(sid: i64, fid: i64, height: i64) -> Self {
+    fn new(sid: i64, fid: i64, height: i64, version: u8) -> Self {
         Self {
             sid,
             fid,
@@ -163,6 +222,7 @@ impl GroupSession {
             suspend_me: false,
             suspend_remote: false,
             remain: 0,
+            version,
         }
     }
 
@@ -1,9 +1,11 @@
 mod friend;
 mod message;
+mod poll;
 mod request;
 
 pub(crate) use self::friend::Friend;
 pub(crate) use self::message::{handle_nmsg, Message};
+pub(crate) use self::poll::{Poll, Vote};
 pub(crate) use self::request::Request;
 
 use esse_primitives::{id_from_str, id_to_str, MessageType, NetworkMessage};
@@ -11,12 +13,13 @@ use group_types::GroupChatId;
 use std::path::PathBuf;
 use tdn::types::primitives::{HandleResult, PeerId, Result, PEER_ID_LENGTH};
 
+use crate::apps::file::share_manifest;
 use crate::apps::group::GroupChat;
 use crate::rpc::session_create;
 use crate::storage::{
-    chat_db, group_db, read_avatar, read_db_file, read_file, read_image, read_record, session_db,
-    write_avatar_sync, write_file, write_file_sync, write_image, write_image_sync,
-    write_record_sync,
+    attachment_name, chat_db, group_db, read_avatar, read_db_file, read_file, read_image,
+    read_record, session_db, write_attachment, write_attachment_sync, write_avatar_sync,
+    write_image, write_image_sync, write_preview_sync, write_record_sync,
 };
 
 pub(crate) async fn from_network_message(
@@ -30,15 +33,19 @@ pub(crate) async fn from_network_message(
         NetworkMessage::String(content) => Ok((MessageType::String, content)),
         NetworkMessage::Transfer(content) => Ok((MessageType::Transfer, content)),
         NetworkMessage::Image(bytes) => {
-            let image_name = write_image_sync(base, own, bytes)?;
+            let image_name = write_image_sync(base, own, db_key, bytes)?;
+            Ok((MessageType::Image, image_name))
+        }
+        NetworkMessage::ImagePreview(preview, image_name) => {
+            write_preview_sync(base, own, db_key, &image_name, preview)?;
             Ok((MessageType::Image, image_name))
         }
         NetworkMessage::File(old_name, bytes) => {
-            let filename = write_file_sync(base, own, &old_name, bytes)?;
+            let filename = write_attachment_sync(base, own, db_key, &old_name, bytes)?;
             Ok((MessageType::File, filename))
         }
         NetworkMessage::Contact(pid, name, avatar_bytes) => {
-            write_avatar_sync(base, own, &pid, avatar_bytes)?;
+            write_avatar_sync(base, own, db_key, &pid, avatar_bytes)?;
             let contact_values = format!("{};;{}", id_to_str(&pid), name);
             Ok((MessageType::Contact, contact_values))
         }
@@ -47,7 +54,7 @@ pub(crate) async fn from_network_message(
             Ok((MessageType::Emoji, "".to_owned()))
         }
         NetworkMessage::Record(bytes, time) => {
-            let record_name = write_record_sync(base, own, time, bytes)?;
+            let record_name = write_record_sync(base, own, db_key, time, bytes)?;
             Ok((MessageType::Record, record_name))
         }
         NetworkMessage::Invite(content) => {
@@ -78,6 +85,11 @@ pub(crate) async fn from_network_message(
             // TODO
             Ok((MessageType::Video, "".to_owned()))
         }
+        NetworkMessage::Poll(question, options, multiple, deadline) => {
+            let poll = Poll::new(question, options, multiple, deadline);
+            Ok((MessageType::Poll, poll.to_content()))
+        }
+        NetworkMessage::Share(content) => Ok((MessageType::Share, content)),
     }
 }
 
@@ -95,7 +107,7 @@ pub(crate) async fn raw_to_network_message(
         )),
         MessageType::Image => {
             let bytes = read_file(&PathBuf::from(content)).await?;
-            let image_name = write_image(base, own, &bytes).await?;
+            let image_name = write_image(base, own, db_key, &bytes).await?;
             Ok((NetworkMessage::Image(bytes), image_name))
         }
         MessageType::File => {
@@ -106,15 +118,15 @@ pub(crate) async fn raw_to_network_message(
                 .and_then(|s| s.to_str())
                 .unwrap_or("")
                 .to_owned();
-            let filename = write_file(base, own, &old_name, &bytes).await?;
-            Ok((NetworkMessage::File(filename.clone(), bytes), filename))
+            let filename = write_attachment(base, own, db_key, &old_name, &bytes).await?;
+            Ok((NetworkMessage::File(old_name, bytes), filename))
         }
         MessageType::Contact => {
             let cid: i64 = content.parse()?;
             let db = chat_db(base, own, db_key)?;
             let contact = Friend::get(&db, &cid)?;
             drop(db);
-            let avatar_bytes = read_avatar(base, own, &contact.pid).await?;
+            let avatar_bytes = read_avatar(base, own, db_key, &contact.pid).await?;
             let contact_values = format!("{};;{}", id_to_str(&contact.pid), contact.name);
             Ok((
                 NetworkMessage::Contact(contact.pid, contact.name, avatar_bytes),
@@ -124,7 +136,7 @@ pub(crate) async fn raw_to_network_message(
         MessageType::Record => {
             let (bytes, time) = if let Some(i) = content.find('-') {
                 let time = content[0..i].parse().unwrap_or(0);
-                let bytes = read_record(base, own, &content[i + 1..]).await?;
+                let bytes = read_record(base, own, db_key, &content[i + 1..]).await?;
                 (bytes, time)
             } else {
                 (vec![], 0)
@@ -151,12 +163,25 @@ pub(crate) async fn raw_to_network_message(
             NetworkMessage::Transfer(content.to_owned()),
             content.to_owned(),
         )),
+        MessageType::Poll => {
+            let poll = Poll::from_content(content)?;
+            let raw = poll.to_content();
+            Ok((
+                NetworkMessage::Poll(poll.question, poll.options, poll.multiple, poll.deadline),
+                raw,
+            ))
+        }
+        MessageType::Share => {
+            let manifest = share_manifest(base, own, db_key, content)?;
+            Ok((NetworkMessage::Share(manifest.clone()), manifest))
+        }
     }
 }
 
 pub(crate) async fn to_network_message(
     own: &PeerId,
     base: &PathBuf,
+    db_key: &str,
     mtype: MessageType,
     content: String,
 ) -> Result<NetworkMessage> {
@@ -164,12 +189,13 @@ pub(crate) async fn to_network_message(
     match mtype {
         MessageType::String => Ok(NetworkMessage::String(content)),
         MessageType::Image => {
-            let bytes = read_image(base, own, &content).await?;
+            let bytes = read_image(base, own, db_key, &content).await?;
             Ok(NetworkMessage::Image(bytes))
         }
         MessageType::File => {
-            let bytes = read_db_file(base, own, &content).await?;
-            Ok(NetworkMessage::File(content, bytes))
+            let bytes = read_db_file(base, own, db_key, &content).await?;
+            let name = attachment_name(&content).to_owned();
+            Ok(NetworkMessage::File(name, bytes))
         }
         MessageType::Contact => {
             let index = content.find(";;").ok_or(anyhow!("message is invalid"))?;
@@ -179,13 +205,13 @@ pub(crate) async fn to_network_message(
             let cpid = id_from_str(&content[0..index])?;
             let cname = content[index + 2..].to_owned();
 
-            let avatar_bytes = read_avatar(base, own, &cpid).await?;
+            let avatar_bytes = read_avatar(base, own, db_key, &cpid).await?;
             Ok(NetworkMessage::Contact(cpid, cname, avatar_bytes))
         }
         MessageType::Record => {
             let (bytes, time) = if let Some(i) = content.find('-') {
                 let time = content[0..i].parse().unwrap_or(0);
-                let bytes = read_record(base, own, &content[i + 1..]).await?;
+                let bytes = read_record(base, own, db_key, &content[i + 1..]).await?;
                 (bytes, time)
             } else {
                 (vec![], 0)
@@ -197,6 +223,16 @@ pub(crate) async fn to_network_message(
         MessageType::Emoji => Ok(NetworkMessage::Emoji),
         MessageType::Phone => Ok(NetworkMessage::Phone),
         MessageType::Video => Ok(NetworkMessage::Video),
+        MessageType::Poll => {
+            let poll = Poll::from_content(&content)?;
+            Ok(NetworkMessage::Poll(
+                poll.question,
+                poll.options,
+                poll.multiple,
+                poll.deadline,
+            ))
+        }
+        MessageType::Share => Ok(NetworkMessage::Share(content)),
     }
 }
 
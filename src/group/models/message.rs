@@ -8,7 +8,9 @@ use tdn::types::{
 };
 use tdn_storage::local::{DStorage, DsValue};
 
-use super::{from_network_message, to_network_message};
+use crate::storage::release_content;
+
+use super::{from_network_message, to_network_message, Poll};
 
 pub(crate) async fn handle_nmsg(
     own: &PeerId,
@@ -24,16 +26,21 @@ pub(crate) async fn handle_nmsg(
     // handle event.
     let (m_type, raw) = from_network_message(own, base, db_key, nmsg, results).await?;
     let mut msg = Message::new_with_id(hash, fid, is_me, m_type, raw, true);
-    msg.insert(db)?;
+    if let Err(e) = msg.insert(db) {
+        // the blob reference only kept by the saved message.
+        let _ = release_content(base, own, db_key, &msg.m_type, &msg.content);
+        return Err(e);
+    }
     Ok(msg)
 }
 
 pub(crate) async fn from_model(
     own: &PeerId,
     base: &PathBuf,
+    db_key: &str,
     model: Message,
 ) -> Result<NetworkMessage> {
-    to_network_message(own, base, model.m_type, model.content).await
+    to_network_message(own, base, db_key, model.m_type, model.content).await
 }
 
 pub(crate) struct Message {
@@ -132,7 +139,7 @@ impl Message {
             self.fid,
             self.is_me,
             self.m_type.to_int(),
-            self.content,
+            Poll::content_rpc(&self.m_type, &self.content),
             self.is_delivery,
             self.datetime,
         ])
@@ -159,7 +166,7 @@ impl Message {
     }
 
     pub fn get_by_hash(db: &DStorage, hash: &EventId) -> Result<Message> {
-        let sql = format!("SELECT id, hash, fid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE hash = {}", hash.to_hex());
+        let sql = format!("SELECT id, hash, fid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE hash = '{}'", hash.to_hex());
         let mut matrix = db.query(&sql)?;
         if matrix.len() > 0 {
             Ok(Message::from_values(matrix.pop().unwrap()))
@@ -168,6 +175,17 @@ impl Message {
         }
     }
 
+    /// the shared manifest messages, prefix is the manifest content's prefix.
+    pub fn get_shared(db: &DStorage, prefix: &str) -> Result<Vec<Message>> {
+        let sql = format!("SELECT id, hash, fid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE m_type = {} AND content LIKE '{}%'", MessageType::Share.to_int(), prefix);
+        let matrix = db.query(&sql)?;
+        let mut messages = vec![];
+        for values in matrix {
+            messages.push(Message::from_values(values));
+        }
+        Ok(messages)
+    }
+
     pub fn insert(&mut self, db: &DStorage) -> Result<()> {
         let sql = format!(
             "INSERT INTO messages (hash, fid, is_me, m_type, content, is_delivery, datetime) VALUES ('{}',{},{},{},'{}',{},{})",
@@ -191,6 +209,14 @@ impl Message {
         db.update(&sql)
     }
 
+    pub fn update_content(db: &DStorage, id: &i64, content: &str) -> Result<usize> {
+        let sql = format!(
+            "UPDATE messages SET content = '{}' WHERE id = {}",
+            content, id,
+        );
+        db.update(&sql)
+    }
+
     pub fn delete(db: &DStorage, id: &i64) -> Result<usize> {
         let sql = format!("DELETE FROM messages WHERE id = {}", id);
         // TODO delete content
@@ -204,6 +230,17 @@ impl Message {
         Ok(size)
     }
 
+    /// the friend's image message which only the preview received.
+    pub fn get_image(db: &DStorage, fid: &i64, name: &str) -> Result<Message> {
+        let sql = format!("SELECT id, hash, fid, is_me, m_type, content, is_delivery, datetime FROM messages WHERE fid = {} AND is_me = false AND m_type = {} AND content = '{}'", fid, MessageType::Image.to_int(), name);
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            Ok(Message::from_values(matrix.pop().unwrap())) // safe unwrap()
+        } else {
+            Err(anyhow!("message is missing."))
+        }
+    }
+
     pub fn exist(db: &DStorage, hash: &EventId) -> Result<bool> {
         let sql = format!("SELECT id FROM messages WHERE hash = '{}'", hash.to_hex());
         let matrix = db.query(&sql)?;
@@ -0,0 +1,233 @@
+use esse_primitives::{id_from_str, id_to_str, MessageType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::{
+    primitives::{PeerId, Result},
+    rpc::{json, RpcParam},
+};
+use tdn_storage::local::{DStorage, DsValue};
+
+/// Poll message content, saved in message's content.
+/// format: base64 of the bincode, so any characters are safe in question & options.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+    pub multiple: bool,
+    /// poll deadline. 0 is no deadline, when closed it is the closed time.
+    pub deadline: i64,
+}
+
+impl Poll {
+    pub fn new(question: String, options: Vec<String>, multiple: bool, deadline: i64) -> Self {
+        Self {
+            question,
+            options,
+            multiple,
+            deadline,
+        }
+    }
+
+    pub fn to_content(&self) -> String {
+        base64::encode(bincode::serialize(self).unwrap_or(vec![]))
+    }
+
+    pub fn from_content(content: &str) -> Result<Self> {
+        let bytes = base64::decode(content).map_err(|_| anyhow!("poll is invalid"))?;
+        let poll: Poll = bincode::deserialize(&bytes).map_err(|_| anyhow!("poll is invalid"))?;
+        if poll.options.len() < 2 {
+            return Err(anyhow!("poll options is invalid"));
+        }
+        Ok(poll)
+    }
+
+    /// poll for rpc: [question, options, multiple, deadline].
+    pub fn to_rpc(&self) -> RpcParam {
+        json!([self.question, self.options, self.multiple, self.deadline])
+    }
+
+    /// message content for rpc, poll message's content is the decoded poll.
+    pub fn content_rpc(m_type: &MessageType, content: &str) -> RpcParam {
+        if *m_type == MessageType::Poll {
+            if let Ok(poll) = Poll::from_content(content) {
+                return poll.to_rpc();
+            }
+        }
+        json!(content)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.deadline > 0 && self.deadline <= now()
+    }
+
+    /// close the poll now, return if changed.
+    pub fn close(&mut self) -> bool {
+        if self.is_closed() {
+            false
+        } else {
+            self.deadline = now();
+            true
+        }
+    }
+
+    /// check the choosed options is valid for this poll.
+    pub fn check(&self, choosed: &[u32]) -> Result<()> {
+        if self.is_closed() {
+            return Err(anyhow!("poll is closed"));
+        }
+        if choosed.len() == 0 || (!self.multiple && choosed.len() != 1) {
+            return Err(anyhow!("poll options is invalid"));
+        }
+        for (i, c) in choosed.iter().enumerate() {
+            if *c as usize >= self.options.len() || choosed[..i].contains(c) {
+                return Err(anyhow!("poll options is invalid"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Poll vote model. mid is the poll message's db id.
+pub(crate) struct Vote {
+    pub id: i64,
+    pub mid: i64,
+    pub pid: PeerId,
+    pub options: Vec<u32>,
+    pub datetime: i64,
+}
+
+impl Vote {
+    pub fn new(mid: i64, pid: PeerId, options: Vec<u32>) -> Self {
+        Self {
+            mid,
+            pid,
+            options,
+            id: 0,
+            datetime: now(),
+        }
+    }
+
+    /// here is zero-copy and unwrap is safe. checked.
+    fn from_values(mut v: Vec<DsValue>) -> Self {
+        Self {
+            datetime: v.pop().unwrap().as_i64(),
+            options: v
+                .pop()
+                .unwrap()
+                .as_str()
+                .split(',')
+                .filter_map(|s| s.parse().ok())
+                .collect(),
+            pid: id_from_str(v.pop().unwrap().as_str()).unwrap_or(PeerId::default()),
+            mid: v.pop().unwrap().as_i64(),
+            id: v.pop().unwrap().as_i64(),
+        }
+    }
+
+    fn options_string(&self) -> String {
+        self.options
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub fn list(db: &DStorage, mid: &i64) -> Result<Vec<Vote>> {
+        let matrix = db.query(&format!(
+            "SELECT id, mid, pid, options, datetime FROM votes WHERE mid = {}",
+            mid
+        ))?;
+        let mut votes = vec![];
+        for values in matrix {
+            votes.push(Vote::from_values(values));
+        }
+        Ok(votes)
+    }
+
+    /// one vote per member, new vote will replace the old one.
+    pub fn insert(&mut self, db: &DStorage) -> Result<()> {
+        let mut unique_check = db.query(&format!(
+            "SELECT id FROM votes WHERE mid = {} AND pid = '{}'",
+            self.mid,
+            id_to_str(&self.pid)
+        ))?;
+        if unique_check.len() > 0 {
+            self.id = unique_check.pop().unwrap().pop().unwrap().as_i64();
+            let sql = format!(
+                "UPDATE votes SET options = '{}', datetime = {} WHERE id = {}",
+                self.options_string(),
+                self.datetime,
+                self.id
+            );
+            db.update(&sql)?;
+        } else {
+            let sql = format!(
+                "INSERT INTO votes (mid, pid, options, datetime) VALUES ({}, '{}', '{}', {})",
+                self.mid,
+                id_to_str(&self.pid),
+                self.options_string(),
+                self.datetime,
+            );
+            self.id = db.insert(&sql)?;
+        }
+        Ok(())
+    }
+
+    /// replace all votes with the host's tallied votes.
+    pub fn reset(db: &DStorage, mid: &i64, votes: Vec<(PeerId, Vec<u32>)>) -> Result<()> {
+        Vote::delete(db, mid)?;
+        for (pid, options) in votes {
+            Vote::new(*mid, pid, options).insert(db)?;
+        }
+        Ok(())
+    }
+
+    pub fn delete(db: &DStorage, mid: &i64) -> Result<usize> {
+        let sql = format!("DELETE FROM votes WHERE mid = {}", mid);
+        db.delete(&sql)
+    }
+
+    /// votes for network.
+    pub fn to_network(votes: &[Vote]) -> Vec<(PeerId, Vec<u32>)> {
+        votes.iter().map(|v| (v.pid, v.options.clone())).collect()
+    }
+
+    /// poll result: [message id, question, options, multiple, deadline, is closed,
+    /// options count, votes].
+    pub fn to_rpc(mid: &i64, poll: &Poll, votes: &[Vote]) -> RpcParam {
+        let mut counts: HashMap<u32, u32> = HashMap::new();
+        for v in votes {
+            for o in &v.options {
+                *counts.entry(*o).or_insert(0) += 1;
+            }
+        }
+        let counts: Vec<u32> = (0..poll.options.len() as u32)
+            .map(|i| counts.get(&i).cloned().unwrap_or(0))
+            .collect();
+        let votes: Vec<RpcParam> = votes
+            .iter()
+            .map(|v| json!([id_to_str(&v.pid), v.options]))
+            .collect();
+
+        json!([
+            mid,
+            poll.question,
+            poll.options,
+            poll.multiple,
+            poll.deadline,
+            poll.is_closed(),
+            counts,
+            votes
+        ])
+    }
+}
+
+#[inline]
+fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
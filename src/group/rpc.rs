@@ -6,12 +6,17 @@ use tdn::types::{
     rpc::{json, rpc_response, RpcError, RpcHandler, RpcParam},
 };
 
-//use crate::event::InnerEvent;
+use crate::apps::cloud::{cloud_push, CloudEvent};
+use crate::apps::file::{share_fetch, Manifest};
+use crate::event::InnerEvent;
 use crate::global::Global;
 use crate::rpc::session_create;
-use crate::storage::{chat_db, delete_avatar, session_db};
+use crate::storage::{chat_db, delete_avatar, release_content, session_db};
 
-use super::{raw_to_network_message, update_session, Friend, GroupEvent, Message, Request};
+use super::{
+    message_event, raw_to_network_message, update_session, Friend, GroupEvent, Message, Poll,
+    Request, Vote,
+};
 
 #[inline]
 pub(crate) fn friend_info(friend: &Friend) -> RpcParam {
@@ -29,7 +34,7 @@ pub(crate) fn friend_close(fid: i64) -> RpcParam {
 }
 
 #[inline]
-pub(crate) fn _friend_delete(fid: i64) -> RpcParam {
+pub(crate) fn friend_delete(fid: i64) -> RpcParam {
     rpc_response(0, "chat-friend-delete", json!([fid]))
 }
 
@@ -69,10 +74,25 @@ pub(crate) fn message_delivery(id: i64, is_d: bool) -> RpcParam {
 }
 
 #[inline]
-pub(crate) fn _message_delete(id: i64) -> RpcParam {
+pub(crate) fn message_delete(id: i64) -> RpcParam {
     rpc_response(0, "chat-message-delete", json!([id]))
 }
 
+#[inline]
+pub(crate) fn poll_result(mid: &i64, poll: &Poll, votes: &[Vote]) -> RpcParam {
+    rpc_response(0, "chat-poll-result", Vote::to_rpc(mid, poll, votes))
+}
+
+#[inline]
+pub(crate) fn share_file(share: &str, did: &str, file: RpcParam) -> RpcParam {
+    rpc_response(0, "chat-share-file", json!([share, did, file]))
+}
+
+#[inline]
+pub(crate) fn share_sync(mid: &i64, content: &str) -> RpcParam {
+    rpc_response(0, "chat-share-sync", json!([mid, content]))
+}
+
 #[inline]
 fn request_list(requests: Vec<Request>) -> RpcParam {
     let mut results = vec![];
@@ -140,7 +160,7 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
             let remark = params[1].as_str().ok_or(RpcError::ParseError)?;
 
-            let results = HandleResult::new();
+            let mut results = HandleResult::new();
             let pid = state.pid().await;
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = chat_db(&state.base, &pid, &db_key)?;
@@ -150,13 +170,8 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             f.me_update(&db)?;
             drop(db);
 
-            // state.own.write().await.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionFriendUpdate(f.gid, f.remark),
-            //     FRIEND_TABLE_PATH,
-            //     f.id,
-            //     &mut results,
-            // )?;
+            let event = InnerEvent::SessionFriend(f.pid, f.name, f.remark, f.is_closed);
+            event.broadcast(&state, &mut results).await?;
 
             Ok(results)
         },
@@ -183,13 +198,8 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
                 results.groups.push(SendType::Disconnect(friend.pid));
             }
 
-            // state.own.write().await.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionFriendClose(friend.gid),
-            //     FRIEND_TABLE_PATH,
-            //     friend.id,
-            //     &mut results,
-            // )?;
+            let event = InnerEvent::SessionFriend(friend.pid, friend.name, friend.remark, true);
+            event.broadcast(&state, &mut results).await?;
 
             Ok(results)
         },
@@ -206,11 +216,15 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let db = chat_db(&state.base, &pid, &db_key)?;
 
             let friend = Friend::get(&db, &id)?;
+            let messages = Message::get_by_fid(&db, &id)?;
             Friend::delete(&db, &id)?;
             drop(db);
+            for msg in messages {
+                release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content)?;
+            }
 
             let online = state.group.write().await.rm_online(&friend.pid);
-            delete_avatar(&state.base, &pid, &friend.pid).await?;
+            delete_avatar(&state.base, &pid, &db_key, &friend.pid).await?;
 
             if online {
                 let data = bincode::serialize(&GroupEvent::Close)?;
@@ -218,13 +232,11 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
                 results.groups.push(SendType::Disconnect(friend.pid));
             }
 
-            // state.own.write().await.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionFriendDelete(friend.gid),
-            //     FRIEND_TABLE_PATH,
-            //     friend.id,
-            //     &mut results,
-            // )?;
+            let event = InnerEvent::SessionFriendDelete(friend.pid);
+            event.broadcast(&state, &mut results).await?;
+
+            // sync to all devices by cloud.
+            cloud_push(&state, CloudEvent::FriendDelete(friend.pid), &mut results).await?;
 
             Ok(results)
         },
@@ -270,6 +282,16 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
 
             let mut results = HandleResult::rpc(json!(request.to_rpc()));
 
+            let event = InnerEvent::SessionRequest(
+                request.pid,
+                request.name,
+                request.remark.clone(),
+                true,
+                false,
+                false,
+            );
+            event.broadcast(&state, &mut results).await?;
+
             let name = state.own.read().await.account(&pid)?.name.clone();
             let req = GroupEvent::Request(name, request.remark);
             let data = bincode::serialize(&req).unwrap_or(vec![]);
@@ -290,18 +312,20 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let db = chat_db(&state.base, &pid, &db_key)?;
 
             let mut request = Request::get(&db, &id)?;
-
-            // group_lock.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionRequestHandle(request.gid, true, vec![]),
-            //     REQUEST_TABLE_PATH,
-            //     request.id,
-            //     &mut results,
-            // )?;
             request.is_ok = true;
             request.is_over = true;
             request.update(&db)?;
 
+            let event = InnerEvent::SessionRequest(
+                request.pid,
+                request.name.clone(),
+                request.remark,
+                request.is_me,
+                true,
+                true,
+            );
+            event.broadcast(&state, &mut results).await?;
+
             let friend =
                 Friend::from_remote(&db, request.pid, request.name, PeerId::default(), [0u8; 32])?;
             results.rpcs.push(json!([id, friend.to_rpc()]));
@@ -315,6 +339,18 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let data = bincode::serialize(&GroupEvent::Agree).unwrap_or(vec![]);
             results.groups.push(SendType::Event(0, friend.pid, data));
 
+            let event = InnerEvent::SessionFriend(
+                friend.pid,
+                friend.name.clone(),
+                friend.remark.clone(),
+                false,
+            );
+            event.broadcast(&state, &mut results).await?;
+
+            // sync to all devices by cloud.
+            let event = CloudEvent::Friend(friend.pid, friend.name.clone());
+            cloud_push(&state, event, &mut results).await?;
+
             Ok(results)
         },
     );
@@ -336,15 +372,11 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
 
             let data = bincode::serialize(&GroupEvent::Reject).unwrap_or(vec![]);
             let msg = SendType::Event(0, req.pid, data);
-            let results = HandleResult::group(msg);
-
-            // state.own.write().await.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionRequestHandle(req.gid, false, vec![]),
-            //     REQUEST_TABLE_PATH,
-            //     req.id,
-            //     &mut results,
-            // )?;
+            let mut results = HandleResult::group(msg);
+
+            let event =
+                InnerEvent::SessionRequest(req.pid, req.name, req.remark, req.is_me, false, true);
+            event.broadcast(&state, &mut results).await?;
             Ok(results)
         },
     );
@@ -363,18 +395,13 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
 
             // delete avatar. check had friend.
             if Friend::get_id(&db, &req.pid).is_err() {
-                delete_avatar(&state.base, &pid, &req.pid).await?;
+                delete_avatar(&state.base, &pid, &db_key, &req.pid).await?;
             }
             drop(db);
 
-            let results = HandleResult::new();
-            // state.own.write().await.broadcast(
-            //     &gid,
-            //     InnerEvent::SessionRequestDelete(req.gid),
-            //     REQUEST_TABLE_PATH,
-            //     req.id,
-            //     &mut results,
-            // )?;
+            let mut results = HandleResult::new();
+            let event = InnerEvent::SessionRequestDelete(req.pid);
+            event.broadcast(&state, &mut results).await?;
             Ok(results)
         },
     );
@@ -426,14 +453,28 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let (nm, raw) =
                 raw_to_network_message(&pid, &state.base, &db_key, &m_type, content).await?;
             let mut msg = Message::new(&pid, fid, true, m_type, raw, false);
-            msg.insert(&db)?;
+            if let Err(e) = msg.insert(&db) {
+                let _ = release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content);
+                return Err(e.into());
+            }
 
             let mut results = HandleResult::rpc(json!(msg.to_rpc()));
 
-            let tid = state.group.write().await.delivery(msg.id);
-            let event = GroupEvent::Message(msg.hash, nm);
+            let mut group_lock = state.group.write().await;
+            let tid = group_lock.delivery(msg.id);
+            let version = group_lock.version(&fpid);
+            drop(group_lock);
+
+            let (event, image) = message_event(msg.hash, nm.clone(), version);
             let data = bincode::serialize(&event).unwrap_or(vec![]);
             results.groups.push(SendType::Event(tid, fpid, data));
+            if let Some(event) = image {
+                let data = bincode::serialize(&event).unwrap_or(vec![]);
+                results.groups.push(SendType::Event(0, fpid, data));
+            }
+
+            let event = InnerEvent::SessionMessageCreate(fpid, msg.hash, true, nm);
+            event.broadcast(&state, &mut results).await?;
 
             // UPDATE SESSION.
             let s_db = session_db(&state.base, &pid, &db_key)?;
@@ -452,10 +493,189 @@ pub(crate) fn group_rpc(handler: &mut RpcHandler<Global>) {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = chat_db(&state.base, &pid, &db_key)?;
 
+            let msg = Message::get(&db, &id)?;
             Message::delete(&db, &id)?;
+            Vote::delete(&db, &id)?;
+            drop(db);
+            release_content(&state.base, &pid, &db_key, &msg.m_type, &msg.content)?;
+
+            let mut results = HandleResult::new();
+            let event = InnerEvent::SessionMessageDelete(msg.hash);
+            event.broadcast(&state, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "chat-poll-create",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let fid = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let fpid = id_from_str(params[1].as_str().ok_or(RpcError::ParseError)?)?;
+            let question = params[2].as_str().ok_or(RpcError::ParseError)?.to_owned();
+            let options: Vec<String> = params[3]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_owned()))
+                .collect();
+            let multiple = params[4].as_bool().ok_or(RpcError::ParseError)?;
+            let deadline = params[5].as_i64().ok_or(RpcError::ParseError)?;
+
+            let poll = Poll::new(question, options, multiple, deadline);
+            if poll.options.len() < 2 || poll.is_closed() {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = chat_db(&state.base, &pid, &db_key)?;
+
+            let m_type = MessageType::Poll;
+            let (nm, raw) =
+                raw_to_network_message(&pid, &state.base, &db_key, &m_type, &poll.to_content())
+                    .await?;
+            let mut msg = Message::new(&pid, fid, true, m_type, raw, false);
+            msg.insert(&db)?;
+
+            let mut results = HandleResult::rpc(json!(msg.to_rpc()));
+
+            let tid = state.group.write().await.delivery(msg.id);
+            let event = GroupEvent::Message(msg.hash, nm.clone());
+            let data = bincode::serialize(&event).unwrap_or(vec![]);
+            results.groups.push(SendType::Event(tid, fpid, data));
+
+            let event = InnerEvent::SessionMessageCreate(fpid, msg.hash, true, nm);
+            event.broadcast(&state, &mut results).await?;
+
+            // UPDATE SESSION.
+            let s_db = session_db(&state.base, &pid, &db_key)?;
+            update_session(&s_db, &fid, &msg, &mut results);
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "chat-poll-vote",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let options: Vec<u32> = params[1]
+                .as_array()
+                .ok_or(RpcError::ParseError)?
+                .iter()
+                .filter_map(|v| v.as_u64().map(|o| o as u32))
+                .collect();
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = chat_db(&state.base, &pid, &db_key)?;
+
+            let msg = Message::get(&db, &id)?;
+            if msg.m_type != MessageType::Poll {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+            let poll = Poll::from_content(&msg.content)?;
+            poll.check(&options)?;
+            let friend = Friend::get(&db, &msg.fid)?;
+
+            Vote::new(msg.id, pid, options.clone()).insert(&db)?;
+            let votes = Vote::list(&db, &msg.id)?;
+            drop(db);
+
+            let mut results = HandleResult::rpc(Vote::to_rpc(&msg.id, &poll, &votes));
+
+            // poll's creator is the host, others send the vote to it.
+            let event = if msg.is_me {
+                GroupEvent::PollResult(msg.hash, poll.deadline, Vote::to_network(&votes))
+            } else {
+                GroupEvent::PollVote(msg.hash, options)
+            };
+            let data = bincode::serialize(&event).unwrap_or(vec![]);
+            results.groups.push(SendType::Event(0, friend.pid, data));
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "chat-share-fetch",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let did = params[1].as_str().ok_or(RpcError::ParseError)?.to_owned();
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = chat_db(&state.base, &pid, &db_key)?;
+
+            let msg = Message::get(&db, &id)?;
+            if msg.m_type != MessageType::Share || msg.is_me {
+                return Err(RpcError::Custom("Share is invalid!".to_owned()));
+            }
+            let manifest = Manifest::from_content(&msg.content)?;
+            let friend = Friend::get(&db, &msg.fid)?;
+            if manifest.owner != friend.pid {
+                return Err(RpcError::Custom("Share is invalid!".to_owned()));
+            }
+            share_fetch(&manifest, &did)?;
+
+            // file content will response in chat-share-file.
+            let event = GroupEvent::ShareReq(manifest.did.to_hex(), did);
+            let data = bincode::serialize(&event).unwrap_or(vec![]);
+            let mut results = HandleResult::new();
+            results.groups.push(SendType::Event(0, friend.pid, data));
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "chat-poll-close",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = chat_db(&state.base, &pid, &db_key)?;
+
+            let msg = Message::get(&db, &id)?;
+            if !msg.is_me || msg.m_type != MessageType::Poll {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+            let mut poll = Poll::from_content(&msg.content)?;
+            if poll.close() {
+                Message::update_content(&db, &msg.id, &poll.to_content())?;
+            }
+            let friend = Friend::get(&db, &msg.fid)?;
+            let votes = Vote::list(&db, &msg.id)?;
+            drop(db);
+
+            let mut results = HandleResult::rpc(Vote::to_rpc(&msg.id, &poll, &votes));
+
+            let event = GroupEvent::PollResult(msg.hash, poll.deadline, Vote::to_network(&votes));
+            let data = bincode::serialize(&event).unwrap_or(vec![]);
+            results.groups.push(SendType::Event(0, friend.pid, data));
+
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "chat-poll-result",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = chat_db(&state.base, &pid, &db_key)?;
+
+            let msg = Message::get(&db, &id)?;
+            if msg.m_type != MessageType::Poll {
+                return Err(RpcError::Custom("Poll is invalid!".to_owned()));
+            }
+            let poll = Poll::from_content(&msg.content)?;
+            let votes = Vote::list(&db, &msg.id)?;
             drop(db);
 
-            Ok(HandleResult::new())
+            Ok(HandleResult::rpc(Vote::to_rpc(&msg.id, &poll, &votes)))
         },
     );
 }
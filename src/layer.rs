@@ -1,3 +1,4 @@
+use dao_types::DaoId;
 use group_types::GroupChatId;
 use std::collections::HashMap;
 use tdn::types::primitives::{PeerId, Result};
@@ -6,6 +7,8 @@ use tdn::types::primitives::{PeerId, Result};
 pub(crate) struct Layer {
     /// group chat id => Session
     pub groups: HashMap<GroupChatId, LayerSession>,
+    /// dao id => Session
+    pub daos: HashMap<DaoId, LayerSession>,
     /// delivery feedback.
     pub delivery: HashMap<u64, i64>,
     /// delivery counter.
@@ -16,6 +19,7 @@ impl Layer {
     pub fn init() -> Layer {
         Layer {
             groups: HashMap::new(),
+            daos: HashMap::new(),
             delivery: HashMap::new(),
             delivery_count: 0,
         }
@@ -30,6 +34,7 @@ impl Layer {
 
     pub fn clear(&mut self) {
         self.groups.clear();
+        self.daos.clear();
         self.delivery.clear();
     }
 
@@ -40,6 +45,12 @@ impl Layer {
             }
         }
 
+        for (_, session) in &self.daos {
+            if session.addrs.contains(addr) {
+                return true;
+            }
+        }
+
         false
     }
 
@@ -107,6 +118,58 @@ impl Layer {
         false
     }
 
+    pub fn dao_active(&mut self, did: &DaoId, is_me: bool) -> Option<PeerId> {
+        if let Some(session) = self.daos.get_mut(did) {
+            Some(session.active(is_me))
+        } else {
+            None
+        }
+    }
+
+    pub fn dao(&self, did: &DaoId) -> Result<&LayerSession> {
+        if let Some(session) = self.daos.get(did) {
+            Ok(session)
+        } else {
+            Err(anyhow!("session missing!"))
+        }
+    }
+
+    pub fn dao_mut(&mut self, did: &DaoId) -> Result<&mut LayerSession> {
+        if let Some(session) = self.daos.get_mut(did) {
+            Ok(session)
+        } else {
+            Err(anyhow!("session missing!"))
+        }
+    }
+
+    pub fn dao_add(&mut self, did: DaoId, pid: PeerId, sid: i64, fid: i64, h: i64) {
+        if !self.daos.contains_key(&did) {
+            self.daos.insert(did, LayerSession::new(pid, sid, fid, h));
+        }
+    }
+
+    pub fn dao_del(&mut self, did: &DaoId) -> Option<Vec<PeerId>> {
+        self.daos.remove(did).map(|session| session.addrs)
+    }
+
+    pub fn dao_add_member(&mut self, did: &DaoId, addr: PeerId) {
+        if let Some(session) = self.daos.get_mut(did) {
+            if !session.addrs.contains(&addr) {
+                session.addrs.push(addr);
+            }
+        }
+    }
+
+    pub fn dao_del_online(&mut self, did: &DaoId, addr: &PeerId) -> bool {
+        if let Some(session) = self.daos.get_mut(did) {
+            if let Some(pos) = session.addrs.iter().skip(1).position(|x| x == addr) {
+                session.addrs.remove(pos + 1);
+                return true;
+            }
+        }
+        false
+    }
+
     // pub fn broadcast(&self, user: User, results: &mut HandleResult) {
     //     let info = GroupEvent::InfoRes(user);
     //     let data = bincode::serialize(&info).unwrap_or(vec![]);
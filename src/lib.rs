@@ -10,8 +10,9 @@ use std::path::PathBuf;
 
 mod account;
 mod apps;
-//mod consensus;
-//mod event;
+mod blob;
+mod consensus;
+mod event;
 mod global;
 mod group;
 mod layer;
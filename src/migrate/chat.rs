@@ -1,5 +1,5 @@
 #[rustfmt::skip]
-pub(super) const CHAT_VERSIONS: [&str; 3] = [
+pub(super) const CHAT_VERSIONS: [&str; 4] = [
   "CREATE TABLE IF NOT EXISTS friends(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     pid TEXT NOT NULL,
@@ -29,4 +29,10 @@ pub(super) const CHAT_VERSIONS: [&str; 3] = [
     content TEXT NOT NULL,
     is_delivery INTEGER NOT NULL,
     datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS votes(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    mid INTEGER NOT NULL,
+    pid TEXT NOT NULL,
+    options TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
 ];
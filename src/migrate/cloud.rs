@@ -1,3 +1,14 @@
 #[rustfmt::skip]
-pub(super) const CLOUD_VERSIONS: [&str; 0] = [
+pub(super) const CLOUD_VERSIONS: [&str; 5] = [
+  "CREATE TABLE IF NOT EXISTS providers(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    addr TEXT NOT NULL,
+    height INTEGER NOT NULL,
+    used INTEGER NOT NULL,
+    space INTEGER NOT NULL);",
+  "ALTER TABLE providers ADD COLUMN free INTEGER NOT NULL DEFAULT 0;",
+  "ALTER TABLE providers ADD COLUMN vips TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE providers ADD COLUMN is_ok INTEGER NOT NULL DEFAULT 0;",
+  "ALTER TABLE providers ADD COLUMN is_default INTEGER NOT NULL DEFAULT 0;",
 ];
@@ -1,11 +1,12 @@
-//pub(crate) const ACCOUNT_TABLE_PATH: i64 = 0;
-//pub(crate) const FRIEND_TABLE_PATH: i64 = 1;
-//pub(crate) const REQUEST_TABLE_PATH: i64 = 2;
-//pub(crate) const MESSAGE_TABLE_PATH: i64 = 3;
+pub(crate) const ACCOUNT_TABLE_PATH: i64 = 0;
+pub(crate) const FRIEND_TABLE_PATH: i64 = 1;
+pub(crate) const REQUEST_TABLE_PATH: i64 = 2;
+pub(crate) const MESSAGE_TABLE_PATH: i64 = 3;
 //pub(crate) const FILE_TABLE_PATH: i64 = 4;
+pub(crate) const SESSION_TABLE_PATH: i64 = 5;
 
 #[rustfmt::skip]
-pub(super) const CONSENSUS_VERSIONS: [&str; 9] = [
+pub(super) const CONSENSUS_VERSIONS: [&str; 21] = [
   "CREATE TABLE IF NOT EXISTS devices(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     name TEXT NOT NULL,
@@ -30,4 +31,40 @@ pub(super) const CONSENSUS_VERSIONS: [&str; 9] = [
   "INSERT INTO db_tables (db_name, table_name) values ('session.db', 'requests')",
   "INSERT INTO db_tables (db_name, table_name) values ('session.db', 'messages')",
   "INSERT INTO db_tables (db_name, table_name) values ('file.db', 'files')",
+  "CREATE TABLE IF NOT EXISTS revokes(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    assist TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "ALTER TABLE devices ADD COLUMN pair TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE events ADD COLUMN key TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE events ADD COLUMN chain TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE events ADD COLUMN data TEXT NOT NULL DEFAULT '';",
+  "CREATE INDEX events_key
+    ON events (db_table, key);",
+  "CREATE TABLE IF NOT EXISTS snapshots(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    hash TEXT NOT NULL,
+    db_table INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    data TEXT NOT NULL);",
+  "CREATE INDEX snapshots_key
+    ON snapshots (db_table, key);",
+  "CREATE TABLE IF NOT EXISTS snapshot_infos(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    height INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    chain TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS device_logs(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    assist TEXT NOT NULL,
+    command INTEGER NOT NULL,
+    is_me BOOLEAN NOT NULL,
+    status INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "ALTER TABLE revokes ADD COLUMN pair TEXT NOT NULL DEFAULT '';",
+  "CREATE TABLE IF NOT EXISTS command_nonces(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    nonce TEXT NOT NULL UNIQUE,
+    datetime INTEGER NOT NULL);",
 ];
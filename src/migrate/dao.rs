@@ -1,56 +1,44 @@
 #[rustfmt::skip]
-pub(super) const DAO_VERSIONS: [&str; 0] = [
-
-];
-
-#[rustfmt::skip]
-pub(super) const _DAO_VERSIONS: [&str; 8] = [
+pub(super) const DAO_VERSIONS: [&str; 9] = [
   "CREATE TABLE IF NOT EXISTS daos(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     height INTEGER NOT NULL,
     owner TEXT NOT NULL,
-    gcd TEXT NOT NULL,
-    gtype INTEGER NOT NULL,
+    did INTEGER NOT NULL,
+    dtype INTEGER NOT NULL,
     addr TEXT NOT NULL,
     name TEXT NOT NULL,
     bio TEXT NOT NULL,
+    avatar TEXT NOT NULL,
     is_ok INTEGER NOT NULL,
     is_need_agree INTEGER NOT NULL,
     is_closed INTEGER NOT NULL,
     key TEXT NOT NULL,
     datetime INTEGER NOT NULL,
-    is_remote INTEGER NOT NULL);",
-  "CREATE TABLE IF NOT EXISTS categories(
-    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
-    fid INTEGER NOT NULL,
-    name TEXT NOT NULL);",
-  "CREATE TABLE IF NOT EXISTS channels(
-    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
-    fid INTEGER NOT NULL,
-    c_type INTEGER NOT NULL,
-    name TEXT NOT NULL,
-    public INTEGER NOT NULL);",
+    is_local INTEGER NOT NULL,
+    is_deleted INTEGER NOT NULL);",
   "CREATE TABLE IF NOT EXISTS requests(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     fid INTEGER NOT NULL,
     rid INTEGER NOT NULL,
-    gid TEXT NOT NULL,
-    addr TEXT NOT NULL,
+    did INTEGER NOT NULL,
+    pid TEXT NOT NULL,
     name TEXT NOT NULL,
     remark TEXT NOT NULL,
     key TEXT NOT NULL,
     is_ok INTEGER NOT NULL,
     is_over INTEGER NOT NULL,
-    datetime INTEGER NOT NULL);",
+    datetime INTEGER NOT NULL,
+    is_deleted INTEGER NOT NULL);",
   "CREATE TABLE IF NOT EXISTS members(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     fid INTEGER NOT NULL,
-    mid TEXT NOT NULL,
-    addr TEXT NOT NULL,
+    pid TEXT NOT NULL,
     name TEXT NOT NULL,
     is_manager INTEGER NOT NULL,
     is_block INTEGER NOT NULL,
-    datetime INTEGER NOT NULL);",
+    datetime INTEGER NOT NULL,
+    is_deleted INTEGER NOT NULL);",
   "CREATE TABLE IF NOT EXISTS messages(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     height INTEGER NOT NULL,
@@ -61,17 +49,37 @@ pub(super) const _DAO_VERSIONS: [&str; 8] = [
     content TEXT NOT NULL,
     is_delivery INTEGER NOT NULL,
     datetime INTEGER NOT NULL);",
- "CREATE TABLE IF NOT EXISTS consensus(
+  "CREATE TABLE IF NOT EXISTS consensus(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     fid INTEGER NOT NULL,
     height INTEGER NOT NULL,
     ctype INTEGER NOT NULL,
     cid INTEGER NOT NULL);",
- "CREATE TABLE IF NOT EXISTS providers(
+  "CREATE TABLE IF NOT EXISTS providers(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     name TEXT NOT NULL,
     addr TEXT NOT NULL,
     kinds INTEGER NOT NULL,
     remain INTEGER NOT NULL,
     is_ok INTEGER NOT NULL);",
+  "UPDATE daos SET key = lower(hex(randomblob(32))) WHERE dtype = 2 AND is_local = 1 AND key = '';",
+  "CREATE TABLE IF NOT EXISTS proposals(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    fid INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    pid TEXT NOT NULL,
+    p_type INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    start_at INTEGER NOT NULL,
+    end_at INTEGER NOT NULL,
+    agree INTEGER NOT NULL,
+    disagree INTEGER NOT NULL,
+    is_over INTEGER NOT NULL,
+    is_passed INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS votes(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    fid INTEGER NOT NULL,
+    pid TEXT NOT NULL,
+    is_agree INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
 ];
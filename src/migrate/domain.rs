@@ -1,5 +1,5 @@
 #[rustfmt::skip]
-pub(super) const DOMAIN_VERSIONS: [&str; 3] = [
+pub(super) const DOMAIN_VERSIONS: [&str; 8] = [
   "CREATE TABLE IF NOT EXISTS names(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     provider INTEGER NOT NULL,
@@ -16,4 +16,28 @@ pub(super) const DOMAIN_VERSIONS: [&str; 3] = [
     is_proxy INTEGER NOT NULL,
     is_actived INTEGER NOT NULL);",
   "INSERT INTO providers (name, addr, is_ok, is_default, is_proxy, is_actived) VALUES ('domain.esse', '35d1cc54836a151da67fc8d32ec4d3e92777f42f', true, true, true, true);", // domain.esse default inserted.
+  "CREATE TABLE IF NOT EXISTS records(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    provider INTEGER NOT NULL,
+    pid TEXT NOT NULL,
+    name TEXT NOT NULL,
+    bio TEXT NOT NULL,
+    avatar TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    expire INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "ALTER TABLE names ADD COLUMN version INTEGER NOT NULL DEFAULT 0;",
+  "ALTER TABLE names ADD COLUMN expire INTEGER NOT NULL DEFAULT 0;",
+  "ALTER TABLE names ADD COLUMN reminded INTEGER NOT NULL DEFAULT 0;",
+  "CREATE TABLE IF NOT EXISTS requests(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    provider INTEGER NOT NULL,
+    rid INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    remark TEXT NOT NULL,
+    is_me INTEGER NOT NULL,
+    is_ok INTEGER NOT NULL,
+    is_over INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
 ];
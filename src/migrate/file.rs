@@ -1,5 +1,5 @@
 #[rustfmt::skip]
-pub(super) const FILE_VERSIONS: [&str; 1] = [
+pub(super) const FILE_VERSIONS: [&str; 21] = [
   "CREATE TABLE IF NOT EXISTS files(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     did TEXT NOT NULL,
@@ -9,4 +9,50 @@ pub(super) const FILE_VERSIONS: [&str; 1] = [
     starred INTEGER NOT NULL,
     device TEXT NOT NULL,
     datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS blobs(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    hash TEXT NOT NULL,
+    dir TEXT NOT NULL,
+    name TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    refs INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE UNIQUE INDEX IF NOT EXISTS blobs_hash ON blobs (dir, hash);",
+  "CREATE TABLE IF NOT EXISTS avatars(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pid TEXT NOT NULL UNIQUE,
+    hash TEXT NOT NULL);",
+  "ALTER TABLE files ADD COLUMN origin INTEGER NOT NULL DEFAULT 3;",
+  "ALTER TABLE files ADD COLUMN trashed INTEGER NOT NULL DEFAULT 0;",
+  "UPDATE files SET trashed = strftime('%s', 'now') WHERE root = 1;",
+  "CREATE TABLE IF NOT EXISTS trash(
+    retention INTEGER NOT NULL);",
+  "INSERT INTO trash (retention) VALUES (30);",
+  "ALTER TABLE files ADD COLUMN updated INTEGER NOT NULL DEFAULT 0;",
+  "CREATE TABLE IF NOT EXISTS tombstones(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    did TEXT NOT NULL UNIQUE,
+    datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS versions(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    did TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    device TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "CREATE INDEX IF NOT EXISTS versions_did ON versions (did);",
+  "CREATE TABLE IF NOT EXISTS history(
+    keep INTEGER NOT NULL,
+    days INTEGER NOT NULL);",
+  "INSERT INTO history (keep, days) VALUES (10, 0);",
+  "CREATE TABLE IF NOT EXISTS shares(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    did TEXT NOT NULL,
+    sync INTEGER NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "ALTER TABLE files ADD COLUMN size INTEGER NOT NULL DEFAULT 0;",
+  "ALTER TABLE files ADD COLUMN mime TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE files ADD COLUMN hash TEXT NOT NULL DEFAULT '';",
+  "ALTER TABLE files ADD COLUMN tags TEXT NOT NULL DEFAULT '';",
+  "CREATE INDEX IF NOT EXISTS files_datetime ON files (datetime);",
 ];
@@ -1,5 +1,5 @@
 #[rustfmt::skip]
-pub(super) const GROUP_VERSIONS: [&str; 3] = [
+pub(super) const GROUP_VERSIONS: [&str; 5] = [
   "CREATE TABLE IF NOT EXISTS groups(
     id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
     height INTEGER NOT NULL,
@@ -25,4 +25,11 @@ pub(super) const GROUP_VERSIONS: [&str; 3] = [
     content TEXT NOT NULL,
     is_delivery INTEGER NOT NULL,
     datetime INTEGER NOT NULL);",
+  "CREATE TABLE IF NOT EXISTS votes(
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    mid INTEGER NOT NULL,
+    pid TEXT NOT NULL,
+    options TEXT NOT NULL,
+    datetime INTEGER NOT NULL);",
+  "ALTER TABLE messages ADD COLUMN mentions TEXT NOT NULL DEFAULT '';",
 ];
@@ -1,3 +1,5 @@
+mod pair;
+
 //use esse_primitives::id_to_str;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -6,22 +8,31 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
     group::EventId,
-    message::{RecvType, SendType},
+    message::{NetworkType, RecvType, SendType},
     primitives::{HandleResult, Peer, PeerId, PeerKey, Result},
 };
+use tdn_storage::local::DStorage;
 
 use crate::account::{Account, User};
 use crate::apps::device::rpc as device_rpc;
-use crate::apps::device::Device;
+use crate::apps::device::{CommandStatus, Device, DeviceCommand, DeviceLog};
+use crate::apps::file::{file_snapshot, own_handle as file_handle, FileEvent};
+use crate::consensus::Snapshot;
+use crate::event::{
+    snapshot_request, sync_check, sync_check_handle, sync_request, sync_response, sync_snapshot,
+    InnerEvent,
+};
 use crate::global::Global;
-//use crate::consensus::Event;
-//use crate::event::{InnerEvent, StatusEvent, SyncEvent};
 //use crate::layer::Layer;
-//use crate::rpc;
-use crate::storage::{account_db, account_init, consensus_db, wallet_db, write_avatar};
+use crate::rpc::{account_lock, account_wipe};
+use crate::storage::{
+    account_db, account_init, account_remove, consensus_db, wallet_db, write_avatar,
+};
 //use crate::utils::crypto::{decrypt, encrypt};
 use crate::utils::device_status::{device_info, device_status as local_device_status};
 
+pub(crate) use pair::Pairing;
+
 /// ESSE own distributed accounts.
 pub(crate) struct Own {
     /// all accounts.
@@ -32,6 +43,8 @@ pub(crate) struct Own {
     pub distributes: Vec<Device>,
     /// current account uptime
     pub uptime: u32,
+    /// the running device pairing.
+    pub pairing: Option<Pairing>,
 }
 
 /// Request for make distributed.
@@ -39,16 +52,22 @@ pub(crate) struct Own {
 enum OwnConnect {
     /// Params: User, consensus height, event_id, remote_name, remote_info, other_devices addr.
     Create(User, u64, EventId, String, String, Vec<PeerId>),
-    /// connected.
-    Connect(u64, EventId),
+    /// connected, only paired devices can connect.
+    /// Params: consensus height, event_id, nonce, proof.
+    Connect(u64, EventId, Vec<u8>, Vec<u8>),
+    /// connect accepted. Params: nonce, proof.
+    Accept(Vec<u8>, Vec<u8>),
+    /// new device pairing request with the code. Params: ephemeral public key, proof.
+    Pair(Vec<u8>, Vec<u8>),
+    /// pairing accepted. Params: ephemeral public key, proof.
+    Paired(Vec<u8>, Vec<u8>),
 }
 
 /// Esse group's Event.
 #[derive(Serialize, Deserialize)]
 pub(crate) enum OwnEvent {
-    /// Sync event.
-    Event(u64, EventId, EventId),
-    //Event(u64, EventId, EventId, InnerEvent),
+    /// Sync event. Params: height, event_id, previous chain hash, event.
+    Event(u64, EventId, EventId, InnerEvent),
     /// Sync infomations (name, info).
     Info(String, String),
     /// update device's name.
@@ -60,13 +79,31 @@ pub(crate) enum OwnEvent {
     /// Device status response.
     /// (cpu_num, memory_space, swap_space, disk_space, cpu%, memory%, swap%, disk%, uptime).
     StatusResponse(u32, u32, u32, u32, u16, u16, u16, u16, u32),
-    /// check consensus stable.
+    /// check consensus stable. Params: heights, chain hashes, is reply.
     SyncCheck(Vec<u64>, Vec<EventId>, bool),
     /// Sync height from..to request.
     SyncRequest(u64, u64),
     /// Sync height from..last_to, to, response.
-    SyncResponse(u64, u64, u64),
-    //SyncResponse(u64, u64, u64, Vec<SyncEvent>),
+    SyncResponse(u64, u64, u64, Vec<(EventId, InnerEvent)>),
+    /// File app's files sync.
+    File(FileEvent),
+    /// Sync snapshot's events, when the request events are truncated.
+    /// Params: height, last event_id, chain hash, offset, total, to, events.
+    SyncSnapshot(
+        u64,
+        EventId,
+        EventId,
+        u64,
+        u64,
+        u64,
+        Vec<(EventId, InnerEvent)>,
+    ),
+    /// Sync snapshot's events request. Params: offset, to.
+    SnapshotRequest(u64, u64),
+    /// remote lock or wipe this device. Params: command, log id, nonce, proof.
+    Command(DeviceCommand, i64, Vec<u8>, Vec<u8>),
+    /// the command is acknowledged. Params: log id, is executed.
+    CommandAck(i64, bool),
 }
 
 /// handle inner-group message.
@@ -74,24 +111,113 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
     let mut results = HandleResult::new();
 
     match msg {
-        RecvType::Connect(peer, _) => {
+        RecvType::Connect(peer, data) | RecvType::ResultConnect(peer, data) => {
+            let pid = global.pid().await;
+            let db_key = global.own.read().await.db_key(&pid)?;
+            let db = consensus_db(&global.base, &pid, &db_key)?;
+            let aid = peer.id;
+            let current = global.own.read().await.current_assist()?;
+
+            match bincode::deserialize(&data) {
+                Ok(OwnConnect::Pair(public, proof)) => {
+                    // pairing with the code which displayed in this device.
+                    let mut own_lock = global.own.write().await;
+                    let res = if let Some(pairing) = own_lock.pairing.as_mut() {
+                        pairing.accept(&current, &aid, &public, &proof)
+                    } else {
+                        Err(anyhow!("no pairing"))
+                    };
+                    match res {
+                        Ok((key, public, proof)) => {
+                            own_lock.pairing = None;
+                            drop(own_lock);
+                            let data = bincode::serialize(&OwnConnect::Paired(public, proof))?;
+                            let msg = SendType::Result(0, peer.clone(), true, false, data);
+                            results.owns.push(msg);
+                            joined(peer, Some(key.to_vec()), &db, global, &mut results).await?;
+                            results.rpcs.push(device_rpc::device_pair(true));
+                        }
+                        Err(e) => {
+                            warn!("device pairing: {}", e);
+                            let msg = SendType::Result(0, peer, false, false, vec![]);
+                            results.owns.push(msg);
+                        }
+                    }
+                }
+                Ok(OwnConnect::Connect(_height, _eid, nonce, proof)) => {
+                    // only the paired and not revoked devices.
+                    let key = Device::get(&db, &aid)?.map(|d| d.pair).unwrap_or(vec![]);
+                    let is_ok = !Device::is_revoked(&db, &aid)?
+                        && pair::proof_verify(&key, b"connect", &aid, &nonce, &proof);
+                    if is_ok {
+                        let (nonce, proof) = pair::proof(&key, b"accept", &current)?;
+                        let data = bincode::serialize(&OwnConnect::Accept(nonce, proof))?;
+                        let msg = SendType::Result(0, peer.clone(), true, false, data);
+                        results.owns.push(msg);
+                        joined(peer, None, &db, global, &mut results).await?;
+                    } else if let Some(pending) = revoked_pending(&db, &aid, &nonce, &proof)? {
+                        // the revoked device only receives its pending commands.
+                        let key = Device::revoked_pair(&db, &aid)?;
+                        let (nonce, proof) = pair::proof(&key, b"accept", &current)?;
+                        let data = bincode::serialize(&OwnConnect::Accept(nonce, proof))?;
+                        results
+                            .owns
+                            .push(SendType::Result(0, peer, true, false, data));
+                        let own_lock = global.own.read().await;
+                        for log in pending {
+                            results.owns.push(own_lock.command(&key, &aid, &log)?);
+                        }
+                    } else {
+                        results.owns.push(SendType::Disconnect(aid));
+                    }
+                }
+                _ => {
+                    results.owns.push(SendType::Disconnect(aid));
+                }
+            }
+        }
+        RecvType::Result(peer, is_ok, data) => {
             let pid = global.pid().await;
             let db_key = global.own.read().await.db_key(&pid)?;
             let db = consensus_db(&global.base, &pid, &db_key)?;
-            if let Ok(id) = global.own.write().await.online(&peer.id) {
-                results.rpcs.push(device_rpc::device_online(id));
-            } else {
-                let aid = peer.id;
-                let mut device = Device::new(peer);
-                device.insert(&db)?;
-                let (_id, name, info) = global.own.read().await.current_device()?;
-                let own_event = OwnEvent::Info(name, info);
-                let data = bincode::serialize(&own_event)?;
-                let msg = SendType::Event(0, aid, data);
-                results.owns.push(msg);
-                results.rpcs.push(device_rpc::device_create(&device));
-                global.own.write().await.add_device(device);
-            };
+            let aid = peer.id;
+
+            match bincode::deserialize(&data) {
+                Ok(OwnConnect::Paired(public, proof)) if is_ok => {
+                    let mut own_lock = global.own.write().await;
+                    let res = if let Some(pairing) = own_lock.pairing.as_ref() {
+                        pairing.finish(&aid, &public, &proof)
+                    } else {
+                        Err(anyhow!("no pairing"))
+                    };
+                    own_lock.pairing = None;
+                    drop(own_lock);
+
+                    if let Ok(key) = res {
+                        joined(peer, Some(key.to_vec()), &db, global, &mut results).await?;
+                        results.rpcs.push(device_rpc::device_pair(true));
+                    } else {
+                        results.owns.push(SendType::Disconnect(aid));
+                        results.rpcs.push(device_rpc::device_pair(false));
+                    }
+                }
+                Ok(OwnConnect::Accept(nonce, proof)) if is_ok => {
+                    let key = Device::get(&db, &aid)?.map(|d| d.pair).unwrap_or(vec![]);
+                    if pair::proof_verify(&key, b"accept", &aid, &nonce, &proof) {
+                        joined(peer, None, &db, global, &mut results).await?;
+                    } else {
+                        results.owns.push(SendType::Disconnect(aid));
+                    }
+                }
+                _ => {
+                    // the pairing code is rejected.
+                    let mut own_lock = global.own.write().await;
+                    if own_lock.pairing.as_ref().map(|p| !p.host).unwrap_or(false) {
+                        own_lock.pairing = None;
+                        results.rpcs.push(device_rpc::device_pair(false));
+                    }
+                }
+            }
         }
         RecvType::Leave(peer) => {
             if let Ok(id) = global.own.write().await.offline(&peer.id) {
@@ -100,6 +226,15 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
         }
         RecvType::Event(aid, bytes) => {
             let event: OwnEvent = bincode::deserialize(&bytes)?;
+            if !matches!(event, OwnEvent::CommandAck(..)) {
+                // the revoked device only acknowledges the commands.
+                let pid = global.pid().await;
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = consensus_db(&global.base, &pid, &db_key)?;
+                if Device::is_revoked(&db, &aid)? {
+                    return Ok(results);
+                }
+            }
             return OwnEvent::handle(aid, event, global).await;
         }
         RecvType::Stream(_uid, _stream, _bytes) => {
@@ -114,6 +249,77 @@ pub(crate) async fn handle(msg: RecvType, global: &Arc<Global>) -> Result<Handle
     Ok(results)
 }
 
+/// the revoked device's pending commands, if it connected with the revoked pair key.
+fn revoked_pending(
+    db: &DStorage,
+    aid: &PeerId,
+    nonce: &[u8],
+    proof: &[u8],
+) -> Result<Option<Vec<DeviceLog>>> {
+    if !Device::is_revoked(db, aid)? {
+        return Ok(None);
+    }
+    let key = Device::revoked_pair(db, aid)?;
+    if !pair::proof_verify(&key, b"connect", aid, nonce, proof) {
+        return Ok(None);
+    }
+    let pending = DeviceLog::pending(db, aid)?;
+    if pending.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(pending))
+    }
+}
+
+/// the paired device is connected, if pair key is some, it is new paired.
+async fn joined(
+    peer: Peer,
+    pair: Option<Vec<u8>>,
+    db: &DStorage,
+    global: &Arc<Global>,
+    results: &mut HandleResult,
+) -> Result<()> {
+    let aid = peer.id;
+    if let Ok(id) = global.own.write().await.online(&aid) {
+        if let Some(key) = pair {
+            if let Some(mut device) = Device::get(db, &aid)? {
+                device.paired(db, key.clone())?;
+            }
+            global.own.write().await.paired(&aid, key);
+        }
+        results.rpcs.push(device_rpc::device_online(id));
+    } else if let Some(key) = pair {
+        let mut device = Device::new(peer);
+        device.insert(db)?;
+        device.paired(db, key)?;
+        let (_id, name, info) = global.own.read().await.current_device()?;
+        let own_event = OwnEvent::Info(name, info);
+        let data = bincode::serialize(&own_event)?;
+        let msg = SendType::Event(0, aid, data);
+        results.owns.push(msg);
+        results.rpcs.push(device_rpc::device_create(&device));
+        global.own.write().await.add_device(device);
+    } else {
+        return Err(anyhow!("device is not paired"));
+    }
+
+    // sync the files metadata with the device.
+    let data = bincode::serialize(&OwnEvent::File(file_snapshot(global).await?))?;
+    results.owns.push(SendType::Event(0, aid, data));
+
+    // check the consensus with the device.
+    let data = bincode::serialize(&sync_check(db, false)?)?;
+    results.owns.push(SendType::Event(0, aid, data));
+
+    // send the commands which sent when the device is offline.
+    let pair = Device::get(db, &aid)?.map(|d| d.pair).unwrap_or(vec![]);
+    let own_lock = global.own.read().await;
+    for log in DeviceLog::pending(db, &aid)? {
+        results.owns.push(own_lock.command(&pair, &aid, &log)?);
+    }
+    Ok(())
+}
+
 impl Own {
     pub fn init(accounts: HashMap<PeerId, Account>) -> Own {
         Own {
@@ -121,6 +327,7 @@ impl Own {
             keypair: PeerKey::default(),
             distributes: vec![],
             uptime: 0,
+            pairing: None,
         }
     }
 
@@ -166,6 +373,88 @@ impl Own {
         self.distributes.push(device);
     }
 
+    /// update the device's pair key.
+    pub fn paired(&mut self, aid: &PeerId, key: Vec<u8>) {
+        for device in self.distributes.iter_mut() {
+            if &device.assist == aid {
+                device.pair = key;
+                return;
+            }
+        }
+    }
+
+    /// connect to all paired devices with the proof.
+    pub fn connect_devices(&self, height: u64, results: &mut HandleResult) -> Result<()> {
+        let current = self.current_assist()?;
+        for device in self.distributes.iter().skip(1) {
+            if device.online || device.pair.is_empty() {
+                continue;
+            }
+            let (nonce, proof) = pair::proof(&device.pair, b"connect", &current)?;
+            let event = OwnConnect::Connect(height, EventId::default(), nonce, proof);
+            let data = bincode::serialize(&event)?;
+            results
+                .owns
+                .push(SendType::Connect(0, device.peer.clone(), data));
+        }
+        Ok(())
+    }
+
+    /// new device start pairing with the code, connect to the device in the QR,
+    /// or the account's devices if input the code.
+    pub fn pair_devices(&mut self, pid: PeerId, pairing: Pairing) -> Result<SendType> {
+        let (public, proof) = pairing.request(&self.current_assist()?);
+        let peer = Peer::peer(pairing.assist.unwrap_or(pid));
+        self.pairing = Some(pairing);
+        let data = bincode::serialize(&OwnConnect::Pair(public, proof))?;
+        Ok(SendType::Connect(0, peer, data))
+    }
+
+    /// remove the device, return the device's id.
+    pub fn remove_device(&mut self, aid: &PeerId) -> Option<i64> {
+        let index = self.distributes.iter().position(|d| &d.assist == aid)?;
+        Some(self.distributes.remove(index).id)
+    }
+
+    /// the remote command to the device, with the proof of the pair key.
+    /// the PIN is checked in this device before the command.
+    pub fn command(&self, pair: &[u8], aid: &PeerId, log: &DeviceLog) -> Result<SendType> {
+        let current = self.current_assist()?;
+        let label = log.command.label();
+        let (nonce, proof) = pair::proof(pair, label, &current)?;
+        let event = OwnEvent::Command(log.command, log.id, nonce, proof);
+        let data = bincode::serialize(&event)?;
+        Ok(SendType::Event(0, *aid, data))
+    }
+
+    /// current device's assist id.
+    pub fn current_assist(&self) -> Result<PeerId> {
+        if self.distributes.len() > 0 {
+            Ok(self.distributes[0].assist)
+        } else {
+            Err(anyhow!("no devices"))
+        }
+    }
+
+    /// other online devices.
+    pub fn online_devices(&self) -> Vec<PeerId> {
+        self.distributes
+            .iter()
+            .skip(1)
+            .filter(|d| d.online)
+            .map(|d| d.assist)
+            .collect()
+    }
+
+    /// broadcast the event to other online devices.
+    pub fn broadcast(&self, event: &OwnEvent, results: &mut HandleResult) -> Result<()> {
+        let data = bincode::serialize(event)?;
+        for aid in self.online_devices() {
+            results.owns.push(SendType::Event(0, aid, data.clone()));
+        }
+        Ok(())
+    }
+
     pub fn check_lock(&self, pid: &PeerId, lock: &str) -> bool {
         if let Some(account) = self.accounts.get(pid) {
             account.check_lock(lock).is_ok()
@@ -273,8 +562,8 @@ impl Own {
         account.insert(&account_db)?;
         account_db.close()?;
         let account_did = account.id;
-        let _key = account.plainkey();
-        let _ = write_avatar(base, &account_id, &account_id, &account.avatar).await;
+        let key = account.plainkey();
+        let _ = write_avatar(base, &account_id, &key, &account_id, &account.avatar).await;
         self.accounts.insert(account.pid, account);
 
         let db_key = self.db_key(&account_id)?;
@@ -363,8 +652,25 @@ impl OwnEvent {
             OwnEvent::DeviceUpdate(_aid, _name) => {
                 // TODO
             }
-            OwnEvent::DeviceDelete(_aid) => {
-                // TODO
+            OwnEvent::DeviceDelete(did) => {
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = consensus_db(&global.base, &pid, &db_key)?;
+                let current = global.own.read().await.current_assist()?;
+                if did == current {
+                    // this device is deleted by others, stop syncing with them.
+                    results.owns.push(SendType::Disconnect(aid));
+                    return Ok(results);
+                }
+
+                let mut pair = vec![];
+                if let Some(device) = Device::get(&db, &did)? {
+                    Device::delete(&db, &device.id)?;
+                    results.rpcs.push(device_rpc::device_delete(device.id));
+                    pair = device.pair;
+                }
+                Device::revoke(&db, &did, &pair)?;
+                global.own.write().await.remove_device(&did);
+                results.owns.push(SendType::Disconnect(did));
             }
             OwnEvent::StatusRequest => {
                 let uptime = global.own.read().await.uptime;
@@ -393,111 +699,166 @@ impl OwnEvent {
                     id, cpu_n, mem_s, swap_s, disk_s, cpu_p, mem_p, swap_p, disk_p, uptime,
                 ));
             }
-            OwnEvent::Event(_eheight, _eid, _pre) => {
-                //inner_event.handle(group, pid, addr, eheight, eid, pre, &mut results, layer)?;
+            OwnEvent::File(event) => {
+                return file_handle(aid, event, global).await;
+            }
+            OwnEvent::Event(eheight, eid, pre, inner_event) => {
+                inner_event
+                    .handle(aid, eheight, eid, pre, global, &mut results)
+                    .await?;
+            }
+            OwnEvent::SyncCheck(heights, chains, is_reply) => {
+                sync_check_handle(aid, heights, chains, is_reply, global, &mut results).await?;
+            }
+            OwnEvent::SyncRequest(from, to) => {
+                let event = sync_request(global, from, to).await?;
+                let data = bincode::serialize(&event)?;
+                results.owns.push(SendType::Event(0, aid, data));
+            }
+            OwnEvent::SyncResponse(_from, last_to, to, events) => {
+                sync_response(aid, last_to, to, events, global, &mut results).await?;
             }
-            OwnEvent::SyncCheck(_ancestors, _hashes, _is_min) => {
-                // println!("sync check: {:?}", ancestors);
-                // let account = group.account(&pid)?;
-                // if ancestors.len() == 0 || hashes.len() == 0 {
-                //     return Ok(results);
-                // }
-
-                // // remote is new need it handle.
-                // if hashes[0] == EventId::default() {
-                //     return Ok(results);
-                // }
-
-                // let remote_height = ancestors.last().map(|v| *v).unwrap_or(0);
-                // let remote_event = hashes.last().map(|v| *v).unwrap_or(EventId::default());
-                // if account.own_height != remote_height || account.event != remote_event {
-                //     // check ancestor and merge.
-                //     let db = group.consensus_db(&pid)?;
-                //     let ours = vec![];
-                //     //let ours = crate::consensus::Event::get_assign_hash(&db, &ancestors)?;
-                //     drop(db);
-
-                //     if ours.len() == 0 {
-                //         let event = OwnEvent::SyncRequest(1, remote_height);
-                //         let data = bincode::serialize(&event).unwrap_or(vec![]);
-                //         results.groups.push((pid, SendType::Event(0, addr, data)));
-                //         return Ok(results);
-                //     }
-
-                //     let mut ancestor = 0u64;
-                //     for i in 0..ancestors.len() {
-                //         if hashes[i] != ours[i] {
-                //             if i == 0 {
-                //                 ancestor = ancestors[0];
-                //                 break;
-                //             }
-
-                //             if ancestors[i - 1] == ancestors[i] + 1 {
-                //                 ancestor = ancestors[i - 1];
-                //             } else {
-                //                 if is_min {
-                //                     ancestor = ancestors[i - 1];
-                //                 } else {
-                //                     results.groups.push((
-                //                         pid,
-                //                         group.sync_message(
-                //                             &pid,
-                //                             addr,
-                //                             ancestors[i - 1],
-                //                             ancestors[i],
-                //                         )?,
-                //                     ));
-                //                     return Ok(results);
-                //                 }
-                //             }
-
-                //             break;
-                //         }
-                //     }
-
-                //     if ancestor != 0 {
-                //         let event = OwnEvent::SyncRequest(ancestor, remote_height);
-                //         let data = bincode::serialize(&event).unwrap_or(vec![]);
-                //         results.groups.push((pid, SendType::Event(0, addr, data)));
-                //     } else {
-                //         results.groups.push((
-                //             pid,
-                //             group.sync_message(&pid, addr, remote_height, account.own_height)?,
-                //         ));
-                //     }
-                // }
+            OwnEvent::SyncSnapshot(height, hash, chain, offset, total, to, events) => {
+                let snapshot = Snapshot {
+                    height,
+                    hash,
+                    chain,
+                };
+                sync_snapshot(
+                    aid,
+                    snapshot,
+                    offset,
+                    total,
+                    to,
+                    events,
+                    global,
+                    &mut results,
+                )
+                .await?;
             }
-            OwnEvent::SyncRequest(_from, _to) => {
-                //println!("====== DEBUG Sync Request: from: {} to {}", from, to);
-                // every time sync MAX is 100.
-                //let last_to = if to - from > 100 { to - 100 } else { to };
-                // let sync_events = SyncEvent::sync(
-                //     &group,
-                //     &group.base,
-                //     &pid,
-                //     group.account(&pid)?,
-                //     from,
-                //     last_to,
-                // )
-                // .await?;
-                // let event = OwnEvent::SyncResponse(from, last_to, to);
-                // let data = bincode::serialize(&event).unwrap_or(vec![]);
-                // results.groups.push((pid, SendType::Event(0, addr, data)));
+            OwnEvent::SnapshotRequest(offset, to) => {
+                let event = snapshot_request(global, offset, to).await?;
+                let data = bincode::serialize(&event)?;
+                results.owns.push(SendType::Event(0, aid, data));
+            }
+            OwnEvent::Command(command, id, nonce, proof) => {
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = consensus_db(&global.base, &pid, &db_key)?;
+
+                // only the paired and not revoked devices, and not replayed.
+                let key = Device::get(&db, &aid)?.map(|d| d.pair).unwrap_or(vec![]);
+                let label = command.label();
+                let is_ok = !Device::is_revoked(&db, &aid)?
+                    && pair::proof_verify(&key, label, &aid, &nonce, &proof)
+                    && DeviceLog::nonce(&db, &nonce)?;
+                let status = if is_ok {
+                    CommandStatus::Done
+                } else {
+                    CommandStatus::Failed
+                };
+                let mut log = DeviceLog::new(aid, command, false, status);
+                log.insert(&db)?;
+                db.close()?;
+
+                // acknowledge first, the network will stop.
+                let data = bincode::serialize(&OwnEvent::CommandAck(id, is_ok))?;
+                results.owns.push(SendType::Event(0, aid, data));
+                if !is_ok {
+                    warn!("device command proof is invalid");
+                    return Ok(results);
+                }
+
+                match command {
+                    DeviceCommand::Lock => {
+                        results.rpcs.push(account_lock(&pid));
+                    }
+                    DeviceCommand::Wipe => {
+                        let mut own_lock = global.own.write().await;
+                        if let Some(account) = own_lock.accounts.remove(&pid) {
+                            let db = account_db(&global.base, &global.secret)?;
+                            account.delete(&db)?;
+                            db.close()?;
+                        }
+                        drop(own_lock);
+                        account_remove(&global.base, &pid).await?;
+                        results.rpcs.push(account_wipe(&pid));
+                    }
+                }
+                results.networks.push(NetworkType::NetworkStop);
+                global.clear().await;
             }
-            OwnEvent::SyncResponse(_from, _last_to, _to) => {
-                // println!(
-                //     "====== DEBUG Sync Response: from: {} last {}, to {}",
-                //     from, last_to, to
-                // );
-                // if last_to < to {
-                //     let event = OwnEvent::SyncRequest(last_to + 1, to);
-                //     let data = bincode::serialize(&event).unwrap_or(vec![]);
-                //     results.groups.push((pid, SendType::Event(0, addr, data)));
-                // }
-                //SyncEvent::handle(pid, from, last_to, events, group, layer, &mut results, addr)?;
+            OwnEvent::CommandAck(id, is_ok) => {
+                let db_key = global.own.read().await.db_key(&pid)?;
+                let db = consensus_db(&global.base, &pid, &db_key)?;
+                let mut log = DeviceLog::get(&db, &id)?;
+                if log.assist == aid && log.is_me && log.status == CommandStatus::Pending {
+                    let status = if is_ok {
+                        CommandStatus::Done
+                    } else {
+                        CommandStatus::Failed
+                    };
+                    log.update_status(&db, status)?;
+                    results.rpcs.push(device_rpc::device_log(&log));
+                }
+                // the revoked device had received its commands.
+                if Device::is_revoked(&db, &aid)? && DeviceLog::pending(&db, &aid)?.is_empty() {
+                    results.owns.push(SendType::Disconnect(aid));
+                }
             }
         }
 
         Ok(results)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::Node;
+
+    /// the command's acknowledge in the results.
+    fn ack(results: &HandleResult) -> bool {
+        for msg in results.owns.iter() {
+            if let SendType::Event(_, _, data) = msg {
+                if let Ok(OwnEvent::CommandAck(_, is_ok)) = bincode::deserialize(data) {
+                    return is_ok;
+                }
+            }
+        }
+        panic!("no command acknowledge");
+    }
+
+    #[tokio::test]
+    async fn command_need_pair_and_nonce() {
+        let node = Node::new("node").await;
+        let db = node.db(consensus_db).await;
+        let aid = PeerId([9u8; 32]);
+        let key = vec![3u8; 32];
+        let mut device = Device::new(Peer::peer(aid));
+        device.insert(&db).unwrap();
+        device.paired(&db, key.clone()).unwrap();
+
+        // the device without the pair key.
+        let label = DeviceCommand::Lock.label();
+        let (nonce, proof) = pair::proof(&[4u8; 32], label, &aid).unwrap();
+        let event = OwnEvent::Command(DeviceCommand::Lock, 1, nonce, proof);
+        let results = OwnEvent::handle(aid, event, &node.global).await.unwrap();
+        assert!(!ack(&results));
+        assert!(results.networks.is_empty());
+
+        // the connect proof is not a command.
+        let (nonce, proof) = pair::proof(&key, b"connect", &aid).unwrap();
+        let event = OwnEvent::Command(DeviceCommand::Lock, 2, nonce, proof);
+        let results = OwnEvent::handle(aid, event, &node.global).await.unwrap();
+        assert!(!ack(&results));
+
+        let (nonce, proof) = pair::proof(&key, label, &aid).unwrap();
+        let event = OwnEvent::Command(DeviceCommand::Lock, 3, nonce.clone(), proof);
+        let results = OwnEvent::handle(aid, event, &node.global).await.unwrap();
+        assert!(ack(&results));
+        assert!(!results.networks.is_empty());
+
+        // the nonce is used, replayed command is rejected.
+        assert!(!DeviceLog::nonce(&db, &nonce).unwrap());
+    }
+}
@@ -0,0 +1,272 @@
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaChaRng,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tdn::types::primitives::{PeerId, Result};
+
+/// pairing code's characters, no ambiguous characters (0/O, 1/I).
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// pairing code's length.
+const CODE_LEN: usize = 8;
+
+/// pairing code's valid time, 5 minutes.
+const PAIR_EXPIRE: i64 = 300;
+
+/// max wrong tries of the pairing code.
+const PAIR_TRIES: u32 = 3;
+
+/// QR payload's prefix, `esse-pair:assist:code`.
+const QR_PREFIX: &str = "esse-pair:";
+
+const CODE_CONTEXT: &str = "ESSE 2022 own device pairing code";
+
+const PAIR_CONTEXT: &str = "ESSE 2022 own device pairing key";
+
+#[inline]
+fn now() -> i64 {
+    let start = SystemTime::now();
+    start
+        .duration_since(UNIX_EPOCH)
+        .map(|s| s.as_secs())
+        .unwrap_or(0) as i64 // safe for all life.
+}
+
+fn random() -> [u8; 32] {
+    let mut rng = ChaChaRng::from_entropy();
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn mac(key: &[u8; 32], parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(key);
+    for part in parts {
+        hasher.update(part);
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// constant-time compare the proof.
+fn verify(key: &[u8; 32], parts: &[&[u8]], proof: &[u8]) -> bool {
+    if proof.len() != 32 {
+        return false;
+    }
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(proof);
+    blake3::Hash::from(mac(key, parts)) == blake3::Hash::from(bytes)
+}
+
+/// the X25519 ephemeral public key of the secret.
+fn public(secret: &[u8; 32]) -> [u8; 32] {
+    MontgomeryPoint::mul_base_clamped(*secret).to_bytes()
+}
+
+/// the pair key, derived from the X25519 shared secret and the public keys.
+fn pair_key(secret: &[u8; 32], remote: &[u8], host: &[u8], new: &[u8]) -> Result<[u8; 32]> {
+    let remote: [u8; 32] = remote
+        .try_into()
+        .map_err(|_| anyhow!("pairing key is invalid"))?;
+    let shared = MontgomeryPoint(remote).mul_clamped(*secret).to_bytes();
+    if shared == [0u8; 32] {
+        return Err(anyhow!("pairing key is invalid"));
+    }
+    let mut hasher = blake3::Hasher::new_derive_key(PAIR_CONTEXT);
+    hasher.update(&shared);
+    hasher.update(host);
+    hasher.update(new);
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// One-time pairing between an existing device (host) and a new device.
+/// the devices exchange X25519 ephemeral keys which authenticated by the code,
+/// the pair key is derived from the shared secret, not from the code.
+pub(crate) struct Pairing {
+    /// the key derived from the code, only authenticate the exchange.
+    code: [u8; 32],
+    /// the ephemeral secret key.
+    secret: [u8; 32],
+    expire: i64,
+    tries: u32,
+    /// the code is displayed in this device.
+    pub host: bool,
+    /// the host device in the QR payload, None if input the code.
+    pub assist: Option<PeerId>,
+}
+
+impl Pairing {
+    /// generate a new code in the existing device, return the code.
+    pub fn host() -> (Self, String) {
+        let mut rng = ChaChaRng::from_entropy();
+        let code: String = (0..CODE_LEN)
+            .map(|_| CODE_CHARS[(rng.next_u32() as usize) % CODE_CHARS.len()] as char)
+            .collect();
+        let pairing = Self {
+            code: blake3::derive_key(CODE_CONTEXT, code.as_bytes()),
+            secret: random(),
+            expire: now() + PAIR_EXPIRE,
+            tries: 0,
+            host: true,
+            assist: None,
+        };
+        (pairing, code)
+    }
+
+    /// the QR payload of the code.
+    pub fn qr(aid: &PeerId, code: &str) -> String {
+        format!("{}{}:{}", QR_PREFIX, aid.to_hex(), code)
+    }
+
+    /// the new device input the code or scan the QR payload.
+    pub fn join(input: &str) -> Result<Self> {
+        let (assist, code) = if let Some(payload) = input.trim().strip_prefix(QR_PREFIX) {
+            let (aid, code) = payload
+                .split_once(':')
+                .ok_or(anyhow!("pairing code is invalid"))?;
+            let aid = PeerId::from_hex(aid).map_err(|_| anyhow!("pairing code is invalid"))?;
+            (Some(aid), code)
+        } else {
+            (None, input)
+        };
+        let code: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if code.len() != CODE_LEN {
+            return Err(anyhow!("pairing code is invalid"));
+        }
+
+        Ok(Self {
+            code: blake3::derive_key(CODE_CONTEXT, code.as_bytes()),
+            secret: random(),
+            expire: now() + PAIR_EXPIRE,
+            tries: 0,
+            host: false,
+            assist,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        now() > self.expire || self.tries >= PAIR_TRIES
+    }
+
+    /// new device's pairing request. return ephemeral public key, proof.
+    pub fn request(&self, aid: &PeerId) -> (Vec<u8>, Vec<u8>) {
+        let public = public(&self.secret);
+        let proof = mac(&self.code, &[b"pair", &public, &aid.0]);
+        (public.to_vec(), proof.to_vec())
+    }
+
+    /// host check the request from the new device (remote).
+    /// return the pair key, and response ephemeral public key, proof.
+    pub fn accept(
+        &mut self,
+        aid: &PeerId,
+        remote: &PeerId,
+        remote_public: &[u8],
+        proof: &[u8],
+    ) -> Result<([u8; 32], Vec<u8>, Vec<u8>)> {
+        if !self.host || self.is_expired() {
+            return Err(anyhow!("pairing is expired"));
+        }
+        if !verify(&self.code, &[b"pair", remote_public, &remote.0], proof) {
+            self.tries += 1;
+            return Err(anyhow!("pairing code is invalid"));
+        }
+
+        let public = public(&self.secret);
+        let key = pair_key(&self.secret, remote_public, &public, remote_public)?;
+        let proof = mac(&self.code, &[b"paired", &public, remote_public, &aid.0]);
+        Ok((key, public.to_vec(), proof.to_vec()))
+    }
+
+    /// new device check the host's response, return the pair key.
+    pub fn finish(&self, remote: &PeerId, remote_public: &[u8], proof: &[u8]) -> Result<[u8; 32]> {
+        if self.host || self.is_expired() {
+            return Err(anyhow!("pairing is expired"));
+        }
+        if self.assist.map(|a| &a != remote).unwrap_or(false) {
+            return Err(anyhow!("pairing device is invalid"));
+        }
+        let public = public(&self.secret);
+        let parts: [&[u8]; 4] = [b"paired", remote_public, &public, &remote.0];
+        if !verify(&self.code, &parts, proof) {
+            return Err(anyhow!("pairing proof is invalid"));
+        }
+        pair_key(&self.secret, remote_public, remote_public, &public)
+    }
+}
+
+/// the paired device's connect or accept proof. return nonce, proof.
+pub(crate) fn proof(key: &[u8], label: &[u8], aid: &PeerId) -> Result<(Vec<u8>, Vec<u8>)> {
+    let key: [u8; 32] = key
+        .try_into()
+        .map_err(|_| anyhow!("device is not paired"))?;
+    let nonce = random();
+    Ok((nonce.to_vec(), mac(&key, &[label, &nonce, &aid.0]).to_vec()))
+}
+
+/// check the paired device's connect or accept proof.
+pub(crate) fn proof_verify(
+    key: &[u8],
+    label: &[u8],
+    aid: &PeerId,
+    nonce: &[u8],
+    proof: &[u8],
+) -> bool {
+    if let Ok(key) = key.try_into() {
+        verify(&key, &[label, nonce, &aid.0], proof)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairing_exchange() {
+        let host_aid = PeerId([1u8; 32]);
+        let new_aid = PeerId([2u8; 32]);
+        let (mut host, code) = Pairing::host();
+
+        // the QR keeps the host device.
+        let qr = Pairing::qr(&host_aid, &code);
+        let new = Pairing::join(&qr).unwrap();
+        assert_eq!(new.assist, Some(host_aid));
+        assert_eq!(Pairing::join(&code).unwrap().assist, None);
+
+        let (public, proof) = new.request(&new_aid);
+        let (key, host_public, host_proof) =
+            host.accept(&host_aid, &new_aid, &public, &proof).unwrap();
+        let new_key = new.finish(&host_aid, &host_public, &host_proof).unwrap();
+        assert_eq!(key, new_key);
+
+        // only the device in the QR.
+        assert!(new.finish(&new_aid, &host_public, &host_proof).is_err());
+
+        // the pair key is not derived from the code only.
+        let other = Pairing::join(&code).unwrap();
+        let (public, proof) = other.request(&new_aid);
+        let (other_key, ..) = host.accept(&host_aid, &new_aid, &public, &proof).unwrap();
+        assert_ne!(key, other_key);
+    }
+
+    #[test]
+    fn pairing_wrong_code() {
+        let host_aid = PeerId([1u8; 32]);
+        let new_aid = PeerId([2u8; 32]);
+        let (mut host, _code) = Pairing::host();
+
+        for _ in 0..PAIR_TRIES {
+            let new = Pairing::join("AAAAAAAA").unwrap();
+            let (public, proof) = new.request(&new_aid);
+            assert!(host.accept(&host_aid, &new_aid, &public, &proof).is_err());
+        }
+        assert!(host.is_expired());
+    }
+}
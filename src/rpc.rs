@@ -1,3 +1,4 @@
+use dao_types::{DaoId, LayerEvent as DaoLayerEvent, DAO_ID};
 use esse_primitives::{id_from_str, id_to_str};
 use group_types::{GroupChatId, LayerEvent as GroupLayerEvent, GROUP_CHAT_ID};
 use std::net::SocketAddr;
@@ -16,12 +17,17 @@ use tdn_did::{generate_eth_account, generate_mnemonic, Count};
 
 use crate::account::lang_from_i64;
 use crate::apps::app_rpc_inject;
+use crate::apps::cloud::{cloud_push, CloudEvent};
+use crate::apps::dao::{dao_conn, Dao};
 use crate::apps::group::{group_conn as group_chat_conn, GroupChat};
+use crate::event::InnerEvent;
 use crate::global::Global;
 use crate::group::{group_conn, group_rpc, GroupEvent};
-//use crate::event::InnerEvent;
-use crate::session::{connect_session, Session, SessionType};
-use crate::storage::{group_db, session_db};
+use crate::session::{connect_session, Session, SessionNotice, SessionType};
+use crate::storage::{
+    blob_gc, dao_db, enable_encrypt, encrypt_local_files, group_db, read_local_file,
+    read_or_generate_thumb, session_db,
+};
 
 pub(crate) fn init_rpc(global: Arc<Global>) -> RpcHandler<Global> {
     let mut handler = new_rpc_handler(global);
@@ -57,10 +63,22 @@ pub(crate) fn network_dht(peers: Vec<PeerId>) -> RpcParam {
 }
 
 #[inline]
-pub(crate) fn _account_update(pid: &PeerId, name: &str, avatar: String) -> RpcParam {
+pub(crate) fn account_update(pid: &PeerId, name: &str, avatar: String) -> RpcParam {
     rpc_response(0, "account-update", json!([id_to_str(pid), name, avatar]))
 }
 
+/// the account is locked by other own device, need login with PIN.
+#[inline]
+pub(crate) fn account_lock(pid: &PeerId) -> RpcParam {
+    rpc_response(0, "account-lock", json!([id_to_str(pid)]))
+}
+
+/// the account is wiped by other own device.
+#[inline]
+pub(crate) fn account_wipe(pid: &PeerId) -> RpcParam {
+    rpc_response(0, "account-wipe", json!([id_to_str(pid)]))
+}
+
 #[inline]
 pub(crate) fn session_create(session: &Session) -> RpcParam {
     rpc_response(0, "session-create", session.to_rpc())
@@ -76,6 +94,11 @@ pub(crate) fn notice_menu(t: &SessionType) -> RpcParam {
     rpc_response(0, "notice-menu", json!([t.to_int()]))
 }
 
+#[inline]
+pub(crate) fn notice_mention(id: &i64, mid: &i64) -> RpcParam {
+    rpc_response(0, "notice-mention", json!([id, mid]))
+}
+
 #[inline]
 pub(crate) fn session_update_name(id: &i64, name: &str) -> RpcParam {
     rpc_response(0, "session-update", json!([id, "", name, false]))
@@ -298,11 +321,14 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
             own_lock.update_account(pid, name, avatar_bytes.clone(), &state.base, &state.secret)?;
             drop(own_lock);
 
-            let results = HandleResult::new();
+            let mut results = HandleResult::new();
 
-            // TODO broadcast to all devices.
-            //let user = own_lock.clone_user(&pid)?;
-            //own_lock.broadcast(&pid, &mut results)?;
+            let event = InnerEvent::UserInfo(name.to_owned(), avatar_bytes.clone());
+            event.broadcast(&state, &mut results).await?;
+
+            // sync to all devices by cloud.
+            let event = CloudEvent::Info(name.to_owned(), avatar_bytes);
+            cloud_push(&state, event, &mut results).await?;
 
             // TODO broadcast to all layers.
             //state.layer.read().await.broadcast(user, &mut results);
@@ -311,6 +337,59 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
         },
     );
 
+    handler.add_method(
+        "account-files-encrypt",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            // the UI reads local files by account-file & account-thumb after it.
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            enable_encrypt(&state.base, &pid).await?;
+
+            let base = state.base.clone();
+            tokio::spawn(async move {
+                if let Err(e) = encrypt_local_files(&base, &pid, &db_key).await {
+                    warn!("encrypt local files failure: {}", e);
+                }
+            });
+            Ok(HandleResult::new())
+        },
+    );
+
+    handler.add_method(
+        "account-file",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let dir = params[0].as_str().ok_or(RpcError::ParseError)?;
+            let name = params[1].as_str().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let bytes = read_local_file(&state.base, &pid, &db_key, dir, name).await?;
+            Ok(HandleResult::rpc(json!([dir, name, base64::encode(bytes)])))
+        },
+    );
+
+    handler.add_method(
+        "account-thumb",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let name = params[0].as_str().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let bytes = read_or_generate_thumb(&state.base, &pid, &db_key, name).await?;
+            Ok(HandleResult::rpc(json!([name, base64::encode(bytes)])))
+        },
+    );
+
+    handler.add_method(
+        "account-storage-gc",
+        |_params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let (count, size) = blob_gc(&state.base, &pid, &db_key).await?;
+            Ok(HandleResult::rpc(json!([count, size])))
+        },
+    );
+
     handler.add_method(
         "account-pin-check",
         |params: Vec<RpcParam>, state: Arc<Global>| async move {
@@ -353,7 +432,7 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
             let pid = id_from_str(params[0].as_str().ok_or(RpcError::ParseError)?)?;
             let me_lock = params[1].as_str().ok_or(RpcError::ParseError)?;
 
-            let results = HandleResult::rpc(json!([id_to_str(&pid)]));
+            let mut results = HandleResult::rpc(json!([id_to_str(&pid)]));
 
             let (tdn_send, tdn_recv) = new_send_channel();
             let running = state.reset(&pid, me_lock, tdn_send).await?;
@@ -363,6 +442,15 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
 
             // load all local services created by this account.
             let db_key = state.own.read().await.db_key(&pid)?;
+
+            // continue encrypt the old plaintext local files, if enabled.
+            let (base, key) = (state.base.clone(), db_key.clone());
+            tokio::spawn(async move {
+                if let Err(e) = encrypt_local_files(&base, &pid, &key).await {
+                    warn!("encrypt local files failure: {}", e);
+                }
+            });
+
             let group_db = group_db(&state.base, &pid, &db_key)?;
             let s_db = session_db(&state.base, &pid, &db_key)?;
             // 1. group chat.
@@ -374,6 +462,13 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
                     layer.group_add(g.gid, g.addr, s.id, g.id, g.height);
                 }
             }
+            // 3. dao.
+            let dao_db = dao_db(&state.base, &pid, &db_key)?;
+            for d in Dao::local(&dao_db)? {
+                if let Some(s) = connect_session(&s_db, &SessionType::Dao, &d.id, &pid)? {
+                    layer.dao_add(d.did, d.addr, s.id, d.id, d.height);
+                }
+            }
             drop(layer);
 
             let key = state.own.read().await.keypair();
@@ -389,6 +484,14 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
 
             debug!("Account Logined: {}.", id_to_str(&peer_id));
 
+            // connect the paired devices.
+            let height = *state.peer_own_height.read().await;
+            state
+                .own
+                .read()
+                .await
+                .connect_devices(height, &mut results)?;
+
             Ok(results)
         },
     );
@@ -446,6 +549,16 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
                     }
                     group_chat_conn(s.addr, remote_gid, &mut results);
                 }
+                SessionType::Dao => {
+                    let remote_did: DaoId = remote.parse().map_err(|_| RpcError::ParseError)?;
+                    let online = state.layer.write().await.dao_active(&remote_did, true);
+                    if let Some(addr) = online {
+                        return Ok(HandleResult::rpc(json!([id, id_to_str(&addr)])));
+                    }
+                    let dao_db = dao_db(&state.base, &pid, &db_key)?;
+                    let dao = Dao::get_id(&dao_db, &remote_did, &s.addr)?;
+                    dao_conn(s.addr, remote_did, dao.connect_proof(&pid), &mut results);
+                }
                 _ => {}
             }
 
@@ -488,6 +601,21 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
                     let msg = SendType::Event(0, s.addr, data);
                     results.layers.push((GROUP_CHAT_ID, msg));
                 }
+                SessionType::Dao => {
+                    let remote_did: DaoId = remote.parse().map_err(|_| RpcError::ParseError)?;
+                    let mut layer_lock = state.layer.write().await;
+                    if layer_lock
+                        .dao_mut(&remote_did)?
+                        .suspend(true, must)
+                        .is_some()
+                    {
+                        results.rpcs.push(json!([id]));
+                    }
+                    drop(layer_lock);
+                    let data = bincode::serialize(&DaoLayerEvent::Suspend(remote_did))?;
+                    let msg = SendType::Event(0, s.addr, data);
+                    results.layers.push((DAO_ID, msg));
+                }
                 _ => {
                     return Ok(HandleResult::new()); // others has no online.
                 }
@@ -521,7 +649,36 @@ fn new_rpc_handler(global: Arc<Global>) -> RpcHandler<Global> {
             let db_key = state.own.read().await.db_key(&pid)?;
             let db = session_db(&state.base, &pid, &db_key)?;
             Session::update(&db, &id, is_top, is_close)?;
-            Ok(HandleResult::new())
+            let session = Session::get(&db, &id)?;
+            drop(db);
+
+            let mut results = HandleResult::new();
+            let s_type = session.s_type.to_int();
+            let event = InnerEvent::SessionUpdate(session.pid, s_type, is_top, is_close);
+            event.broadcast(&state, &mut results).await?;
+            Ok(results)
+        },
+    );
+
+    handler.add_method(
+        "session-notice",
+        |params: Vec<RpcParam>, state: Arc<Global>| async move {
+            let id = params[0].as_i64().ok_or(RpcError::ParseError)?;
+            let notice = SessionNotice::from_int(params[1].as_i64().ok_or(RpcError::ParseError)?);
+            let mute_until = params[2].as_i64().ok_or(RpcError::ParseError)?;
+
+            let pid = state.pid().await;
+            let db_key = state.own.read().await.db_key(&pid)?;
+            let db = session_db(&state.base, &pid, &db_key)?;
+            Session::update_notice(&db, &id, &notice, mute_until)?;
+            let session = Session::get(&db, &id)?;
+            drop(db);
+
+            let mut results = HandleResult::new();
+            let s_type = session.s_type.to_int();
+            let event = InnerEvent::SessionNotice(session.pid, s_type, notice.to_int(), mute_until);
+            event.broadcast(&state, &mut results).await?;
+            Ok(results)
         },
     );
 
@@ -12,7 +12,7 @@ use tdn::{
 use tdn_storage::local::DStorage;
 
 use crate::account::Account;
-use crate::apps::app_layer_handle;
+use crate::apps::{app_layer_handle, dao::proposal_tally, domain::name_remind, file::file_expire};
 use crate::global::Global;
 use crate::group::group_handle;
 use crate::migrate::{main_migrate, ACCOUNT_DB};
@@ -157,8 +157,39 @@ async fn session_remain(global: Arc<Global>) -> Result<()> {
                 layer_lock.group_del(&gcid);
                 rpcs.push(session_lost(&sid));
             }
+            let mut closed = vec![];
+            for (did, session) in layer_lock.daos.iter_mut() {
+                if session.clear() {
+                    closed.push((*did, session.sid));
+                    for addr in &session.addrs {
+                        addrs.push(*addr);
+                    }
+                }
+            }
+            for (did, sid) in closed {
+                layer_lock.dao_del(&did);
+                rpcs.push(session_lost(&sid));
+            }
             drop(layer_lock);
 
+            // tally the dao proposals which voting time is end.
+            match proposal_tally(&global).await {
+                Ok(res) => handle(res, *uid, true, &global).await,
+                Err(e) => warn!("dao proposal tally: {}", e),
+            }
+
+            // remind the domain names which will expire.
+            match name_remind(&global).await {
+                Ok(res) => handle(res, *uid, true, &global).await,
+                Err(e) => warn!("domain name remind: {}", e),
+            }
+
+            // empty the trash files and versions which keeping time is over.
+            match file_expire(&global).await {
+                Ok(res) => handle(res, *uid, true, &global).await,
+                Err(e) => warn!("file expire: {}", e),
+            }
+
             for rpc in rpcs {
                 let _ = global.send(SendMessage::Rpc(*uid, rpc, true)).await;
             }
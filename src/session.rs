@@ -1,3 +1,4 @@
+use std::time::{SystemTime, UNIX_EPOCH};
 use tdn::types::{
     primitives::{PeerId, Result},
     rpc::{json, RpcParam},
@@ -9,6 +10,7 @@ pub(crate) enum SessionType {
     Group,
     Device,
     Jarvis,
+    Dao,
 }
 
 impl SessionType {
@@ -18,20 +20,52 @@ impl SessionType {
             SessionType::Group => 1,
             SessionType::Device => 2,
             SessionType::Jarvis => 3,
+            SessionType::Dao => 4,
         }
     }
 
-    fn from_int(i: i64) -> Self {
+    pub fn from_int(i: i64) -> Self {
         match i {
             0 => SessionType::Chat,
             1 => SessionType::Group,
             2 => SessionType::Device,
             3 => SessionType::Jarvis,
+            4 => SessionType::Dao,
             _ => SessionType::Chat,
         }
     }
 }
 
+/// session notification rules.
+#[derive(Eq, PartialEq)]
+pub(crate) enum SessionNotice {
+    /// notice all messages.
+    All,
+    /// only notice when mentioned me.
+    Mention,
+    /// mute until a time, 0 is muted forever.
+    Mute,
+}
+
+impl SessionNotice {
+    pub fn to_int(&self) -> i64 {
+        match self {
+            SessionNotice::All => 0,
+            SessionNotice::Mention => 1,
+            SessionNotice::Mute => 2,
+        }
+    }
+
+    pub fn from_int(i: i64) -> Self {
+        match i {
+            0 => SessionNotice::All,
+            1 => SessionNotice::Mention,
+            2 => SessionNotice::Mute,
+            _ => SessionNotice::All,
+        }
+    }
+}
+
 pub(crate) struct Session {
     pub id: i64,
     fid: i64,
@@ -44,6 +78,8 @@ pub(crate) struct Session {
     pub last_datetime: i64,
     pub last_content: String,
     pub last_readed: bool,
+    pub notice: SessionNotice,
+    pub mute_until: i64,
 }
 
 impl Session {
@@ -67,6 +103,8 @@ impl Session {
             last_datetime: datetime,
             last_content: "".to_owned(),
             last_readed: true,
+            notice: SessionNotice::All,
+            mute_until: 0,
         }
     }
 
@@ -83,11 +121,15 @@ impl Session {
             self.last_datetime,
             self.last_content,
             self.last_readed,
+            self.notice.to_int(),
+            self.mute_until,
         ])
     }
 
     fn from_values(mut v: Vec<DsValue>) -> Self {
         Self {
+            mute_until: v.pop().unwrap().as_i64(),
+            notice: SessionNotice::from_int(v.pop().unwrap().as_i64()),
             last_readed: v.pop().unwrap().as_bool(),
             last_content: v.pop().unwrap().as_string(),
             last_datetime: v.pop().unwrap().as_i64(),
@@ -141,7 +183,27 @@ impl Session {
     }
 
     pub fn get(db: &DStorage, id: &i64) -> Result<Session> {
-        let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed FROM sessions WHERE id = {}", id);
+        let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed, notice, mute_until FROM sessions WHERE id = {}", id);
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            Ok(Session::from_values(matrix.pop().unwrap())) // safe unwrap()
+        } else {
+            Err(anyhow!("session missing."))
+        }
+    }
+
+    pub fn get_by_fid(db: &DStorage, fid: &i64, s_type: &SessionType) -> Result<Session> {
+        let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed, notice, mute_until FROM sessions WHERE fid = {} AND s_type = {}", fid, s_type.to_int());
+        let mut matrix = db.query(&sql)?;
+        if matrix.len() > 0 {
+            Ok(Session::from_values(matrix.pop().unwrap())) // safe unwrap()
+        } else {
+            Err(anyhow!("session missing."))
+        }
+    }
+
+    pub fn get_by_pid(db: &DStorage, pid: &str, s_type: &SessionType) -> Result<Session> {
+        let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed, notice, mute_until FROM sessions WHERE pid = '{}' AND s_type = {}", pid, s_type.to_int());
         let mut matrix = db.query(&sql)?;
         if matrix.len() > 0 {
             Ok(Session::from_values(matrix.pop().unwrap())) // safe unwrap()
@@ -151,7 +213,7 @@ impl Session {
     }
 
     pub fn list(db: &DStorage) -> Result<Vec<Session>> {
-        let matrix = db.query("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed FROM sessions ORDER BY last_datetime DESC")?;
+        let matrix = db.query("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed, notice, mute_until FROM sessions ORDER BY last_datetime DESC")?;
         let mut sessions = vec![];
         for values in matrix {
             sessions.push(Session::from_values(values));
@@ -166,6 +228,35 @@ impl Session {
         ))
     }
 
+    pub fn update_notice(
+        db: &DStorage,
+        id: &i64,
+        notice: &SessionNotice,
+        mute_until: i64,
+    ) -> Result<usize> {
+        db.update(&format!(
+            "UPDATE sessions SET notice = {}, mute_until = {} WHERE id = {}",
+            notice.to_int(),
+            mute_until,
+            id
+        ))
+    }
+
+    /// check the notification rules, mentioned is the message mentioned me.
+    pub fn is_notice(&self, mentioned: bool) -> bool {
+        match self.notice {
+            SessionNotice::All => true,
+            SessionNotice::Mention => mentioned,
+            SessionNotice::Mute => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|s| s.as_secs())
+                    .unwrap_or(0) as i64; // safe for all life.
+                self.mute_until > 0 && self.mute_until <= now
+            }
+        }
+    }
+
     pub fn update_name(db: &DStorage, id: &i64, name: &str) -> Result<usize> {
         db.update(&format!(
             "UPDATE sessions SET name='{}' WHERE id = {}",
@@ -267,7 +358,7 @@ pub(crate) fn connect_session(
     fid: &i64,
     addr: &PeerId,
 ) -> Result<Option<Session>> {
-    let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed FROM sessions WHERE s_type = {} AND fid = {}", s_type.to_int(), fid);
+    let sql = format!("SELECT id, fid, pid, addr, s_type, name, is_top, is_close, last_datetime, last_content, last_readed, notice, mute_until FROM sessions WHERE s_type = {} AND fid = {}", s_type.to_int(), fid);
 
     let mut matrix = db.query(&sql)?;
     if matrix.len() > 0 {
@@ -1,20 +1,18 @@
-use esse_primitives::id_to_str;
-use image::{load_from_memory, DynamicImage, GenericImageView};
-use rand_chacha::{
-    rand_core::{RngCore, SeedableRng},
-    ChaChaRng,
-};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use esse_primitives::{id_from_str, id_to_str, MessageType};
+use image::{guess_format, load_from_memory, GenericImageView, ImageOutputFormat};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use tdn::types::primitives::{PeerId, Result};
 use tdn_storage::local::DStorage;
-use tokio::fs;
+use tokio::{fs, io::AsyncWriteExt};
 
+use crate::blob::{Avatar, Blob};
 use crate::migrate::account_init_migrate;
 use crate::migrate::{
     ACCOUNT_DB, CHAT_DB, CLOUD_DB, CONSENSUS_DB, DAO_DB, DOMAIN_DB, FILE_DB, GROUP_DB, JARVIS_DB,
     SERVICE_DB, SESSION_DB, WALLET_DB,
 };
+use crate::utils::crypto::{file_decrypt, file_encrypt, file_key, is_file_encrypted};
 
 const FILES_DIR: &'static str = "files";
 const IMAGE_DIR: &'static str = "images";
@@ -23,6 +21,25 @@ const EMOJI_DIR: &'static str = "emojis";
 const RECORD_DIR: &'static str = "records";
 const AVATAR_DIR: &'static str = "avatars";
 
+/// the max width & height of thumbnail.
+const THUMB_SIZE: u32 = 240;
+
+/// the max width & height of preview which sent with image.
+const PREVIEW_SIZE: u32 = 32;
+
+/// local file directories which are encrypted.
+const ENCRYPTED_DIRS: [&'static str; 5] = [FILES_DIR, IMAGE_DIR, THUMB_DIR, RECORD_DIR, AVATAR_DIR];
+
+/// the account's flag file, created when the UI reads local files by rpc,
+/// new local files are encrypted after it.
+const ENCRYPT_FLAG: &'static str = ".encrypt";
+
+/// the account's flag file, created when all local files are encrypted.
+const ENCRYPTED_FLAG: &'static str = ".encrypted";
+
+/// the temporary file extension when encrypt the old plaintext file.
+const ENCRYPTING_EXT: &'static str = "encrypting";
+
 pub(crate) async fn init_local_files(base: &PathBuf) -> Result<()> {
     let mut files_path = base.clone();
     files_path.push(FILES_DIR);
@@ -61,23 +78,69 @@ pub(crate) async fn read_file(base: &PathBuf) -> Result<Vec<u8>> {
     Ok(fs::read(base).await?)
 }
 
-pub(crate) async fn copy_file(
-    target: &PathBuf,
-    base: &PathBuf,
-    pid: &PeerId,
-    name: &str,
-) -> Result<()> {
+/// check the account's old plaintext files had been encrypted.
+fn is_encrypted_all(base: &PathBuf, pid: &PeerId) -> bool {
     let mut path = base.clone();
     path.push(id_to_str(pid));
-    path.push(FILES_DIR);
-    path.push(name);
-    fs::copy(target, path).await?;
+    path.push(ENCRYPTED_FLAG);
+    path.exists()
+}
+
+/// check the account enabled the local files encryption.
+fn is_encrypt_enabled(base: &PathBuf, pid: &PeerId) -> bool {
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(ENCRYPT_FLAG);
+    path.exists()
+}
+
+/// encrypt the local file's bytes if the account enabled encryption,
+/// the path is `base/pid/dir/name`.
+fn local_encrypt(path: &Path, key: &[u8; 32], bytes: &[u8]) -> Result<Vec<u8>> {
+    let enabled = path
+        .parent()
+        .and_then(|p| p.parent())
+        .map(|p| p.join(ENCRYPT_FLAG).exists())
+        .unwrap_or(false);
+    if enabled {
+        file_encrypt(key, bytes)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// read the local encrypted file, and decrypt it.
+async fn read_encrypted(
+    base: &PathBuf,
+    pid: &PeerId,
+    path: PathBuf,
+    db_key: &str,
+) -> Result<Vec<u8>> {
+    if path.exists() {
+        let migrated = is_encrypted_all(base, pid);
+        file_decrypt(&file_key(db_key), fs::read(path).await?, migrated)
+    } else {
+        Ok(vec![])
+    }
+}
+
+/// encrypt the bytes, and write to local file.
+async fn write_encrypted(path: PathBuf, db_key: &str, bytes: &[u8]) -> Result<()> {
+    let data = local_encrypt(&path, &file_key(db_key), bytes)?;
+    Ok(fs::write(path, data).await?)
+}
+
+/// encrypt the bytes, and write to local file in background.
+fn write_encrypted_sync(path: PathBuf, db_key: &str, bytes: Vec<u8>) -> Result<()> {
+    let data = local_encrypt(&path, &file_key(db_key), &bytes)?;
+    tokio::spawn(async move { fs::write(path, data).await });
     Ok(())
 }
 
 pub(crate) async fn write_file(
     base: &PathBuf,
     pid: &PeerId,
+    db_key: &str,
     name: &str,
     bytes: &[u8],
 ) -> Result<String> {
@@ -85,113 +148,374 @@ pub(crate) async fn write_file(
     path.push(id_to_str(pid));
     path.push(FILES_DIR);
     path.push(name);
-    fs::write(path, bytes).await?;
+    write_encrypted(path, db_key, bytes).await?;
     Ok(name.to_owned())
 }
 
-pub(crate) fn write_file_sync(
+/// rename the stored file, the content keeps encrypted.
+pub(crate) async fn rename_file(base: &PathBuf, pid: &PeerId, from: &str, to: &str) -> Result<()> {
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(FILES_DIR);
+    let mut to_path = path.clone();
+    path.push(from);
+    to_path.push(to);
+    Ok(fs::rename(path, to_path).await?)
+}
+
+/// the stored file content's hash and size.
+pub(crate) async fn file_hash(
     base: &PathBuf,
     pid: &PeerId,
+    db_key: &str,
     name: &str,
-    bytes: Vec<u8>,
-) -> Result<String> {
+) -> Result<(String, i64)> {
+    let bytes = read_db_file(base, pid, db_key, name).await?;
+    Ok((blob_hash(&bytes), bytes.len() as i64))
+}
+
+/// check the file content is stored in this device.
+pub(crate) fn has_file(base: &PathBuf, pid: &PeerId, name: &str) -> bool {
     let mut path = base.clone();
     path.push(id_to_str(pid));
     path.push(FILES_DIR);
     path.push(name);
-    tokio::spawn(async move { fs::write(path, bytes).await });
-
-    Ok(name.to_owned())
+    path.exists()
 }
 
-pub(crate) async fn read_db_file(base: &PathBuf, pid: &PeerId, name: &str) -> Result<Vec<u8>> {
+pub(crate) async fn delete_file(base: &PathBuf, pid: &PeerId, name: &str) -> Result<()> {
     let mut path = base.clone();
     path.push(id_to_str(pid));
     path.push(FILES_DIR);
     path.push(name);
     if path.exists() {
-        Ok(fs::read(path).await?)
+        Ok(fs::remove_file(path).await?)
     } else {
-        Ok(vec![])
+        Ok(())
     }
 }
 
-pub(crate) async fn read_image(base: &PathBuf, pid: &PeerId, name: &str) -> Result<Vec<u8>> {
+/// write the blob if it is new, and add a reference to it.
+fn write_blob(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    dir: &str,
+    hash: &str,
+    name: &str,
+    bytes: &[u8],
+) -> Result<Option<PathBuf>> {
     let mut path = base.clone();
     path.push(id_to_str(pid));
-    path.push(IMAGE_DIR);
+    path.push(dir);
     path.push(name);
-    if path.exists() {
-        Ok(fs::read(path).await?)
+
+    let db = file_db(base, pid, db_key)?;
+    let is_new = Blob::add(&db, dir, hash, name, bytes.len())?;
+    db.close()?;
+
+    if is_new || !path.exists() {
+        Ok(Some(path))
     } else {
-        Ok(vec![])
+        Ok(None)
     }
 }
 
 #[inline]
-fn image_name() -> String {
-    let mut rng = ChaChaRng::from_entropy();
-    let mut key = [0u8; 20];
-    rng.fill_bytes(&mut key);
-    let mut name = hex::encode(&key);
-    name.push_str(".png");
-    name
+fn blob_hash(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
 }
 
 #[inline]
-fn image_thumb(bytes: &[u8]) -> Result<DynamicImage> {
-    // thumbnail image. 120*800
-    let img = load_from_memory(&bytes)?;
-    let (x, _) = img.dimensions();
-    if x > 100 {
-        Ok(img.thumbnail(120, 800))
-    } else {
-        Ok(img)
+fn is_blob_hash(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// the stored name of file attachment, the content is `{hash}_{name}`.
+#[inline]
+fn attachment_path(content: &str) -> &str {
+    match content.split_once('_') {
+        Some((hash, _)) if is_blob_hash(hash) => hash,
+        _ => content,
+    }
+}
+
+/// the original name of file attachment.
+pub(crate) fn attachment_name(content: &str) -> &str {
+    match content.split_once('_') {
+        Some((hash, name)) if is_blob_hash(hash) => name,
+        _ => content,
+    }
+}
+
+/// save the file attachment in blob store, return the message content.
+pub(crate) async fn write_attachment(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+    bytes: &[u8],
+) -> Result<String> {
+    let hash = blob_hash(bytes);
+    if let Some(path) = write_blob(base, pid, db_key, FILES_DIR, &hash, &hash, bytes)? {
+        write_encrypted(path, db_key, bytes).await?;
+    }
+    Ok(format!("{}_{}", hash, name))
+}
+
+/// save the file attachment in blob store in background, return the message content.
+pub(crate) fn write_attachment_sync(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+    bytes: Vec<u8>,
+) -> Result<String> {
+    let hash = blob_hash(&bytes);
+    if let Some(path) = write_blob(base, pid, db_key, FILES_DIR, &hash, &hash, &bytes)? {
+        write_encrypted_sync(path, db_key, bytes)?;
+    }
+    Ok(format!("{}_{}", hash, name))
+}
+
+/// release the blob referenced by the message content.
+pub(crate) fn release_content(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    m_type: &MessageType,
+    content: &str,
+) -> Result<()> {
+    let (dir, hash) = match m_type {
+        MessageType::Image => (IMAGE_DIR, content.trim_end_matches(".png")),
+        MessageType::File => (FILES_DIR, attachment_path(content)),
+        MessageType::Record => match content.split_once('-') {
+            Some((_, name)) => (RECORD_DIR, name.trim_end_matches(".m4a")),
+            None => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+    if is_blob_hash(hash) {
+        let db = file_db(base, pid, db_key)?;
+        Blob::release(&db, dir, hash)?;
+        db.close()?;
+    }
+    Ok(())
+}
+
+/// remove the blobs which have no reference, return the count & reclaimed size.
+pub(crate) async fn blob_gc(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<(usize, i64)> {
+    let db = file_db(base, pid, db_key)?;
+    let blobs = Blob::orphans(&db)?;
+    let mut size = 0;
+    for blob in &blobs {
+        let mut path = base.clone();
+        path.push(id_to_str(pid));
+        let mut blob_path = path.clone();
+        blob_path.push(&blob.dir);
+        blob_path.push(&blob.name);
+        if blob_path.exists() {
+            fs::remove_file(blob_path).await?;
+        }
+        if blob.dir == IMAGE_DIR {
+            path.push(THUMB_DIR);
+            path.push(&blob.name);
+            if path.exists() {
+                fs::remove_file(path).await?;
+            }
+        }
+        Blob::delete(&db, &blob.id)?;
+        size += blob.size;
     }
+    db.close()?;
+    Ok((blobs.len(), size))
+}
+
+pub(crate) async fn read_db_file(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(FILES_DIR);
+    path.push(attachment_path(name));
+    read_encrypted(base, pid, path, db_key).await
+}
+
+pub(crate) async fn read_image(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(IMAGE_DIR);
+    path.push(name);
+    read_encrypted(base, pid, path, db_key).await
 }
 
-pub(crate) fn write_image_sync(base: &PathBuf, pid: &PeerId, bytes: Vec<u8>) -> Result<String> {
+pub(crate) async fn read_thumb(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
     let mut path = base.clone();
     path.push(id_to_str(pid));
+    path.push(THUMB_DIR);
+    path.push(name);
+    read_encrypted(base, pid, path, db_key).await
+}
 
-    let thumb = image_thumb(&bytes)?;
-    let name = image_name();
+/// resize the image into the bound box, and encode to png.
+fn image_resize(bytes: &[u8], bound: u32) -> Result<Vec<u8>> {
+    let img = load_from_memory(bytes)?;
+    let (x, y) = img.dimensions();
+    let img = if x > bound || y > bound {
+        img.thumbnail(bound, bound)
+    } else {
+        img
+    };
+    let mut data = vec![];
+    img.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)?;
+    Ok(data)
+}
 
-    let mut thumb_path = path.clone();
-    thumb_path.push(THUMB_DIR);
-    thumb_path.push(name.clone());
+/// the small preview of image, it is sent before the image.
+pub(crate) fn image_preview(bytes: &[u8]) -> Result<Vec<u8>> {
+    image_resize(bytes, PREVIEW_SIZE)
+}
+
+/// generate the thumbnail in background, the preview is the placeholder until it is done.
+fn thumb_spawn(path: PathBuf, db_key: &str, bytes: Vec<u8>) {
+    let key = file_key(db_key);
     tokio::spawn(async move {
-        let _ = thumb.save(thumb_path);
+        let thumb = tokio::task::spawn_blocking(move || image_resize(&bytes, THUMB_SIZE)).await;
+        match thumb.map(|t| t.and_then(|t| local_encrypt(&path, &key, &t))) {
+            Ok(Ok(data)) => {
+                let _ = fs::write(&path, data).await;
+            }
+            _ => warn!("generate thumbnail failure: {:?}", path),
+        }
     });
+}
 
-    path.push(IMAGE_DIR);
-    path.push(name.clone());
-    tokio::spawn(async move { fs::write(path, bytes).await });
+pub(crate) fn write_image_sync(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    bytes: Vec<u8>,
+) -> Result<String> {
+    guess_format(&bytes)?;
+    let hash = blob_hash(&bytes);
+    let name = format!("{}.png", hash);
+    if let Some(path) = write_blob(base, pid, db_key, IMAGE_DIR, &hash, &name, &bytes)? {
+        let mut thumb_path = base.clone();
+        thumb_path.push(id_to_str(pid));
+        thumb_path.push(THUMB_DIR);
+        thumb_path.push(&name);
+        thumb_spawn(thumb_path, db_key, bytes.clone());
+        write_encrypted_sync(path, db_key, bytes)?;
+    }
 
     Ok(name)
 }
 
-pub(crate) async fn write_image(base: &PathBuf, pid: &PeerId, bytes: &[u8]) -> Result<String> {
+/// the image's stored name.
+#[inline]
+pub(crate) fn image_name(bytes: &[u8]) -> String {
+    format!("{}.png", blob_hash(bytes))
+}
+
+/// save the preview which received from network as the image's thumbnail,
+/// the image will be received later.
+pub(crate) fn write_preview_sync(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+    preview: Vec<u8>,
+) -> Result<()> {
+    if !is_blob_hash(name.trim_end_matches(".png")) || !name.ends_with(".png") {
+        return Err(anyhow!("image is invalid"));
+    }
     let mut path = base.clone();
     path.push(id_to_str(pid));
+    path.push(THUMB_DIR);
+    path.push(name);
+    if !path.exists() && preview.len() > 0 {
+        write_encrypted_sync(path, db_key, preview)?;
+    }
+    Ok(())
+}
 
-    let thumb = image_thumb(bytes)?;
-    let name = image_name();
-
-    let mut thumb_path = path.clone();
-    thumb_path.push(THUMB_DIR);
-    thumb_path.push(name.clone());
-    tokio::spawn(async move {
-        let _ = thumb.save(thumb_path);
-    });
+/// save the image which preview had received, the image must match the name.
+pub(crate) fn write_previewed_image_sync(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+    bytes: Vec<u8>,
+) -> Result<()> {
+    if image_name(&bytes) != name {
+        return Err(anyhow!("image is invalid"));
+    }
+    write_image_sync(base, pid, db_key, bytes)?;
+    Ok(())
+}
 
-    path.push(IMAGE_DIR);
-    path.push(name.clone());
-    fs::write(path, bytes).await?;
+pub(crate) async fn write_image(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    bytes: &[u8],
+) -> Result<String> {
+    guess_format(bytes)?;
+    let hash = blob_hash(bytes);
+    let name = format!("{}.png", hash);
+    if let Some(path) = write_blob(base, pid, db_key, IMAGE_DIR, &hash, &name, bytes)? {
+        let mut thumb_path = base.clone();
+        thumb_path.push(id_to_str(pid));
+        thumb_path.push(THUMB_DIR);
+        thumb_path.push(&name);
+        thumb_spawn(thumb_path, db_key, bytes.to_vec());
+        write_encrypted(path, db_key, bytes).await?;
+    }
 
     Ok(name)
 }
 
+/// read the image thumbnail, if missing (old images), generate it.
+pub(crate) async fn read_or_generate_thumb(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
+    if name.contains('/') || name.contains('\\') {
+        return Err(anyhow!("file is invalid"));
+    }
+    let thumb = read_thumb(base, pid, db_key, name).await?;
+    if thumb.len() > 0 {
+        return Ok(thumb);
+    }
+
+    let bytes = read_image(base, pid, db_key, name).await?;
+    if bytes.len() == 0 {
+        return Ok(vec![]);
+    }
+    let thumb = tokio::task::spawn_blocking(move || image_resize(&bytes, THUMB_SIZE)).await??;
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(THUMB_DIR);
+    path.push(name);
+    write_encrypted(path, db_key, &thumb).await?;
+    Ok(thumb)
+}
+
 #[inline]
 fn avatar_png(pid: &PeerId) -> String {
     let mut gs = id_to_str(pid);
@@ -199,64 +523,146 @@ fn avatar_png(pid: &PeerId) -> String {
     gs
 }
 
-pub(crate) async fn read_avatar(base: &PathBuf, pid: &PeerId, remote: &PeerId) -> Result<Vec<u8>> {
-    let mut path = base.clone();
-    path.push(id_to_str(pid));
-    path.push(AVATAR_DIR);
-    path.push(avatar_png(remote));
+pub(crate) async fn read_avatar(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    remote: &PeerId,
+) -> Result<Vec<u8>> {
+    let path = avatar_path(base, pid, db_key, remote);
+    read_encrypted(base, pid, path, db_key).await
+}
+
+pub(crate) fn _read_avatar_sync(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    remote: &PeerId,
+) -> Result<Vec<u8>> {
+    let path = avatar_path(base, pid, db_key, remote);
     if path.exists() {
-        Ok(fs::read(path).await?)
+        let migrated = is_encrypted_all(base, pid);
+        file_decrypt(&file_key(db_key), std::fs::read(path)?, migrated)
     } else {
         Ok(vec![])
     }
 }
 
-pub(crate) fn _read_avatar_sync(base: &PathBuf, pid: &PeerId, remote: &PeerId) -> Result<Vec<u8>> {
+/// the avatar file of the peer, in blob store, or the old file named by the peer.
+fn avatar_path(base: &PathBuf, pid: &PeerId, db_key: &str, remote: &PeerId) -> PathBuf {
+    let hash = file_db(base, pid, db_key).and_then(|db| {
+        let hash = Avatar::get(&db, remote);
+        db.close()?;
+        hash
+    });
+
     let mut path = base.clone();
     path.push(id_to_str(pid));
     path.push(AVATAR_DIR);
-    path.push(avatar_png(remote));
-    if path.exists() {
-        Ok(std::fs::read(path)?)
+    match hash {
+        Ok(hash) => path.push(format!("{}.png", hash)),
+        Err(_) => path.push(avatar_png(remote)),
+    }
+    path
+}
+
+/// save the avatar in blob store, the peer's avatar row keeps the reference,
+/// and release the old one. return the path when need write the file.
+fn save_avatar(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    remote: &PeerId,
+    bytes: &[u8],
+) -> Result<Option<PathBuf>> {
+    let hash = blob_hash(bytes);
+    let name = format!("{}.png", hash);
+    let db = file_db(base, pid, db_key)?;
+
+    let path = if Avatar::get(&db, remote).map(|h| h == hash).unwrap_or(false) {
+        // not changed, only write when the file is missing.
+        let mut path = base.clone();
+        path.push(id_to_str(pid));
+        path.push(AVATAR_DIR);
+        path.push(&name);
+        if path.exists() {
+            None
+        } else {
+            Some(path)
+        }
     } else {
-        Ok(vec![])
+        let path = write_blob(base, pid, db_key, AVATAR_DIR, &hash, &name, bytes)?;
+        match Avatar::set(&db, remote, &hash) {
+            Ok(Some(old)) => {
+                Blob::release(&db, AVATAR_DIR, &old)?;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                Blob::release(&db, AVATAR_DIR, &hash)?;
+                return Err(e);
+            }
+        }
+        path
+    };
+    db.close()?;
+
+    // remove the old file which named by the peer.
+    let mut old_path = base.clone();
+    old_path.push(id_to_str(pid));
+    old_path.push(AVATAR_DIR);
+    old_path.push(avatar_png(remote));
+    if old_path.exists() {
+        let _ = std::fs::remove_file(old_path);
     }
+
+    Ok(path)
 }
 
 pub(crate) async fn write_avatar(
     base: &PathBuf,
     pid: &PeerId,
+    db_key: &str,
     remote: &PeerId,
     bytes: &[u8],
 ) -> Result<()> {
     if bytes.len() < 1 {
         return Ok(());
     }
-    let mut path = base.clone();
-    path.push(id_to_str(pid));
-    path.push(AVATAR_DIR);
-    path.push(avatar_png(remote));
-    Ok(fs::write(path, bytes).await?)
+    if let Some(path) = save_avatar(base, pid, db_key, remote, bytes)? {
+        write_encrypted(path, db_key, bytes).await?;
+    }
+    Ok(())
 }
 
 pub(crate) fn write_avatar_sync(
     base: &PathBuf,
     pid: &PeerId,
+    db_key: &str,
     remote: &PeerId,
     bytes: Vec<u8>,
 ) -> Result<()> {
     if bytes.len() < 1 {
         return Ok(());
     }
-    let mut path = base.clone();
-    path.push(id_to_str(pid));
-    path.push(AVATAR_DIR);
-    path.push(avatar_png(remote));
-    tokio::spawn(async move { fs::write(path, bytes).await });
+    if let Some(path) = save_avatar(base, pid, db_key, remote, &bytes)? {
+        write_encrypted_sync(path, db_key, bytes)?;
+    }
     Ok(())
 }
 
-pub(crate) async fn delete_avatar(base: &PathBuf, pid: &PeerId, remote: &PeerId) -> Result<()> {
+/// delete the peer's avatar, the blob will be removed by gc.
+pub(crate) async fn delete_avatar(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    remote: &PeerId,
+) -> Result<()> {
+    let db = file_db(base, pid, db_key)?;
+    if let Some(hash) = Avatar::delete(&db, remote)? {
+        Blob::release(&db, AVATAR_DIR, &hash)?;
+    }
+    db.close()?;
+
     let mut path = base.clone();
     path.push(id_to_str(pid));
     path.push(AVATAR_DIR);
@@ -279,37 +685,33 @@ pub(crate) fn _delete_avatar_sync(base: &PathBuf, pid: &PeerId, remote: &PeerId)
     Ok(())
 }
 
-pub(crate) async fn read_record(base: &PathBuf, pid: &PeerId, name: &str) -> Result<Vec<u8>> {
+pub(crate) async fn read_record(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
     let mut path = base.clone();
     path.push(id_to_str(pid));
     path.push(RECORD_DIR);
     path.push(name);
-    if path.exists() {
-        Ok(fs::read(path).await?)
-    } else {
-        Ok(vec![])
-    }
+    read_encrypted(base, pid, path, db_key).await
 }
 
 pub(crate) fn write_record_sync(
     base: &PathBuf,
     pid: &PeerId,
+    db_key: &str,
     t: u32,
     bytes: Vec<u8>,
 ) -> Result<String> {
-    let start = SystemTime::now();
-    let datetime = start
-        .duration_since(UNIX_EPOCH)
-        .map(|s| s.as_millis())
-        .unwrap_or(0u128);
-
-    let mut path = base.clone();
-    path.push(id_to_str(pid));
-    path.push(RECORD_DIR);
-    path.push(format!("{}.m4a", datetime));
-    tokio::spawn(async move { fs::write(path, bytes).await });
+    let hash = blob_hash(&bytes);
+    let name = format!("{}.m4a", hash);
+    if let Some(path) = write_blob(base, pid, db_key, RECORD_DIR, &hash, &name, &bytes)? {
+        write_encrypted_sync(path, db_key, bytes)?;
+    }
 
-    Ok(format!("{}_{}.m4a", t, datetime))
+    Ok(format!("{}-{}", t, name))
 }
 
 pub(crate) async fn _delete_record(base: &PathBuf, pid: &PeerId, name: &str) -> Result<()> {
@@ -327,6 +729,96 @@ pub(crate) fn _write_emoji(base: &PathBuf, pid: &PeerId) -> Result<()> {
     Ok(())
 }
 
+/// read the account local file by directory name, use in rpc.
+pub(crate) async fn read_local_file(
+    base: &PathBuf,
+    pid: &PeerId,
+    db_key: &str,
+    dir: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
+    if !ENCRYPTED_DIRS.contains(&dir) || name.contains('/') || name.contains('\\') {
+        return Err(anyhow!("file is invalid"));
+    }
+    // the avatar is named by the peer, stored in blob store.
+    if dir == AVATAR_DIR {
+        if let Ok(remote) = id_from_str(name.trim_end_matches(".png")) {
+            return read_avatar(base, pid, db_key, &remote).await;
+        }
+    }
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(dir);
+    path.push(name);
+    read_encrypted(base, pid, path, db_key).await
+}
+
+/// enable the local files encryption, the UI must read local files by rpc
+/// `account-file` & `account-thumb` after it.
+pub(crate) async fn enable_encrypt(base: &PathBuf, pid: &PeerId) -> Result<()> {
+    if is_encrypt_enabled(base, pid) {
+        return Ok(());
+    }
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(ENCRYPT_FLAG);
+    Ok(fs::write(path, b"").await?)
+}
+
+/// write to the temporary file, sync it, then replace the file.
+async fn replace_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(ENCRYPTING_EXT);
+    let mut file = fs::File::create(&tmp).await?;
+    file.write_all(bytes).await?;
+    file.sync_all().await?;
+    drop(file);
+    Ok(fs::rename(tmp, path).await?)
+}
+
+/// encrypt the old plaintext files when encryption enabled,
+/// run when enabled and account login (continue the interrupted one).
+pub(crate) async fn encrypt_local_files(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<()> {
+    if !is_encrypt_enabled(base, pid) || is_encrypted_all(base, pid) {
+        return Ok(());
+    }
+
+    let key = file_key(db_key);
+    for dir in ENCRYPTED_DIRS {
+        let mut path = base.clone();
+        path.push(id_to_str(pid));
+        path.push(dir);
+        if !path.exists() {
+            continue;
+        }
+        let mut entries = fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            // the interrupted temporary file, the old file is not replaced.
+            if path
+                .extension()
+                .map(|e| e == ENCRYPTING_EXT)
+                .unwrap_or(false)
+            {
+                fs::remove_file(&path).await?;
+                continue;
+            }
+            let bytes = fs::read(&path).await?;
+            if !is_file_encrypted(&bytes) {
+                replace_file(&path, &file_encrypt(&key, &bytes)?).await?;
+            }
+        }
+    }
+
+    // all encrypted, plaintext files will be rejected.
+    let mut path = base.clone();
+    path.push(id_to_str(pid));
+    path.push(ENCRYPTED_FLAG);
+    Ok(fs::write(path, b"").await?)
+}
+
 /// account independent db and storage directory.
 pub(crate) async fn account_init(base: &PathBuf, key: &str, pid: &PeerId) -> Result<()> {
     let mut db_path = base.clone();
@@ -337,6 +829,16 @@ pub(crate) async fn account_init(base: &PathBuf, key: &str, pid: &PeerId) -> Res
     account_init_migrate(&db_path, key)
 }
 
+/// delete the account's all databases and files.
+pub(crate) async fn account_remove(base: &PathBuf, pid: &PeerId) -> Result<()> {
+    let mut db_path = base.clone();
+    db_path.push(id_to_str(pid));
+    if db_path.exists() {
+        fs::remove_dir_all(db_path).await?;
+    }
+    Ok(())
+}
+
 pub(crate) fn account_db(base: &PathBuf, secret: &[u8]) -> Result<DStorage> {
     let mut db_path = base.clone();
     db_path.push(ACCOUNT_DB);
@@ -392,7 +894,7 @@ pub(crate) fn group_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DSt
     DStorage::open(db_path, db_key)
 }
 
-pub(crate) fn _dao_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DStorage> {
+pub(crate) fn dao_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DStorage> {
     let mut db_path = base.clone();
     db_path.push(id_to_str(pid));
     db_path.push(DAO_DB);
@@ -413,9 +915,99 @@ pub(crate) fn wallet_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DS
     DStorage::open(db_path, db_key)
 }
 
-pub(crate) fn _cloud_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DStorage> {
+pub(crate) fn cloud_db(base: &PathBuf, pid: &PeerId, db_key: &str) -> Result<DStorage> {
     let mut db_path = base.clone();
     db_path.push(id_to_str(pid));
     db_path.push(CLOUD_DB);
     DStorage::open(db_path, db_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::Node;
+
+    #[tokio::test]
+    async fn avatar_in_blob_store() {
+        let node = Node::new("me").await;
+        let key = node.db_key().await;
+        let (base, pid) = (&node.global.base, &node.pid);
+        let remote = PeerId([1u8; 32]);
+
+        write_avatar(base, pid, &key, &remote, b"first")
+            .await
+            .unwrap();
+        write_avatar(base, pid, &key, &remote, b"first")
+            .await
+            .unwrap();
+        assert_eq!(
+            read_avatar(base, pid, &key, &remote).await.unwrap(),
+            b"first"
+        );
+        let db = node.db(file_db).await;
+        let first = blob_hash(b"first");
+        assert_eq!(Blob::get(&db, AVATAR_DIR, &first).unwrap().refs, 1);
+
+        // the changed avatar release the old one.
+        write_avatar(base, pid, &key, &remote, b"second")
+            .await
+            .unwrap();
+        assert_eq!(
+            read_avatar(base, pid, &key, &remote).await.unwrap(),
+            b"second"
+        );
+        assert_eq!(Blob::get(&db, AVATAR_DIR, &first).unwrap().refs, 0);
+
+        // the same avatar shared by peers.
+        let other = PeerId([2u8; 32]);
+        write_avatar(base, pid, &key, &other, b"second")
+            .await
+            .unwrap();
+        let second = blob_hash(b"second");
+        assert_eq!(Blob::get(&db, AVATAR_DIR, &second).unwrap().refs, 2);
+
+        delete_avatar(base, pid, &key, &remote).await.unwrap();
+        assert!(read_avatar(base, pid, &key, &remote)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(Blob::get(&db, AVATAR_DIR, &second).unwrap().refs, 1);
+
+        let name = avatar_png(&other);
+        let bytes = read_local_file(base, pid, &key, AVATAR_DIR, &name)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"second");
+    }
+
+    #[tokio::test]
+    async fn encrypt_after_enabled() {
+        let node = Node::new("me").await;
+        let key = node.db_key().await;
+        let (base, pid) = (&node.global.base, &node.pid);
+        let mut dir = base.clone();
+        dir.push(id_to_str(pid));
+        dir.push(FILES_DIR);
+
+        // the UI still reads the plaintext files before enabled.
+        write_file(base, pid, &key, "old", b"old").await.unwrap();
+        assert_eq!(fs::read(dir.join("old")).await.unwrap(), b"old");
+        encrypt_local_files(base, pid, &key).await.unwrap();
+        assert!(!is_encrypted_all(base, pid));
+
+        // the interrupted temporary file is removed.
+        fs::write(dir.join("old.encrypting"), b"tmp").await.unwrap();
+        enable_encrypt(base, pid).await.unwrap();
+        encrypt_local_files(base, pid, &key).await.unwrap();
+        assert!(is_encrypted_all(base, pid));
+        assert!(!dir.join("old.encrypting").exists());
+        assert!(is_file_encrypted(&fs::read(dir.join("old")).await.unwrap()));
+        let bytes = read_local_file(base, pid, &key, FILES_DIR, "old")
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"old");
+
+        write_file(base, pid, &key, "new", b"new").await.unwrap();
+        assert!(is_file_encrypted(&fs::read(dir.join("new")).await.unwrap()));
+    }
+}
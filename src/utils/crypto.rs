@@ -3,7 +3,10 @@ use aes_gcm::{
     Aes256Gcm, KeyInit,
 };
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
     Argon2,
 };
 use sha2::{Digest, Sha256};
@@ -197,6 +200,99 @@ pub fn _decrypt_multiple(
 
 /// Compute the session key in the cloud.
 #[inline]
-pub fn _cloud_key(key: &[u8; 32]) -> Aes256Gcm {
+pub fn cloud_key(key: &[u8; 32]) -> Aes256Gcm {
     Aes256Gcm::new(GenericArray::from_slice(key))
 }
+
+/// Derive the cloud session key by account secret and cloud address,
+/// so the same account & cloud always has the same key, include restore.
+pub fn cloud_session_key(secret: &[u8], cloud: &[u8]) -> [u8; 32] {
+    let mut bytes = secret.to_vec();
+    bytes.extend(cloud);
+    blake3::derive_key("ESSE cloud session key", &bytes)
+}
+
+/// Derive the cloud data key, the cloud session key will share to friends,
+/// so the data key also need the account secret.
+pub fn cloud_data_key(secret: &[u8], session_key: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = secret.to_vec();
+    bytes.extend(session_key);
+    blake3::derive_key("ESSE cloud data key", &bytes)
+}
+
+/// encrypted bytes to cloud, random nonce is in the head.
+pub fn cloud_encrypt(key: &[u8; 32], ptext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let mut ctext = cloud_key(key)
+        .encrypt(GenericArray::from_slice(&nonce), ptext)
+        .or(Err(anyhow!("encrypt data failure.")))?;
+    let mut bytes = nonce.to_vec();
+    bytes.append(&mut ctext);
+    Ok(bytes)
+}
+
+/// decrypted bytes from cloud.
+pub fn cloud_decrypt(key: &[u8; 32], ctext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if ctext.len() < 12 {
+        return Err(anyhow!("decrypt data failure."));
+    }
+    cloud_key(key)
+        .decrypt(GenericArray::from_slice(&ctext[0..12]), &ctext[12..])
+        .or(Err(anyhow!("decrypt data failure.")))
+}
+
+/// the head of encrypted local file.
+const FILE_MAGIC: [u8; 4] = [69, 83, 70, 1]; // "ESF" + version.
+
+/// Derive the local files key by account encrypt key (db key).
+#[inline]
+pub fn file_key(db_key: &str) -> [u8; 32] {
+    blake3::derive_key("ESSE local file key", db_key.as_bytes())
+}
+
+/// check the local file is encrypted.
+#[inline]
+pub fn is_file_encrypted(bytes: &[u8]) -> bool {
+    bytes.len() >= FILE_MAGIC.len() && bytes[0..FILE_MAGIC.len()] == FILE_MAGIC
+}
+
+/// encrypted bytes to local file.
+pub fn file_encrypt(key: &[u8; 32], ptext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = FILE_MAGIC.to_vec();
+    bytes.append(&mut cloud_encrypt(key, ptext)?);
+    Ok(bytes)
+}
+
+/// decrypted bytes from local file. before the account's old files are all
+/// encrypted (migrated), the plaintext file will return directly.
+pub fn file_decrypt(key: &[u8; 32], ctext: Vec<u8>, migrated: bool) -> anyhow::Result<Vec<u8>> {
+    if is_file_encrypted(&ctext) {
+        cloud_decrypt(key, &ctext[FILE_MAGIC.len()..])
+    } else if migrated {
+        Err(anyhow!("local file is not encrypted."))
+    } else {
+        warn!("read the plaintext local file.");
+        Ok(ctext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_decrypt_migrated() {
+        let key = file_key("db key");
+        let ctext = file_encrypt(&key, b"hello").unwrap();
+        assert_eq!(file_decrypt(&key, ctext.clone(), true).unwrap(), b"hello");
+        assert_eq!(file_decrypt(&key, ctext, false).unwrap(), b"hello");
+
+        // the old plaintext file only allowed before migrated.
+        assert_eq!(
+            file_decrypt(&key, b"plain".to_vec(), false).unwrap(),
+            b"plain"
+        );
+        assert!(file_decrypt(&key, b"plain".to_vec(), true).is_err());
+    }
+}
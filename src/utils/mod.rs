@@ -1,3 +1,6 @@
 pub(crate) mod answer;
 pub(crate) mod crypto;
 pub(crate) mod device_status;
+
+#[cfg(test)]
+pub(crate) mod testing;
@@ -0,0 +1,92 @@
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaChaRng,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tdn::prelude::{new_receive_channel, new_send_channel, Config, PeerId};
+use tdn::types::primitives::Result;
+use tdn_did::{generate_mnemonic, Count};
+use tdn_storage::local::DStorage;
+
+use crate::account::lang_from_i64;
+use crate::global::Global;
+use crate::migrate::main_migrate;
+
+/// the test account's PIN.
+pub(crate) const LOCK: &str = "123456";
+
+/// in-process node for tests, with a temporary storage and a logined account.
+pub(crate) struct Node {
+    pub global: Arc<Global>,
+    pub pid: PeerId,
+    pub mnemonic: String,
+}
+
+impl Node {
+    /// new node with a new account.
+    pub async fn new(name: &str) -> Node {
+        let mnemonic = generate_mnemonic(lang_from_i64(0), Count::Words12);
+        Node::device(name, &mnemonic).await
+    }
+
+    /// new device of the account which has the mnemonic.
+    pub async fn device(name: &str, mnemonic: &str) -> Node {
+        let mut rng = ChaChaRng::from_entropy();
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+
+        let mut base = std::env::temp_dir();
+        base.push(format!("esse-test-{}", hex::encode(&secret[0..8])));
+        tokio::fs::create_dir_all(&base).await.unwrap();
+        main_migrate(&base, &hex::encode(&secret)).unwrap();
+
+        // the messages to network & UI are dropped.
+        let (_, _, p2p_config, _) = Config::default().split();
+        let (self_send, mut self_recv) = new_receive_channel();
+        let (rpc_send, mut rpc_recv) = tokio::sync::mpsc::channel(1024);
+        let (p2p_send, mut p2p_recv) = new_send_channel();
+        tokio::spawn(async move { while self_recv.recv().await.is_some() {} });
+        tokio::spawn(async move { while rpc_recv.recv().await.is_some() {} });
+        tokio::spawn(async move { while p2p_recv.recv().await.is_some() {} });
+
+        let global = Arc::new(Global::init(
+            HashMap::new(),
+            base.clone(),
+            secret,
+            p2p_config,
+            self_send,
+            rpc_send,
+        ));
+        let (_, pid) = global
+            .own
+            .write()
+            .await
+            .add_account(0, mnemonic, "", name, LOCK, vec![], &base, &secret)
+            .await
+            .unwrap();
+        global.reset(&pid, LOCK, p2p_send).await.unwrap();
+
+        Node {
+            global,
+            pid,
+            mnemonic: mnemonic.to_owned(),
+        }
+    }
+
+    pub async fn db_key(&self) -> String {
+        self.global.own.read().await.db_key(&self.pid).unwrap()
+    }
+
+    /// open the account's database, e.g. `node.db(dao_db).await`.
+    pub async fn db(&self, open: fn(&PathBuf, &PeerId, &str) -> Result<DStorage>) -> DStorage {
+        open(&self.global.base, &self.pid, &self.db_key().await).unwrap()
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.global.base);
+    }
+}
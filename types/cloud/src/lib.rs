@@ -4,6 +4,9 @@ use tdn_types::{group::GroupId, primitives::PeerId};
 /// Personal data cloud service default TDN GROUP ID.
 pub const CLOUD_ID: GroupId = 4;
 
+/// Max events in one sync response.
+pub const SYNC_BATCH: usize = 100;
+
 /// ESSE service to peer Event.
 #[derive(Serialize, Deserialize)]
 pub enum LayerServerEvent {
@@ -12,10 +15,21 @@ pub enum LayerServerEvent {
     Status(String, u64, Vec<(u64, u64)>),
     /// Peer check result: PeerId, is running.
     PeerStatus(PeerId, bool),
-    /// Sync event.
-    SyncEvent,
-    /// Sync file.
-    SyncFile,
+    /// Sync event, all data is encrypted by peer.
+    /// params: events (height, data), has more events.
+    SyncEvent(Vec<(u64, Vec<u8>)>, bool),
+    /// Sync file, if file is missing, data is empty.
+    /// params: file name, encrypted data.
+    SyncFile(String, Vec<u8>),
+    /// stored files list.
+    /// params: files (name, size).
+    Files(Vec<(String, u64)>),
+    /// event or file is stored.
+    /// params: used space, total space.
+    Stored(u64, u64),
+    /// event or file is rejected, space is full.
+    /// params: used space, total space.
+    Full(u64, u64),
 }
 
 /// ESSE peer to service Event.
@@ -26,7 +40,17 @@ pub enum LayerPeerEvent {
     /// check PeerId is running at this service.
     PeerCheck(PeerId),
     /// Send sync event.
-    Event,
-    /// Send sync file.
-    File,
+    /// params: encrypted event data.
+    Event(Vec<u8>),
+    /// Send sync file, same name will replace.
+    /// params: file name, encrypted data.
+    File(String, Vec<u8>),
+    /// Pull sync events after the height.
+    /// params: height.
+    Pull(u64),
+    /// Pull the sync file.
+    /// params: file name.
+    PullFile(String),
+    /// list all stored files.
+    FileList,
 }
@@ -1,5 +1,9 @@
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT, ristretto::CompressedRistretto, scalar::Scalar,
+};
 use esse_primitives::NetworkMessage;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
 use tdn_types::{group::GroupId, primitives::PeerId};
 
 /// Dao app(service) default TDN GROUP ID.
@@ -36,6 +40,89 @@ impl DaoType {
             _ => DaoType::Open,
         }
     }
+
+    /// the proposal voting rule of this type dao.
+    pub fn vote_rule(&self) -> VoteRule {
+        match self {
+            DaoType::Open => VoteRule {
+                min_window: 3600,
+                max_window: 604800,
+                quorum: 10,
+                threshold: 50,
+            },
+            DaoType::Private => VoteRule {
+                min_window: 3600,
+                max_window: 1209600,
+                quorum: 30,
+                threshold: 60,
+            },
+            DaoType::Encrypted => VoteRule {
+                min_window: 3600,
+                max_window: 1209600,
+                quorum: 50,
+                threshold: 66,
+            },
+        }
+    }
+}
+
+/// DAO proposal voting rule.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VoteRule {
+    /// min voting window (seconds).
+    pub min_window: i64,
+    /// max voting window (seconds).
+    pub max_window: i64,
+    /// min percent of members need to vote.
+    pub quorum: i64,
+    /// percent of votes need to agree, must more than it.
+    pub threshold: i64,
+}
+
+impl VoteRule {
+    /// fixed the voting end time into the voting window.
+    pub fn window(&self, start: i64, end: i64) -> i64 {
+        end.max(start + self.min_window)
+            .min(start + self.max_window)
+    }
+
+    /// check the proposal is passed by the tally.
+    pub fn passed(&self, members: i64, agree: i64, disagree: i64) -> bool {
+        let votes = agree + disagree;
+        votes > 0 && votes * 100 >= members * self.quorum && agree * 100 > votes * self.threshold
+    }
+}
+
+/// DAO proposal type and content.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ProposalType {
+    /// text proposal, only vote, not execute.
+    /// params: title, content.
+    Text(String, String),
+    /// add a member to manager.
+    /// params: member id.
+    ManagerAdd(PeerId),
+    /// remove a manager, the owner cannot be removed.
+    /// params: member id.
+    ManagerDel(PeerId),
+    /// change dao info.
+    /// params: dao name, dao bio, dao avatar.
+    Info(String, String, Vec<u8>),
+    /// transfer dao owner to a member.
+    /// params: member id.
+    Transfer(PeerId),
+}
+
+impl ProposalType {
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            ProposalType::Text(..) => 0,
+            ProposalType::ManagerAdd(..) => 1,
+            ProposalType::ManagerDel(..) => 2,
+            ProposalType::Info(..) => 3,
+            ProposalType::Transfer(..) => 4,
+        }
+    }
 }
 
 /// DaoInfo transfer in the network.
@@ -87,7 +174,8 @@ pub enum ConnectProof {
     Common,
     /// zero-knowledge-proof. not has account id.
     /// verify(proof, key_hash, current_peer_addr).
-    Zkp, // TODO MOCK-PROOF
+    /// params: proof.
+    Zkp(ZkpProof),
 }
 
 /// Dao join proof.
@@ -101,7 +189,92 @@ pub enum JoinProof {
     Invite(PeerId, String, Vec<u8>),
     /// zero-knowledge-proof. not has account id.
     /// verify(proof, key_hash, current_peer_addr).
-    Zkp, // TODO MOCK-PROOF
+    /// params: proof, member name, member avatar.
+    Zkp(ZkpProof, String, Vec<u8>),
+}
+
+/// Zero-knowledge proof of the DAO key, it is a Schnorr proof (Fiat-Shamir)
+/// on ristretto255. The secret is derived from the DAO key, and the public
+/// key_hash is the secret's point, so every member proves with the same
+/// statement, and the challenge is bound to the DAO ID and prover's peer address.
+/// params: commitment point, response scalar.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ZkpProof(pub [u8; 32], pub [u8; 32]);
+
+impl ZkpProof {
+    /// generate the proof by DAO key, bound to the DAO ID and current peer address.
+    pub fn prove(key: &[u8], did: &DaoId, addr: &PeerId) -> Self {
+        let secret = key_secret(key);
+        let public = (RISTRETTO_BASEPOINT_POINT * secret).compress();
+
+        // deterministic nonce, likes ed25519.
+        let nonce = hash_to_scalar(&[
+            b"esse-dao-zkp-nonce",
+            &secret.to_bytes(),
+            &did.to_le_bytes(),
+            addr.to_hex().as_bytes(),
+        ]);
+        let commit = (RISTRETTO_BASEPOINT_POINT * nonce).compress();
+        let c = challenge(public.as_bytes(), commit.as_bytes(), did, addr);
+        let response = nonce + c * secret;
+
+        ZkpProof(commit.to_bytes(), response.to_bytes())
+    }
+
+    /// verify the proof with DAO key_hash, DAO ID and the peer address.
+    pub fn verify(&self, key_hash: &[u8], did: &DaoId, addr: &PeerId) -> bool {
+        let public = match CompressedRistretto::from_slice(key_hash)
+            .ok()
+            .and_then(|p| p.decompress())
+        {
+            Some(p) => p,
+            None => return false,
+        };
+        let commit = match CompressedRistretto(self.0).decompress() {
+            Some(p) => p,
+            None => return false,
+        };
+        let response: Scalar = match Option::from(Scalar::from_canonical_bytes(self.1)) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let c = challenge(key_hash, &self.0, did, addr);
+        RISTRETTO_BASEPOINT_POINT * response == commit + public * c
+    }
+}
+
+/// the public key_hash of DAO key, it is used to verify the ZkpProof.
+pub fn key_hash(key: &[u8]) -> Vec<u8> {
+    (RISTRETTO_BASEPOINT_POINT * key_secret(key))
+        .compress()
+        .to_bytes()
+        .to_vec()
+}
+
+fn key_secret(key: &[u8]) -> Scalar {
+    hash_to_scalar(&[b"esse-dao-zkp-key", key])
+}
+
+fn challenge(public: &[u8], commit: &[u8], did: &DaoId, addr: &PeerId) -> Scalar {
+    hash_to_scalar(&[
+        b"esse-dao-zkp-challenge",
+        public,
+        commit,
+        &did.to_le_bytes(),
+        addr.to_hex().as_bytes(),
+    ])
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let mut bytes = [0u8; 64];
+    bytes.copy_from_slice(&hasher.finalize());
+    Scalar::from_bytes_mod_order_wide(&bytes)
 }
 
 /// check result type.
@@ -223,11 +396,25 @@ impl LayerEvent {
 /// DAO packed event.
 #[derive(Serialize, Deserialize)]
 pub enum PackedEvent {
-    Info,
-    Transfer,
-    ManagerAdd,
-    ManagerDel,
+    /// passed proposal execute: change dao info.
+    /// params: proposal height, dao name, dao bio, dao avatar.
+    Info(i64, String, String, Vec<u8>),
+    /// passed proposal execute: transfer dao owner.
+    /// params: proposal height, new owner.
+    Transfer(i64, PeerId),
+    /// passed proposal execute: add manager.
+    /// params: proposal height, member id.
+    ManagerAdd(i64, PeerId),
+    /// passed proposal execute: remove manager.
+    /// params: proposal height, member id.
+    ManagerDel(i64, PeerId),
     Close,
+    /// params: proposer, proposal, voting start time, voting end time.
+    ProposalCreate(PeerId, ProposalType, i64, i64),
+    /// params: voter, proposal height, is agree, vote time.
+    ProposalVote(PeerId, i64, bool, i64),
+    /// params: proposal height, agree count, disagree count, is passed.
+    ProposalClose(i64, i64, i64, bool),
     /// params: member id, member name, member avatar.
     MemberInfo(PeerId, String, Vec<u8>),
     /// params: member id, member name, member avatar, member join time.
@@ -243,17 +430,61 @@ pub enum PackedEvent {
 /// Dao chat event.
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Event {
-    Info,
-    Transfer,
-    ManagerAdd,
-    ManagerDel,
+    /// passed proposal execute: change dao info.
+    /// params: proposal height, dao name, dao bio, dao avatar.
+    Info(i64, String, String, Vec<u8>),
+    /// passed proposal execute: transfer dao owner.
+    /// params: proposal height, new owner.
+    Transfer(i64, PeerId),
+    /// passed proposal execute: add manager.
+    /// params: proposal height, member id.
+    ManagerAdd(i64, PeerId),
+    /// passed proposal execute: remove manager.
+    /// params: proposal height, member id.
+    ManagerDel(i64, PeerId),
     Close,
+    /// params: proposer, proposal, voting start time, voting end time.
+    ProposalCreate(PeerId, ProposalType, i64, i64),
+    /// params: voter, proposal height, is agree, vote time.
+    ProposalVote(PeerId, i64, bool, i64),
+    /// params: proposal height, agree count, disagree count, is passed.
+    ProposalClose(i64, i64, i64, bool),
     /// params: member id, member name, member avatar.
     MemberInfo(PeerId, String, Vec<u8>),
     /// params: member id, member name, member avatar, member join time.
     MemberJoin(PeerId, String, Vec<u8>, i64),
     /// params: member id,
     MemberLeave(PeerId),
-    /// params: member id, message, height.
+    /// params: member id, message, message time.
     MessageCreate(PeerId, NetworkMessage, i64),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zkp_proof_round_trip() {
+        let key = [7u8; 32];
+        let addr = PeerId([1u8; 32]);
+        let proof = ZkpProof::prove(&key, &42, &addr);
+        assert!(proof.verify(&key_hash(&key), &42, &addr));
+    }
+
+    #[test]
+    fn zkp_proof_wrong_key() {
+        let addr = PeerId([1u8; 32]);
+        let proof = ZkpProof::prove(&[8u8; 32], &42, &addr);
+        assert!(!proof.verify(&key_hash(&[7u8; 32]), &42, &addr));
+        assert!(!proof.verify(&[0u8; 32], &42, &addr));
+    }
+
+    #[test]
+    fn zkp_proof_bound_to_dao_and_addr() {
+        let key = [7u8; 32];
+        let addr = PeerId([1u8; 32]);
+        let proof = ZkpProof::prove(&key, &42, &addr);
+        assert!(!proof.verify(&key_hash(&key), &43, &addr));
+        assert!(!proof.verify(&key_hash(&key), &42, &PeerId([2u8; 32])));
+    }
+}
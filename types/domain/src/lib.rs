@@ -1,11 +1,121 @@
 use serde::{Deserialize, Serialize};
-use tdn_types::{group::GroupId, primitives::PeerId};
+use tdn_types::{
+    group::GroupId,
+    primitives::{PeerId, PeerKey, PeerSignature},
+};
 
 // Same ID can has many name !.
 
 /// Group chat app(service) default TDN GROUP ID.
 pub const DOMAIN_ID: GroupId = 3;
 
+/// Max length of name.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// name only allow ascii alphanumeric, '_', '-' and '.'.
+pub fn check_name(name: &str) -> bool {
+    name.len() > 0
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Default name record valid time (seconds), one year.
+pub const RECORD_TTL: i64 = 31536000;
+
+/// After expired, the name is still kept for owner to renew (seconds), 30 days.
+pub const RECORD_GRACE: i64 = 2592000;
+
+/// Name record, signed by the owner identity. providers can only store and
+/// forward it, the peers can verify it without trusting the provider.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NameRecord {
+    /// owner identity.
+    pub pid: PeerId,
+    /// name.
+    pub name: String,
+    /// bio.
+    pub bio: String,
+    /// avatar.
+    pub avatar: Vec<u8>,
+    /// record version, every update must increase it.
+    pub version: i64,
+    /// record expire time.
+    pub expire: i64,
+    /// owner's signature of record.
+    pub signature: Vec<u8>,
+}
+
+impl NameRecord {
+    /// build and sign the record by owner's key.
+    pub fn sign(
+        key: &PeerKey,
+        name: String,
+        bio: String,
+        avatar: Vec<u8>,
+        version: i64,
+        expire: i64,
+    ) -> Self {
+        let pid = key.peer_id();
+        let msg = Self::message(&pid, &name, &bio, &avatar, version, expire);
+        let signature = key.sign(&msg).to_bytes();
+        Self {
+            pid,
+            name,
+            bio,
+            avatar,
+            version,
+            expire,
+            signature,
+        }
+    }
+
+    /// check the signature is signed by the record's owner.
+    pub fn verify(&self) -> bool {
+        let msg = Self::message(
+            &self.pid,
+            &self.name,
+            &self.bio,
+            &self.avatar,
+            self.version,
+            self.expire,
+        );
+        if let Ok(sign) = PeerSignature::from_bytes(&self.signature) {
+            if let Ok(pid) = sign.peer_id(&msg) {
+                return pid == self.pid;
+            }
+        }
+        false
+    }
+
+    /// check the record is expired.
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expire <= now
+    }
+
+    /// the signed message, name is case-insensitive.
+    fn message(
+        pid: &PeerId,
+        name: &str,
+        bio: &str,
+        avatar: &[u8],
+        version: i64,
+        expire: i64,
+    ) -> Vec<u8> {
+        let mut bytes = b"esse-domain-record".to_vec();
+        bytes.extend(pid.to_hex().as_bytes());
+        let lname = name.to_lowercase();
+        for field in [lname.as_bytes(), bio.as_bytes(), avatar] {
+            bytes.extend((field.len() as u64).to_le_bytes());
+            bytes.extend(field);
+        }
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(expire.to_le_bytes());
+        bytes
+    }
+}
+
 /// ESSE domain service to peer layer Event.
 #[derive(Serialize, Deserialize)]
 pub enum LayerServerEvent {
@@ -15,9 +125,9 @@ pub enum LayerServerEvent {
     /// register result.
     /// params: name, is_ok.
     Result(String, bool),
-    /// a identity info.
-    /// params: user_id, user_name, user_bio, user_avatar.
-    Info(PeerId, String, String, Vec<u8>),
+    /// a identity info, signed by the identity.
+    /// params: name record.
+    Info(NameRecord),
     /// not found a user by name.
     None(String),
     /// current name is active.
@@ -26,9 +136,22 @@ pub enum LayerServerEvent {
     /// current name is deleted.
     /// params: name.
     Deleted(String),
-    /// response the make friend.
+    /// response the make friend. when accepted, both sides will get the
+    /// other's identity, and continue the chat request.
     /// params: remote_id, name, is_ok.
     Response(PeerId, String, bool),
+    /// a make friend request relayed by provider, not has requester's identity.
+    /// params: request_id, requester_name, request_remark.
+    Request(i64, String, String),
+    /// the name is renewed.
+    /// params: name, new expire time.
+    Renewed(String, i64),
+    /// someone want to transfer the name to you.
+    /// params: name, from identity, current record version.
+    Transfer(String, PeerId, i64),
+    /// the name transfer result.
+    /// params: name, to identity, is_ok.
+    Transferred(String, PeerId, bool),
 }
 
 /// ESSE domain peer to service layer Event.
@@ -37,17 +160,20 @@ pub enum LayerPeerEvent {
     /// check service status is ok.
     Check,
     /// register new unique identity to service.
-    /// params: name, bio, avatar.
-    Register(String, String, Vec<u8>),
-    /// update user info.
-    /// params: name, bio, avatar.
-    Update(String, String, Vec<u8>),
+    /// params: signed name record.
+    Register(NameRecord),
+    /// update user info, record version must be increased.
+    /// params: signed name record.
+    Update(NameRecord),
     /// search a identity info.
     /// params: name.
     Search(String),
-    /// make a friend request,
+    /// make a friend request, provider will relay it to remote.
     /// params: remote_name, my_name, request_remark.
     Request(String, String, String),
+    /// handle the relayed friend request.
+    /// params: request_id, is_ok.
+    RequestHandle(i64, bool),
     /// suspend the name.
     /// params: name.
     Suspend(String),
@@ -57,4 +183,16 @@ pub enum LayerPeerEvent {
     /// delete the name.
     /// params: name.
     Delete(String),
+    /// renew the name, record expire and version must be increased.
+    /// params: signed name record.
+    Renew(NameRecord),
+    /// transfer the name to other identity, need the receiver accept.
+    /// params: name, to identity.
+    Transfer(String, PeerId),
+    /// accept the name transfer, the record is signed by receiver.
+    /// params: signed name record.
+    TransferAccept(NameRecord),
+    /// reject the name transfer.
+    /// params: name.
+    TransferReject(String),
 }
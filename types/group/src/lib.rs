@@ -57,6 +57,35 @@ pub enum LayerEvent {
         Vec<(i64, PeerId)>,
         Vec<(i64, PeerId, NetworkMessage, i64)>,
     ),
+    /// request the poll result from server. Group ID, poll message height.
+    PollReq(GroupChatId, i64),
+    /// poll result tallied by server.
+    /// Group ID, poll message height, poll deadline, votes(member id, options).
+    PollRes(GroupChatId, i64, i64, Vec<(PeerId, Vec<u32>)>),
+    /// request the shared file, relayed by server.
+    /// Group ID, owner (to server) or requester (to owner), share did, file did.
+    ShareReq(GroupChatId, PeerId, String, String),
+    /// the shared file content, relayed by server.
+    /// Group ID, requester (to server) or owner (to requester), share did, file did,
+    /// file name, file bytes (empty if missing).
+    ShareRes(GroupChatId, PeerId, String, String, String, Vec<u8>),
+    /// the shared folder updated by owner, relayed by server.
+    /// Group ID, owner, shared manifest.
+    ShareSync(GroupChatId, PeerId, String),
+    /// sync members status, and messages with mentioned members.
+    /// Group ID, current height, from height, to height,
+    /// add members(height, member id, addr, name, avatar),
+    /// leaved members(height, member id),
+    /// add messages(height, member id, message, time, mentions).
+    SyncResMention(
+        GroupChatId,
+        i64,
+        i64,
+        i64,
+        Vec<(i64, PeerId, String, Vec<u8>)>,
+        Vec<(i64, PeerId)>,
+        Vec<(i64, PeerId, NetworkMessage, i64, Vec<PeerId>)>,
+    ),
 }
 
 impl LayerEvent {
@@ -75,6 +104,53 @@ impl LayerEvent {
             Self::Sync(gid, ..) => gid,
             Self::SyncReq(gid, ..) => gid,
             Self::SyncRes(gid, ..) => gid,
+            Self::PollReq(gid, ..) => gid,
+            Self::PollRes(gid, ..) => gid,
+            Self::ShareReq(gid, ..) => gid,
+            Self::ShareRes(gid, ..) => gid,
+            Self::ShareSync(gid, ..) => gid,
+            Self::SyncResMention(gid, ..) => gid,
+        }
+    }
+
+    /// create sync members status event, only use the mention variant when
+    /// messages has mentions, so members in older versions can still read it.
+    pub fn sync_res(
+        gid: GroupChatId,
+        height: i64,
+        from: i64,
+        to: i64,
+        adds: Vec<(i64, PeerId, String, Vec<u8>)>,
+        leaves: Vec<(i64, PeerId)>,
+        messages: Vec<(i64, PeerId, NetworkMessage, i64, Vec<PeerId>)>,
+    ) -> Self {
+        if messages.iter().all(|m| m.4.is_empty()) {
+            let messages = messages
+                .into_iter()
+                .map(|(h, mpid, nmsg, time, _)| (h, mpid, nmsg, time))
+                .collect();
+            Self::SyncRes(gid, height, from, to, adds, leaves, messages)
+        } else {
+            Self::SyncResMention(gid, height, from, to, adds, leaves, messages)
+        }
+    }
+
+    /// events from members in older versions have no mentions,
+    /// upgrade them to the mention variants.
+    pub fn with_mentions(self) -> Self {
+        match self {
+            Self::SyncRes(gid, height, from, to, adds, leaves, messages) => {
+                let messages = messages
+                    .into_iter()
+                    .map(|(h, mpid, nmsg, time)| (h, mpid, nmsg, time, vec![]))
+                    .collect();
+                Self::SyncResMention(gid, height, from, to, adds, leaves, messages)
+            }
+            Self::Sync(gid, height, Event::MessageCreate(mpid, nmsg, time)) => {
+                let event = Event::MessageCreateMention(mpid, nmsg, time, vec![]);
+                Self::Sync(gid, height, event)
+            }
+            event => event,
         }
     }
 }
@@ -88,4 +164,22 @@ pub enum Event {
     MemberLeave(PeerId),
     /// params: member id, message, message time.
     MessageCreate(PeerId, NetworkMessage, i64),
+    /// params: member id, poll message height, choosed options.
+    PollVote(PeerId, i64, Vec<u32>),
+    /// params: member id, poll message height.
+    PollClose(PeerId, i64),
+    /// params: member id, message, message time, mentioned members.
+    MessageCreateMention(PeerId, NetworkMessage, i64, Vec<PeerId>),
+}
+
+impl Event {
+    /// create message event, only use the mention variant when has mentions,
+    /// so members in older versions can still read the message.
+    pub fn message(mpid: PeerId, nmsg: NetworkMessage, time: i64, mentions: Vec<PeerId>) -> Self {
+        if mentions.is_empty() {
+            Self::MessageCreate(mpid, nmsg, time)
+        } else {
+            Self::MessageCreateMention(mpid, nmsg, time, mentions)
+        }
+    }
 }
@@ -17,6 +17,9 @@ pub enum NetworkMessage {
     Video,
     Invite(String),
     Transfer(String),
+    Poll(String, Vec<String>, bool, i64), // question, options, is multiple choice, deadline (0 is none).
+    ImagePreview(Vec<u8>, String), // small preview bytes, image name, the image is sent later.
+    Share(String),                 // shared files manifest.
 }
 
 /// common message types.
@@ -32,6 +35,8 @@ pub enum MessageType {
     Video,
     Invite,
     Transfer,
+    Poll,
+    Share,
 }
 
 impl MessageType {
@@ -47,6 +52,8 @@ impl MessageType {
             MessageType::Video => 7,
             MessageType::Invite => 8,
             MessageType::Transfer => 9,
+            MessageType::Poll => 10,
+            MessageType::Share => 11,
         }
     }
 
@@ -62,6 +69,8 @@ impl MessageType {
             7 => MessageType::Video,
             8 => MessageType::Invite,
             9 => MessageType::Transfer,
+            10 => MessageType::Poll,
+            11 => MessageType::Share,
             _ => MessageType::String,
         }
     }